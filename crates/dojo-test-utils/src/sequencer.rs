@@ -1,11 +1,10 @@
 use std::sync::Arc;
 
-use jsonrpsee::core::Error;
+use katana::{KatanaNode, NodeBuilder};
 pub use katana_core::backend::config::{Environment, StarknetConfig};
 use katana_core::sequencer::KatanaSequencer;
 pub use katana_core::sequencer::SequencerConfig;
 use katana_rpc::config::ServerConfig;
-use katana_rpc::{spawn, KatanaApi, NodeHandle, StarknetApi};
 use starknet::accounts::SingleOwnerAccount;
 use starknet::core::chain_id;
 use starknet::core::types::FieldElement;
@@ -22,26 +21,28 @@ pub struct TestAccount {
 #[allow(unused)]
 pub struct TestSequencer {
     url: Url,
-    handle: NodeHandle,
+    node: KatanaNode,
     account: TestAccount,
     pub sequencer: Arc<KatanaSequencer>,
 }
 
 impl TestSequencer {
     pub async fn start(config: SequencerConfig, starknet_config: StarknetConfig) -> Self {
-        let sequencer = Arc::new(KatanaSequencer::new(config, starknet_config));
+        let node = NodeBuilder::new()
+            .sequencer_config(config)
+            .starknet_config(starknet_config)
+            .server_config(ServerConfig {
+                port: 0,
+                host: "localhost".into(),
+                faucet: true,
+                ..Default::default()
+            })
+            .start()
+            .await
+            .expect("Unable to start node");
 
-        sequencer.start().await;
-
-        let starknet_api = StarknetApi::new(sequencer.clone());
-        let katana_api = KatanaApi::new(sequencer.clone());
-
-        let handle =
-            spawn(katana_api, starknet_api, ServerConfig { port: 0, host: "localhost".into() })
-                .await
-                .expect("Unable to spawn server");
-
-        let url = Url::parse(&format!("http://{}", handle.addr)).expect("Failed to parse URL");
+        let sequencer = node.sequencer();
+        let url = Url::parse(&format!("http://{}", node.addr())).expect("Failed to parse URL");
 
         let account = sequencer.starknet.read().await.predeployed_accounts.accounts[0].clone();
         let account = TestAccount {
@@ -49,7 +50,7 @@ impl TestSequencer {
             account_address: FieldElement::from(*account.account_address.0.key()),
         };
 
-        TestSequencer { sequencer, account, handle, url }
+        TestSequencer { sequencer, account, node, url }
     }
 
     pub fn account(&self) -> SingleOwnerAccount<JsonRpcClient<HttpTransport>, LocalWallet> {
@@ -65,8 +66,8 @@ impl TestSequencer {
         &self.account
     }
 
-    pub fn stop(self) -> Result<(), Error> {
-        self.handle.handle.stop()
+    pub fn stop(self) -> anyhow::Result<()> {
+        self.node.stop()
     }
 
     pub fn url(&self) -> Url {
@@ -18,10 +18,9 @@ use starknet_api::transaction::{Calldata, ContractAddressSalt};
 use starknet_api::{patricia_key, stark_felt};
 
 use crate::backend::state::{ClassRecord, MemDb, StorageRecord};
-use crate::constants::{
-    DEFAULT_ACCOUNT_CONTRACT, DEFAULT_ACCOUNT_CONTRACT_CLASS_HASH, FEE_TOKEN_ADDRESS,
-};
-use crate::util::compute_legacy_class_hash;
+use crate::block_context::FeeTokenAddresses;
+use crate::constants::{DEFAULT_ACCOUNT_CONTRACT, DEFAULT_ACCOUNT_CONTRACT_CLASS_HASH};
+use crate::utils::compute_legacy_class_hash;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Account {
@@ -51,7 +50,12 @@ impl Account {
         Self { balance, public_key, private_key, class_hash, account_address }
     }
 
-    pub fn deploy(&self, contract_class: &ContractClass, state: &mut MemDb) {
+    pub fn deploy(
+        &self,
+        contract_class: &ContractClass,
+        fee_token_addresses: &FeeTokenAddresses,
+        state: &mut MemDb,
+    ) {
         self.declare(contract_class, state);
 
         state.storage.insert(
@@ -68,14 +72,14 @@ impl Account {
             },
         );
 
-        // set the balance in the FEE CONTRACT
-        state.storage.entry(ContractAddress(patricia_key!(*FEE_TOKEN_ADDRESS))).and_modify(|r| {
-            r.storage.insert(
-                get_storage_var_address("ERC20_balances", &[*self.account_address.0.key()])
-                    .unwrap(),
-                self.balance,
-            );
-        });
+        // set the balance in the FEE CONTRACTS
+        let balance_key =
+            get_storage_var_address("ERC20_balances", &[*self.account_address.0.key()]).unwrap();
+        for fee_token_address in fee_token_addresses.all() {
+            state.storage.entry(fee_token_address).and_modify(|r| {
+                r.storage.insert(balance_key, self.balance);
+            });
+        }
     }
 
     fn declare(&self, contract_class: &ContractClass, state: &mut MemDb) {
@@ -122,9 +126,9 @@ impl PredeployedAccounts {
         Ok(Self { seed, accounts, contract_class, initial_balance })
     }
 
-    pub fn deploy_accounts(&self, state: &mut MemDb) {
+    pub fn deploy_accounts(&self, state: &mut MemDb, fee_token_addresses: &FeeTokenAddresses) {
         for account in &self.accounts {
-            account.deploy(&self.contract_class, state);
+            account.deploy(&self.contract_class, fee_token_addresses, state);
         }
     }
 
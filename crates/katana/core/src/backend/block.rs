@@ -15,6 +15,17 @@ use crate::backend::state::MemDb;
 pub struct StarknetBlock {
     pub inner: Block,
     pub status: Option<BlockStatus>,
+    /// The balances minted in the block outside of any transaction.
+    pub mints: Vec<Mint>,
+}
+
+/// A balance credited to an address in every fee token, by `katana_mint` or the faucet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mint {
+    pub address: ContractAddress,
+    pub amount: u128,
+    /// The number of transactions of the block executed before the mint.
+    pub transaction_index: usize,
 }
 
 impl StarknetBlock {
@@ -45,6 +56,7 @@ impl StarknetBlock {
                 body: BlockBody { transactions, transaction_outputs },
             },
             status,
+            mints: Vec::new(),
         }
     }
 
@@ -118,6 +130,15 @@ impl StarknetBlocks {
         self.num_to_block.insert(block_number, block);
     }
 
+    /// Removes a block along with its state and state update.
+    pub fn remove(&mut self, block_number: BlockNumber) -> Option<StarknetBlock> {
+        let block = self.num_to_block.remove(&block_number)?;
+        self.hash_to_num.remove(&block.block_hash());
+        self.state_archive.remove(&block_number);
+        self.num_to_state_update.remove(&block_number);
+        Some(block)
+    }
+
     pub fn current_block_number(&self) -> BlockNumber {
         let block_len = self.total_blocks();
         // Safe to do unchecked subtraction ( if `block_len` == 0 )
@@ -6,10 +6,12 @@ use starknet_api::core::{ChainId, ContractAddress, PatriciaKey};
 use starknet_api::hash::StarkHash;
 use starknet_api::patricia_key;
 
-use crate::block_context::{get_default_vm_resource_fee_cost, BlockContextGenerator};
+use crate::block_context::{
+    get_default_vm_resource_fee_cost, BlockContextGenerator, FeeToken, FeeTokenAddresses,
+};
 use crate::constants::{
-    DEFAULT_GAS_PRICE, DEFAULT_INVOKE_MAX_STEPS, DEFAULT_VALIDATE_MAX_STEPS, FEE_TOKEN_ADDRESS,
-    SEQUENCER_ADDRESS,
+    DEFAULT_GAS_PRICE, DEFAULT_INVOKE_MAX_STEPS, DEFAULT_PARALLEL_BATCH_SIZE,
+    DEFAULT_VALIDATE_MAX_STEPS, SEQUENCER_ADDRESS,
 };
 
 #[derive(Debug)]
@@ -20,6 +22,19 @@ pub struct StarknetConfig {
     pub allow_zero_max_fee: bool,
     pub account_path: Option<PathBuf>,
     pub env: Environment,
+    /// Directory to write the Cairo VM profile of every executed transaction to.
+    pub profile_dir: Option<PathBuf>,
+    /// Directory of Sierra contract class artifacts to read the debug info of declared classes
+    /// from, to name the functions of the profiles.
+    pub profile_debug_info_dir: Option<PathBuf>,
+    /// Execute the transactions of a block in parallel. Without auto mining, transactions are
+    /// queued and executed in batches of [StarknetConfig::parallel_batch_size], when the block
+    /// is mined, or periodically by the sequencer.
+    pub parallel_execution: bool,
+    /// The number of queued transactions which triggers their parallel execution.
+    pub parallel_batch_size: usize,
+    /// File to append a JSON record of every executed transaction to.
+    pub tx_log: Option<PathBuf>,
 }
 
 impl StarknetConfig {
@@ -29,7 +44,7 @@ impl StarknetConfig {
             chain_id: ChainId(self.env.chain_id.clone()),
             block_timestamp: BlockTimestamp::default(),
             sequencer_address: ContractAddress(patricia_key!(*SEQUENCER_ADDRESS)),
-            fee_token_address: ContractAddress(patricia_key!(*FEE_TOKEN_ADDRESS)),
+            fee_token_address: self.env.fee_token_addresses.eth,
             vm_resource_fee_cost: get_default_vm_resource_fee_cost(),
             gas_price: self.env.gas_price,
             validate_max_n_steps: self.env.validate_max_steps,
@@ -40,6 +55,19 @@ impl StarknetConfig {
     pub fn block_context_generator(&self) -> BlockContextGenerator {
         BlockContextGenerator::default()
     }
+
+    pub fn fee_token_addresses(&self) -> FeeTokenAddresses {
+        self.env.fee_token_addresses
+    }
+
+    /// Returns the token in which the fees of transactions of the given version are charged.
+    pub fn fee_token(&self, version: u8) -> FeeToken {
+        if self.env.strk_fee_versions.contains(&version) {
+            FeeToken::Strk
+        } else {
+            FeeToken::Eth
+        }
+    }
 }
 
 impl Default for StarknetConfig {
@@ -51,6 +79,11 @@ impl Default for StarknetConfig {
             account_path: None,
             allow_zero_max_fee: false,
             env: Environment::default(),
+            profile_dir: None,
+            profile_debug_info_dir: None,
+            parallel_execution: false,
+            parallel_batch_size: DEFAULT_PARALLEL_BATCH_SIZE,
+            tx_log: None,
         }
     }
 }
@@ -61,6 +94,10 @@ pub struct Environment {
     pub gas_price: u128,
     pub invoke_max_steps: u32,
     pub validate_max_steps: u32,
+    /// The transaction versions whose fees are charged in STRK instead of ETH.
+    pub strk_fee_versions: Vec<u8>,
+    /// The addresses the fee tokens are deployed at.
+    pub fee_token_addresses: FeeTokenAddresses,
 }
 
 impl Default for Environment {
@@ -70,6 +107,8 @@ impl Default for Environment {
             chain_id: "KATANA".to_string(),
             invoke_max_steps: DEFAULT_INVOKE_MAX_STEPS,
             validate_max_steps: DEFAULT_VALIDATE_MAX_STEPS,
+            strk_fee_versions: Vec::new(),
+            fee_token_addresses: FeeTokenAddresses::default(),
         }
     }
 }
@@ -1,7 +1,47 @@
 use blockifier::execution::contract_class::ContractClassV0;
+use starknet::core::types::contract::legacy::LegacyContractClass;
 use starknet::core::types::FlattenedSierraClass;
+use starknet_api::core::ClassHash;
+
+use crate::sequencer_error::SequencerError;
 
 pub enum StarknetContract {
     Legacy(ContractClassV0),
     Sierra(FlattenedSierraClass),
 }
+
+/// The definition of the class declared by a declare transaction, from which its class hash is
+/// computed.
+pub enum DeclaredClass {
+    Legacy(LegacyContractClass),
+    Sierra(FlattenedSierraClass),
+}
+
+impl DeclaredClass {
+    /// Checks that the class definition hashes to the declared `class_hash`, and returns the
+    /// Sierra class to store along the compiled one.
+    pub fn verify(
+        self,
+        class_hash: ClassHash,
+    ) -> Result<Option<FlattenedSierraClass>, SequencerError> {
+        let (actual_class_hash, sierra_class) = match self {
+            DeclaredClass::Legacy(class) => {
+                let class_hash = class
+                    .class_hash()
+                    .map_err(|err| SequencerError::InvalidContractClass(err.to_string()))?;
+                (class_hash, None)
+            }
+            DeclaredClass::Sierra(class) => (class.class_hash(), Some(class)),
+        };
+
+        let actual_class_hash = ClassHash(actual_class_hash.into());
+        if actual_class_hash != class_hash {
+            return Err(SequencerError::ClassHashMismatch {
+                declared: class_hash,
+                actual: actual_class_hash,
+            });
+        }
+
+        Ok(sierra_class)
+    }
+}
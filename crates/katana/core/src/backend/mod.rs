@@ -1,4 +1,10 @@
-use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use blockifier::abi::abi_utils::get_storage_var_address;
 use blockifier::block_context::BlockContext;
 use blockifier::execution::entry_point::{
     CallEntryPoint, CallInfo, EntryPointExecutionContext, ExecutionResources,
@@ -6,6 +12,7 @@ use blockifier::execution::entry_point::{
 use blockifier::execution::errors::EntryPointExecutionError;
 use blockifier::fee::fee_utils::{calculate_l1_gas_by_vm_usage, extract_l1_gas_and_vm_usage};
 use blockifier::state::cached_state::{CachedState, MutRefState};
+use blockifier::state::errors::StateError;
 use blockifier::state::state_api::{State, StateReader};
 use blockifier::transaction::account_transaction::AccountTransaction;
 use blockifier::transaction::errors::TransactionExecutionError;
@@ -13,60 +20,85 @@ use blockifier::transaction::objects::{
     AccountTransactionContext, ResourcesMapping, TransactionExecutionInfo,
 };
 use blockifier::transaction::transaction_execution::Transaction;
-use blockifier::transaction::transactions::ExecutableTransaction;
+use blockifier::transaction::transactions::{
+    DeclareTransaction, DeployAccountTransaction, ExecutableTransaction, L1HandlerTransaction,
+};
 use convert_case::{Case, Casing};
-use starknet::core::types::{FeeEstimate, FieldElement, StateUpdate, TransactionStatus};
+use starknet::core::types::{
+    BlockId, FeeEstimate, FieldElement, FlattenedSierraClass, StateUpdate, TransactionStatus,
+};
 use starknet_api::block::{BlockHash, BlockNumber, BlockTimestamp, GasPrice};
 use starknet_api::core::{ClassHash, ContractAddress, GlobalRoot, PatriciaKey};
 use starknet_api::hash::{StarkFelt, StarkHash};
 use starknet_api::transaction::{DeclareTransactionV0V1, DeployTransaction, TransactionHash};
 use starknet_api::{patricia_key, stark_felt};
+use tokio::sync::broadcast;
 use tracing::{info, trace, warn};
 
 pub mod block;
 pub mod config;
 pub mod contract;
 pub mod event;
+mod parallel;
+pub mod profile;
 pub mod state;
 pub mod transaction;
+pub mod tx_log;
 
-use block::{StarknetBlock, StarknetBlocks};
+use block::{Mint, StarknetBlock, StarknetBlocks};
 use config::StarknetConfig;
+use profile::debug_info::SierraDebugInfo;
+use profile::TransactionProfile;
 use transaction::{ExternalFunctionCall, StarknetTransaction, StarknetTransactions};
+use tx_log::{ExecutionStatus, TransactionLog, TransactionLogRecord};
 
 use crate::accounts::PredeployedAccounts;
 use crate::backend::state::{MemDb, StateExt};
-use crate::block_context::BlockContextGenerator;
+use crate::block_context::{BlockContextGenerator, FeeTokenAddresses};
 use crate::constants::{
-    DEFAULT_PREFUNDED_ACCOUNT_BALANCE, ERC20_CONTRACT_CLASS_HASH, FEE_TOKEN_ADDRESS, UDC_ADDRESS,
-    UDC_CLASS_HASH,
+    DEFAULT_PREFUNDED_ACCOUNT_BALANCE, ERC20_CONTRACT_CLASS_HASH, UDC_ADDRESS, UDC_CLASS_HASH,
 };
 use crate::sequencer_error::SequencerError;
-use crate::util::{
+use crate::utils::transaction::account_transaction_version;
+use crate::utils::{
     convert_blockifier_tx_to_starknet_api_tx, convert_state_diff_to_rpc_state_diff,
-    get_current_timestamp,
+    get_current_timestamp, starkfelt_to_u128,
 };
 
+/// The number of blocks a lagging block subscriber may fall behind before missing blocks.
+const BLOCK_NOTIFIER_CAPACITY: usize = 128;
+
 pub struct StarknetWrapper {
     pub config: StarknetConfig,
     pub blocks: StarknetBlocks,
     pub block_context: BlockContext,
     pub block_context_generator: BlockContextGenerator,
+    pub fee_token_addresses: FeeTokenAddresses,
     pub transactions: StarknetTransactions,
     pub state: MemDb,
     pub predeployed_accounts: PredeployedAccounts,
     pub pending_cached_state: CachedState<MemDb>,
+    /// Notifies subscribers whenever a new block is appended to the chain.
+    pub block_notifier: broadcast::Sender<StarknetBlock>,
+    /// Transactions waiting to be executed in parallel.
+    queued_transactions: Vec<Transaction>,
+    tx_log: Option<TransactionLog>,
+    /// The Sierra functions of the classes of the transactions profiled so far.
+    sierra_debug_info: Mutex<SierraDebugInfo>,
 }
 
 impl StarknetWrapper {
-    pub fn new(config: StarknetConfig) -> Self {
+    /// Creates the chain and deploys the predeployed accounts. Fails if the account class can't
+    /// be read or the transaction log can't be opened.
+    pub fn new(config: StarknetConfig) -> Result<Self> {
         let blocks = StarknetBlocks::default();
         let transactions = StarknetTransactions::default();
 
         let block_context = config.block_context();
         let block_context_generator = config.block_context_generator();
+        let fee_token_addresses = config.fee_token_addresses();
 
-        let mut state = MemDb::default();
+        let mut state = MemDb::new(&fee_token_addresses);
         let pending_state = CachedState::new(state.clone());
 
         let predeployed_accounts = PredeployedAccounts::initialize(
@@ -75,33 +107,86 @@ impl StarknetWrapper {
             *DEFAULT_PREFUNDED_ACCOUNT_BALANCE,
             config.account_path.clone(),
         )
-        .expect("should be able to generate accounts");
-        predeployed_accounts.deploy_accounts(&mut state);
+        .context("failed to generate the predeployed accounts")?;
+        predeployed_accounts.deploy_accounts(&mut state, &fee_token_addresses);
+
+        let (block_notifier, _) = broadcast::channel(BLOCK_NOTIFIER_CAPACITY);
+
+        let tx_log = config
+            .tx_log
+            .as_deref()
+            .map(|path| {
+                TransactionLog::open(path).with_context(|| {
+                    format!("failed to open the transaction log {}", path.display())
+                })
+            })
+            .transpose()?;
 
-        Self {
+        let sierra_debug_info =
+            Mutex::new(SierraDebugInfo::new(config.profile_debug_info_dir.as_deref()));
+
+        Ok(Self {
             state,
             config,
             blocks,
             transactions,
             block_context,
             block_context_generator,
+            fee_token_addresses,
             pending_cached_state: pending_state,
             predeployed_accounts,
-        }
+            block_notifier,
+            queued_transactions: Vec::new(),
+            tx_log,
+            sierra_debug_info,
+        })
     }
 
+    /// Returns a receiver which yields every block appended to the chain after this call.
+    pub fn subscribe_blocks(&self) -> broadcast::Receiver<StarknetBlock> {
+        self.block_notifier.subscribe()
+    }
+
+    /// Estimates the fees of `transactions`, executing them in order on the same state so that
+    /// each transaction sees the changes made by the ones before it. Fails on the first
+    /// transaction which cannot be executed.
     pub fn estimate_fee(
         &mut self,
-        transaction: AccountTransaction,
+        transactions: Vec<AccountTransaction>,
+        state: Option<MemDb>,
+    ) -> Result<Vec<FeeEstimate>, SequencerError> {
+        let mut state = CachedState::new(state.unwrap_or(self.pending_state()));
+
+        transactions
+            .into_iter()
+            .enumerate()
+            .map(|(index, transaction)| {
+                self.estimate_transaction_fee(
+                    Transaction::AccountTransaction(transaction),
+                    &mut state,
+                )
+                .map_err(|error| SequencerError::FeeEstimation { index, error })
+            })
+            .collect()
+    }
+
+    /// Estimates the fee of the L1 handler transaction which consumes a message sent from L1.
+    pub fn estimate_message_fee(
+        &mut self,
+        transaction: L1HandlerTransaction,
         state: Option<MemDb>,
     ) -> Result<FeeEstimate, TransactionExecutionError> {
         let mut state = CachedState::new(state.unwrap_or(self.pending_state()));
+        self.estimate_transaction_fee(Transaction::L1HandlerTransaction(transaction), &mut state)
+    }
 
-        let exec_info = execute_transaction(
-            Transaction::AccountTransaction(transaction),
-            &mut state,
-            &self.block_context,
-        )?;
+    fn estimate_transaction_fee(
+        &self,
+        transaction: Transaction,
+        state: &mut CachedState<MemDb>,
+    ) -> Result<FeeEstimate, TransactionExecutionError> {
+        let block_context = self.transaction_block_context(&transaction);
+        let exec_info = execute_transaction(transaction, state, &block_context)?;
 
         if exec_info.revert_error.is_some() {
             // TEMP: change this once `Reverted` transaction error is no longer `String`.
@@ -123,20 +208,135 @@ impl StarknetWrapper {
 
     // execute the tx
     pub fn handle_transaction(&mut self, transaction: Transaction) {
-        let api_tx = convert_blockifier_tx_to_starknet_api_tx(&transaction);
+        self.check_transaction(&transaction);
 
-        info!("Transaction received | Hash: {}", api_tx.transaction_hash());
+        if self.executes_in_parallel() {
+            self.queue_transaction(transaction);
+        } else {
+            self.execute_transactions(vec![transaction]);
+        }
+    }
+
+    /// Executes `transactions` in order, in parallel if parallel execution is enabled.
+    pub fn handle_transactions(&mut self, transactions: Vec<Transaction>) {
+        transactions.iter().for_each(|tx| self.check_transaction(tx));
+        self.execute_transactions(transactions);
+    }
 
-        if let Transaction::AccountTransaction(tx) = &transaction {
+    fn check_transaction(&self, transaction: &Transaction) {
+        let api_tx = convert_blockifier_tx_to_starknet_api_tx(transaction);
+
+        info!(transaction_hash = %api_tx.transaction_hash(), "Transaction received");
+
+        if let Transaction::AccountTransaction(tx) = transaction {
             self.check_tx_fee(tx);
         }
+    }
+
+    /// Transactions are only executed in parallel when several of them go into the same block.
+    pub fn executes_in_parallel(&self) -> bool {
+        self.config.parallel_execution && !self.config.auto_mine
+    }
+
+    /// Queues `transaction` to be executed in parallel with the other queued transactions, and
+    /// executes the queue once it holds a full batch. The transaction is stored as pending until
+    /// it is executed.
+    fn queue_transaction(&mut self, transaction: Transaction) {
+        let api_tx = convert_blockifier_tx_to_starknet_api_tx(&transaction);
+        let block_context = self.transaction_block_context(&transaction);
+        self.store_transaction(StarknetTransaction::new(
+            api_tx,
+            TransactionStatus::Pending,
+            None,
+            None,
+            block_context,
+        ));
+
+        self.queued_transactions.push(transaction);
+        if self.queued_transactions.len() >= self.config.parallel_batch_size {
+            self.execute_queued_transactions();
+        }
+    }
 
-        let res =
-            execute_transaction(transaction, &mut self.pending_cached_state, &self.block_context);
+    /// Executes the queued transactions in parallel and appends them to the pending block.
+    pub fn execute_queued_transactions(&mut self) {
+        if self.queued_transactions.is_empty() {
+            return;
+        }
 
+        let transactions = std::mem::take(&mut self.queued_transactions);
+        self.execute_transactions(transactions);
+    }
+
+    /// Returns whether the class is declared, including by a transaction of the pending block or
+    /// a queued transaction.
+    pub fn is_class_declared(&mut self, class_hash: &ClassHash) -> bool {
+        self.pending_cached_state.get_compiled_contract_class(class_hash).is_ok()
+            || self.is_class_queued(class_hash)
+    }
+
+    /// Returns whether a queued transaction declares the class `class_hash`.
+    pub fn is_class_queued(&self, class_hash: &ClassHash) -> bool {
+        self.queued_transactions.iter().any(|transaction| {
+            matches!(
+                transaction,
+                Transaction::AccountTransaction(AccountTransaction::Declare(tx))
+                    if tx.tx().class_hash() == *class_hash
+            )
+        })
+    }
+
+    /// Returns the number of queued transactions sent by `address`, each of which will increment
+    /// its nonce once executed.
+    pub fn queued_transactions_from(&self, address: ContractAddress) -> usize {
+        self.queued_transactions
+            .iter()
+            .filter(|transaction| nonce_owner(transaction) == Some(address))
+            .count()
+    }
+
+    fn execute_transactions(&mut self, transactions: Vec<Transaction>) {
+        if !self.executes_in_parallel() {
+            for transaction in transactions {
+                let api_tx = convert_blockifier_tx_to_starknet_api_tx(&transaction);
+                let block_context = self.transaction_block_context(&transaction);
+                let res = execute_transaction(
+                    transaction,
+                    &mut self.pending_cached_state,
+                    &block_context,
+                );
+                self.store_execution(api_tx, block_context, res);
+            }
+            return;
+        }
+
+        let mut api_txs = Vec::with_capacity(transactions.len());
+        let mut batch = Vec::with_capacity(transactions.len());
+        for transaction in transactions {
+            let block_context = self.transaction_block_context(&transaction);
+            api_txs.push((
+                convert_blockifier_tx_to_starknet_api_tx(&transaction),
+                block_context.clone(),
+            ));
+            batch.push((transaction, block_context));
+        }
+
+        let results = parallel::execute_transactions(&mut self.pending_cached_state, batch);
+        for ((api_tx, block_context), res) in api_txs.into_iter().zip(results) {
+            self.store_execution(api_tx, block_context, res);
+        }
+    }
+
+    fn store_execution(
+        &mut self,
+        api_tx: starknet_api::transaction::Transaction,
+        block_context: BlockContext,
+        res: Result<TransactionExecutionInfo, TransactionExecutionError>,
+    ) {
         match res {
             Ok(exec_info) => {
                 trace!(
+                    transaction_hash = %api_tx.transaction_hash(),
                     "Transaction resource usage: {}",
                     pretty_print_resources(&exec_info.actual_resources)
                 );
@@ -156,6 +356,7 @@ impl StarknetWrapper {
                     // stored. but right now `revert_error` is not of type
                     // `TransactionExecutionError`, so we store `None` instead.
                     None,
+                    block_context,
                 );
 
                 let pending_block = self.blocks.pending_block.as_mut().expect("no pending block");
@@ -164,34 +365,93 @@ impl StarknetWrapper {
                 pending_block.insert_transaction(api_tx);
                 pending_block.insert_transaction_output(starknet_tx.output());
 
+                let transaction_hash = starknet_tx.inner.transaction_hash();
                 self.store_transaction(starknet_tx);
+                self.log_transaction(&transaction_hash);
 
                 if self.config.auto_mine {
                     self.generate_latest_block();
                     self.generate_pending_block();
                 }
+
+                if let Some(dir) = &self.config.profile_dir {
+                    self.write_transaction_profile(dir, transaction_hash);
+                }
             }
 
             Err(exec_err) => {
-                warn!("Transaction validation error: {exec_err:?}");
+                warn!(
+                    transaction_hash = %api_tx.transaction_hash(),
+                    "Transaction validation error: {exec_err:?}"
+                );
 
                 let tx = StarknetTransaction::new(
                     api_tx,
                     TransactionStatus::Rejected,
                     None,
                     Some(exec_err),
+                    block_context,
                 );
 
+                let transaction_hash = tx.inner.transaction_hash();
                 self.store_transaction(tx);
+                self.log_transaction(&transaction_hash);
             }
         }
     }
 
+    /// Appends the execution of a stored transaction to the transaction log, if enabled.
+    fn log_transaction(&mut self, hash: &TransactionHash) {
+        let (Some(tx_log), Some(transaction)) =
+            (self.tx_log.as_mut(), self.transactions.by_hash(hash))
+        else {
+            return;
+        };
+
+        let record = TransactionLogRecord::new(
+            transaction,
+            self.block_context.block_number.0,
+            self.block_context.block_timestamp.0,
+        );
+
+        if let Err(err) = tx_log.write(&record) {
+            warn!(transaction_hash = %hash, "Failed to write the transaction log: {err}");
+        }
+    }
+
+    /// Appends a record marking a transaction of a block dropped by a reorg to the transaction
+    /// log, if enabled.
+    fn log_dropped_transaction(
+        &mut self,
+        transaction: &StarknetTransaction,
+        block: &StarknetBlock,
+    ) {
+        let Some(tx_log) = self.tx_log.as_mut() else {
+            return;
+        };
+
+        let mut record = TransactionLogRecord::new(
+            transaction,
+            block.block_number().0,
+            block.header().timestamp.0,
+        );
+        record.status = ExecutionStatus::Dropped;
+
+        if let Err(err) = tx_log.write(&record) {
+            warn!(
+                transaction_hash = %transaction.inner.transaction_hash(),
+                "Failed to write the transaction log: {err}"
+            );
+        }
+    }
+
     // Creates a new block that contains all the pending txs
     // Will update the txs status to accepted
     // Append the block to the chain
     // Update the block context
     pub fn generate_latest_block(&mut self) {
+        self.execute_queued_transactions();
+
         let mut new_block = if let Some(ref pending) = self.blocks.pending_block {
             pending.clone()
         } else {
@@ -213,9 +473,10 @@ impl StarknetWrapper {
         }
 
         info!(
-            "⛏️ New block generated | Hash: {} | Number: {}",
-            new_block.block_hash(),
-            new_block.block_number()
+            block_hash = %new_block.block_hash(),
+            block_number = new_block.block_number().0,
+            transaction_count = new_block.transactions().len(),
+            "⛏️ New block generated"
         );
 
         let pending_state_diff = self.pending_cached_state.to_state_diff();
@@ -238,8 +499,11 @@ impl StarknetWrapper {
         );
 
         self.blocks.pending_block = None;
-        self.blocks.insert(new_block);
+        self.blocks.insert(new_block.clone());
         self.update_latest_state();
+
+        // It's fine if there are no active subscribers.
+        let _ = self.block_notifier.send(new_block);
     }
 
     pub fn generate_pending_block(&mut self) {
@@ -285,6 +549,8 @@ impl StarknetWrapper {
         self.blocks.get_state(&block_number).cloned()
     }
 
+    /// Returns the state of the pending block. The queued transactions are not executed, so
+    /// their changes are not included until they are.
     pub fn pending_state(&mut self) -> MemDb {
         let mut state = self.pending_cached_state.state.clone();
         state.apply_state(&mut self.pending_cached_state);
@@ -311,6 +577,59 @@ impl StarknetWrapper {
         }
     }
 
+    /// Returns the Cairo VM profile of an executed transaction.
+    pub fn transaction_profile(&self, hash: &TransactionHash) -> Option<TransactionProfile> {
+        let transaction = self.transactions.by_hash(hash)?;
+        let execution_info = transaction.execution_info.as_ref()?;
+        let block_context = transaction.block_context.as_ref()?;
+        let mut sierra_debug_info = self.sierra_debug_info.lock().expect("poisoned lock");
+
+        Some(TransactionProfile::new(
+            &transaction.inner,
+            block_context,
+            execution_info,
+            &self.state,
+            &mut sierra_debug_info,
+        ))
+    }
+
+    /// Writes the profile of a transaction to `<dir>/<transaction hash>.folded`.
+    fn write_transaction_profile(&self, dir: &Path, hash: TransactionHash) {
+        let Some(profile) = self.transaction_profile(&hash) else {
+            return;
+        };
+
+        let path = dir.join(format!("{:#x}.folded", FieldElement::from(hash.0)));
+        let res = fs::create_dir_all(dir).and_then(|_| fs::write(&path, profile.to_folded()));
+
+        match res {
+            Ok(()) => info!(
+                transaction_hash = %hash,
+                steps = profile.total_steps(),
+                "Transaction profile written to {}",
+                path.display()
+            ),
+            Err(err) => warn!(
+                transaction_hash = %hash,
+                "Failed to write transaction profile to {}: {err}",
+                path.display()
+            ),
+        }
+    }
+
+    /// Returns the block context to execute `transaction` with, which charges the fee in the token
+    /// selected for the transaction version.
+    fn transaction_block_context(&self, transaction: &Transaction) -> BlockContext {
+        let mut block_context = self.block_context.clone();
+
+        if let Transaction::AccountTransaction(tx) = transaction {
+            let fee_token = self.config.fee_token(account_transaction_version(tx));
+            block_context.fee_token_address = self.fee_token_addresses.get(fee_token);
+        }
+
+        block_context
+    }
+
     /// Generate the genesis block and append it to the chain.
     /// This block should include transactions which set the initial state of the chain.
     pub fn generate_genesis_block(&mut self) {
@@ -318,8 +637,11 @@ impl StarknetWrapper {
         self.pending_cached_state = CachedState::new(self.state.clone());
 
         let mut transactions = vec![];
-        let deploy_data =
-            vec![(*UDC_CLASS_HASH, *UDC_ADDRESS), (*ERC20_CONTRACT_CLASS_HASH, *FEE_TOKEN_ADDRESS)];
+        let deploy_data = vec![
+            (*UDC_CLASS_HASH, *UDC_ADDRESS),
+            (*ERC20_CONTRACT_CLASS_HASH, *self.fee_token_addresses.eth.0.key()),
+            (*ERC20_CONTRACT_CLASS_HASH, *self.fee_token_addresses.strk.0.key()),
+        ];
 
         deploy_data.into_iter().for_each(|(class_hash, address)| {
             let declare_tx = starknet_api::transaction::Transaction::Declare(
@@ -335,6 +657,7 @@ impl StarknetWrapper {
             self.store_transaction(StarknetTransaction {
                 execution_info: None,
                 execution_error: None,
+                block_context: None,
                 inner: declare_tx.clone(),
                 block_hash: Default::default(),
                 block_number: Default::default(),
@@ -351,6 +674,7 @@ impl StarknetWrapper {
             self.store_transaction(StarknetTransaction {
                 execution_info: None,
                 execution_error: None,
+                block_context: None,
                 inner: deploy_tx.clone(),
                 block_hash: Default::default(),
                 block_number: Default::default(),
@@ -413,6 +737,60 @@ impl StarknetWrapper {
         self.blocks.store_state(self.block_context.block_number, self.state.clone());
     }
 
+    /// Adds `amount` to the balance of `address` in every fee token.
+    pub fn mint(&mut self, address: ContractAddress, amount: u128) -> Result<(), SequencerError> {
+        self.credit(address, amount)?;
+
+        if self.config.auto_mine {
+            self.generate_latest_block();
+            self.generate_pending_block();
+        }
+
+        Ok(())
+    }
+
+    /// Adds `amount` to the balance of `address` in the pending state, and records the mint in the
+    /// pending block.
+    fn credit(&mut self, address: ContractAddress, amount: u128) -> Result<(), SequencerError> {
+        add_balance(&mut self.pending_cached_state, &self.fee_token_addresses, address, amount)?;
+
+        if let Some(block) = self.blocks.pending_block.as_mut() {
+            let transaction_index = block.transactions().len();
+            block.mints.push(Mint { address, amount, transaction_index });
+        }
+
+        Ok(())
+    }
+
+    /// Funds the account deployed by `transaction` with `amount` of every fee token, then deploys
+    /// it. The deployment is first simulated on a funded copy of the pending state, and nothing is
+    /// minted if it would be rejected or reverted.
+    pub fn drip_and_deploy_account(
+        &mut self,
+        transaction: DeployAccountTransaction,
+        amount: u128,
+    ) -> Result<(), SequencerError> {
+        let address = transaction.contract_address;
+
+        let mut state = CachedState::new(self.pending_state());
+        add_balance(&mut state, &self.fee_token_addresses, address, amount)?;
+
+        let simulated =
+            Transaction::AccountTransaction(AccountTransaction::DeployAccount(transaction.clone()));
+        let block_context = self.transaction_block_context(&simulated);
+        let exec_info = execute_transaction(simulated, &mut state, &block_context)?;
+        if let Some(error) = exec_info.revert_error {
+            return Err(SequencerError::TransactionReverted(error));
+        }
+
+        self.mint(address, amount)?;
+        self.handle_transaction(Transaction::AccountTransaction(
+            AccountTransaction::DeployAccount(transaction),
+        ));
+
+        Ok(())
+    }
+
     pub fn set_next_block_timestamp(&mut self, timestamp: u64) -> Result<(), SequencerError> {
         if has_pending_transactions(self) {
             return Err(SequencerError::PendingTransactions);
@@ -428,6 +806,185 @@ impl StarknetWrapper {
         self.block_context_generator.block_timestamp_offset += timestamp as i64;
         Ok(())
     }
+
+    /// Drops the latest `depth` blocks and rolls the state back to the block before them, as a
+    /// reorganisation of the chain would. The genesis block cannot be dropped.
+    ///
+    /// Returns the transactions of the dropped blocks in execution order, so that they can be
+    /// mined again. A new pending block is opened on top of the new latest block.
+    pub fn reorg(&mut self, depth: u64) -> Result<Vec<ReorgTransaction>, SequencerError> {
+        if has_pending_transactions(self) {
+            return Err(SequencerError::PendingTransactions);
+        }
+
+        let latest = self.blocks.current_block_number();
+        if depth == 0 || depth > latest.0 {
+            return Err(SequencerError::InvalidReorgDepth { depth, max: latest.0 });
+        }
+
+        let new_latest = BlockNumber(latest.0 - depth);
+        let mut dropped_blocks = Vec::with_capacity(depth as usize);
+        for number in (new_latest.0 + 1..=latest.0).rev() {
+            dropped_blocks.extend(self.blocks.remove(BlockNumber(number)));
+        }
+
+        let mut dropped = Vec::new();
+        let mut dropped_mints = Vec::new();
+        for block in dropped_blocks.into_iter().rev() {
+            dropped_mints.extend(block.mints.iter().copied());
+            for api_tx in block.transactions() {
+                let Some(tx) = self.transactions.transactions.remove(&api_tx.transaction_hash())
+                else {
+                    continue;
+                };
+                self.log_dropped_transaction(&tx, &block);
+                dropped.extend(self.reorg_transaction(tx)?);
+            }
+        }
+        // The pending block is replaced too, along with the balances minted in it.
+        if let Some(pending_block) = &self.blocks.pending_block {
+            dropped_mints.extend(pending_block.mints.iter().copied());
+        }
+
+        self.state = self
+            .blocks
+            .get_state(&new_latest)
+            .cloned()
+            .ok_or(SequencerError::StateNotFound(BlockId::Number(new_latest.0)))?;
+        self.block_context.block_number = new_latest;
+        self.generate_pending_block();
+
+        // Minted balances don't come from transactions, so they are credited again in the new
+        // pending block, before the dropped transactions are mined again.
+        for mint in dropped_mints {
+            self.credit(mint.address, mint.amount)?;
+        }
+
+        info!(dropped_blocks = depth, block_number = new_latest.0, "🔀 Chain reorganised");
+
+        Ok(dropped)
+    }
+
+    /// Checks that the classes declared by the `transactions` mined on top of a reorganisation
+    /// of `depth` blocks aren't declared yet, in the state rolled back to, by a dropped
+    /// transaction mined again before them or by another of them.
+    pub fn verify_reorg_declares(
+        &self,
+        depth: u64,
+        remine_dropped: bool,
+        transactions: &[ReorgTransaction],
+    ) -> Result<(), SequencerError> {
+        let latest = self.blocks.current_block_number();
+        // An invalid depth is reported by the reorganisation itself.
+        let Some(new_latest) = latest.0.checked_sub(depth) else {
+            return Ok(());
+        };
+        let Some(state) = self.blocks.get_state(&BlockNumber(new_latest)) else {
+            return Ok(());
+        };
+
+        let mut declared = HashSet::new();
+        if remine_dropped {
+            for number in new_latest + 1..=latest.0 {
+                let Some(block) = self.blocks.num_to_block.get(&BlockNumber(number)) else {
+                    continue;
+                };
+                for transaction in block.transactions() {
+                    if let starknet_api::transaction::Transaction::Declare(tx) = transaction {
+                        declared.insert(tx.class_hash());
+                    }
+                }
+            }
+        }
+
+        for transaction in transactions {
+            if let Transaction::AccountTransaction(AccountTransaction::Declare(tx)) =
+                &transaction.transaction
+            {
+                let class_hash = tx.tx().class_hash();
+                if state.classes.contains_key(&class_hash) || !declared.insert(class_hash) {
+                    return Err(SequencerError::ClassAlreadyDeclared(class_hash));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds an executable transaction from a transaction of a block being dropped. The
+    /// classes it declares are read from the latest state, so it must be called before the state
+    /// is rolled back.
+    fn reorg_transaction(
+        &self,
+        transaction: StarknetTransaction,
+    ) -> Result<Option<ReorgTransaction>, SequencerError> {
+        let actual_fee = transaction.actual_fee();
+
+        let (transaction, sierra_class) = match transaction.inner {
+            starknet_api::transaction::Transaction::Invoke(tx) => {
+                (Transaction::AccountTransaction(AccountTransaction::Invoke(tx)), None)
+            }
+            starknet_api::transaction::Transaction::DeployAccount(tx) => {
+                (Transaction::AccountTransaction(AccountTransaction::DeployAccount(tx)), None)
+            }
+            starknet_api::transaction::Transaction::Declare(tx) => {
+                let record = self
+                    .state
+                    .classes
+                    .get(&tx.class_hash())
+                    .ok_or(StateError::UndeclaredClassHash(tx.class_hash()))?;
+                let sierra_class = record.sierra_class.clone();
+                let tx = DeclareTransaction::new(tx, record.class.clone())?;
+                (Transaction::AccountTransaction(AccountTransaction::Declare(tx)), sierra_class)
+            }
+            starknet_api::transaction::Transaction::L1Handler(tx) => (
+                Transaction::L1HandlerTransaction(L1HandlerTransaction {
+                    tx,
+                    paid_fee_on_l1: actual_fee,
+                }),
+                None,
+            ),
+            // Deploy transactions are only part of the genesis block, which is never dropped.
+            starknet_api::transaction::Transaction::Deploy(_) => return Ok(None),
+        };
+
+        Ok(Some(ReorgTransaction { transaction, sierra_class }))
+    }
+}
+
+/// A transaction to mine on top of a reorganised chain, along with the Sierra class it declares.
+pub struct ReorgTransaction {
+    pub transaction: Transaction,
+    pub sierra_class: Option<FlattenedSierraClass>,
+}
+
+/// Adds `amount` to the balance of `address` in every fee token of `state`.
+pub fn add_balance<S: State>(
+    state: &mut S,
+    fee_token_addresses: &FeeTokenAddresses,
+    address: ContractAddress,
+    amount: u128,
+) -> Result<(), SequencerError> {
+    let balance_key = get_storage_var_address("ERC20_balances", &[*address.0.key()])?;
+
+    let mut balances = Vec::new();
+    for fee_token_address in fee_token_addresses.all() {
+        let balance = state.get_storage_at(fee_token_address, balance_key)?;
+        let balance = starkfelt_to_u128(balance)?.checked_add(amount).ok_or_else(|| {
+            SequencerError::ConversionError {
+                from: "u128".into(),
+                to: "Uint256".into(),
+                message: "balance overflows the low part of the Uint256".into(),
+            }
+        })?;
+        balances.push((fee_token_address, balance));
+    }
+
+    for (fee_token_address, balance) in balances {
+        state.set_storage_at(fee_token_address, balance_key, stark_felt!(balance));
+    }
+
+    Ok(())
 }
 
 fn execute_transaction<S: StateReader>(
@@ -455,11 +1012,31 @@ fn execute_transaction<S: StateReader>(
     }
 }
 
+/// Returns the account whose nonce is incremented by `transaction`, if any.
+fn nonce_owner(transaction: &Transaction) -> Option<ContractAddress> {
+    match transaction {
+        Transaction::AccountTransaction(AccountTransaction::Invoke(tx)) => {
+            Some(tx.sender_address())
+        }
+        Transaction::AccountTransaction(AccountTransaction::DeployAccount(tx)) => {
+            Some(tx.contract_address)
+        }
+        Transaction::AccountTransaction(AccountTransaction::Declare(tx)) => match tx.tx() {
+            // Version 0 declare transactions don't increment the nonce of their sender.
+            starknet_api::transaction::DeclareTransaction::V0(_) => None,
+            starknet_api::transaction::DeclareTransaction::V1(tx) => Some(tx.sender_address),
+            starknet_api::transaction::DeclareTransaction::V2(tx) => Some(tx.sender_address),
+        },
+        Transaction::L1HandlerTransaction(_) => None,
+    }
+}
+
 fn has_pending_transactions(starknet: &StarknetWrapper) -> bool {
-    match starknet.blocks.pending_block {
+    let has_pending_block_transactions = match starknet.blocks.pending_block {
         Some(ref pending_block) => !pending_block.inner.body.transactions.is_empty(),
         None => false,
-    }
+    };
+    has_pending_block_transactions || !starknet.queued_transactions.is_empty()
 }
 
 fn pretty_print_resources(resources: &ResourcesMapping) -> String {
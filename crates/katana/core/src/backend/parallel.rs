@@ -0,0 +1,438 @@
+//! Optimistic parallel execution of transactions.
+//!
+//! The transactions of a batch are first executed in parallel, each one on its own fork of the
+//! state as it was before the batch, while recording the state keys it reads. They are then
+//! committed in order: a transaction which read a key written by a transaction before it in the
+//! batch saw a stale value, so it is re-executed on the committed state instead. The results are
+//! therefore the same as executing the batch serially.
+//!
+//! Every transaction pays its fee to the sequencer, which would make all of them conflict on the
+//! sequencer balance. A transaction which only touches the sequencer balance in its fee transfer
+//! is not considered conflicting on it, and the fee it paid is added to the committed balance.
+//!
+//! Only invoke and deploy account transactions are executed in parallel. Declare and L1 handler
+//! transactions are executed serially, and split the batch around them.
+
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::thread;
+
+use blockifier::abi::abi_utils::get_storage_var_address;
+use blockifier::block_context::BlockContext;
+use blockifier::execution::contract_class::ContractClass;
+use blockifier::execution::entry_point::CallInfo;
+use blockifier::state::cached_state::{CachedState, CommitmentStateDiff};
+use blockifier::state::state_api::{State, StateReader, StateResult};
+use blockifier::transaction::account_transaction::AccountTransaction;
+use blockifier::transaction::errors::TransactionExecutionError;
+use blockifier::transaction::objects::TransactionExecutionInfo;
+use blockifier::transaction::transaction_execution::Transaction;
+use starknet::core::types::FieldElement;
+use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey};
+use starknet_api::hash::{StarkFelt, StarkHash};
+use starknet_api::patricia_key;
+use starknet_api::state::StorageKey;
+use tracing::{trace, warn};
+
+use super::execute_transaction;
+use crate::backend::state::{MemDb, StateExt};
+use crate::utils::starkfelt_to_u128;
+
+type ExecutionResult = Result<TransactionExecutionInfo, TransactionExecutionError>;
+
+/// Executes `transactions` in order on `state`, running independent transactions in parallel,
+/// and returns their results in the same order.
+pub(super) fn execute_transactions(
+    state: &mut CachedState<MemDb>,
+    transactions: Vec<(Transaction, BlockContext)>,
+) -> Vec<ExecutionResult> {
+    let mut results = Vec::with_capacity(transactions.len());
+    let mut transactions = transactions.into_iter().peekable();
+
+    while transactions.peek().is_some() {
+        let mut batch = Vec::new();
+        while let Some(copy) = transactions.peek().and_then(|(tx, _)| clone_transaction(tx)) {
+            let (transaction, block_context) = transactions.next().expect("transaction was peeked");
+            batch.push(BatchTransaction { transaction, copy, block_context });
+        }
+
+        if batch.is_empty() {
+            let (transaction, block_context) = transactions.next().expect("transaction was peeked");
+            results.push(execute_transaction(transaction, state, &block_context));
+        } else {
+            results.extend(execute_batch(state, batch));
+        }
+    }
+
+    results
+}
+
+struct BatchTransaction {
+    transaction: Transaction,
+    /// A copy of the transaction, to re-execute it if its parallel execution conflicts.
+    copy: Transaction,
+    block_context: BlockContext,
+}
+
+fn execute_batch(
+    state: &mut CachedState<MemDb>,
+    batch: Vec<BatchTransaction>,
+) -> Vec<ExecutionResult> {
+    if batch.len() == 1 {
+        let BatchTransaction { transaction, block_context, .. } =
+            batch.into_iter().next().expect("batch is not empty");
+        return vec![execute_transaction(transaction, state, &block_context)];
+    }
+
+    let mut base = state.state.clone();
+    base.apply_state(state);
+
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get).min(batch.len());
+    let mut chunks = (0..threads).map(|_| Vec::new()).collect::<Vec<_>>();
+    let mut copies = Vec::with_capacity(batch.len());
+
+    for (index, tx) in batch.into_iter().enumerate() {
+        chunks[index % threads].push((index, tx.transaction, tx.block_context.clone()));
+        copies.push((tx.copy, tx.block_context));
+    }
+
+    let mut executions = thread::scope(|scope| {
+        let handles = chunks
+            .into_iter()
+            .map(|chunk| {
+                let base = &base;
+                scope.spawn(move || {
+                    let mut state = base.clone();
+                    chunk
+                        .into_iter()
+                        .map(|(index, transaction, block_context)| {
+                            (index, Execution::run(transaction, &mut state, &block_context))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("execution thread panicked"))
+            .collect::<Vec<_>>()
+    });
+    executions.sort_by_key(|(index, _)| *index);
+
+    let mut written = HashSet::new();
+    let mut results = Vec::with_capacity(executions.len());
+
+    for ((_, execution), (copy, block_context)) in executions.into_iter().zip(copies) {
+        let sequencer_balance = sequencer_balance_keys(&block_context);
+        let fee_only = !execution.accesses_sequencer_balance(&block_context, &sequencer_balance);
+
+        let writes = if execution.conflicts(&written, &sequencer_balance, fee_only) {
+            None
+        } else if fee_only {
+            execution.writes.with_fee_delta(&mut base, state, &sequencer_balance)
+        } else {
+            Some(execution.writes.clone())
+        };
+
+        let (result, writes) = match writes {
+            Some(writes) => (execution.result, writes),
+            None => {
+                trace!("Re-executing conflicting transaction serially");
+                let execution = Execution::run(copy, state, &block_context);
+                (execution.result, execution.writes)
+            }
+        };
+
+        // A transaction whose writes can't be committed is rejected, like one which failed
+        // validation.
+        match writes.apply(state) {
+            Ok(()) => {
+                written.extend(writes.keys());
+                results.push(result);
+            }
+            Err(err) => {
+                warn!("Failed to commit transaction writes: {err:?}");
+                results.push(Err(TransactionExecutionError::from(err)));
+            }
+        }
+    }
+
+    results
+}
+
+/// Returns a copy of `transaction` if it can be executed in parallel.
+fn clone_transaction(transaction: &Transaction) -> Option<Transaction> {
+    match transaction {
+        Transaction::AccountTransaction(AccountTransaction::Invoke(tx)) => {
+            Some(Transaction::AccountTransaction(AccountTransaction::Invoke(tx.clone())))
+        }
+        Transaction::AccountTransaction(AccountTransaction::DeployAccount(tx)) => {
+            Some(Transaction::AccountTransaction(AccountTransaction::DeployAccount(tx.clone())))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the keys of the low and high parts of the sequencer balance in the fee token of
+/// `block_context`.
+fn sequencer_balance_keys(block_context: &BlockContext) -> [StateKey; 2] {
+    let low =
+        get_storage_var_address("ERC20_balances", &[*block_context.sequencer_address.0.key()])
+            .expect("balance storage key should be valid");
+    let high = StorageKey(patricia_key!(StarkFelt::from(
+        FieldElement::from(*low.0.key()) + FieldElement::ONE
+    )));
+
+    [
+        StateKey::Storage(block_context.fee_token_address, low),
+        StateKey::Storage(block_context.fee_token_address, high),
+    ]
+}
+
+/// A state entry read or written by a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum StateKey {
+    Storage(ContractAddress, StorageKey),
+    Nonce(ContractAddress),
+    ClassHash(ContractAddress),
+}
+
+/// The outcome of executing a transaction on a fork of the state.
+struct Execution {
+    result: ExecutionResult,
+    reads: HashSet<StateKey>,
+    writes: StateWrites,
+}
+
+impl Execution {
+    /// Executes `transaction` on a fork of `state`, leaving `state` untouched.
+    fn run<S: StateReader>(
+        transaction: Transaction,
+        state: &mut S,
+        block_context: &BlockContext,
+    ) -> Self {
+        let mut fork = CachedState::new(ReadTracker::new(state));
+        let result = execute_transaction(transaction, &mut fork, block_context);
+        let writes = StateWrites::from(fork.to_state_diff());
+        let reads = std::mem::take(&mut fork.state.reads);
+        Self { result, reads, writes }
+    }
+
+    /// Returns whether the transaction read a key written by a transaction committed before it.
+    /// The sequencer balance is not considered if the transaction only touched it to pay its fee.
+    fn conflicts(
+        &self,
+        written: &HashSet<StateKey>,
+        sequencer_balance: &[StateKey; 2],
+        fee_only: bool,
+    ) -> bool {
+        self.reads
+            .iter()
+            .any(|key| written.contains(key) && !(fee_only && sequencer_balance.contains(key)))
+    }
+
+    /// Returns whether the validation or the execution of the transaction touched the sequencer
+    /// balance, outside of the fee transfer.
+    fn accesses_sequencer_balance(
+        &self,
+        block_context: &BlockContext,
+        sequencer_balance: &[StateKey; 2],
+    ) -> bool {
+        let Ok(execution_info) = &self.result else {
+            return false;
+        };
+
+        let keys = sequencer_balance
+            .iter()
+            .filter_map(|key| match key {
+                StateKey::Storage(_, key) => Some(*key),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        [&execution_info.validate_call_info, &execution_info.execute_call_info]
+            .into_iter()
+            .flatten()
+            .any(|call_info| accesses_storage(call_info, block_context.fee_token_address, &keys))
+    }
+}
+
+fn accesses_storage(call_info: &CallInfo, address: ContractAddress, keys: &[StorageKey]) -> bool {
+    (call_info.call.storage_address == address
+        && keys.iter().any(|key| call_info.accessed_storage_keys.contains(key)))
+        || call_info.inner_calls.iter().any(|call| accesses_storage(call, address, keys))
+}
+
+/// The state changes of a transaction. Transactions executed on forks don't declare classes, so
+/// only storage, nonces and deployed contracts are tracked.
+#[derive(Debug, Clone, Default)]
+struct StateWrites {
+    storage: Vec<(ContractAddress, StorageKey, StarkFelt)>,
+    nonces: Vec<(ContractAddress, Nonce)>,
+    class_hashes: Vec<(ContractAddress, ClassHash)>,
+}
+
+impl StateWrites {
+    fn keys(&self) -> impl Iterator<Item = StateKey> + '_ {
+        let storage =
+            self.storage.iter().map(|(address, key, _)| StateKey::Storage(*address, *key));
+        let nonces = self.nonces.iter().map(|(address, _)| StateKey::Nonce(*address));
+        let class_hashes =
+            self.class_hashes.iter().map(|(address, _)| StateKey::ClassHash(*address));
+        storage.chain(nonces).chain(class_hashes)
+    }
+
+    /// Returns the writes with the sequencer balance increased by the fee paid by the transaction
+    /// instead of overwritten, or `None` if the fee can't be applied as a change of the low part
+    /// of the balance.
+    fn with_fee_delta(
+        &self,
+        base: &mut MemDb,
+        state: &mut CachedState<MemDb>,
+        sequencer_balance: &[StateKey; 2],
+    ) -> Option<Self> {
+        let [low, high] = sequencer_balance;
+        let mut writes = self.clone();
+        let mut storage = Vec::with_capacity(writes.storage.len());
+
+        for (address, key, value) in writes.storage {
+            let state_key = StateKey::Storage(address, key);
+            let base_value = base.get_storage_at(address, key).ok()?;
+
+            if state_key == *low {
+                let fee = starkfelt_to_u128(value)
+                    .ok()?
+                    .checked_sub(starkfelt_to_u128(base_value).ok()?)?;
+                let balance = starkfelt_to_u128(state.get_storage_at(address, key).ok()?).ok()?;
+                storage.push((address, key, StarkFelt::from(balance.checked_add(fee)?)));
+            } else if state_key == *high {
+                if value != base_value {
+                    return None;
+                }
+            } else {
+                storage.push((address, key, value));
+            }
+        }
+
+        writes.storage = storage;
+        Some(writes)
+    }
+
+    /// Applies the writes to `state`. The nonces are read before anything is written, so that
+    /// the writes of a transaction are not partially applied if they can't be read.
+    fn apply<S: State>(&self, state: &mut S) -> StateResult<()> {
+        let mut nonce_increments = Vec::with_capacity(self.nonces.len());
+        for (address, nonce) in &self.nonces {
+            let current = starkfelt_to_u128(state.get_nonce_at(*address)?.0)?;
+            nonce_increments.push((*address, starkfelt_to_u128(nonce.0)?.saturating_sub(current)));
+        }
+
+        for (address, class_hash) in &self.class_hashes {
+            state.set_class_hash_at(*address, *class_hash)?;
+        }
+
+        for (address, key, value) in &self.storage {
+            state.set_storage_at(*address, *key, *value);
+        }
+
+        for (address, increments) in nonce_increments {
+            for _ in 0..increments {
+                state.increment_nonce(address)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<CommitmentStateDiff> for StateWrites {
+    fn from(diff: CommitmentStateDiff) -> Self {
+        let storage = diff
+            .storage_updates
+            .into_iter()
+            .flat_map(|(address, updates)| {
+                updates.into_iter().map(move |(key, value)| (address, key, value))
+            })
+            .collect();
+
+        Self {
+            storage,
+            nonces: diff.address_to_nonce.into_iter().collect(),
+            class_hashes: diff.address_to_class_hash.into_iter().collect(),
+        }
+    }
+}
+
+/// A state reader which records the keys read from the underlying state.
+struct ReadTracker<'a, S: StateReader> {
+    state: &'a mut S,
+    reads: HashSet<StateKey>,
+}
+
+impl<'a, S: StateReader> ReadTracker<'a, S> {
+    fn new(state: &'a mut S) -> Self {
+        Self { state, reads: HashSet::new() }
+    }
+}
+
+impl<S: StateReader> StateReader for ReadTracker<'_, S> {
+    fn get_storage_at(
+        &mut self,
+        contract_address: ContractAddress,
+        key: StorageKey,
+    ) -> StateResult<StarkFelt> {
+        self.reads.insert(StateKey::Storage(contract_address, key));
+        self.state.get_storage_at(contract_address, key)
+    }
+
+    fn get_nonce_at(&mut self, contract_address: ContractAddress) -> StateResult<Nonce> {
+        self.reads.insert(StateKey::Nonce(contract_address));
+        self.state.get_nonce_at(contract_address)
+    }
+
+    fn get_compiled_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> StateResult<ContractClass> {
+        self.state.get_compiled_contract_class(class_hash)
+    }
+
+    fn get_class_hash_at(&mut self, contract_address: ContractAddress) -> StateResult<ClassHash> {
+        self.reads.insert(StateKey::ClassHash(contract_address));
+        self.state.get_class_hash_at(contract_address)
+    }
+
+    fn get_compiled_class_hash(&mut self, class_hash: ClassHash) -> StateResult<CompiledClassHash> {
+        self.state.get_compiled_class_hash(class_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage_key(address: u8, key: u8) -> StateKey {
+        StateKey::Storage(
+            ContractAddress(patricia_key!(StarkFelt::from(address as u64))),
+            StorageKey(patricia_key!(StarkFelt::from(key as u64))),
+        )
+    }
+
+    #[test]
+    fn fee_only_reads_of_sequencer_balance_do_not_conflict() {
+        let sequencer_balance = [storage_key(1, 1), storage_key(1, 2)];
+        let execution = Execution {
+            result: Ok(TransactionExecutionInfo::default()),
+            reads: HashSet::from([storage_key(1, 1), storage_key(2, 1)]),
+            writes: StateWrites::default(),
+        };
+
+        let written = HashSet::from([storage_key(1, 1)]);
+        assert!(!execution.conflicts(&written, &sequencer_balance, true));
+        assert!(execution.conflicts(&written, &sequencer_balance, false));
+
+        let written = HashSet::from([storage_key(2, 1)]);
+        assert!(execution.conflicts(&written, &sequencer_balance, true));
+    }
+}
@@ -0,0 +1,197 @@
+//! Maps the program counters of compiled Sierra classes back to the Sierra functions they run.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use cairo_lang_sierra::extensions::gas::CostTokenType;
+use cairo_lang_sierra_to_casm::compiler::compile;
+use cairo_lang_sierra_to_casm::metadata::{calc_metadata, MetadataComputationConfig};
+use cairo_lang_starknet::casm_contract_class::ENTRY_POINT_COST;
+use cairo_lang_starknet::contract_class::ContractClass;
+use starknet::core::types::contract::SierraClass;
+use starknet::core::types::FieldElement;
+use starknet_api::core::ClassHash;
+use starknet_api::hash::StarkFelt;
+
+use crate::backend::state::MemDb;
+use crate::utils::contract::sierra_contract_class;
+
+/// The Sierra functions of the classes profiled so far.
+#[derive(Debug)]
+pub struct SierraDebugInfo {
+    dir: Option<DebugInfoDir>,
+    /// The functions of each class, along with whether they were named from debug info.
+    functions: HashMap<ClassHash, (Arc<SierraFunctions>, bool)>,
+}
+
+impl SierraDebugInfo {
+    /// Reads the debug info of classes from the artifacts in `dir`, if any.
+    pub fn new(dir: Option<&Path>) -> Self {
+        Self { dir: dir.map(DebugInfoDir::new), functions: HashMap::new() }
+    }
+
+    /// Returns the functions of the Sierra class `class_hash`, named from its artifact when one
+    /// is found, and by their id otherwise. Returns `None` for legacy classes.
+    pub fn functions(
+        &mut self,
+        class_hash: ClassHash,
+        state: &MemDb,
+    ) -> Option<Arc<SierraFunctions>> {
+        match self.functions.get(&class_hash) {
+            Some((functions, true)) => return Some(functions.clone()),
+            // The artifact of the class may have been built since it was last looked up.
+            Some((functions, false)) => {
+                let functions = functions.clone();
+                return Some(self.functions_from_artifact(class_hash).unwrap_or(functions));
+            }
+            None => {}
+        }
+
+        if let Some(functions) = self.functions_from_artifact(class_hash) {
+            return Some(functions);
+        }
+
+        let sierra_class = state.classes.get(&class_hash)?.sierra_class.as_ref()?;
+        let functions =
+            Arc::new(SierraFunctions::new(&sierra_contract_class(sierra_class).ok()?).ok()?);
+        self.functions.insert(class_hash, (functions.clone(), false));
+        Some(functions)
+    }
+
+    fn functions_from_artifact(&mut self, class_hash: ClassHash) -> Option<Arc<SierraFunctions>> {
+        let contract_class = self.dir.as_mut()?.find(class_hash)?;
+        let functions = Arc::new(SierraFunctions::new(&contract_class).ok()?);
+        self.functions.insert(class_hash, (functions.clone(), true));
+        Some(functions)
+    }
+}
+
+/// The Sierra functions of a class, sorted by the offset of their first instruction in its
+/// compiled bytecode.
+#[derive(Debug)]
+pub struct SierraFunctions(Vec<(usize, String)>);
+
+impl SierraFunctions {
+    /// Compiles `contract_class` the way it is compiled on declaration, to find where each of its
+    /// functions starts in the bytecode. Functions are named after their debug name when the
+    /// class carries debug info, and by their id otherwise.
+    pub fn new(contract_class: &ContractClass) -> Result<Self> {
+        let program = contract_class.extract_sierra_program()?;
+
+        let entry_points = &contract_class.entry_points_by_type;
+        let function_set_costs = entry_points
+            .constructor
+            .iter()
+            .chain(&entry_points.external)
+            .chain(&entry_points.l1_handler)
+            .map(|entry_point| {
+                let id = program.funcs[entry_point.function_idx].id.clone();
+                (id, [(CostTokenType::Const, ENTRY_POINT_COST)].into())
+            })
+            .collect();
+
+        let metadata = calc_metadata(&program, MetadataComputationConfig { function_set_costs })?;
+        let casm_program = compile(&program, &metadata, true)?;
+
+        let mut functions: Vec<_> = program
+            .funcs
+            .iter()
+            .map(|function| {
+                let statement =
+                    &casm_program.debug_info.sierra_statement_info[function.entry_point.0];
+                (statement.code_offset, function.id.to_string())
+            })
+            .collect();
+        functions.sort_by_key(|(offset, _)| *offset);
+
+        Ok(Self(functions))
+    }
+
+    /// Returns the function running the instruction at `pc`, an offset in the bytecode.
+    pub fn at(&self, pc: usize) -> Option<&str> {
+        let index = self.0.partition_point(|(offset, _)| *offset <= pc);
+        index.checked_sub(1).map(|index| self.0[index].1.as_str())
+    }
+}
+
+/// A directory of Sierra contract class artifacts, such as the `target/dev` directory of a Dojo
+/// project, read for the debug info that declared classes don't carry.
+#[derive(Debug)]
+struct DebugInfoDir {
+    path: PathBuf,
+    /// The class hash of each artifact, `None` if it isn't a Sierra class, along with the
+    /// modification time of the artifact when it was read.
+    artifacts: HashMap<PathBuf, (SystemTime, Option<ClassHash>)>,
+}
+
+impl DebugInfoDir {
+    fn new(path: &Path) -> Self {
+        Self { path: path.to_path_buf(), artifacts: HashMap::new() }
+    }
+
+    /// Returns the artifact of the class `class_hash`. The artifacts written since the last
+    /// lookup are hashed first, as classes are usually built after the node is started.
+    fn find(&mut self, class_hash: ClassHash) -> Option<ContractClass> {
+        self.rescan();
+
+        let (path, _) = self.artifacts.iter().find(|(_, (_, hash))| *hash == Some(class_hash))?;
+        let contents = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    fn rescan(&mut self) {
+        let Ok(entries) = fs::read_dir(&self.path) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.file_name().map_or(true, |name| name == "manifest.json")
+                || path.extension().map_or(true, |extension| extension != "json")
+            {
+                continue;
+            }
+
+            let Ok(modified) = entry.metadata().and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+            if self.artifacts.get(&path).is_some_and(|(read_at, _)| *read_at == modified) {
+                continue;
+            }
+
+            let class_hash = artifact_class_hash(&path);
+            self.artifacts.insert(path, (modified, class_hash));
+        }
+    }
+}
+
+fn artifact_class_hash(path: &Path) -> Option<ClassHash> {
+    let contents = fs::read_to_string(path).ok()?;
+    let class_hash: FieldElement =
+        serde_json::from_str::<SierraClass>(&contents).ok()?.class_hash().ok()?;
+    Some(ClassHash(StarkFelt::from(class_hash)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn function_at_pc() {
+        let functions = SierraFunctions(vec![
+            (0, "main".to_string()),
+            (12, "move".to_string()),
+            (40, "attack".to_string()),
+        ]);
+
+        assert_eq!(functions.at(0), Some("main"));
+        assert_eq!(functions.at(11), Some("main"));
+        assert_eq!(functions.at(12), Some("move"));
+        assert_eq!(functions.at(100), Some("attack"));
+        assert_eq!(SierraFunctions(vec![(4, "main".to_string())]).at(0), None);
+    }
+}
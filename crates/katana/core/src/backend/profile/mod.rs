@@ -0,0 +1,353 @@
+//! Cairo VM profiles of the execution of transactions.
+//!
+//! The Sierra calls of a transaction are run again with tracing enabled, and each step is
+//! sampled with the stack of Cairo functions it ran in. Frames are pushed when the frame pointer
+//! grows and popped when it shrinks back, and are named after the Sierra function at the program
+//! counter. Declared classes carry no Sierra debug info, so functions are named from the
+//! artifacts of the classes when a directory of them is given, see [debug_info], and by their
+//! Sierra id otherwise.
+//!
+//! Each call adds a frame named `<contract address>::<function>`, where the function is resolved
+//! from the ABI of the Sierra class of the contract. Entry points of legacy classes, and of
+//! classes declared in the pending block, are named after the protocol entry points when they
+//! match, and by their selector otherwise. Legacy calls, and calls which can't be traced, are
+//! sampled as a whole, with the steps spent in the call itself, excluding its inner calls.
+
+pub mod debug_info;
+mod trace;
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use blockifier::block_context::BlockContext;
+use blockifier::execution::contract_class::ContractClass;
+use blockifier::execution::entry_point::CallInfo;
+use blockifier::transaction::objects::TransactionExecutionInfo;
+use serde_json::Value;
+use starknet::core::types::FieldElement;
+use starknet::core::utils::get_selector_from_name;
+use starknet_api::core::{ClassHash, EntryPointSelector};
+use starknet_api::hash::StarkFelt;
+use starknet_api::transaction::Transaction;
+use tracing::warn;
+
+use self::debug_info::{SierraDebugInfo, SierraFunctions};
+use self::trace::{trace_call, CallTrace, ExecutionContext};
+use crate::backend::state::MemDb;
+
+/// The entry points called by the protocol itself.
+const PROTOCOL_ENTRY_POINTS: [&str; 6] = [
+    "__validate__",
+    "__validate_declare__",
+    "__validate_deploy__",
+    "__execute__",
+    "constructor",
+    "transfer",
+];
+
+/// The Cairo steps spent in a stack of functions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileSample {
+    /// The frames of the call stack, from the transaction phase to the sampled function.
+    pub stack: Vec<String>,
+    pub steps: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionProfile {
+    pub samples: Vec<ProfileSample>,
+}
+
+impl TransactionProfile {
+    /// Builds the profile of an executed transaction, reading classes from `state`.
+    pub fn new(
+        transaction: &Transaction,
+        block_context: &BlockContext,
+        execution_info: &TransactionExecutionInfo,
+        state: &MemDb,
+        debug_info: &mut SierraDebugInfo,
+    ) -> Self {
+        let mut profiler = Profiler {
+            state,
+            context: ExecutionContext::new(transaction, block_context),
+            names: FunctionNames::new(state),
+            debug_info,
+            samples: Vec::new(),
+            sample_indices: HashMap::new(),
+        };
+
+        let phases = [
+            ("validate", &execution_info.validate_call_info),
+            ("execute", &execution_info.execute_call_info),
+            ("fee_transfer", &execution_info.fee_transfer_call_info),
+        ];
+
+        for (phase, call_info) in phases {
+            if let Some(call_info) = call_info {
+                profiler.profile_call(call_info, &mut vec![phase.to_string()]);
+            }
+        }
+
+        Self { samples: profiler.samples }
+    }
+
+    pub fn total_steps(&self) -> usize {
+        self.samples.iter().map(|s| s.steps).sum()
+    }
+
+    /// Returns the profile in the folded stacks format, which is read by `flamegraph.pl`,
+    /// `inferno-flamegraph` and speedscope.
+    pub fn to_folded(&self) -> String {
+        let mut folded = String::new();
+        for sample in self.samples.iter().filter(|s| s.steps > 0) {
+            let _ = writeln!(folded, "{} {}", sample.stack.join(";"), sample.steps);
+        }
+        folded
+    }
+}
+
+struct Profiler<'a> {
+    state: &'a MemDb,
+    context: ExecutionContext,
+    names: FunctionNames<'a>,
+    debug_info: &'a mut SierraDebugInfo,
+    samples: Vec<ProfileSample>,
+    /// The index of the sample of each stack.
+    sample_indices: HashMap<Vec<String>, usize>,
+}
+
+impl Profiler<'_> {
+    fn profile_call(&mut self, call_info: &CallInfo, stack: &mut Vec<String>) {
+        let call = &call_info.call;
+        let class_hash = call.class_hash.or_else(|| {
+            self.state.storage.get(&call.storage_address).map(|record| record.class_hash)
+        });
+
+        stack.push(format!(
+            "{:#x}::{}",
+            FieldElement::from(*call.storage_address.0.key()),
+            self.names.resolve(class_hash, call.entry_point_selector)
+        ));
+
+        match self.trace_call(call_info, class_hash) {
+            Some((trace, functions)) => self.sample_trace(call_info, &trace, &functions, stack),
+            None => {
+                let inner_steps: usize =
+                    call_info.inner_calls.iter().map(|call| call.vm_resources.n_steps).sum();
+                self.sample(stack, call_info.vm_resources.n_steps.saturating_sub(inner_steps));
+
+                for inner_call in &call_info.inner_calls {
+                    self.profile_call(inner_call, stack);
+                }
+            }
+        }
+
+        stack.pop();
+    }
+
+    /// Traces the call if it runs a Sierra class.
+    fn trace_call(
+        &mut self,
+        call_info: &CallInfo,
+        class_hash: Option<ClassHash>,
+    ) -> Option<(CallTrace, Arc<SierraFunctions>)> {
+        let class_hash = class_hash?;
+        let ContractClass::V1(contract_class) = &self.state.classes.get(&class_hash)?.class else {
+            return None;
+        };
+        let functions = self.debug_info.functions(class_hash, self.state)?;
+
+        match trace_call(call_info, contract_class, &self.context) {
+            Ok(trace) => Some((trace, functions)),
+            Err(err) => {
+                warn!(
+                    "Failed to trace call to {:#x}, profiling it as a whole: {err}",
+                    FieldElement::from(*call_info.call.storage_address.0.key())
+                );
+                None
+            }
+        }
+    }
+
+    /// Samples the steps of a traced call, along with its inner calls.
+    fn sample_trace(
+        &mut self,
+        call_info: &CallInfo,
+        trace: &CallTrace,
+        functions: &SierraFunctions,
+        stack: &mut Vec<String>,
+    ) {
+        let depth = stack.len();
+        let mut frame_pointers: Vec<usize> = Vec::new();
+        let mut inner_calls = call_info.inner_calls.iter().zip(&trace.inner_call_steps).peekable();
+        // The consecutive steps run with the current stack, sampled once it changes.
+        let mut steps = 0;
+
+        for (step, &(pc, fp)) in trace.steps.iter().enumerate() {
+            while frame_pointers.last().is_some_and(|frame_pointer| *frame_pointer > fp) {
+                self.sample(stack, std::mem::take(&mut steps));
+                frame_pointers.pop();
+                stack.pop();
+            }
+
+            if frame_pointers.last().map_or(true, |frame_pointer| fp > *frame_pointer) {
+                self.sample(stack, std::mem::take(&mut steps));
+                frame_pointers.push(fp);
+                stack.push(functions.at(pc).unwrap_or("<unknown>").to_string());
+            }
+
+            steps += 1;
+
+            while let Some((inner_call, _)) = inner_calls.next_if(|(_, at)| **at == step) {
+                self.sample(stack, std::mem::take(&mut steps));
+                self.profile_call(inner_call, stack);
+            }
+        }
+
+        self.sample(stack, steps);
+        stack.truncate(depth);
+    }
+
+    fn sample(&mut self, stack: &[String], steps: usize) {
+        if steps == 0 {
+            return;
+        }
+
+        match self.sample_indices.get(stack) {
+            Some(&index) => self.samples[index].steps += steps,
+            None => {
+                self.sample_indices.insert(stack.to_vec(), self.samples.len());
+                self.samples.push(ProfileSample { stack: stack.to_vec(), steps });
+            }
+        }
+    }
+}
+
+/// Resolves entry point selectors to function names, caching the names found in each class.
+struct FunctionNames<'a> {
+    state: &'a MemDb,
+    classes: HashMap<ClassHash, HashMap<EntryPointSelector, String>>,
+}
+
+impl<'a> FunctionNames<'a> {
+    fn new(state: &'a MemDb) -> Self {
+        Self { state, classes: HashMap::new() }
+    }
+
+    fn resolve(&mut self, class_hash: Option<ClassHash>, selector: EntryPointSelector) -> String {
+        let state = self.state;
+        let name = class_hash.and_then(|class_hash| {
+            self.classes
+                .entry(class_hash)
+                .or_insert_with(|| class_function_names(state, class_hash))
+                .get(&selector)
+                .cloned()
+        });
+
+        name.or_else(|| {
+            PROTOCOL_ENTRY_POINTS
+                .into_iter()
+                .find(|name| selector_of(name) == Some(selector))
+                .map(String::from)
+        })
+        .unwrap_or_else(|| format!("{:#x}", FieldElement::from(selector.0)))
+    }
+}
+
+/// Returns the names of the functions listed in the ABI of a Sierra class.
+fn class_function_names(
+    state: &MemDb,
+    class_hash: ClassHash,
+) -> HashMap<EntryPointSelector, String> {
+    let Some(sierra_class) =
+        state.classes.get(&class_hash).and_then(|record| record.sierra_class.as_ref())
+    else {
+        return HashMap::new();
+    };
+
+    let Ok(abi) = serde_json::from_str::<Value>(&sierra_class.abi) else {
+        return HashMap::new();
+    };
+
+    let mut names = Vec::new();
+    collect_function_names(&abi, &mut names);
+
+    names.into_iter().filter_map(|name| Some((selector_of(&name)?, name))).collect()
+}
+
+/// Collects the functions of an ABI, including the ones nested in interfaces.
+fn collect_function_names(abi: &Value, names: &mut Vec<String>) {
+    let Some(items) = abi.as_array() else {
+        return;
+    };
+
+    for item in items {
+        match item.get("type").and_then(Value::as_str) {
+            Some("function") | Some("constructor") | Some("l1_handler") => {
+                if let Some(name) = item.get("name").and_then(Value::as_str) {
+                    names.push(name.to_string());
+                }
+            }
+            Some("interface") => {
+                if let Some(items) = item.get("items") {
+                    collect_function_names(items, names);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn selector_of(name: &str) -> Option<EntryPointSelector> {
+    get_selector_from_name(name).ok().map(|selector| EntryPointSelector(StarkFelt::from(selector)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn collect_nested_function_names() {
+        let abi = json!([
+            { "type": "function", "name": "spawn", "inputs": [], "outputs": [] },
+            { "type": "impl", "name": "WorldImpl", "interface_name": "IWorld" },
+            {
+                "type": "interface",
+                "name": "IWorld",
+                "items": [{ "type": "function", "name": "move", "inputs": [], "outputs": [] }]
+            },
+            { "type": "event", "name": "Moved", "inputs": [] },
+            { "type": "constructor", "name": "constructor", "inputs": [] }
+        ]);
+
+        let mut names = Vec::new();
+        collect_function_names(&abi, &mut names);
+        assert_eq!(names, vec!["spawn", "move", "constructor"]);
+    }
+
+    #[test]
+    fn folded_stacks() {
+        let profile = TransactionProfile {
+            samples: vec![
+                ProfileSample {
+                    stack: vec!["execute".into(), "0x1::__execute__".into()],
+                    steps: 10,
+                },
+                ProfileSample {
+                    stack: vec!["execute".into(), "0x1::__execute__".into(), "0x2::move".into()],
+                    steps: 32,
+                },
+                ProfileSample { stack: vec!["fee_transfer".into()], steps: 0 },
+            ],
+        };
+
+        assert_eq!(profile.total_steps(), 42);
+        assert_eq!(
+            profile.to_folded(),
+            "execute;0x1::__execute__ 10\nexecute;0x1::__execute__;0x2::move 32\n"
+        );
+    }
+}
@@ -0,0 +1,443 @@
+//! Re-executes the Sierra calls of a transaction in a traced VM.
+//!
+//! The VM runs without tracing when transactions are executed, so the calls are re-executed after
+//! the fact, on their own: the results of their syscalls are replayed from what the call recorded
+//! instead of being read from the state, and inner calls are not executed again but traced on
+//! their own. Gas is not charged, which doesn't change the steps of a call that succeeded.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::slice;
+
+use anyhow::{anyhow, bail, Result};
+use blockifier::block_context::BlockContext;
+use blockifier::execution::contract_class::ContractClassV1;
+use blockifier::execution::entry_point::CallInfo;
+use blockifier::execution::execution_utils::{felt_to_stark_felt, stark_felt_to_felt};
+use cairo_lang_casm::hints::{Hint, StarknetHint};
+use cairo_lang_casm::operand::{BinOpOperand, DerefOrImmediate, Operation, Register, ResOperand};
+use cairo_lang_runner::casm_run::execute_core_hint_base;
+use cairo_vm::felt::Felt252;
+use cairo_vm::hint_processor::hint_processor_definition::{HintProcessor, HintReference};
+use cairo_vm::serde::deserialize_program::{ApTracking, BuiltinName};
+use cairo_vm::types::exec_scope::ExecutionScopes;
+use cairo_vm::types::relocatable::{MaybeRelocatable, Relocatable};
+use cairo_vm::vm::errors::hint_errors::HintError;
+use cairo_vm::vm::errors::vm_errors::VirtualMachineError;
+use cairo_vm::vm::runners::cairo_runner::{CairoArg, CairoRunner};
+use cairo_vm::vm::vm_core::VirtualMachine;
+use starknet::core::types::FieldElement;
+use starknet::core::utils::parse_cairo_short_string;
+use starknet_api::hash::StarkFelt;
+use starknet_api::transaction::{DeclareTransaction, InvokeTransaction, Transaction};
+
+/// The builtins the classes are run with, as on execution.
+const BUILTINS: [BuiltinName; 8] = [
+    BuiltinName::bitwise,
+    BuiltinName::ec_op,
+    BuiltinName::ecdsa,
+    BuiltinName::output,
+    BuiltinName::pedersen,
+    BuiltinName::poseidon,
+    BuiltinName::range_check,
+    BuiltinName::segment_arena,
+];
+
+/// The segment arena isn't a builtin of the VM, its segment is set up by the caller.
+const SEGMENT_ARENA_BUILTIN: &str = "segment_arena_builtin";
+
+/// The `ret` instruction written after the bytecode, which the builtin costs pointer follows.
+const RET_INSTRUCTION: u128 = 0x208b7fff7fff7ffe;
+
+/// The number of 64 bits words in a block of the keccak syscall input.
+const KECCAK_FULL_RATE_IN_WORDS: usize = 17;
+
+/// The steps of a traced call.
+#[derive(Debug)]
+pub struct CallTrace {
+    /// The program counter of each step, as an offset in the bytecode of the class, along with
+    /// the frame pointer.
+    pub steps: Vec<(usize, usize)>,
+    /// The step making each inner call, in the order of the inner calls.
+    pub inner_call_steps: Vec<usize>,
+}
+
+/// What `get_execution_info` reads of the transaction and the block a call was executed in.
+#[derive(Debug)]
+pub struct ExecutionContext {
+    block_number: u64,
+    block_timestamp: u64,
+    sequencer_address: StarkFelt,
+    version: StarkFelt,
+    account_contract_address: StarkFelt,
+    max_fee: u128,
+    signature: Vec<StarkFelt>,
+    transaction_hash: StarkFelt,
+    chain_id: StarkFelt,
+    nonce: StarkFelt,
+}
+
+impl ExecutionContext {
+    pub fn new(transaction: &Transaction, block_context: &BlockContext) -> Self {
+        let chain_id = FieldElement::from_hex_be(&block_context.chain_id.as_hex())
+            .map(StarkFelt::from)
+            .unwrap_or_default();
+
+        let (version, account, max_fee, signature, nonce): (u64, _, _, _, _) = match transaction {
+            Transaction::Invoke(InvokeTransaction::V0(tx)) => {
+                (0, tx.contract_address, tx.max_fee, tx.signature.0.clone(), StarkFelt::default())
+            }
+            Transaction::Invoke(InvokeTransaction::V1(tx)) => {
+                (1, tx.sender_address, tx.max_fee, tx.signature.0.clone(), tx.nonce.0)
+            }
+            Transaction::Declare(DeclareTransaction::V0(tx)) => {
+                (0, tx.sender_address, tx.max_fee, tx.signature.0.clone(), tx.nonce.0)
+            }
+            Transaction::Declare(DeclareTransaction::V1(tx)) => {
+                (1, tx.sender_address, tx.max_fee, tx.signature.0.clone(), tx.nonce.0)
+            }
+            Transaction::Declare(DeclareTransaction::V2(tx)) => {
+                (2, tx.sender_address, tx.max_fee, tx.signature.0.clone(), tx.nonce.0)
+            }
+            Transaction::DeployAccount(tx) => {
+                (1, tx.contract_address, tx.max_fee, tx.signature.0.clone(), tx.nonce.0)
+            }
+            Transaction::L1Handler(tx) => {
+                (0, tx.contract_address, Default::default(), Vec::new(), tx.nonce.0)
+            }
+            Transaction::Deploy(tx) => {
+                (0, tx.contract_address, Default::default(), Vec::new(), StarkFelt::default())
+            }
+        };
+
+        Self {
+            block_number: block_context.block_number.0,
+            block_timestamp: block_context.block_timestamp.0,
+            sequencer_address: *block_context.sequencer_address.0.key(),
+            version: StarkFelt::from(version),
+            account_contract_address: *account.0.key(),
+            max_fee: max_fee.0,
+            signature,
+            transaction_hash: transaction.transaction_hash().0,
+            chain_id,
+            nonce,
+        }
+    }
+}
+
+/// Runs the call of `call_info` to `contract_class` again with tracing enabled.
+pub fn trace_call(
+    call_info: &CallInfo,
+    contract_class: &ContractClassV1,
+    context: &ExecutionContext,
+) -> Result<CallTrace> {
+    let call = &call_info.call;
+    let entry_point = contract_class
+        .entry_points_by_type
+        .get(&call.entry_point_type)
+        .and_then(|entry_points| {
+            entry_points
+                .iter()
+                .find(|entry_point| entry_point.selector == call.entry_point_selector)
+        })
+        .ok_or_else(|| anyhow!("entry point {} not found", call.entry_point_selector.0))?;
+
+    let mut runner = CairoRunner::new(&contract_class.program, "all_cairo", false)?;
+    let mut vm = VirtualMachine::new(true);
+    runner.initialize_function_runner_cairo_1(&mut vm, &BUILTINS)?;
+
+    // The builtin costs read by the gas checks are pointed to right after the bytecode.
+    let bytecode_length = contract_class.bytecode_length();
+    let builtin_costs = write_segment(&mut vm, vec![MaybeRelocatable::from(0); 20])?.0;
+    let program_end = Relocatable::from((0, bytecode_length));
+    vm.insert_value(program_end, Felt252::from(RET_INSTRUCTION))?;
+    vm.insert_value((program_end + 1)?, builtin_costs)?;
+
+    let mut args = Vec::new();
+    for builtin in &entry_point.builtins {
+        let initial_stack = vm
+            .get_builtin_runners()
+            .iter()
+            .find(|builtin_runner| builtin_runner.name() == builtin.as_str())
+            .map(|builtin_runner| builtin_runner.initial_stack());
+
+        match initial_stack {
+            Some(initial_stack) => args.extend(initial_stack.into_iter().map(CairoArg::Single)),
+            None if builtin == SEGMENT_ARENA_BUILTIN => {
+                let info = vm.add_memory_segment();
+                let data = vec![info.into(), Felt252::from(0).into(), Felt252::from(0).into()];
+                let (_, segment_arena) = write_segment(&mut vm, data)?;
+                args.push(CairoArg::Single(segment_arena.into()));
+            }
+            None => bail!("unsupported builtin {builtin}"),
+        }
+    }
+
+    args.push(CairoArg::Single(Felt252::from(call.initial_gas).into()));
+    args.push(CairoArg::Single(vm.add_memory_segment().into()));
+
+    let calldata = call.calldata.0.iter().map(|felt| stark_felt_to_felt(*felt).into()).collect();
+    let (calldata_start, calldata_end) = write_segment(&mut vm, calldata)?;
+    args.push(CairoArg::Single(calldata_start.into()));
+    args.push(CairoArg::Single(calldata_end.into()));
+
+    let mut hint_processor = ReplayHintProcessor::new(call_info, contract_class, context);
+    let args: Vec<&CairoArg> = args.iter().collect();
+    runner.run_from_entrypoint(
+        entry_point.offset.0,
+        &args,
+        false,
+        Some(bytecode_length + 2),
+        &mut vm,
+        &mut hint_processor,
+    )?;
+
+    if hint_processor.inner_calls.next().is_some() {
+        bail!("the call made fewer inner calls than recorded");
+    }
+
+    vm.segments.compute_effective_sizes();
+    let relocation_table = vm.segments.relocate_segments()?;
+    vm.relocate_trace(&relocation_table)?;
+    let relocate =
+        |address: Relocatable| relocation_table[address.segment_index as usize] + address.offset;
+
+    let steps: Vec<_> = vm
+        .get_relocated_trace()?
+        .iter()
+        .map(|entry| (entry.pc - relocation_table[0], entry.fp))
+        .collect();
+
+    // The inner calls are made by the syscall hints, which run before the step they are attached
+    // to.
+    let mut inner_call_steps = Vec::new();
+    let mut step = 0;
+    for (pc, fp) in hint_processor.inner_call_sites {
+        let site = (pc.offset, relocate(fp));
+        step += steps[step..]
+            .iter()
+            .position(|step| *step == site)
+            .ok_or_else(|| anyhow!("inner call not found in the trace"))?;
+        inner_call_steps.push(step);
+        step += 1;
+    }
+
+    Ok(CallTrace { steps, inner_call_steps })
+}
+
+/// Executes the hints of a call, answering its syscalls with the results it recorded.
+struct ReplayHintProcessor<'a> {
+    hints: &'a HashMap<String, Hint>,
+    call_info: &'a CallInfo,
+    context: &'a ExecutionContext,
+    storage_read_values: slice::Iter<'a, StarkFelt>,
+    inner_calls: slice::Iter<'a, CallInfo>,
+    /// The program counter and frame pointer of the syscalls making inner calls.
+    inner_call_sites: Vec<(Relocatable, Relocatable)>,
+}
+
+impl<'a> ReplayHintProcessor<'a> {
+    fn new(
+        call_info: &'a CallInfo,
+        contract_class: &'a ContractClassV1,
+        context: &'a ExecutionContext,
+    ) -> Self {
+        Self {
+            hints: &contract_class.hints,
+            call_info,
+            context,
+            storage_read_values: call_info.storage_read_values.iter(),
+            inner_calls: call_info.inner_calls.iter(),
+            inner_call_sites: Vec::new(),
+        }
+    }
+
+    /// Answers the syscall of `hint`. The calls of a transaction which succeeded didn't fail
+    /// any syscall, so the responses are all successful.
+    fn execute_syscall(&mut self, vm: &mut VirtualMachine, hint: &StarknetHint) -> Result<()> {
+        let StarknetHint::SystemCall { system } = hint else {
+            bail!("unsupported hint {hint:?}");
+        };
+
+        let syscall_ptr = syscall_ptr(vm, system)?;
+        let selector = FieldElement::from(felt_to_stark_felt(&vm.get_integer(syscall_ptr)?));
+        let selector = parse_cairo_short_string(&selector)?;
+        let gas = vm.get_integer((syscall_ptr + 1)?)?.into_owned();
+        let request = (syscall_ptr + 2)?;
+
+        let (request_length, response): (usize, Vec<MaybeRelocatable>) = match selector.as_str() {
+            "StorageRead" => {
+                let value = self
+                    .storage_read_values
+                    .next()
+                    .ok_or_else(|| anyhow!("the call read more storage values than recorded"))?;
+                (2, vec![stark_felt_to_felt(*value).into()])
+            }
+            "StorageWrite" => (3, Vec::new()),
+            "EmitEvent" => (4, Vec::new()),
+            "SendMessageToL1" => (3, Vec::new()),
+            "ReplaceClass" => (1, Vec::new()),
+            "CallContract" | "LibraryCall" => {
+                let inner_call = self.next_inner_call(vm)?;
+                let (start, end) = write_retdata(vm, inner_call)?;
+                (4, vec![start.into(), end.into()])
+            }
+            "Deploy" => {
+                let inner_call = self.next_inner_call(vm)?;
+                let address = stark_felt_to_felt(*inner_call.call.storage_address.0.key());
+                let (start, end) = write_retdata(vm, inner_call)?;
+                (5, vec![address.into(), start.into(), end.into()])
+            }
+            "GetExecutionInfo" => (0, vec![self.write_execution_info(vm)?.into()]),
+            "Keccak" => (2, keccak(vm, request)?),
+            _ => bail!("unsupported syscall {selector}"),
+        };
+
+        let response_ptr = (request + request_length)?;
+        let mut data = vec![gas.into(), Felt252::from(0).into()];
+        data.extend(response);
+        vm.load_data(response_ptr, &data)?;
+
+        Ok(())
+    }
+
+    fn next_inner_call(&mut self, vm: &VirtualMachine) -> Result<&'a CallInfo> {
+        let inner_call = self
+            .inner_calls
+            .next()
+            .ok_or_else(|| anyhow!("the call made more inner calls than recorded"))?;
+        self.inner_call_sites.push((vm.get_pc(), vm.get_fp()));
+        Ok(inner_call)
+    }
+
+    fn write_execution_info(&self, vm: &mut VirtualMachine) -> Result<Relocatable> {
+        let context = self.context;
+        let call = &self.call_info.call;
+
+        let block_info = vec![
+            Felt252::from(context.block_number).into(),
+            Felt252::from(context.block_timestamp).into(),
+            stark_felt_to_felt(context.sequencer_address).into(),
+        ];
+        let (block_info, _) = write_segment(vm, block_info)?;
+
+        let signature =
+            context.signature.iter().map(|felt| stark_felt_to_felt(*felt).into()).collect();
+        let (signature_start, signature_end) = write_segment(vm, signature)?;
+
+        let tx_info = vec![
+            stark_felt_to_felt(context.version).into(),
+            stark_felt_to_felt(context.account_contract_address).into(),
+            Felt252::from(context.max_fee).into(),
+            signature_start.into(),
+            signature_end.into(),
+            stark_felt_to_felt(context.transaction_hash).into(),
+            stark_felt_to_felt(context.chain_id).into(),
+            stark_felt_to_felt(context.nonce).into(),
+        ];
+        let (tx_info, _) = write_segment(vm, tx_info)?;
+
+        let execution_info = vec![
+            block_info.into(),
+            tx_info.into(),
+            stark_felt_to_felt(*call.caller_address.0.key()).into(),
+            stark_felt_to_felt(*call.storage_address.0.key()).into(),
+            stark_felt_to_felt(call.entry_point_selector.0).into(),
+        ];
+        Ok(write_segment(vm, execution_info)?.0)
+    }
+}
+
+impl HintProcessor for ReplayHintProcessor<'_> {
+    fn execute_hint(
+        &mut self,
+        vm: &mut VirtualMachine,
+        exec_scopes: &mut ExecutionScopes,
+        hint_data: &Box<dyn Any>,
+        _constants: &HashMap<String, Felt252>,
+    ) -> Result<(), HintError> {
+        let hint = hint_data.downcast_ref::<Hint>().ok_or(HintError::WrongHintData)?;
+        match hint {
+            Hint::Core(hint) => execute_core_hint_base(vm, exec_scopes, hint),
+            Hint::Starknet(hint) => {
+                self.execute_syscall(vm, hint).map_err(|err| HintError::CustomHint(err.to_string()))
+            }
+        }
+    }
+
+    fn compile_hint(
+        &self,
+        hint_code: &str,
+        _ap_tracking_data: &ApTracking,
+        _reference_ids: &HashMap<String, usize>,
+        _references: &HashMap<usize, HintReference>,
+    ) -> Result<Box<dyn Any>, VirtualMachineError> {
+        let hint = self
+            .hints
+            .get(hint_code)
+            .ok_or_else(|| VirtualMachineError::CompileHintFail(hint_code.to_string()))?;
+        Ok(Box::new(hint.clone()))
+    }
+}
+
+/// Returns the address of the syscall, which the hint operand points to.
+fn syscall_ptr(vm: &VirtualMachine, system: &ResOperand) -> Result<Relocatable> {
+    let (cell, offset) = match system {
+        ResOperand::Deref(cell) => (cell, 0),
+        ResOperand::BinOp(BinOpOperand {
+            op: Operation::Add,
+            a,
+            b: DerefOrImmediate::Immediate(b),
+        }) => (a, usize::try_from(&b.value)?),
+        _ => bail!("unsupported syscall operand {system:?}"),
+    };
+
+    let base = match cell.register {
+        Register::AP => vm.get_ap(),
+        Register::FP => vm.get_fp(),
+    };
+    let ptr = vm.get_relocatable((base + i32::from(cell.offset))?)?;
+    Ok((ptr + offset)?)
+}
+
+fn write_segment(
+    vm: &mut VirtualMachine,
+    data: Vec<MaybeRelocatable>,
+) -> Result<(Relocatable, Relocatable)> {
+    let start = vm.add_memory_segment();
+    let end = vm.load_data(start, &data)?;
+    Ok((start, end))
+}
+
+fn write_retdata(
+    vm: &mut VirtualMachine,
+    call_info: &CallInfo,
+) -> Result<(Relocatable, Relocatable)> {
+    let retdata =
+        call_info.execution.retdata.0.iter().map(|felt| stark_felt_to_felt(*felt).into()).collect();
+    write_segment(vm, retdata)
+}
+
+/// Applies the keccak permutation to the blocks of the request input, which the caller has
+/// already padded, and returns the first 256 bits of the state as a `u256`.
+fn keccak(vm: &VirtualMachine, request: Relocatable) -> Result<Vec<MaybeRelocatable>> {
+    let input_start = vm.get_relocatable(request)?;
+    let input_end = vm.get_relocatable((request + 1)?)?;
+    let input_length = (input_end - input_start)?;
+    if input_length % KECCAK_FULL_RATE_IN_WORDS != 0 {
+        bail!("invalid keccak input length {input_length}");
+    }
+
+    let mut state = [0u64; 25];
+    for block_start in (0..input_length).step_by(KECCAK_FULL_RATE_IN_WORDS) {
+        for (i, word) in state.iter_mut().take(KECCAK_FULL_RATE_IN_WORDS).enumerate() {
+            let value = felt_to_stark_felt(&vm.get_integer((input_start + (block_start + i))?)?);
+            *word ^= u64::from_be_bytes(value.bytes()[24..].try_into()?);
+        }
+        keccak::f1600(&mut state);
+    }
+
+    let low = (u128::from(state[1]) << 64) | u128::from(state[0]);
+    let high = (u128::from(state[3]) << 64) | u128::from(state[2]);
+    Ok(vec![Felt252::from(low).into(), Felt252::from(high).into()])
+}
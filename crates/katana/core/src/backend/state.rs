@@ -10,9 +10,9 @@ use starknet_api::hash::{StarkFelt, StarkHash};
 use starknet_api::patricia_key;
 use starknet_api::state::StorageKey;
 
+use crate::block_context::FeeTokenAddresses;
 use crate::constants::{
-    ERC20_CONTRACT, ERC20_CONTRACT_CLASS_HASH, FEE_TOKEN_ADDRESS, UDC_ADDRESS, UDC_CLASS_HASH,
-    UDC_CONTRACT,
+    ERC20_CONTRACT, ERC20_CONTRACT_CLASS_HASH, UDC_ADDRESS, UDC_CLASS_HASH, UDC_CONTRACT,
 };
 
 pub trait StateExt {
@@ -55,15 +55,23 @@ pub struct MemDb {
     pub storage: HashMap<ContractAddress, StorageRecord>,
 }
 
-impl Default for MemDb {
-    fn default() -> Self {
+impl MemDb {
+    /// Creates a state with the universal deployer and the fee tokens deployed at
+    /// `fee_token_addresses`.
+    pub fn new(fee_token_addresses: &FeeTokenAddresses) -> Self {
         let mut state = MemDb { storage: HashMap::new(), classes: HashMap::new() };
-        deploy_fee_contract(&mut state);
+        deploy_fee_contracts(&mut state, fee_token_addresses);
         deploy_universal_deployer_contract(&mut state);
         state
     }
 }
 
+impl Default for MemDb {
+    fn default() -> Self {
+        Self::new(&FeeTokenAddresses::default())
+    }
+}
+
 impl StateExt for MemDb {
     fn get_sierra_class(&mut self, class_hash: &ClassHash) -> StateResult<FlattenedSierraClass> {
         if let ContractClass::V0(_) = self.get_compiled_contract_class(class_hash)? {
@@ -240,8 +248,7 @@ impl StateReader for MemDb {
     }
 }
 
-fn deploy_fee_contract(state: &mut MemDb) {
-    let address = ContractAddress(patricia_key!(*FEE_TOKEN_ADDRESS));
+fn deploy_fee_contracts(state: &mut MemDb, fee_token_addresses: &FeeTokenAddresses) {
     let hash = ClassHash(*ERC20_CONTRACT_CLASS_HASH);
     let compiled_hash = CompiledClassHash(*ERC20_CONTRACT_CLASS_HASH);
 
@@ -250,10 +257,16 @@ fn deploy_fee_contract(state: &mut MemDb) {
         ClassRecord { sierra_class: None, class: (*ERC20_CONTRACT).clone(), compiled_hash },
     );
 
-    state.storage.insert(
-        address,
-        StorageRecord { class_hash: hash, nonce: Nonce(1_u128.into()), storage: HashMap::new() },
-    );
+    for address in fee_token_addresses.all() {
+        state.storage.insert(
+            address,
+            StorageRecord {
+                class_hash: hash,
+                nonce: Nonce(1_u128.into()),
+                storage: HashMap::new(),
+            },
+        );
+    }
 }
 
 fn deploy_universal_deployer_contract(state: &mut MemDb) {
@@ -1,7 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::vec;
 
+use blockifier::abi::constants::N_STEPS_RESOURCE;
+use blockifier::block_context::BlockContext;
 use blockifier::execution::entry_point::CallInfo;
+use blockifier::fee::fee_utils::{calculate_l1_gas_by_vm_usage, extract_l1_gas_and_vm_usage};
 use blockifier::transaction::errors::TransactionExecutionError;
 use blockifier::transaction::objects::TransactionExecutionInfo;
 use starknet::core::types::TransactionStatus;
@@ -21,6 +24,35 @@ pub struct ExternalFunctionCall {
     pub entry_point_selector: EntryPointSelector,
 }
 
+/// The Cairo VM and L1 resources consumed by the execution of a transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionResources {
+    /// The number of Cairo steps, including memory holes.
+    pub steps: usize,
+    /// The number of instances used per builtin.
+    pub builtin_instance_counter: BTreeMap<String, usize>,
+    /// The L1 gas consumed by the state diff and the L2 to L1 messages.
+    pub l1_gas_usage: usize,
+}
+
+/// The split of a transaction fee between its L1 gas usage and its Cairo VM usage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeeBreakdown {
+    pub gas_price: u128,
+    /// The fee paid for [ExecutionResources::l1_gas_usage].
+    pub l1_gas_fee: u128,
+    /// The fee paid for the Cairo steps and builtins.
+    pub computation_fee: u128,
+    /// The fee that was actually charged to the sender.
+    pub actual_fee: u128,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionResources {
+    pub execution_resources: ExecutionResources,
+    pub fee: FeeBreakdown,
+}
+
 #[derive(Debug)]
 pub struct StarknetTransaction {
     pub inner: Transaction,
@@ -29,6 +61,8 @@ pub struct StarknetTransaction {
     pub block_number: Option<BlockNumber>,
     pub execution_info: Option<TransactionExecutionInfo>,
     pub execution_error: Option<TransactionExecutionError>,
+    /// The block context the transaction was executed with, `None` for the genesis transactions.
+    pub block_context: Option<BlockContext>,
 }
 
 impl StarknetTransaction {
@@ -37,6 +71,7 @@ impl StarknetTransaction {
         status: TransactionStatus,
         execution_info: Option<TransactionExecutionInfo>,
         execution_error: Option<TransactionExecutionError>,
+        block_context: BlockContext,
     ) -> Self {
         // TODO: uncomment this once `Reverted` transaction error type is
         // `TransactionExecutionError`.
@@ -50,15 +85,56 @@ impl StarknetTransaction {
             status,
             execution_info,
             execution_error,
+            block_context: Some(block_context),
             block_hash: None,
             block_number: None,
         }
     }
 
+    /// Returns whether the transaction is queued for parallel execution and was not executed yet.
+    pub fn is_queued(&self) -> bool {
+        self.execution_info.is_none()
+            && self.execution_error.is_none()
+            && self.status == TransactionStatus::Pending
+    }
+
     pub fn actual_fee(&self) -> Fee {
         self.execution_info.as_ref().map_or(Fee(0), |info| info.actual_fee)
     }
 
+    /// Returns the resources consumed by the transaction, priced with the block context it was
+    /// executed with, or `None` if it failed validation.
+    pub fn resources(&self) -> Result<Option<TransactionResources>, TransactionExecutionError> {
+        let (Some(execution_info), Some(block_context)) =
+            (self.execution_info.as_ref(), self.block_context.as_ref())
+        else {
+            return Ok(None);
+        };
+        let resources = &execution_info.actual_resources;
+
+        let (l1_gas_usage, vm_resources) = extract_l1_gas_and_vm_usage(resources);
+        let l1_gas_by_vm_usage = calculate_l1_gas_by_vm_usage(block_context, &vm_resources)?;
+        let total_l1_gas_usage = (l1_gas_usage as f64 + l1_gas_by_vm_usage).ceil() as u128;
+
+        let builtin_instance_counter =
+            vm_resources.0.into_iter().filter(|(name, _)| name != N_STEPS_RESOURCE).collect();
+
+        Ok(Some(TransactionResources {
+            execution_resources: ExecutionResources {
+                steps: resources.0.get(N_STEPS_RESOURCE).copied().unwrap_or_default(),
+                builtin_instance_counter,
+                l1_gas_usage,
+            },
+            fee: FeeBreakdown {
+                gas_price: block_context.gas_price,
+                l1_gas_fee: l1_gas_usage as u128 * block_context.gas_price,
+                computation_fee: total_l1_gas_usage.saturating_sub(l1_gas_usage as u128)
+                    * block_context.gas_price,
+                actual_fee: execution_info.actual_fee.0,
+            },
+        }))
+    }
+
     pub fn receipt(&self) -> TransactionReceipt {
         TransactionReceipt {
             output: self.output(),
@@ -69,40 +145,7 @@ impl StarknetTransaction {
     }
 
     pub fn emitted_events(&self) -> Vec<Event> {
-        let mut events: Vec<Event> = vec![];
-
-        fn get_events_recursively(call_info: &CallInfo) -> Vec<Event> {
-            let mut events: Vec<Event> = vec![];
-
-            events.extend(call_info.execution.events.iter().map(|e| Event {
-                content: e.event.clone(),
-                from_address: call_info.call.storage_address,
-            }));
-
-            call_info.inner_calls.iter().for_each(|call| {
-                events.extend(get_events_recursively(call));
-            });
-
-            events
-        }
-
-        let Some(ref execution_info) = self.execution_info else {
-            return events;
-        };
-
-        if let Some(ref call) = execution_info.validate_call_info {
-            events.extend(get_events_recursively(call));
-        }
-
-        if let Some(ref call) = execution_info.execute_call_info {
-            events.extend(get_events_recursively(call));
-        }
-
-        if let Some(ref call) = execution_info.fee_transfer_call_info {
-            events.extend(get_events_recursively(call));
-        }
-
-        events
+        self.execution_info.as_ref().map(execution_events).unwrap_or_default()
     }
 
     pub fn l2_to_l1_messages(&self) -> Vec<MessageToL1> {
@@ -194,3 +237,38 @@ impl StarknetTransactions {
         self.transactions.len()
     }
 }
+
+/// Returns the events emitted by the validation, the execution and the fee transfer of a
+/// transaction, in this order.
+pub fn execution_events(execution_info: &TransactionExecutionInfo) -> Vec<Event> {
+    let mut events: Vec<Event> = vec![];
+
+    fn get_events_recursively(call_info: &CallInfo) -> Vec<Event> {
+        let mut events: Vec<Event> = vec![];
+
+        events.extend(call_info.execution.events.iter().map(|e| Event {
+            content: e.event.clone(),
+            from_address: call_info.call.storage_address,
+        }));
+
+        call_info.inner_calls.iter().for_each(|call| {
+            events.extend(get_events_recursively(call));
+        });
+
+        events
+    }
+
+    if let Some(ref call) = execution_info.validate_call_info {
+        events.extend(get_events_recursively(call));
+    }
+
+    if let Some(ref call) = execution_info.execute_call_info {
+        events.extend(get_events_recursively(call));
+    }
+
+    if let Some(ref call) = execution_info.fee_transfer_call_info {
+        events.extend(get_events_recursively(call));
+    }
+
+    events
+}
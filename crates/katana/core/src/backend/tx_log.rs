@@ -0,0 +1,136 @@
+//! A log of the executed transactions, written as one JSON record per line so that it can be
+//! ingested by analytics tools.
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use starknet::core::types::FieldElement;
+use starknet_api::transaction::{InvokeTransaction, Transaction};
+
+use crate::backend::transaction::StarknetTransaction;
+
+/// The outcome of the execution of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExecutionStatus {
+    Succeeded,
+    /// The transaction was included, but its execution failed and its changes were reverted.
+    Reverted,
+    /// The transaction failed validation and was not included.
+    Rejected,
+    /// The block of the transaction was dropped by a reorg. The transaction is logged again if
+    /// it is mined again.
+    Dropped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionLogEvent {
+    pub from_address: FieldElement,
+    pub keys: Vec<FieldElement>,
+    pub data: Vec<FieldElement>,
+}
+
+/// A line of the transaction log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionLogRecord {
+    /// The number of the block the transaction was executed in.
+    pub block_number: u64,
+    /// The timestamp of the block the transaction was executed in.
+    pub timestamp: u64,
+    pub transaction_hash: FieldElement,
+    #[serde(rename = "type")]
+    pub transaction_type: String,
+    /// The account which sent the transaction, or the deployed account for deploy account
+    /// transactions. Not set for L1 handler transactions.
+    pub sender_address: Option<FieldElement>,
+    /// The calldata of the transaction, or the constructor calldata for deployments.
+    pub calldata: Vec<FieldElement>,
+    pub status: ExecutionStatus,
+    pub revert_reason: Option<String>,
+    pub events: Vec<TransactionLogEvent>,
+    pub actual_fee: u128,
+    /// The Cairo steps, builtins and L1 gas consumed by the transaction.
+    pub resources: BTreeMap<String, usize>,
+}
+
+impl TransactionLogRecord {
+    pub fn new(transaction: &StarknetTransaction, block_number: u64, timestamp: u64) -> Self {
+        let (transaction_type, sender_address, calldata) = match &transaction.inner {
+            Transaction::Invoke(InvokeTransaction::V0(tx)) => {
+                ("INVOKE", Some(*tx.contract_address.0.key()), Some(&tx.calldata))
+            }
+            Transaction::Invoke(InvokeTransaction::V1(tx)) => {
+                ("INVOKE", Some(*tx.sender_address.0.key()), Some(&tx.calldata))
+            }
+            Transaction::Declare(tx) => ("DECLARE", Some(*tx.sender_address().0.key()), None),
+            Transaction::DeployAccount(tx) => (
+                "DEPLOY_ACCOUNT",
+                Some(*tx.contract_address.0.key()),
+                Some(&tx.constructor_calldata),
+            ),
+            Transaction::Deploy(tx) => {
+                ("DEPLOY", Some(*tx.contract_address.0.key()), Some(&tx.constructor_calldata))
+            }
+            Transaction::L1Handler(tx) => ("L1_HANDLER", None, Some(&tx.calldata)),
+        };
+
+        let execution_info = transaction.execution_info.as_ref();
+        let (status, revert_reason) = match (execution_info, &transaction.execution_error) {
+            (Some(info), _) => match &info.revert_error {
+                Some(error) => (ExecutionStatus::Reverted, Some(error.to_string())),
+                None => (ExecutionStatus::Succeeded, None),
+            },
+            (None, error) => (ExecutionStatus::Rejected, error.as_ref().map(|e| e.to_string())),
+        };
+
+        let events = transaction
+            .emitted_events()
+            .into_iter()
+            .map(|event| TransactionLogEvent {
+                from_address: (*event.from_address.0.key()).into(),
+                keys: event.content.keys.into_iter().map(|key| key.0.into()).collect(),
+                data: event.content.data.0.iter().map(|data| (*data).into()).collect(),
+            })
+            .collect();
+
+        Self {
+            block_number,
+            timestamp,
+            transaction_hash: transaction.inner.transaction_hash().0.into(),
+            transaction_type: transaction_type.to_string(),
+            sender_address: sender_address.map(Into::into),
+            calldata: calldata
+                .map(|calldata| calldata.0.iter().map(|felt| (*felt).into()).collect())
+                .unwrap_or_default(),
+            status,
+            revert_reason,
+            events,
+            actual_fee: transaction.actual_fee().0,
+            resources: execution_info
+                .map(|info| info.actual_resources.0.clone().into_iter().collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Appends [TransactionLogRecord]s to a file, one per line.
+#[derive(Debug)]
+pub struct TransactionLog {
+    writer: LineWriter<File>,
+}
+
+impl TransactionLog {
+    /// Opens the log at `path`, appending to it if it already exists.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { writer: LineWriter::new(file) })
+    }
+
+    pub fn write(&mut self, record: &TransactionLogRecord) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")
+    }
+}
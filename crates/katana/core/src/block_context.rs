@@ -11,7 +11,9 @@ use starknet_api::core::{ChainId, ContractAddress, PatriciaKey};
 use starknet_api::hash::StarkHash;
 use starknet_api::patricia_key;
 
-use crate::constants::{DEFAULT_GAS_PRICE, FEE_TOKEN_ADDRESS, SEQUENCER_ADDRESS};
+use crate::constants::{
+    DEFAULT_GAS_PRICE, FEE_TOKEN_ADDRESS, SEQUENCER_ADDRESS, STRK_FEE_TOKEN_ADDRESS,
+};
 
 pub trait Base {
     fn base() -> Self;
@@ -53,3 +55,44 @@ pub struct BlockContextGenerator {
     pub block_timestamp_offset: i64,
     pub next_block_start_time: u64,
 }
+
+/// A predeployed token in which transaction fees can be charged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeToken {
+    Eth,
+    Strk,
+}
+
+/// The addresses of the predeployed fee tokens, set by
+/// [Environment::fee_token_addresses](crate::backend::config::Environment::fee_token_addresses).
+///
+/// Blockifier's [BlockContext] only holds the address of the token fees are charged in, so the
+/// address of the selected token is swapped into it before a transaction is executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeTokenAddresses {
+    pub eth: ContractAddress,
+    pub strk: ContractAddress,
+}
+
+impl FeeTokenAddresses {
+    pub fn get(&self, token: FeeToken) -> ContractAddress {
+        match token {
+            FeeToken::Eth => self.eth,
+            FeeToken::Strk => self.strk,
+        }
+    }
+
+    /// Returns the addresses of all the fee tokens.
+    pub fn all(&self) -> [ContractAddress; 2] {
+        [self.eth, self.strk]
+    }
+}
+
+impl Default for FeeTokenAddresses {
+    fn default() -> Self {
+        Self {
+            eth: ContractAddress(patricia_key!(*FEE_TOKEN_ADDRESS)),
+            strk: ContractAddress(patricia_key!(*STRK_FEE_TOKEN_ADDRESS)),
+        }
+    }
+}
@@ -8,6 +8,8 @@ pub const DEFAULT_GAS_PRICE: u128 = 100 * u128::pow(10, 9); // Given in units of
 pub const DEFAULT_INVOKE_MAX_STEPS: u32 = 1_000_000;
 pub const DEFAULT_VALIDATE_MAX_STEPS: u32 = 1_000_000;
 
+pub const DEFAULT_PARALLEL_BATCH_SIZE: usize = 64;
+
 lazy_static! {
 
     // Predefined contract addresses
@@ -15,6 +17,7 @@ lazy_static! {
     pub static ref SEQUENCER_ADDRESS: StarkFelt = stark_felt!("0x69420");
     pub static ref UDC_ADDRESS: StarkFelt = stark_felt!("0x041a78e741e5af2fec34b695679bc6891742439f7afb8484ecd7766661ad02bf");
     pub static ref FEE_TOKEN_ADDRESS: StarkFelt = stark_felt!("0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7");
+    pub static ref STRK_FEE_TOKEN_ADDRESS: StarkFelt = stark_felt!("0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d");
 
     // Predefined class hashes
 
@@ -1,40 +1,43 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
 use async_trait::async_trait;
 use auto_impl::auto_impl;
-use blockifier::abi::abi_utils::get_storage_var_address;
 use blockifier::execution::contract_class::ContractClass;
-use blockifier::state::state_api::{State, StateReader};
+use blockifier::state::state_api::StateReader;
 use blockifier::transaction::account_transaction::AccountTransaction;
 use blockifier::transaction::transaction_execution::Transaction;
-use blockifier::transaction::transactions::DeclareTransaction;
+use blockifier::transaction::transactions::{DeclareTransaction, L1HandlerTransaction};
 use starknet::core::types::{
-    BlockId, BlockTag, FeeEstimate, FlattenedSierraClass, StateUpdate, TransactionStatus,
+    BlockId, BlockTag, FeeEstimate, FieldElement, StateUpdate, TransactionStatus,
 };
 use starknet_api::block::{BlockHash, BlockNumber};
 use starknet_api::core::{ChainId, ClassHash, ContractAddress, Nonce};
 use starknet_api::hash::StarkFelt;
-use starknet_api::stark_felt;
 use starknet_api::state::StorageKey;
 use starknet_api::transaction::{
     DeployAccountTransaction, InvokeTransaction, Transaction as StarknetApiTransaction,
     TransactionHash,
 };
-use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::{broadcast, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::task::JoinHandle;
 use tokio::time;
 
 use crate::backend::block::StarknetBlock;
 use crate::backend::config::StarknetConfig;
-use crate::backend::contract::StarknetContract;
+use crate::backend::contract::{DeclaredClass, StarknetContract};
 use crate::backend::event::EmittedEvent;
+use crate::backend::profile::TransactionProfile;
 use crate::backend::state::{MemDb, StateExt};
-use crate::backend::transaction::ExternalFunctionCall;
-use crate::backend::StarknetWrapper;
+use crate::backend::transaction::{ExternalFunctionCall, TransactionResources};
+use crate::backend::{ReorgTransaction, StarknetWrapper};
 use crate::sequencer_error::SequencerError;
 
 type SequencerResult<T> = Result<T, SequencerError>;
 
+/// The interval at which queued transactions are executed, when they don't fill a batch.
+const QUEUE_EXECUTION_INTERVAL: time::Duration = time::Duration::from_millis(500);
+
 #[derive(Debug, Default)]
 pub struct SequencerConfig {
     pub block_time: Option<u64>,
@@ -43,49 +46,64 @@ pub struct SequencerConfig {
 pub struct KatanaSequencer {
     pub config: SequencerConfig,
     pub starknet: Arc<RwLock<StarknetWrapper>>,
+    /// The block production and queue execution loops spawned by `start`.
+    tasks: Mutex<Vec<JoinHandle<()>>>,
 }
 
 impl KatanaSequencer {
-    pub fn new(config: SequencerConfig, starknet_config: StarknetConfig) -> Self {
-        Self { config, starknet: Arc::new(RwLock::new(StarknetWrapper::new(starknet_config))) }
+    pub fn new(config: SequencerConfig, starknet_config: StarknetConfig) -> Result<Self> {
+        let starknet = StarknetWrapper::new(starknet_config)?;
+        Ok(Self { config, starknet: Arc::new(RwLock::new(starknet)), tasks: Mutex::default() })
     }
 
+    /// Starts producing blocks. The genesis block is generated unless the chain already has
+    /// blocks, e.g. imported ones.
     pub async fn start(&self) {
-        self.starknet.write().await.generate_genesis_block();
+        {
+            let mut starknet = self.starknet.write().await;
+            if starknet.blocks.total_blocks() == 0 {
+                starknet.generate_genesis_block();
+            }
+        }
+
+        let mut tasks = vec![];
 
         if let Some(block_time) = self.config.block_time {
             let starknet = self.starknet.clone();
-            tokio::spawn(async move {
+            tasks.push(tokio::spawn(async move {
                 loop {
                     starknet.write().await.generate_pending_block();
                     time::sleep(time::Duration::from_secs(block_time)).await;
                     starknet.write().await.generate_latest_block();
                 }
-            });
+            }));
         } else {
             self.starknet.write().await.generate_pending_block();
         }
-    }
 
-    pub async fn drip_and_deploy_account(
-        &self,
-        transaction: DeployAccountTransaction,
-        balance: u64,
-    ) -> SequencerResult<(TransactionHash, ContractAddress)> {
-        let (transaction_hash, contract_address) =
-            self.add_deploy_account_transaction(transaction).await;
+        if self.starknet.read().await.executes_in_parallel() {
+            let starknet = self.starknet.clone();
+            tasks.push(tokio::spawn(async move {
+                loop {
+                    time::sleep(QUEUE_EXECUTION_INTERVAL).await;
+                    starknet.write().await.execute_queued_transactions();
+                }
+            }));
+        }
 
-        let deployed_account_balance_key =
-            get_storage_var_address("ERC20_balances", &[*contract_address.0.key()])
-                .map_err(SequencerError::StarknetApi)?;
+        self.tasks.lock().expect("poisoned lock").extend(tasks);
+    }
 
-        self.starknet.write().await.pending_cached_state.set_storage_at(
-            self.starknet.read().await.block_context.fee_token_address,
-            deployed_account_balance_key,
-            stark_felt!(balance),
-        );
+    /// Stops producing blocks and executing queued transactions. The loops are only aborted
+    /// while waiting, never in the middle of a block.
+    pub fn stop(&self) {
+        for task in self.tasks.lock().expect("poisoned lock").drain(..) {
+            task.abort();
+        }
+    }
 
-        Ok((transaction_hash, contract_address))
+    pub async fn subscribe_blocks(&self) -> broadcast::Receiver<StarknetBlock> {
+        self.starknet.read().await.subscribe_blocks()
     }
 
     pub async fn block_number_from_block_id(&self, block_id: &BlockId) -> Option<BlockNumber> {
@@ -108,7 +126,7 @@ impl KatanaSequencer {
         }
     }
 
-    pub(self) async fn verify_contract_exists(&self, contract_address: &ContractAddress) -> bool {
+    async fn verify_contract_exists(&self, contract_address: &ContractAddress) -> bool {
         self.starknet
             .write()
             .await
@@ -160,26 +178,47 @@ impl Sequencer for KatanaSequencer {
         (transaction_hash, contract_address)
     }
 
+    async fn drip_and_deploy_account(
+        &self,
+        transaction: DeployAccountTransaction,
+        balance: u128,
+    ) -> SequencerResult<(TransactionHash, ContractAddress)> {
+        let transaction_hash = transaction.transaction_hash;
+        let contract_address = transaction.contract_address;
+
+        self.starknet.write().await.drip_and_deploy_account(transaction, balance)?;
+
+        Ok((transaction_hash, contract_address))
+    }
+
     async fn add_declare_transaction(
         &self,
         transaction: DeclareTransaction,
-        sierra_class: Option<FlattenedSierraClass>,
-    ) {
+        class: DeclaredClass,
+    ) -> SequencerResult<()> {
         let class_hash = transaction.tx().class_hash();
+        let sierra_class = class.verify(class_hash)?;
 
-        self.starknet.write().await.handle_transaction(Transaction::AccountTransaction(
-            AccountTransaction::Declare(transaction),
-        ));
+        // The class is checked and declared under the same lock, so that only one of concurrent
+        // declarations of a class is accepted.
+        let mut starknet = self.starknet.write().await;
+        if starknet.is_class_declared(&class_hash) {
+            return Err(SequencerError::ClassAlreadyDeclared(class_hash));
+        }
+
+        starknet.handle_transaction(Transaction::AccountTransaction(AccountTransaction::Declare(
+            transaction,
+        )));
 
         if let Some(sierra_class) = sierra_class {
-            self.starknet
-                .write()
-                .await
+            starknet
                 .state
                 .classes
                 .entry(class_hash)
                 .and_modify(|r| r.sierra_class = Some(sierra_class));
         }
+
+        Ok(())
     }
 
     async fn add_invoke_transaction(&self, transaction: InvokeTransaction) {
@@ -190,25 +229,37 @@ impl Sequencer for KatanaSequencer {
 
     async fn estimate_fee(
         &self,
-        account_transaction: AccountTransaction,
+        transactions: Vec<AccountTransaction>,
+        block_id: BlockId,
+    ) -> SequencerResult<Vec<FeeEstimate>> {
+        if self.block(block_id).await.is_none() {
+            return Err(SequencerError::BlockNotFound(block_id));
+        }
+
+        let state = self.state(&block_id).await?;
+
+        self.starknet.write().await.estimate_fee(transactions, Some(state))
+    }
+
+    async fn estimate_message_fee(
+        &self,
+        transaction: L1HandlerTransaction,
         block_id: BlockId,
     ) -> SequencerResult<FeeEstimate> {
         if self.block(block_id).await.is_none() {
             return Err(SequencerError::BlockNotFound(block_id));
         }
 
-        match &account_transaction {
-            AccountTransaction::Invoke(tx) => tx.sender_address(),
-            AccountTransaction::Declare(tx) => tx.tx().sender_address(),
-            AccountTransaction::DeployAccount(tx) => tx.contract_address,
-        };
+        if !self.verify_contract_exists(&transaction.tx.contract_address).await {
+            return Err(SequencerError::ContractNotFound(transaction.tx.contract_address));
+        }
 
         let state = self.state(&block_id).await?;
 
         self.starknet
             .write()
             .await
-            .estimate_fee(account_transaction, Some(state))
+            .estimate_message_fee(transaction, Some(state))
             .map_err(SequencerError::TransactionExecution)
     }
 
@@ -309,7 +360,17 @@ impl Sequencer for KatanaSequencer {
         }
 
         let mut state = self.state(&block_id).await?;
-        state.get_nonce_at(contract_address).map_err(SequencerError::State)
+        let nonce = state.get_nonce_at(contract_address).map_err(SequencerError::State)?;
+
+        // The queued transactions of the account are not executed yet, but will increment its
+        // nonce before the next transaction it sends.
+        if let BlockId::Tag(BlockTag::Pending) = block_id {
+            let queued = self.starknet.read().await.queued_transactions_from(contract_address);
+            let nonce = FieldElement::from(nonce.0) + FieldElement::from(queued as u64);
+            return Ok(Nonce(nonce.into()));
+        }
+
+        Ok(nonce)
     }
 
     async fn call(
@@ -343,7 +404,29 @@ impl Sequencer for KatanaSequencer {
         &self,
         hash: &TransactionHash,
     ) -> Option<starknet_api::transaction::TransactionReceipt> {
-        self.starknet.read().await.transactions.by_hash(hash).map(|tx| tx.receipt())
+        // Queued transactions have no receipt until they are executed.
+        self.starknet
+            .read()
+            .await
+            .transactions
+            .by_hash(hash)
+            .filter(|tx| !tx.is_queued())
+            .map(|tx| tx.receipt())
+    }
+
+    async fn transaction_resources(
+        &self,
+        hash: &TransactionHash,
+    ) -> SequencerResult<Option<TransactionResources>> {
+        let starknet = self.starknet.read().await;
+        match starknet.transactions.by_hash(hash) {
+            Some(tx) => Ok(tx.resources()?),
+            None => Err(SequencerError::TxnNotFound(*hash)),
+        }
+    }
+
+    async fn transaction_profile(&self, hash: &TransactionHash) -> Option<TransactionProfile> {
+        self.starknet.read().await.transaction_profile(hash)
     }
 
     async fn transaction(
@@ -458,6 +541,47 @@ impl Sequencer for KatanaSequencer {
             .get_state_update(block_number)
             .ok_or(SequencerError::StateUpdateNotFound(block_id))
     }
+
+    async fn reorg(
+        &self,
+        depth: u64,
+        remine_dropped: bool,
+        transactions: Vec<ReorgTransaction>,
+    ) -> SequencerResult<()> {
+        let mut starknet = self.starknet.write().await;
+
+        starknet.verify_reorg_declares(depth, remine_dropped, &transactions)?;
+        let dropped = starknet.reorg(depth)?;
+        let mut mined = if remine_dropped { dropped } else { Vec::new() };
+        mined.extend(transactions);
+
+        let mut sierra_classes = Vec::new();
+        let transactions = mined
+            .into_iter()
+            .map(|ReorgTransaction { transaction, sierra_class }| {
+                if let (
+                    Some(sierra_class),
+                    Transaction::AccountTransaction(AccountTransaction::Declare(tx)),
+                ) = (sierra_class, &transaction)
+                {
+                    sierra_classes.push((tx.tx().class_hash(), sierra_class));
+                }
+                transaction
+            })
+            .collect();
+
+        starknet.handle_transactions(transactions);
+
+        for (class_hash, sierra_class) in sierra_classes {
+            starknet
+                .state
+                .classes
+                .entry(class_hash)
+                .and_modify(|r| r.sierra_class = Some(sierra_class));
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -476,6 +600,13 @@ pub trait Sequencer {
         hash: &TransactionHash,
     ) -> Option<starknet_api::transaction::TransactionReceipt>;
 
+    async fn transaction_resources(
+        &self,
+        hash: &TransactionHash,
+    ) -> SequencerResult<Option<TransactionResources>>;
+
+    async fn transaction_profile(&self, hash: &TransactionHash) -> Option<TransactionProfile>;
+
     async fn transaction_status(&self, hash: &TransactionHash) -> Option<TransactionStatus>;
 
     async fn nonce_at(
@@ -525,17 +656,35 @@ pub trait Sequencer {
         transaction: DeployAccountTransaction,
     ) -> (TransactionHash, ContractAddress);
 
+    /// Funds the account to be deployed by `transaction` with `balance` before deploying it. Fails
+    /// without funding the account if the deployment would be rejected or reverted.
+    async fn drip_and_deploy_account(
+        &self,
+        transaction: DeployAccountTransaction,
+        balance: u128,
+    ) -> SequencerResult<(TransactionHash, ContractAddress)>;
+
+    /// Fails if the class is already declared, or if `class` doesn't hash to the declared class
+    /// hash.
     async fn add_declare_transaction(
         &self,
         transaction: DeclareTransaction,
-        sierra_class: Option<FlattenedSierraClass>,
-    );
+        class: DeclaredClass,
+    ) -> SequencerResult<()>;
 
     async fn add_invoke_transaction(&self, transaction: InvokeTransaction);
 
+    /// Estimates the fees of `transactions`, executed in order on top of the state of `block_id`.
     async fn estimate_fee(
         &self,
-        account_transaction: AccountTransaction,
+        transactions: Vec<AccountTransaction>,
+        block_id: BlockId,
+    ) -> SequencerResult<Vec<FeeEstimate>>;
+
+    /// Estimates the fee of the L1 handler transaction which consumes a message sent from L1.
+    async fn estimate_message_fee(
+        &self,
+        transaction: L1HandlerTransaction,
         block_id: BlockId,
     ) -> SequencerResult<FeeEstimate>;
 
@@ -550,4 +699,13 @@ pub trait Sequencer {
     ) -> SequencerResult<Vec<EmittedEvent>>;
 
     async fn state_update(&self, block_id: BlockId) -> SequencerResult<StateUpdate>;
+
+    /// Drops the latest `depth` blocks, then mines the transactions of the dropped blocks again
+    /// if `remine_dropped` is set, followed by `transactions`.
+    async fn reorg(
+        &self,
+        depth: u64,
+        remine_dropped: bool,
+        transactions: Vec<ReorgTransaction>,
+    ) -> SequencerResult<()>;
 }
@@ -2,7 +2,7 @@ use blockifier::execution::errors::EntryPointExecutionError;
 use blockifier::state::errors::StateError;
 use blockifier::transaction::errors::TransactionExecutionError;
 use starknet::core::types::BlockId;
-use starknet_api::core::ContractAddress;
+use starknet_api::core::{ClassHash, ContractAddress};
 use starknet_api::transaction::TransactionHash;
 use starknet_api::StarknetApiError;
 
@@ -22,12 +22,24 @@ pub enum SequencerError {
     State(#[from] StateError),
     #[error(transparent)]
     TransactionExecution(#[from] TransactionExecutionError),
+    #[error("Transaction reverted: {0}")]
+    TransactionReverted(String),
+    #[error("Transaction at index {index} failed: {error}")]
+    FeeEstimation { index: usize, error: TransactionExecutionError },
     #[error("Error converting {from} into {to}: {message}")]
     ConversionError { from: String, to: String, message: String },
     #[error(transparent)]
     StarknetApi(#[from] StarknetApiError),
     #[error(transparent)]
     EntryPointExecution(#[from] EntryPointExecutionError),
+    #[error("Class {0:?} is already declared.")]
+    ClassAlreadyDeclared(ClassHash),
+    #[error("Class hash {declared:?} does not match the hash {actual:?} of the class definition.")]
+    ClassHashMismatch { declared: ClassHash, actual: ClassHash },
+    #[error("Invalid contract class: {0}")]
+    InvalidContractClass(String),
+    #[error("Cannot drop {depth} blocks, at most {max} blocks can be dropped.")]
+    InvalidReorgDepth { depth: u64, max: u64 },
     #[error("Wait for pending transactions")]
     PendingTransactions,
 }
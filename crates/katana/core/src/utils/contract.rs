@@ -9,6 +9,7 @@ use cairo_lang_starknet::casm_contract_class::CasmContractClass;
 use cairo_vm::serde::deserialize_program::ProgramJson;
 use serde_json::json;
 use starknet::core::types::contract::legacy::{LegacyContractClass, LegacyProgram};
+use starknet::core::types::contract::CompiledClass;
 use starknet::core::types::{
     CompressedLegacyContractClass, ContractClass, FieldElement, FlattenedSierraClass,
     LegacyContractEntryPoint, LegacyEntryPointsByType,
@@ -38,9 +39,22 @@ pub fn rpc_to_inner_class(
     contract_class: &FlattenedSierraClass,
 ) -> Result<(FieldElement, InnerContractClass)> {
     let class_hash = contract_class.class_hash();
+    let casm_contract = compile_sierra_class(contract_class)?;
+    Ok((class_hash, InnerContractClass::V1(casm_contract.try_into()?)))
+}
+
+/// Compiles a Sierra class to CASM.
+pub fn compile_sierra_class(contract_class: &FlattenedSierraClass) -> Result<CasmContractClass> {
+    let contract_class = sierra_contract_class(contract_class)?;
+    Ok(CasmContractClass::from_contract_class(contract_class, true)?)
+}
 
+/// Converts a Sierra class to the class read by the Cairo compiler.
+pub fn sierra_contract_class(
+    contract_class: &FlattenedSierraClass,
+) -> Result<cairo_lang_starknet::contract_class::ContractClass> {
     let value = serde_json::to_value(contract_class)?;
-    let contract_class = cairo_lang_starknet::contract_class::ContractClass {
+    Ok(cairo_lang_starknet::contract_class::ContractClass {
         abi: serde_json::from_value(value["abi"].clone()).ok(),
         sierra_program: serde_json::from_value(value["sierra_program"].clone())?,
         entry_points_by_type: serde_json::from_value(value["entry_points_by_type"].clone())?,
@@ -49,23 +63,20 @@ pub fn rpc_to_inner_class(
             value["sierra_program_debug_info"].clone(),
         )
         .ok(),
-    };
+    })
+}
 
-    let casm_contract = CasmContractClass::from_contract_class(contract_class, true)?;
-    Ok((class_hash, InnerContractClass::V1(casm_contract.try_into()?)))
+/// Returns the hash of a compiled class, which is the `compiled_class_hash` of its declaration.
+pub fn compiled_class_hash(casm_contract: &CasmContractClass) -> Result<FieldElement> {
+    let compiled_class: CompiledClass =
+        serde_json::from_str(&serde_json::to_string(casm_contract)?)?;
+    Ok(compiled_class.class_hash()?)
 }
 
 pub fn legacy_rpc_to_inner_class(
     compressed_legacy_contract: &CompressedLegacyContractClass,
 ) -> Result<(FieldElement, InnerContractClass)> {
-    let legacy_program_json = decompress(&compressed_legacy_contract.program)?;
-    let legacy_program: LegacyProgram = serde_json::from_str(&legacy_program_json)?;
-
-    let flattened = json!({
-        "program": legacy_program,
-        "abi": compressed_legacy_contract.abi,
-        "entry_points_by_type": compressed_legacy_contract.entry_points_by_type,
-    });
+    let flattened = flatten_legacy_class(compressed_legacy_contract)?;
 
     let legacy_contract_class: LegacyContractClass = serde_json::from_value(flattened.clone())?;
     let class_hash = legacy_contract_class.class_hash()?;
@@ -74,6 +85,27 @@ pub fn legacy_rpc_to_inner_class(
     Ok((class_hash, InnerContractClass::V0(contract_class)))
 }
 
+/// Returns the definition of a legacy class, from which its class hash is computed.
+pub fn legacy_rpc_to_legacy_class(
+    compressed_legacy_contract: &CompressedLegacyContractClass,
+) -> Result<LegacyContractClass> {
+    Ok(serde_json::from_value(flatten_legacy_class(compressed_legacy_contract)?)?)
+}
+
+/// Returns the JSON definition of a legacy class, with its program decompressed.
+fn flatten_legacy_class(
+    compressed_legacy_contract: &CompressedLegacyContractClass,
+) -> Result<serde_json::Value> {
+    let legacy_program_json = decompress(&compressed_legacy_contract.program)?;
+    let legacy_program: LegacyProgram = serde_json::from_str(&legacy_program_json)?;
+
+    Ok(json!({
+        "program": legacy_program,
+        "abi": compressed_legacy_contract.abi,
+        "entry_points_by_type": compressed_legacy_contract.entry_points_by_type,
+    }))
+}
+
 /// Returns a [LegacyEntryPointsByType] (RPC type) from a [EntryPointType] (blockifier type)
 fn to_rpc_legacy_entry_points_by_type(
     entries: &HashMap<EntryPointType, Vec<EntryPoint>>,
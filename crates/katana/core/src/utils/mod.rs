@@ -17,6 +17,9 @@ use starknet_api::transaction::{
 };
 use starknet_api::StarknetApiError;
 
+pub mod contract;
+pub mod transaction;
+
 pub fn get_current_timestamp() -> Duration {
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
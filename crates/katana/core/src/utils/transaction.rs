@@ -0,0 +1,529 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Ok, Result};
+use blockifier::transaction::account_transaction::AccountTransaction;
+use blockifier::transaction::transaction_execution::Transaction as BlockifierTransaction;
+use blockifier::transaction::transactions::{
+    DeclareTransaction as BlockifierDeclareTransaction,
+    L1HandlerTransaction as BlockifierL1HandlerTransaction,
+};
+use starknet::core::crypto::compute_hash_on_elements;
+use starknet::core::types::{
+    BroadcastedDeployAccountTransaction, BroadcastedInvokeTransactionV1, ContractClass,
+    DeclareTransaction, DeclareTransactionV1, DeclareTransactionV2, DeployAccountTransaction,
+    DeployTransaction, FieldElement, InvokeTransaction, InvokeTransactionV1, L1HandlerTransaction,
+    Transaction,
+};
+use starknet::core::utils::get_contract_address;
+use starknet_api::core::{
+    ClassHash, CompiledClassHash, ContractAddress, EntryPointSelector, Nonce, PatriciaKey,
+};
+use starknet_api::hash::{StarkFelt, StarkHash};
+use starknet_api::transaction::{
+    Calldata, ContractAddressSalt, DeclareTransaction as InnerDeclareTransaction,
+    DeclareTransactionV0V1 as InnerDeclareTransactionV0V1,
+    DeclareTransactionV2 as InnerDeclareTransactionV2,
+    DeployAccountTransaction as InnerDeployAccountTransaction,
+    DeployTransaction as InnerDeployTransaction, Fee, InvokeTransaction as InnerInvokeTransaction,
+    InvokeTransactionV1 as InnerInvokeTransactionV1,
+    L1HandlerTransaction as InnerL1HandlerTransaction, Transaction as InnerTransaction,
+    TransactionHash, TransactionSignature, TransactionVersion,
+};
+use starknet_api::{patricia_key, stark_felt};
+
+use crate::utils::contract::{legacy_rpc_to_inner_class, rpc_to_inner_class};
+use crate::utils::starkfelt_to_u128;
+
+const PREFIX_INVOKE: FieldElement = FieldElement::from_mont([
+    18443034532770911073,
+    18446744073709551615,
+    18446744073709551615,
+    513398556346534256,
+]);
+
+/// Cairo string for "declare"
+const PREFIX_DECLARE: FieldElement = FieldElement::from_mont([
+    17542456862011667323,
+    18446744073709551615,
+    18446744073709551615,
+    191557713328401194,
+]);
+
+/// Cairo string for "deploy_account"
+const PREFIX_DEPLOY_ACCOUNT: FieldElement = FieldElement::from_mont([
+    3350261884043292318,
+    18443211694809419988,
+    18446744073709551615,
+    461298303000467581,
+]);
+
+pub fn compute_deploy_account_v1_transaction_hash(
+    contract_address: FieldElement,
+    constructor_calldata: &[FieldElement],
+    class_hash: FieldElement,
+    salt: FieldElement,
+    max_fee: FieldElement,
+    chain_id: FieldElement,
+    nonce: FieldElement,
+) -> FieldElement {
+    let calldata_to_hash = [&[class_hash, salt], constructor_calldata].concat();
+
+    compute_hash_on_elements(&[
+        PREFIX_DEPLOY_ACCOUNT,
+        FieldElement::ONE, // version
+        contract_address,
+        FieldElement::ZERO, // entry_point_selector
+        compute_hash_on_elements(&calldata_to_hash),
+        max_fee,
+        chain_id,
+        nonce,
+    ])
+}
+
+/// Converts a broadcasted v1 deploy account transaction into the transaction executed by the
+/// sequencer.
+pub fn broadcasted_deploy_account_rpc_to_inner(
+    transaction: BroadcastedDeployAccountTransaction,
+    chain_id: FieldElement,
+) -> Result<InnerDeployAccountTransaction> {
+    let contract_address = get_contract_address(
+        transaction.contract_address_salt,
+        transaction.class_hash,
+        &transaction.constructor_calldata,
+        FieldElement::ZERO,
+    );
+
+    let transaction_hash = compute_deploy_account_v1_transaction_hash(
+        contract_address,
+        &transaction.constructor_calldata,
+        transaction.class_hash,
+        transaction.contract_address_salt,
+        transaction.max_fee,
+        chain_id,
+        transaction.nonce,
+    );
+
+    deploy_account_rpc_to_inner(DeployAccountTransaction {
+        transaction_hash,
+        max_fee: transaction.max_fee,
+        signature: transaction.signature,
+        nonce: transaction.nonce,
+        contract_address_salt: transaction.contract_address_salt,
+        constructor_calldata: transaction.constructor_calldata,
+        class_hash: transaction.class_hash,
+    })
+}
+
+/// Converts a broadcasted v1 invoke transaction into the transaction executed by the sequencer.
+pub fn broadcasted_invoke_v1_rpc_to_inner(
+    transaction: BroadcastedInvokeTransactionV1,
+    chain_id: FieldElement,
+) -> Result<InnerInvokeTransactionV1> {
+    let transaction_hash = compute_invoke_v1_transaction_hash(
+        transaction.sender_address,
+        &transaction.calldata,
+        transaction.max_fee,
+        chain_id,
+        transaction.nonce,
+    );
+
+    invoke_v1_rpc_to_inner(InvokeTransactionV1 {
+        transaction_hash,
+        max_fee: transaction.max_fee,
+        signature: transaction.signature,
+        nonce: transaction.nonce,
+        sender_address: transaction.sender_address,
+        calldata: transaction.calldata,
+    })
+}
+
+pub fn invoke_v1_rpc_to_inner(
+    transaction: InvokeTransactionV1,
+) -> Result<InnerInvokeTransactionV1> {
+    Ok(InnerInvokeTransactionV1 {
+        transaction_hash: TransactionHash(transaction.transaction_hash.into()),
+        sender_address: ContractAddress(patricia_key!(transaction.sender_address)),
+        nonce: Nonce(transaction.nonce.into()),
+        calldata: Calldata(Arc::new(transaction.calldata.into_iter().map(Into::into).collect())),
+        max_fee: Fee(starkfelt_to_u128(transaction.max_fee.into())?),
+        signature: TransactionSignature(
+            transaction.signature.into_iter().map(Into::into).collect(),
+        ),
+    })
+}
+
+pub fn declare_v1_rpc_to_inner(
+    transaction: DeclareTransactionV1,
+) -> Result<InnerDeclareTransactionV0V1> {
+    Ok(InnerDeclareTransactionV0V1 {
+        transaction_hash: TransactionHash(transaction.transaction_hash.into()),
+        class_hash: ClassHash(transaction.class_hash.into()),
+        sender_address: ContractAddress(patricia_key!(transaction.sender_address)),
+        nonce: Nonce(transaction.nonce.into()),
+        max_fee: Fee(starkfelt_to_u128(transaction.max_fee.into())?),
+        signature: TransactionSignature(
+            transaction.signature.into_iter().map(Into::into).collect(),
+        ),
+    })
+}
+
+pub fn declare_v2_rpc_to_inner(
+    transaction: DeclareTransactionV2,
+) -> Result<InnerDeclareTransactionV2> {
+    Ok(InnerDeclareTransactionV2 {
+        transaction_hash: TransactionHash(transaction.transaction_hash.into()),
+        class_hash: ClassHash(transaction.class_hash.into()),
+        compiled_class_hash: CompiledClassHash(transaction.compiled_class_hash.into()),
+        sender_address: ContractAddress(patricia_key!(transaction.sender_address)),
+        nonce: Nonce(transaction.nonce.into()),
+        max_fee: Fee(starkfelt_to_u128(transaction.max_fee.into())?),
+        signature: TransactionSignature(
+            transaction.signature.into_iter().map(Into::into).collect(),
+        ),
+    })
+}
+
+/// Converts a v1 deploy account transaction, the only version of the spec.
+pub fn deploy_account_rpc_to_inner(
+    transaction: DeployAccountTransaction,
+) -> Result<InnerDeployAccountTransaction> {
+    let contract_address = get_contract_address(
+        transaction.contract_address_salt,
+        transaction.class_hash,
+        &transaction.constructor_calldata,
+        FieldElement::ZERO,
+    );
+
+    Ok(InnerDeployAccountTransaction {
+        transaction_hash: TransactionHash(transaction.transaction_hash.into()),
+        class_hash: ClassHash(transaction.class_hash.into()),
+        contract_address: ContractAddress(patricia_key!(contract_address)),
+        contract_address_salt: ContractAddressSalt(transaction.contract_address_salt.into()),
+        constructor_calldata: Calldata(Arc::new(
+            transaction.constructor_calldata.into_iter().map(Into::into).collect(),
+        )),
+        nonce: Nonce(transaction.nonce.into()),
+        max_fee: Fee(starkfelt_to_u128(transaction.max_fee.into())?),
+        signature: TransactionSignature(
+            transaction.signature.into_iter().map(Into::into).collect(),
+        ),
+        version: TransactionVersion(stark_felt!(1_u32)),
+    })
+}
+
+pub fn l1_handler_rpc_to_inner(transaction: L1HandlerTransaction) -> InnerL1HandlerTransaction {
+    InnerL1HandlerTransaction {
+        transaction_hash: TransactionHash(transaction.transaction_hash.into()),
+        version: TransactionVersion(FieldElement::from(transaction.version).into()),
+        nonce: Nonce(FieldElement::from(transaction.nonce).into()),
+        contract_address: ContractAddress(patricia_key!(transaction.contract_address)),
+        entry_point_selector: EntryPointSelector(transaction.entry_point_selector.into()),
+        calldata: Calldata(Arc::new(transaction.calldata.into_iter().map(Into::into).collect())),
+    }
+}
+
+/// Converts a transaction of a block into a transaction executable by the sequencer.
+///
+/// `contract_class` is the class declared by a declare transaction, which is required for them.
+/// The fee an L1 handler transaction paid on L1 isn't part of the transaction, so it is assumed
+/// to cover any fee.
+pub fn rpc_to_blockifier_transaction(
+    transaction: Transaction,
+    contract_class: Option<ContractClass>,
+) -> Result<BlockifierTransaction> {
+    let transaction_hash = *transaction.transaction_hash();
+    let missing_class =
+        || anyhow!("missing the class declared by transaction {transaction_hash:#x}");
+
+    let transaction = match transaction {
+        Transaction::Invoke(InvokeTransaction::V1(tx)) => {
+            AccountTransaction::Invoke(InnerInvokeTransaction::V1(invoke_v1_rpc_to_inner(tx)?))
+        }
+
+        Transaction::Declare(DeclareTransaction::V1(tx)) => {
+            let contract_class = match contract_class.ok_or_else(missing_class)? {
+                ContractClass::Legacy(class) => legacy_rpc_to_inner_class(&class)?.1,
+                ContractClass::Sierra(_) => bail!("expected a legacy class for declare v1"),
+            };
+
+            AccountTransaction::Declare(BlockifierDeclareTransaction::new(
+                InnerDeclareTransaction::V1(declare_v1_rpc_to_inner(tx)?),
+                contract_class,
+            )?)
+        }
+
+        Transaction::Declare(DeclareTransaction::V2(tx)) => {
+            let contract_class = match contract_class.ok_or_else(missing_class)? {
+                ContractClass::Sierra(class) => rpc_to_inner_class(&class)?.1,
+                ContractClass::Legacy(_) => bail!("expected a Sierra class for declare v2"),
+            };
+
+            AccountTransaction::Declare(BlockifierDeclareTransaction::new(
+                InnerDeclareTransaction::V2(declare_v2_rpc_to_inner(tx)?),
+                contract_class,
+            )?)
+        }
+
+        Transaction::DeployAccount(tx) => {
+            AccountTransaction::DeployAccount(deploy_account_rpc_to_inner(tx)?)
+        }
+
+        Transaction::L1Handler(tx) => {
+            return Ok(BlockifierTransaction::L1HandlerTransaction(
+                BlockifierL1HandlerTransaction {
+                    tx: l1_handler_rpc_to_inner(tx),
+                    paid_fee_on_l1: Fee(u128::MAX),
+                },
+            ));
+        }
+
+        transaction => bail!("unsupported transaction {:#x}", transaction.transaction_hash()),
+    };
+
+    Ok(BlockifierTransaction::AccountTransaction(transaction))
+}
+
+/// Returns the version of an account transaction, which selects the token its fee is charged in.
+pub fn account_transaction_version(transaction: &AccountTransaction) -> u8 {
+    match transaction {
+        AccountTransaction::Invoke(tx) => match tx {
+            InnerInvokeTransaction::V0(_) => 0,
+            InnerInvokeTransaction::V1(_) => 1,
+        },
+        AccountTransaction::Declare(tx) => match tx.tx() {
+            InnerDeclareTransaction::V0(_) => 0,
+            InnerDeclareTransaction::V1(_) => 1,
+            InnerDeclareTransaction::V2(_) => 2,
+        },
+        // Unsupported versions are rejected by the execution, whatever the fee token.
+        AccountTransaction::DeployAccount(tx) => starkfelt_to_u128(tx.version.0)
+            .ok()
+            .and_then(|version| u8::try_from(version).ok())
+            .unwrap_or_default(),
+    }
+}
+
+pub fn compute_declare_v1_transaction_hash(
+    sender_address: FieldElement,
+    class_hash: FieldElement,
+    max_fee: FieldElement,
+    chain_id: FieldElement,
+    nonce: FieldElement,
+) -> FieldElement {
+    compute_hash_on_elements(&[
+        PREFIX_DECLARE,
+        FieldElement::ONE, // version
+        sender_address,
+        FieldElement::ZERO, // entry_point_selector
+        compute_hash_on_elements(&[class_hash]),
+        max_fee,
+        chain_id,
+        nonce,
+    ])
+}
+
+pub fn compute_declare_v2_transaction_hash(
+    sender_address: FieldElement,
+    class_hash: FieldElement,
+    max_fee: FieldElement,
+    chain_id: FieldElement,
+    nonce: FieldElement,
+    compiled_class_hash: FieldElement,
+) -> FieldElement {
+    compute_hash_on_elements(&[
+        PREFIX_DECLARE,
+        FieldElement::TWO, // version
+        sender_address,
+        FieldElement::ZERO, // entry_point_selector
+        compute_hash_on_elements(&[class_hash]),
+        max_fee,
+        chain_id,
+        nonce,
+        compiled_class_hash,
+    ])
+}
+
+pub fn compute_invoke_v1_transaction_hash(
+    sender_address: FieldElement,
+    calldata: &[FieldElement],
+    max_fee: FieldElement,
+    chain_id: FieldElement,
+    nonce: FieldElement,
+) -> FieldElement {
+    compute_hash_on_elements(&[
+        PREFIX_INVOKE,
+        FieldElement::ONE, // version
+        sender_address,
+        FieldElement::ZERO, // entry_point_selector
+        compute_hash_on_elements(calldata),
+        max_fee,
+        chain_id,
+        nonce,
+    ])
+}
+
+pub fn convert_stark_felt_array_to_field_element_array(
+    calldata: &[StarkFelt],
+) -> Result<Vec<FieldElement>> {
+    calldata.iter().try_fold(Vec::new(), |mut data, &felt| {
+        data.push(felt.into());
+        Ok(data)
+    })
+}
+
+pub fn convert_inner_to_rpc_tx(transaction: InnerTransaction) -> Result<Transaction> {
+    let tx = match transaction {
+        InnerTransaction::Invoke(invoke) => Transaction::Invoke(convert_invoke_to_rpc_tx(invoke)?),
+        InnerTransaction::Declare(declare) => {
+            Transaction::Declare(convert_declare_to_rpc_tx(declare)?)
+        }
+        InnerTransaction::DeployAccount(deploy) => {
+            Transaction::DeployAccount(convert_deploy_account_to_rpc_tx(deploy)?)
+        }
+        InnerTransaction::L1Handler(l1handler) => {
+            Transaction::L1Handler(convert_l1_handle_to_rpc(l1handler)?)
+        }
+        InnerTransaction::Deploy(deploy) => Transaction::Deploy(convert_deploy_to_rpc(deploy)?),
+    };
+    Ok(tx)
+}
+
+fn convert_l1_handle_to_rpc(
+    transaction: InnerL1HandlerTransaction,
+) -> Result<L1HandlerTransaction> {
+    Ok(L1HandlerTransaction {
+        transaction_hash: transaction.transaction_hash.0.into(),
+        contract_address: (*transaction.contract_address.0.key()).into(),
+        nonce: <StarkFelt as Into<FieldElement>>::into(transaction.nonce.0).try_into().unwrap(),
+        version: <StarkFelt as Into<FieldElement>>::into(transaction.version.0).try_into().unwrap(),
+        entry_point_selector: transaction.entry_point_selector.0.into(),
+        calldata: convert_stark_felt_array_to_field_element_array(&transaction.calldata.0)?,
+    })
+}
+
+fn convert_deploy_to_rpc(transaction: InnerDeployTransaction) -> Result<DeployTransaction> {
+    Ok(DeployTransaction {
+        transaction_hash: transaction.transaction_hash.0.into(),
+        version: <StarkFelt as Into<FieldElement>>::into(transaction.version.0).try_into()?,
+        class_hash: transaction.class_hash.0.into(),
+        contract_address_salt: transaction.contract_address_salt.0.into(),
+        constructor_calldata: convert_stark_felt_array_to_field_element_array(
+            &transaction.constructor_calldata.0,
+        )?,
+    })
+}
+
+fn convert_deploy_account_to_rpc_tx(
+    transaction: InnerDeployAccountTransaction,
+) -> Result<DeployAccountTransaction> {
+    Ok(DeployAccountTransaction {
+        transaction_hash: transaction.transaction_hash.0.into(),
+        class_hash: transaction.class_hash.0.into(),
+        contract_address_salt: transaction.contract_address_salt.0.into(),
+        nonce: transaction.nonce.0.into(),
+        constructor_calldata: convert_stark_felt_array_to_field_element_array(
+            &transaction.constructor_calldata.0,
+        )?,
+        signature: convert_stark_felt_array_to_field_element_array(&transaction.signature.0)?,
+        max_fee: FieldElement::from_str(&transaction.max_fee.0.to_string())?,
+    })
+}
+
+fn convert_invoke_to_rpc_tx(transaction: InnerInvokeTransaction) -> Result<InvokeTransaction> {
+    Ok(match transaction {
+        InnerInvokeTransaction::V1(tx) => InvokeTransaction::V1(InvokeTransactionV1 {
+            transaction_hash: tx.transaction_hash.0.into(),
+            sender_address: (*tx.sender_address.0.key()).into(),
+            nonce: tx.nonce.0.into(),
+            calldata: convert_stark_felt_array_to_field_element_array(&tx.calldata.0)?,
+            signature: convert_stark_felt_array_to_field_element_array(&tx.signature.0)?,
+            max_fee: FieldElement::from_str(&tx.max_fee.0.to_string())?,
+        }),
+        _ => unimplemented!("invoke v0 not supported"),
+    })
+}
+
+fn convert_declare_to_rpc_tx(transaction: InnerDeclareTransaction) -> Result<DeclareTransaction> {
+    Ok(match transaction {
+        InnerDeclareTransaction::V0(tx) | InnerDeclareTransaction::V1(tx) => {
+            DeclareTransaction::V1(DeclareTransactionV1 {
+                nonce: tx.nonce.0.into(),
+                max_fee: FieldElement::from_str(&tx.max_fee.0.to_string())?,
+                class_hash: tx.class_hash.0.into(),
+                transaction_hash: tx.transaction_hash.0.into(),
+                sender_address: (*tx.sender_address.0.key()).into(),
+                signature: convert_stark_felt_array_to_field_element_array(&tx.signature.0)?,
+            })
+        }
+        InnerDeclareTransaction::V2(tx) => DeclareTransaction::V2(DeclareTransactionV2 {
+            nonce: tx.nonce.0.into(),
+            class_hash: tx.class_hash.0.into(),
+            transaction_hash: tx.transaction_hash.0.into(),
+            sender_address: (*tx.sender_address.0.key()).into(),
+            compiled_class_hash: tx.compiled_class_hash.0.into(),
+            max_fee: FieldElement::from_str(&tx.max_fee.0.to_string())?,
+            signature: convert_stark_felt_array_to_field_element_array(&tx.signature.0)?,
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet::core::chain_id;
+
+    use super::*;
+
+    #[test]
+    fn test_compute_deploy_account_v1_transaction_hash() {
+        let contract_address = FieldElement::from_hex_be(
+            "0x0617e350ebed9897037bdef9a09af65049b85ed2e4c9604b640f34bffa152149",
+        )
+        .unwrap();
+        let constructor_calldata = vec![
+            FieldElement::from_hex_be(
+                "0x33434ad846cdd5f23eb73ff09fe6fddd568284a0fb7d1be20ee482f044dabe2",
+            )
+            .unwrap(),
+            FieldElement::from_hex_be(
+                "0x79dc0da7c54b95f10aa182ad0a46400db63156920adb65eca2654c0945a463",
+            )
+            .unwrap(),
+            FieldElement::from_hex_be("0x2").unwrap(),
+            FieldElement::from_hex_be(
+                "0x43a8fbe19d5ace41a2328bb870143241831180eb3c3c48096642d63709c3096",
+            )
+            .unwrap(),
+            FieldElement::from_hex_be("0x0").unwrap(),
+        ];
+        let class_hash = FieldElement::from_hex_be(
+            "0x025ec026985a3bf9d0cc1fe17326b245dfdc3ff89b8fde106542a3ea56c5a918",
+        )
+        .unwrap();
+        let salt = FieldElement::from_hex_be(
+            "0x43a8fbe19d5ace41a2328bb870143241831180eb3c3c48096642d63709c3096",
+        )
+        .unwrap();
+        let max_fee = FieldElement::from_hex_be("0x38d7ea4c68000").unwrap();
+        let chain_id = chain_id::MAINNET;
+        let nonce = FieldElement::ZERO;
+
+        let hash = compute_deploy_account_v1_transaction_hash(
+            contract_address,
+            &constructor_calldata,
+            class_hash,
+            salt,
+            max_fee,
+            chain_id,
+            nonce,
+        );
+
+        assert_eq!(
+            hash,
+            FieldElement::from_hex_be(
+                "0x3d013d17c20a5db05d5c2e06c948a4e0bf5ea5b851b15137316533ec4788b6b"
+            )
+            .unwrap()
+        );
+    }
+}
@@ -1,35 +1,56 @@
 use blockifier::abi::abi_utils::{get_storage_var_address, selector_from_name};
+use blockifier::state::state_api::StateReader;
 use blockifier::transaction::account_transaction::AccountTransaction;
 use blockifier::transaction::transaction_execution::Transaction;
 use katana_core::backend::config::{Environment, StarknetConfig};
+use katana_core::backend::tx_log::{ExecutionStatus, TransactionLogRecord};
 use katana_core::backend::StarknetWrapper;
-use katana_core::constants::FEE_TOKEN_ADDRESS;
-use starknet::core::types::TransactionStatus;
+use katana_core::block_context::FeeTokenAddresses;
+use katana_core::constants::{DEFAULT_PARALLEL_BATCH_SIZE, FEE_TOKEN_ADDRESS};
+use katana_core::sequencer_error::SequencerError;
+use katana_core::utils::starkfelt_to_u128;
+use starknet::core::types::{FieldElement, TransactionStatus};
 use starknet_api::block::BlockNumber;
-use starknet_api::core::Nonce;
-use starknet_api::hash::StarkFelt;
+use starknet_api::core::{ClassHash, ContractAddress, Nonce, PatriciaKey};
+use starknet_api::hash::{StarkFelt, StarkHash};
 use starknet_api::transaction::{
-    Calldata, InvokeTransaction, InvokeTransactionV1, TransactionHash,
+    Calldata, Fee, InvokeTransaction, InvokeTransactionV1, TransactionHash,
 };
-use starknet_api::{calldata, stark_felt};
+use starknet_api::{calldata, patricia_key, stark_felt};
 
 fn create_test_starknet() -> StarknetWrapper {
+    create_test_starknet_with_env(Environment::default())
+}
+
+fn create_test_starknet_with_env(env: Environment) -> StarknetWrapper {
+    create_test_starknet_with_config(StarknetConfig { env, ..test_starknet_config() })
+}
+
+fn create_test_starknet_with_config(config: StarknetConfig) -> StarknetWrapper {
+    let mut starknet = StarknetWrapper::new(config).unwrap();
+    starknet.generate_genesis_block();
+    starknet
+}
+
+fn test_starknet_config() -> StarknetConfig {
     let test_account_path =
         [env!("CARGO_MANIFEST_DIR"), "./contracts/compiled/account_without_validation.json"]
             .iter()
             .collect();
 
-    let mut starknet = StarknetWrapper::new(StarknetConfig {
+    StarknetConfig {
         seed: [0u8; 32],
         auto_mine: true,
         total_accounts: 2,
         allow_zero_max_fee: true,
         account_path: Some(test_account_path),
         env: Environment::default(),
-    });
-
-    starknet.generate_genesis_block();
-    starknet
+        profile_dir: None,
+        profile_debug_info_dir: None,
+        parallel_execution: false,
+        parallel_batch_size: DEFAULT_PARALLEL_BATCH_SIZE,
+        tx_log: None,
+    }
 }
 
 #[test]
@@ -92,12 +113,26 @@ fn test_creating_blocks() {
     let block1 = starknet.blocks.by_number(BlockNumber(1)).unwrap();
     let last_block = starknet.blocks.latest().unwrap();
 
-    assert_eq!(block0.transactions().len(), 4, "genesis block should have 4 transactions");
+    assert_eq!(block0.transactions().len(), 6, "genesis block should have 6 transactions");
     assert_eq!(block0.block_number(), BlockNumber(0));
     assert_eq!(block1.block_number(), BlockNumber(1));
     assert_eq!(last_block.block_number(), BlockNumber(1));
 }
 
+#[test]
+fn test_subscribe_blocks() {
+    let mut starknet = create_test_starknet();
+    let mut blocks = starknet.subscribe_blocks();
+
+    starknet.generate_pending_block();
+    starknet.generate_latest_block();
+
+    let block = blocks.try_recv().expect("should be notified of the new block");
+    assert_eq!(block.block_number(), BlockNumber(1));
+    assert_eq!(block, starknet.blocks.latest().unwrap());
+    assert!(blocks.try_recv().is_err(), "no other block should be notified");
+}
+
 #[test]
 fn test_add_transaction() {
     let mut starknet = create_test_starknet();
@@ -145,6 +180,19 @@ fn test_add_transaction() {
     );
     assert_eq!(tx.unwrap().status, TransactionStatus::AcceptedOnL2);
 
+    // CHECK THE EXECUTION RESOURCES
+    //
+
+    let resources = tx.unwrap().resources().unwrap().unwrap();
+    let fee = &resources.fee;
+
+    assert!(resources.execution_resources.steps > 0, "execution must consume cairo steps");
+    assert!(resources.execution_resources.l1_gas_usage > 0, "transfer must update the state");
+    assert_eq!(fee.gas_price, starknet.block_context.gas_price);
+    assert_eq!(fee.l1_gas_fee, resources.execution_resources.l1_gas_usage as u128 * fee.gas_price);
+    assert!(fee.computation_fee > 0);
+    assert!(fee.actual_fee <= fee.l1_gas_fee + fee.computation_fee);
+
     // CHECK THAT THE BALANCE IS UPDATED
     //
 
@@ -179,12 +227,13 @@ fn test_add_reverted_transaction() {
 
     assert_eq!(
         starknet.transactions.transactions.len(),
-        5,
+        7,
         "transaction must be stored even if execution fail"
     );
     assert_eq!(tx.unwrap().block_hash, None);
     assert_eq!(tx.unwrap().block_number, None);
     assert_eq!(tx.unwrap().status, TransactionStatus::Rejected);
+    assert_eq!(tx.unwrap().resources().unwrap(), None);
     assert_eq!(
         starknet.blocks.num_to_block.len(),
         1,
@@ -192,6 +241,426 @@ fn test_add_reverted_transaction() {
     );
 }
 
+#[test]
+fn test_mint() {
+    let mut starknet = create_test_starknet();
+    starknet.generate_pending_block();
+
+    let address = ContractAddress(patricia_key!("0x1234"));
+    let balance_key = get_storage_var_address("ERC20_balances", &[*address.0.key()]).unwrap();
+
+    starknet.mint(address, 100).unwrap();
+    starknet.mint(address, 50).unwrap();
+
+    for fee_token_address in starknet.fee_token_addresses.all() {
+        let balance =
+            starknet.latest_state().get_storage_at(fee_token_address, balance_key).unwrap();
+        assert_eq!(balance, stark_felt!(150_u8), "balance must be minted in every fee token");
+    }
+    assert_eq!(starknet.blocks.num_to_block.len(), 3, "each mint is mined in its own block");
+
+    assert!(starknet.mint(address, u128::MAX).is_err(), "balance must not overflow");
+}
+
+#[test]
+fn test_fee_token_by_version() {
+    let mut starknet = create_test_starknet_with_env(Environment {
+        strk_fee_versions: vec![1],
+        ..Default::default()
+    });
+    starknet.generate_pending_block();
+
+    let a = starknet.predeployed_accounts.accounts[0].clone();
+    let b = starknet.predeployed_accounts.accounts[1].clone();
+    let balance_key =
+        get_storage_var_address("ERC20_balances", &[*a.account_address.0.key()]).unwrap();
+
+    let eth = starknet.fee_token_addresses.eth;
+    let strk = starknet.fee_token_addresses.strk;
+    assert_eq!(starknet.latest_state().get_storage_at(eth, balance_key).unwrap(), a.balance);
+    assert_eq!(starknet.latest_state().get_storage_at(strk, balance_key).unwrap(), a.balance);
+
+    // Transfer some ETH with a V1 invoke, whose fee must be charged in STRK.
+    let execute_calldata = calldata![
+        *FEE_TOKEN_ADDRESS,
+        selector_from_name("transfer").0,
+        stark_felt!(3_u8),
+        *b.account_address.0.key(),
+        stark_felt!("0x99"),
+        stark_felt!(0_u8)
+    ];
+
+    starknet.handle_transaction(Transaction::AccountTransaction(AccountTransaction::Invoke(
+        InvokeTransaction::V1(InvokeTransactionV1 {
+            sender_address: a.account_address,
+            calldata: execute_calldata,
+            transaction_hash: TransactionHash(stark_felt!("0x6969")),
+            nonce: Nonce(1u8.into()),
+            max_fee: Fee(10_u128.pow(18)),
+            ..Default::default()
+        }),
+    )));
+
+    let tx = starknet.transactions.transactions.get(&TransactionHash(stark_felt!("0x6969")));
+    assert_eq!(tx.unwrap().status, TransactionStatus::AcceptedOnL2);
+
+    let initial_balance = starkfelt_to_u128(a.balance).unwrap();
+    let eth_balance = starknet.latest_state().get_storage_at(eth, balance_key).unwrap();
+    let strk_balance = starknet.latest_state().get_storage_at(strk, balance_key).unwrap();
+
+    assert_eq!(eth_balance, stark_felt!(initial_balance - 0x99), "fee must not be charged in ETH");
+    assert!(
+        starkfelt_to_u128(strk_balance).unwrap() < initial_balance,
+        "fee must be charged in STRK"
+    );
+}
+
+#[test]
+fn test_custom_fee_token_addresses() {
+    let addresses = FeeTokenAddresses {
+        eth: ContractAddress(patricia_key!("0xe7")),
+        strk: ContractAddress(patricia_key!("0x57")),
+    };
+    let mut starknet = create_test_starknet_with_env(Environment {
+        strk_fee_versions: vec![1],
+        fee_token_addresses: addresses,
+        ..Default::default()
+    });
+    starknet.generate_pending_block();
+
+    let a = starknet.predeployed_accounts.accounts[0].clone();
+    let balance_key =
+        get_storage_var_address("ERC20_balances", &[*a.account_address.0.key()]).unwrap();
+
+    for address in addresses.all() {
+        assert_eq!(
+            starknet.latest_state().get_storage_at(address, balance_key).unwrap(),
+            a.balance
+        );
+    }
+    for address in FeeTokenAddresses::default().all() {
+        assert_eq!(
+            starknet.latest_state().get_class_hash_at(address).unwrap(),
+            ClassHash::default()
+        );
+    }
+
+    // Transfer some ETH with a V1 invoke, whose fee must be charged in STRK.
+    let b = starknet.predeployed_accounts.accounts[1].clone();
+    starknet.handle_transaction(Transaction::AccountTransaction(AccountTransaction::Invoke(
+        InvokeTransaction::V1(InvokeTransactionV1 {
+            sender_address: a.account_address,
+            calldata: calldata![
+                *addresses.eth.0.key(),
+                selector_from_name("transfer").0,
+                stark_felt!(3_u8),
+                *b.account_address.0.key(),
+                stark_felt!("0x99"),
+                stark_felt!(0_u8)
+            ],
+            transaction_hash: TransactionHash(stark_felt!("0x6969")),
+            nonce: Nonce(1u8.into()),
+            max_fee: Fee(10_u128.pow(18)),
+            ..Default::default()
+        }),
+    )));
+
+    let tx = &starknet.transactions.transactions[&TransactionHash(stark_felt!("0x6969"))];
+    assert_eq!(tx.status, TransactionStatus::AcceptedOnL2);
+    assert_eq!(tx.block_context.as_ref().unwrap().fee_token_address, addresses.strk);
+
+    let eth_balance = starknet.latest_state().get_storage_at(addresses.eth, balance_key).unwrap();
+    assert_eq!(eth_balance, stark_felt!(starkfelt_to_u128(a.balance).unwrap() - 0x99));
+    let strk_balance = starknet.latest_state().get_storage_at(addresses.strk, balance_key).unwrap();
+    assert!(starkfelt_to_u128(strk_balance).unwrap() < starkfelt_to_u128(a.balance).unwrap());
+}
+
+fn transfer_transaction(
+    sender: ContractAddress,
+    recipient: StarkFelt,
+    nonce: u8,
+    hash: u8,
+) -> Transaction {
+    Transaction::AccountTransaction(AccountTransaction::Invoke(InvokeTransaction::V1(
+        InvokeTransactionV1 {
+            sender_address: sender,
+            calldata: calldata![
+                *FEE_TOKEN_ADDRESS,
+                selector_from_name("transfer").0,
+                stark_felt!(3_u8),
+                recipient,
+                stark_felt!("0x99"),
+                stark_felt!(0_u8)
+            ],
+            transaction_hash: TransactionHash(stark_felt!(hash)),
+            nonce: Nonce(nonce.into()),
+            max_fee: Fee(10_u128.pow(18)),
+            ..Default::default()
+        },
+    )))
+}
+
+#[test]
+fn test_parallel_execution_matches_serial_execution() {
+    let mut serial = create_test_starknet();
+    let mut parallel = create_test_starknet();
+    parallel.config.auto_mine = false;
+    parallel.config.parallel_execution = true;
+
+    let a = serial.predeployed_accounts.accounts[0].account_address;
+    let b = serial.predeployed_accounts.accounts[1].account_address;
+
+    // The last transfer conflicts with the first one, which bumps the nonce and spends the
+    // balance of the same account.
+    let transactions = || {
+        vec![
+            transfer_transaction(a, stark_felt!("0x1111"), 1, 1),
+            transfer_transaction(b, stark_felt!("0x2222"), 1, 2),
+            transfer_transaction(a, stark_felt!("0x3333"), 2, 3),
+        ]
+    };
+
+    serial.config.auto_mine = false;
+    for starknet in [&mut serial, &mut parallel] {
+        starknet.generate_pending_block();
+        starknet.handle_transactions(transactions());
+        starknet.generate_latest_block();
+    }
+
+    for hash in 1..=3_u8 {
+        let hash = TransactionHash(stark_felt!(hash));
+        let serial_tx = serial.transactions.by_hash(&hash).unwrap();
+        let parallel_tx = parallel.transactions.by_hash(&hash).unwrap();
+
+        assert_eq!(serial_tx.status, TransactionStatus::AcceptedOnL2);
+        assert_eq!(parallel_tx.status, serial_tx.status);
+        assert_eq!(
+            parallel_tx.execution_info.as_ref().unwrap().actual_fee,
+            serial_tx.execution_info.as_ref().unwrap().actual_fee
+        );
+    }
+
+    let serial_state = serial.latest_state();
+    let parallel_state = parallel.latest_state();
+    assert_eq!(parallel_state.storage.len(), serial_state.storage.len());
+    for (address, record) in &serial_state.storage {
+        let parallel_record = &parallel_state.storage[address];
+        assert_eq!(parallel_record.nonce, record.nonce);
+        assert_eq!(parallel_record.class_hash, record.class_hash);
+        assert_eq!(parallel_record.storage, record.storage);
+    }
+}
+
+#[test]
+fn test_queued_transactions_are_executed_on_mining() {
+    let mut starknet = create_test_starknet();
+    starknet.config.auto_mine = false;
+    starknet.config.parallel_execution = true;
+    starknet.generate_pending_block();
+
+    let a = starknet.predeployed_accounts.accounts[0].account_address;
+    starknet.handle_transaction(transfer_transaction(a, stark_felt!("0x1111"), 1, 1));
+
+    let hash = TransactionHash(stark_felt!(1_u8));
+    let queued = starknet.transactions.by_hash(&hash).expect("queued transaction must be stored");
+    assert_eq!(queued.status, TransactionStatus::Pending);
+    assert!(queued.execution_info.is_none(), "transaction must be queued");
+    assert!(starknet.set_next_block_timestamp(0).is_err(), "queued transactions are pending");
+
+    // Reading the pending state doesn't execute the queue.
+    let nonce = starknet.pending_state().get_nonce_at(a).unwrap();
+    assert_eq!(nonce, starknet.latest_state().get_nonce_at(a).unwrap());
+    assert_eq!(starknet.queued_transactions_from(a), 1);
+    assert!(starknet.transactions.by_hash(&hash).unwrap().execution_info.is_none());
+
+    starknet.generate_latest_block();
+    assert_eq!(
+        starknet.transactions.by_hash(&hash).unwrap().status,
+        TransactionStatus::AcceptedOnL2
+    );
+}
+
+#[test]
+fn test_queued_transactions_are_executed_in_batches() {
+    let mut starknet = create_test_starknet_with_config(StarknetConfig {
+        auto_mine: false,
+        parallel_execution: true,
+        parallel_batch_size: 2,
+        ..test_starknet_config()
+    });
+    starknet.generate_pending_block();
+
+    let a = starknet.predeployed_accounts.accounts[0].account_address;
+    let b = starknet.predeployed_accounts.accounts[1].account_address;
+
+    starknet.handle_transaction(transfer_transaction(a, stark_felt!("0x1111"), 1, 1));
+    assert!(starknet.blocks.pending_block.as_ref().unwrap().transactions().is_empty());
+
+    starknet.handle_transaction(transfer_transaction(b, stark_felt!("0x2222"), 1, 2));
+    assert_eq!(starknet.blocks.pending_block.as_ref().unwrap().transactions().len(), 2);
+    assert_eq!(starknet.queued_transactions_from(a), 0);
+
+    for hash in 1..=2_u8 {
+        let transaction = starknet.transactions.by_hash(&TransactionHash(stark_felt!(hash)));
+        assert!(transaction.unwrap().execution_info.is_some(), "the batch must be executed");
+    }
+}
+
+#[test]
+fn test_tx_log() {
+    let path = std::env::temp_dir().join(format!("katana-tx-log-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut starknet = create_test_starknet_with_config(StarknetConfig {
+        tx_log: Some(path.clone()),
+        ..test_starknet_config()
+    });
+    starknet.generate_pending_block();
+
+    let a = starknet.predeployed_accounts.accounts[0].account_address;
+    starknet.handle_transaction(transfer_transaction(a, stark_felt!("0x1111"), 1, 1));
+    starknet.handle_transaction(transfer_transaction(a, stark_felt!("0x1111"), 1, 2));
+
+    let records = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<TransactionLogRecord>(line).unwrap())
+        .collect::<Vec<_>>();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(records.len(), 2, "rejected transactions must be logged too");
+
+    let accepted = &records[0];
+    assert_eq!(accepted.transaction_hash, FieldElement::ONE);
+    assert_eq!(accepted.transaction_type, "INVOKE");
+    assert_eq!(accepted.sender_address, Some((*a.0.key()).into()));
+    assert_eq!(accepted.calldata.len(), 6);
+    assert_eq!(accepted.status, ExecutionStatus::Succeeded);
+    assert_eq!(accepted.block_number, 1);
+    assert!(accepted.revert_reason.is_none());
+    assert!(!accepted.events.is_empty(), "the transfer must emit events");
+    assert!(accepted.actual_fee > 0);
+    assert!(accepted.resources["n_steps"] > 0);
+
+    let rejected = &records[1];
+    assert_eq!(rejected.status, ExecutionStatus::Rejected, "the nonce was already used");
+    assert!(rejected.revert_reason.is_some());
+    assert!(rejected.events.is_empty());
+}
+
+#[test]
+fn test_tx_log_which_cannot_be_opened() {
+    let config = StarknetConfig { tx_log: Some(std::env::temp_dir()), ..test_starknet_config() };
+    let err = StarknetWrapper::new(config).err().expect("a directory can't be opened as a log");
+    assert!(err.to_string().contains("failed to open the transaction log"));
+}
+
+#[test]
+fn test_estimate_fee_of_dependent_transactions() {
+    let mut starknet = create_test_starknet();
+    starknet.generate_pending_block();
+
+    let a = starknet.predeployed_accounts.accounts[0].account_address;
+    let account_transaction =
+        |nonce, hash| match transfer_transaction(a, stark_felt!("0x1111"), nonce, hash) {
+            Transaction::AccountTransaction(tx) => tx,
+            Transaction::L1HandlerTransaction(_) => unreachable!(),
+        };
+
+    // The second transaction can only be executed once the first one has bumped the nonce.
+    let estimates = starknet
+        .estimate_fee(vec![account_transaction(1, 1), account_transaction(2, 2)], None)
+        .unwrap();
+    assert_eq!(estimates.len(), 2);
+    assert!(estimates.iter().all(|estimate| estimate.overall_fee > 0));
+
+    let err = starknet
+        .estimate_fee(vec![account_transaction(1, 1), account_transaction(1, 2)], None)
+        .unwrap_err();
+    assert!(matches!(err, SequencerError::FeeEstimation { index: 1, .. }));
+
+    assert!(
+        starknet.transactions.by_hash(&TransactionHash(stark_felt!(1_u8))).is_none(),
+        "estimated transactions must not be executed on the pending state"
+    );
+}
+
+#[test]
+fn test_reorg() {
+    let mut starknet = create_test_starknet();
+    starknet.generate_pending_block();
+
+    let a = starknet.predeployed_accounts.accounts[0].account_address;
+    let recipient = stark_felt!("0x1111");
+    let balance_key = get_storage_var_address("ERC20_balances", &[recipient]).unwrap();
+    let balance = |starknet: &StarknetWrapper| {
+        starknet.latest_state().get_storage_at(starknet.fee_token_addresses.eth, balance_key)
+    };
+
+    starknet.handle_transaction(transfer_transaction(a, recipient, 1, 1));
+    starknet.handle_transaction(transfer_transaction(a, recipient, 2, 2));
+    assert_eq!(starknet.blocks.current_block_number(), BlockNumber(2));
+    assert_eq!(balance(&starknet).unwrap(), stark_felt!(0x99_u32 * 2));
+
+    assert!(matches!(
+        starknet.reorg(0),
+        Err(SequencerError::InvalidReorgDepth { depth: 0, max: 2 })
+    ));
+    assert!(
+        matches!(starknet.reorg(3), Err(SequencerError::InvalidReorgDepth { depth: 3, max: 2 })),
+        "the genesis block cannot be dropped"
+    );
+
+    let dropped_hash = starknet.blocks.by_number(BlockNumber(2)).unwrap().block_hash();
+    let dropped = starknet.reorg(1).unwrap();
+
+    assert_eq!(dropped.len(), 1);
+    assert_eq!(starknet.blocks.current_block_number(), BlockNumber(1));
+    assert!(starknet.blocks.by_hash(dropped_hash).is_none());
+    assert!(starknet.state(BlockNumber(2)).is_none());
+    assert!(starknet.transactions.by_hash(&TransactionHash(stark_felt!(2_u8))).is_none());
+    assert_eq!(balance(&starknet).unwrap(), stark_felt!(0x99_u32));
+    assert_eq!(starknet.block_context.block_number, BlockNumber(2));
+
+    // The dropped transaction can be mined again on top of the new chain.
+    starknet.handle_transactions(dropped.into_iter().map(|tx| tx.transaction).collect());
+    assert_eq!(starknet.blocks.current_block_number(), BlockNumber(2));
+    assert_eq!(
+        starknet.transactions.by_hash(&TransactionHash(stark_felt!(2_u8))).unwrap().status,
+        TransactionStatus::AcceptedOnL2
+    );
+    assert_eq!(balance(&starknet).unwrap(), stark_felt!(0x99_u32 * 2));
+}
+
+#[test]
+fn test_tx_log_marks_dropped_transactions() {
+    let path = std::env::temp_dir().join(format!("katana-reorg-log-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut starknet = create_test_starknet_with_config(StarknetConfig {
+        tx_log: Some(path.clone()),
+        ..test_starknet_config()
+    });
+    starknet.generate_pending_block();
+
+    let a = starknet.predeployed_accounts.accounts[0].account_address;
+    starknet.handle_transaction(transfer_transaction(a, stark_felt!("0x1111"), 1, 1));
+    starknet.reorg(1).unwrap();
+
+    let records = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<TransactionLogRecord>(line).unwrap())
+        .collect::<Vec<_>>();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].status, ExecutionStatus::Succeeded);
+    assert_eq!(records[1].transaction_hash, records[0].transaction_hash);
+    assert_eq!(records[1].block_number, records[0].block_number);
+    assert_eq!(records[1].status, ExecutionStatus::Dropped);
+}
+
 // #[test]
 // fn test_function_call() {
 //     let starknet = create_test_starknet();
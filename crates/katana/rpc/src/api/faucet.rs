@@ -0,0 +1,30 @@
+use jsonrpsee::core::Error;
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee::types::error::CallError;
+use jsonrpsee::types::ErrorObject;
+use starknet::core::types::{BroadcastedDeployAccountTransaction, DeployAccountTransactionResult};
+
+#[derive(thiserror::Error, Clone, Copy, Debug)]
+pub enum FaucetApiError {
+    #[error("Invalid deploy account transaction")]
+    InvalidTransaction = 1,
+    #[error("Failed to fund the account")]
+    FailedToDrip = 2,
+    #[error("The account deployment would be rejected or reverted")]
+    DeployRejected = 3,
+}
+
+impl From<FaucetApiError> for Error {
+    fn from(err: FaucetApiError) -> Self {
+        Error::Call(CallError::Custom(ErrorObject::owned(err as i32, err.to_string(), None::<()>)))
+    }
+}
+
+#[rpc(server, namespace = "faucet")]
+pub trait FaucetApi {
+    #[method(name = "dripAndDeployAccount")]
+    async fn drip_and_deploy_account(
+        &self,
+        deploy_account_transaction: BroadcastedDeployAccountTransaction,
+    ) -> Result<DeployAccountTransactionResult, Error>;
+}
@@ -3,12 +3,92 @@ use jsonrpsee::proc_macros::rpc;
 use jsonrpsee::types::error::CallError;
 use jsonrpsee::types::ErrorObject;
 use katana_core::accounts::Account;
+use serde::{Deserialize, Serialize};
+use starknet::core::types::{BlockHashAndNumber, BlockId, BroadcastedTransaction, FieldElement};
+use starknet_api::hash::StarkFelt;
+
+use crate::api::starknet::TransactionResources;
 
 #[derive(thiserror::Error, Clone, Copy, Debug)]
 #[allow(clippy::enum_variant_names)]
 pub enum KatanaApiError {
     #[error("Failed to change next block timestamp")]
     FailedToChangeNextBlockTimestamp = 1,
+    #[error("Transaction failed validation and consumed no resources")]
+    TxnResourcesNotFound = 2,
+    #[error("Failed to mint")]
+    FailedToMint = 3,
+    #[error("Transaction hash not found or transaction failed validation")]
+    TxnProfileNotFound = 4,
+    #[error("Failed to reorganise the chain")]
+    FailedToReorg = 5,
+    #[error("Invalid reorg depth")]
+    InvalidReorgDepth = 6,
+    #[error("The pending block must be mined before reorganising the chain")]
+    PendingTransactions = 7,
+}
+
+/// The data of the error returned when a reorg would drop no block or the genesis block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvalidReorgDepthData {
+    pub depth: u64,
+    /// The number of blocks which can be dropped.
+    pub max_depth: u64,
+}
+
+impl From<InvalidReorgDepthData> for Error {
+    fn from(data: InvalidReorgDepthData) -> Self {
+        let err = KatanaApiError::InvalidReorgDepth;
+        Error::Call(CallError::Custom(ErrorObject::owned(err as i32, err.to_string(), Some(data))))
+    }
+}
+
+/// A balance credited to an address in every fee token outside of any transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mint {
+    pub address: FieldElement,
+    pub amount: FieldElement,
+    /// The number of transactions of the block executed before the mint.
+    pub transaction_index: u64,
+}
+
+impl From<katana_core::backend::block::Mint> for Mint {
+    fn from(mint: katana_core::backend::block::Mint) -> Self {
+        Self {
+            address: (*mint.address.0.key()).into(),
+            amount: StarkFelt::from(mint.amount).into(),
+            transaction_index: mint.transaction_index as u64,
+        }
+    }
+}
+
+/// The Cairo steps spent in a stack of functions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileSample {
+    pub stack: Vec<String>,
+    pub steps: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionProfile {
+    pub total_steps: usize,
+    pub samples: Vec<ProfileSample>,
+    /// The samples in the folded stacks format read by flamegraph tools.
+    pub folded: String,
+}
+
+impl From<katana_core::backend::profile::TransactionProfile> for TransactionProfile {
+    fn from(profile: katana_core::backend::profile::TransactionProfile) -> Self {
+        Self {
+            total_steps: profile.total_steps(),
+            folded: profile.to_folded(),
+            samples: profile
+                .samples
+                .into_iter()
+                .map(|s| ProfileSample { stack: s.stack, steps: s.steps })
+                .collect(),
+        }
+    }
 }
 
 impl From<KatanaApiError> for Error {
@@ -33,4 +113,35 @@ pub trait KatanaApi {
 
     #[method(name = "predeployedAccounts")]
     async fn predeployed_accounts(&self) -> Result<Vec<Account>, Error>;
+
+    #[method(name = "mint")]
+    async fn mint(&self, address: FieldElement, amount: FieldElement) -> Result<(), Error>;
+
+    #[method(name = "transactionResources")]
+    async fn transaction_resources(
+        &self,
+        transaction_hash: FieldElement,
+    ) -> Result<TransactionResources, Error>;
+
+    /// Returns the Cairo steps spent in each stack of functions of an executed transaction, traced
+    /// from the Cairo VM.
+    #[method(name = "profileTransaction")]
+    async fn profile_transaction(
+        &self,
+        transaction_hash: FieldElement,
+    ) -> Result<TransactionProfile, Error>;
+
+    /// Returns the balances minted in a block with `katana_mint` or the faucet, in order.
+    #[method(name = "blockMints")]
+    async fn block_mints(&self, block_id: BlockId) -> Result<Vec<Mint>, Error>;
+
+    /// Drops the latest `depth` blocks and rolls the state back, then mines the transactions of
+    /// the dropped blocks again if `remine_dropped` is set, followed by `replacement_txs`.
+    #[method(name = "reorg")]
+    async fn reorg(
+        &self,
+        depth: u64,
+        replacement_txs: Option<Vec<BroadcastedTransaction>>,
+        remine_dropped: Option<bool>,
+    ) -> Result<BlockHashAndNumber, Error>;
 }
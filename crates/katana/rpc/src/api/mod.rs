@@ -1,2 +1,4 @@
+pub mod faucet;
 pub mod katana;
 pub mod starknet;
+pub mod starknet_v0_4;
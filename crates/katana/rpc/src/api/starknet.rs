@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use jsonrpsee::core::Error;
 use jsonrpsee::proc_macros::rpc;
 use jsonrpsee::types::error::{CallError, ErrorObject};
@@ -10,13 +12,148 @@ use starknet::core::types::{
     ContractClass, DeclareTransactionResult, DeployAccountTransactionResult, EventFilterWithPage,
     EventsPage, FeeEstimate, FieldElement, FunctionCall, InvokeTransactionResult,
     MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs, MaybePendingTransactionReceipt,
-    StateUpdate, Transaction,
+    StateUpdate, SyncStatusType, Transaction, TransactionStatus,
 };
 
 #[serde_as]
 #[derive(Serialize, Deserialize)]
 pub struct Felt(#[serde_as(as = "UfeHex")] pub FieldElement);
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResources {
+    pub steps: u64,
+    pub builtin_instance_counter: BTreeMap<String, u64>,
+    pub l1_gas_usage: u64,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeeBreakdown {
+    #[serde_as(as = "UfeHex")]
+    pub gas_price: FieldElement,
+    #[serde_as(as = "UfeHex")]
+    pub l1_gas_fee: FieldElement,
+    #[serde_as(as = "UfeHex")]
+    pub computation_fee: FieldElement,
+    #[serde_as(as = "UfeHex")]
+    pub actual_fee: FieldElement,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TransactionResources {
+    pub execution_resources: ExecutionResources,
+    pub fee: FeeBreakdown,
+}
+
+impl From<katana_core::backend::transaction::TransactionResources> for TransactionResources {
+    fn from(value: katana_core::backend::transaction::TransactionResources) -> Self {
+        let felt = |value: u128| FieldElement::from_byte_slice_be(&value.to_be_bytes()).unwrap();
+        let resources = value.execution_resources;
+
+        Self {
+            execution_resources: ExecutionResources {
+                steps: resources.steps as u64,
+                builtin_instance_counter: resources
+                    .builtin_instance_counter
+                    .into_iter()
+                    .map(|(name, count)| (name, count as u64))
+                    .collect(),
+                l1_gas_usage: resources.l1_gas_usage as u64,
+            },
+            fee: FeeBreakdown {
+                gas_price: felt(value.fee.gas_price),
+                l1_gas_fee: felt(value.fee.l1_gas_fee),
+                computation_fee: felt(value.fee.computation_fee),
+                actual_fee: felt(value.fee.actual_fee),
+            },
+        }
+    }
+}
+
+/// A transaction receipt extended with the resources consumed by the transaction.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TransactionReceiptWithResources {
+    #[serde(flatten)]
+    pub receipt: MaybePendingTransactionReceipt,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub execution_resources: Option<ExecutionResources>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_breakdown: Option<FeeBreakdown>,
+}
+
+/// The finality of a transaction, as returned by `starknet_getTransactionStatus`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TransactionFinalityStatus {
+    Received,
+    Rejected,
+    AcceptedOnL2,
+    AcceptedOnL1,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TransactionExecutionStatus {
+    Succeeded,
+    Reverted,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionStatusResult {
+    pub finality_status: TransactionFinalityStatus,
+    /// Not set for transactions which were not executed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub execution_status: Option<TransactionExecutionStatus>,
+}
+
+impl From<&katana_core::backend::transaction::StarknetTransaction> for TransactionStatusResult {
+    fn from(transaction: &katana_core::backend::transaction::StarknetTransaction) -> Self {
+        if transaction.is_queued() {
+            return Self {
+                finality_status: TransactionFinalityStatus::Received,
+                execution_status: None,
+            };
+        }
+
+        // Transactions which failed validation have no execution info.
+        let Some(execution_info) = &transaction.execution_info else {
+            return Self {
+                finality_status: TransactionFinalityStatus::Rejected,
+                execution_status: None,
+            };
+        };
+
+        let finality_status = match transaction.status {
+            TransactionStatus::AcceptedOnL1 => TransactionFinalityStatus::AcceptedOnL1,
+            _ => TransactionFinalityStatus::AcceptedOnL2,
+        };
+
+        let execution_status = if execution_info.revert_error.is_some() {
+            TransactionExecutionStatus::Reverted
+        } else {
+            TransactionExecutionStatus::Succeeded
+        };
+
+        Self { finality_status, execution_status: Some(execution_status) }
+    }
+}
+
+/// A message sent from L1 to a contract on L2.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MsgFromL1 {
+    /// The Ethereum address of the sender.
+    #[serde_as(as = "UfeHex")]
+    pub from_address: FieldElement,
+    #[serde_as(as = "UfeHex")]
+    pub to_address: FieldElement,
+    /// The selector of the L1 handler consuming the message.
+    #[serde_as(as = "UfeHex")]
+    pub entry_point_selector: FieldElement,
+    #[serde_as(as = "Vec<UfeHex>")]
+    pub payload: Vec<FieldElement>,
+}
+
 #[derive(thiserror::Error, Clone, Copy, Debug)]
 pub enum StarknetApiError {
     #[error("Failed to write transaction")]
@@ -45,6 +182,12 @@ pub enum StarknetApiError {
     ContractError = 40,
     #[error("Invalid contract class")]
     InvalidContractClass = 50,
+    #[error("Class already declared")]
+    ClassAlreadyDeclared = 51,
+    #[error("Compilation failed")]
+    CompilationFailed = 56,
+    #[error("The compiled class hash did not match the one supplied in the transaction")]
+    CompiledClassHashMismatch = 60,
     #[error("Too many storage keys requested")]
     ProofLimitExceeded = 10000,
     #[error("Too many keys provided in a filter")]
@@ -63,11 +206,50 @@ impl From<StarknetApiError> for Error {
     }
 }
 
+/// The data of the error returned when one of the transactions of a fee estimation fails.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionExecutionErrorData {
+    /// The index of the failing transaction in the request.
+    pub transaction_index: usize,
+    pub execution_error: String,
+}
+
+impl From<TransactionExecutionErrorData> for Error {
+    fn from(data: TransactionExecutionErrorData) -> Self {
+        let err = StarknetApiError::ContractError;
+        Error::Call(CallError::Custom(ErrorObject::owned(err as i32, err.to_string(), Some(data))))
+    }
+}
+
+/// The data of the invalid contract class error returned when a declared class doesn't hash to
+/// the declared class hash.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClassHashMismatchData {
+    #[serde_as(as = "UfeHex")]
+    pub declared_class_hash: FieldElement,
+    #[serde_as(as = "UfeHex")]
+    pub actual_class_hash: FieldElement,
+}
+
+impl From<ClassHashMismatchData> for Error {
+    fn from(data: ClassHashMismatchData) -> Self {
+        let err = StarknetApiError::InvalidContractClass;
+        Error::Call(CallError::Custom(ErrorObject::owned(err as i32, err.to_string(), Some(data))))
+    }
+}
+
 #[rpc(server, namespace = "starknet")]
 pub trait StarknetApi {
+    #[method(name = "specVersion")]
+    async fn spec_version(&self) -> Result<String, Error>;
+
     #[method(name = "chainId")]
     async fn chain_id(&self) -> Result<String, Error>;
 
+    #[method(name = "syncing")]
+    async fn syncing(&self) -> Result<SyncStatusType, Error>;
+
     #[method(name = "getNonce")]
     async fn nonce(&self, block_id: BlockId, contract_address: FieldElement)
         -> Result<Felt, Error>;
@@ -117,7 +299,7 @@ pub trait StarknetApi {
     async fn transaction_receipt(
         &self,
         transaction_hash: FieldElement,
-    ) -> Result<MaybePendingTransactionReceipt, Error>;
+    ) -> Result<TransactionReceiptWithResources, Error>;
 
     #[method(name = "getClassHashAt")]
     async fn class_hash_at(
@@ -146,6 +328,13 @@ pub trait StarknetApi {
         block_id: BlockId,
     ) -> Result<Vec<FeeEstimate>, Error>;
 
+    #[method(name = "estimateMessageFee")]
+    async fn estimate_message_fee(
+        &self,
+        message: MsgFromL1,
+        block_id: BlockId,
+    ) -> Result<FeeEstimate, Error>;
+
     #[method(name = "call")]
     async fn call(&self, request: FunctionCall, block_id: BlockId) -> Result<Vec<Felt>, Error>;
 
@@ -0,0 +1,23 @@
+use jsonrpsee::core::Error;
+use jsonrpsee::proc_macros::rpc;
+use serde_json::Value;
+use starknet::core::types::FieldElement;
+
+use crate::api::starknet::TransactionStatusResult;
+
+/// The methods of the v0.4 spec whose output differs from v0.3, or which are new in v0.4. The
+/// other methods are served by [StarknetApiServer](crate::api::starknet::StarknetApiServer).
+#[rpc(server, namespace = "starknet_v0_4")]
+pub trait StarknetApiV0_4 {
+    #[method(name = "specVersion")]
+    async fn spec_version(&self) -> Result<String, Error>;
+
+    #[method(name = "getTransactionReceipt")]
+    async fn transaction_receipt(&self, transaction_hash: FieldElement) -> Result<Value, Error>;
+
+    #[method(name = "getTransactionStatus")]
+    async fn transaction_status(
+        &self,
+        transaction_hash: FieldElement,
+    ) -> Result<TransactionStatusResult, Error>;
+}
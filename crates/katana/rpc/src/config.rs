@@ -1,7 +1,36 @@
+use std::net::IpAddr;
+
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub port: u16,
     pub host: String,
+    /// Origins allowed to make cross-origin requests. Any origin is allowed if empty.
+    pub allowed_origins: Vec<String>,
+    /// Credentials required from clients. The server is open to anyone if `None`.
+    pub auth: Option<AuthConfig>,
+    /// Maximum number of requests a client can make per minute.
+    ///
+    /// Clients are identified by the `X-Forwarded-For` or `X-Real-IP` header set by the
+    /// `trusted_proxies`. All the clients share the limit if there is none.
+    pub rate_limit: Option<u32>,
+    /// Reverse proxies the server is only reachable through, whose forwarding headers identify
+    /// the clients they connect for. The headers can be spoofed if the server is reachable
+    /// directly.
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Don't expose the `katana_*` dev methods.
+    pub disable_dev_api: bool,
+    /// Expose the `faucet_*` methods and the `POST /faucet` route.
+    pub faucet: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum AuthConfig {
+    /// Requests must carry the key in an `Authorization: Bearer <key>` header.
+    ApiKey(String),
+    /// Requests must carry a HS256 JSON Web Token signed with the secret in an
+    /// `Authorization: Bearer <token>` header. The token is rejected once its `exp` claim, if any,
+    /// is in the past.
+    Jwt(String),
 }
 
 impl ServerConfig {
@@ -9,3 +38,18 @@ impl ServerConfig {
         format!("{}:{}", self.host, self.port)
     }
 }
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 5050,
+            host: "0.0.0.0".into(),
+            allowed_origins: Vec::new(),
+            auth: None,
+            rate_limit: None,
+            trusted_proxies: Vec::new(),
+            disable_dev_api: false,
+            faucet: false,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use jsonrpsee::core::{async_trait, Error};
+use katana_core::constants::DEFAULT_PREFUNDED_ACCOUNT_BALANCE;
+use katana_core::sequencer::Sequencer;
+use katana_core::sequencer_error::SequencerError;
+use katana_core::utils::starkfelt_to_u128;
+use katana_core::utils::transaction::broadcasted_deploy_account_rpc_to_inner;
+use starknet::core::types::{
+    BroadcastedDeployAccountTransaction, DeployAccountTransactionResult, FieldElement,
+};
+
+use crate::api::faucet::{FaucetApiError, FaucetApiServer};
+
+pub struct FaucetApi<S> {
+    sequencer: S,
+}
+
+impl<S> FaucetApi<S>
+where
+    S: Sequencer + Send + 'static,
+{
+    pub fn new(sequencer: S) -> Self {
+        Self { sequencer }
+    }
+}
+
+#[async_trait]
+impl<S> FaucetApiServer for FaucetApi<S>
+where
+    S: Sequencer + Send + Sync + 'static,
+{
+    async fn drip_and_deploy_account(
+        &self,
+        deploy_account_transaction: BroadcastedDeployAccountTransaction,
+    ) -> Result<DeployAccountTransactionResult, Error> {
+        let chain_id = FieldElement::from_hex_be(&self.sequencer.chain_id().await.as_hex())
+            .map_err(|_| Error::from(FaucetApiError::InvalidTransaction))?;
+
+        let transaction =
+            broadcasted_deploy_account_rpc_to_inner(deploy_account_transaction, chain_id)
+                .map_err(|_| Error::from(FaucetApiError::InvalidTransaction))?;
+
+        // Accounts are funded with the same balance as the predeployed ones.
+        let balance = starkfelt_to_u128(*DEFAULT_PREFUNDED_ACCOUNT_BALANCE)
+            .map_err(|_| Error::from(FaucetApiError::FailedToDrip))?;
+
+        let (transaction_hash, contract_address) =
+            self.sequencer.drip_and_deploy_account(transaction, balance).await.map_err(|err| {
+                match err {
+                    SequencerError::TransactionExecution(_)
+                    | SequencerError::TransactionReverted(_) => {
+                        Error::from(FaucetApiError::DeployRejected)
+                    }
+                    _ => Error::from(FaucetApiError::FailedToDrip),
+                }
+            })?;
+
+        Ok(DeployAccountTransactionResult {
+            transaction_hash: transaction_hash.0.into(),
+            contract_address: (*contract_address.0.key()).into(),
+        })
+    }
+}
@@ -1,8 +1,21 @@
+use blockifier::transaction::transaction_execution::Transaction;
 use jsonrpsee::core::{async_trait, Error};
 use katana_core::accounts::Account;
+use katana_core::backend::ReorgTransaction;
 use katana_core::sequencer::Sequencer;
+use katana_core::sequencer_error::SequencerError;
+use katana_core::utils::starkfelt_to_u128;
+use starknet::core::types::{BlockHashAndNumber, BlockId, BroadcastedTransaction, FieldElement};
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::{StarkFelt, StarkHash};
+use starknet_api::patricia_key;
+use starknet_api::transaction::TransactionHash;
 
-use crate::api::katana::{KatanaApiError, KatanaApiServer};
+use crate::api::katana::{
+    InvalidReorgDepthData, KatanaApiError, KatanaApiServer, Mint, TransactionProfile,
+};
+use crate::api::starknet::{StarknetApiError, TransactionResources};
+use crate::starknet::{broadcasted_to_account_transaction, declare_error};
 
 pub struct KatanaApi<S> {
     sequencer: S,
@@ -52,4 +65,95 @@ where
     async fn predeployed_accounts(&self) -> Result<Vec<Account>, Error> {
         Ok(self.sequencer.starknet().await.predeployed_accounts.accounts.clone())
     }
+
+    async fn mint(&self, address: FieldElement, amount: FieldElement) -> Result<(), Error> {
+        let amount = starkfelt_to_u128(amount.into())
+            .map_err(|_| Error::from(KatanaApiError::FailedToMint))?;
+
+        self.sequencer
+            .mut_starknet()
+            .await
+            .mint(ContractAddress(patricia_key!(address)), amount)
+            .map_err(|_| Error::from(KatanaApiError::FailedToMint))
+    }
+
+    async fn transaction_resources(
+        &self,
+        transaction_hash: FieldElement,
+    ) -> Result<TransactionResources, Error> {
+        let hash = TransactionHash(StarkFelt::from(transaction_hash));
+        match self.sequencer.transaction_resources(&hash).await {
+            Ok(Some(resources)) => Ok(TransactionResources::from(resources)),
+            Ok(None) => Err(Error::from(KatanaApiError::TxnResourcesNotFound)),
+            Err(SequencerError::TxnNotFound(_)) => {
+                Err(Error::from(StarknetApiError::TxnHashNotFound))
+            }
+            Err(_) => Err(Error::from(StarknetApiError::InternalServerError)),
+        }
+    }
+
+    async fn profile_transaction(
+        &self,
+        transaction_hash: FieldElement,
+    ) -> Result<TransactionProfile, Error> {
+        self.sequencer
+            .transaction_profile(&TransactionHash(StarkFelt::from(transaction_hash)))
+            .await
+            .map(TransactionProfile::from)
+            .ok_or(Error::from(KatanaApiError::TxnProfileNotFound))
+    }
+
+    async fn block_mints(&self, block_id: BlockId) -> Result<Vec<Mint>, Error> {
+        let block = self
+            .sequencer
+            .block(block_id)
+            .await
+            .ok_or(Error::from(StarknetApiError::BlockNotFound))?;
+
+        Ok(block.mints.into_iter().map(Mint::from).collect())
+    }
+
+    async fn reorg(
+        &self,
+        depth: u64,
+        replacement_txs: Option<Vec<BroadcastedTransaction>>,
+        remine_dropped: Option<bool>,
+    ) -> Result<BlockHashAndNumber, Error> {
+        let chain_id = FieldElement::from_hex_be(&self.sequencer.chain_id().await.as_hex())
+            .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
+
+        let transactions = replacement_txs
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tx| {
+                let (transaction, sierra_class) = broadcasted_to_account_transaction(tx, chain_id)?;
+                Ok(ReorgTransaction {
+                    transaction: Transaction::AccountTransaction(transaction),
+                    sierra_class,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        self.sequencer
+            .reorg(depth, remine_dropped.unwrap_or_default(), transactions)
+            .await
+            .map_err(|err| match err {
+                SequencerError::InvalidReorgDepth { depth, max } => {
+                    Error::from(InvalidReorgDepthData { depth, max_depth: max })
+                }
+                SequencerError::PendingTransactions => {
+                    Error::from(KatanaApiError::PendingTransactions)
+                }
+                err @ SequencerError::ClassAlreadyDeclared(_) => declare_error(err),
+                _ => Error::from(KatanaApiError::FailedToReorg),
+            })?;
+
+        let (block_hash, block_number) = self
+            .sequencer
+            .block_hash_and_number()
+            .await
+            .ok_or(Error::from(StarknetApiError::NoBlocks))?;
+
+        Ok(BlockHashAndNumber { block_hash: block_hash.0.into(), block_number: block_number.0 })
+    }
 }
@@ -1,14 +1,17 @@
 mod api;
 pub mod config;
+mod faucet;
 mod katana;
+mod middleware;
 mod starknet;
-mod utils;
+mod starknet_v0_4;
 
 use std::net::SocketAddr;
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use config::ServerConfig;
+use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use hyper::Method;
 use jsonrpsee::server::logger::{Logger, MethodKind, TransportProtocol};
 use jsonrpsee::server::middleware::proxy_get_request::ProxyGetRequestLayer;
@@ -17,40 +20,109 @@ use jsonrpsee::tracing::debug;
 use jsonrpsee::types::Params;
 use jsonrpsee::RpcModule;
 use katana_core::sequencer::Sequencer;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, CorsLayer};
 
+use crate::api::faucet::FaucetApiServer;
 use crate::api::katana::KatanaApiServer;
 use crate::api::starknet::StarknetApiServer;
+use crate::api::starknet_v0_4::StarknetApiV0_4Server;
+pub use crate::faucet::FaucetApi;
 pub use crate::katana::KatanaApi;
+use crate::middleware::{
+    AuthLayer, ProxyPostRequestLayer, RateLimitLayer, RateLimiter, SpecVersionLayer,
+};
 pub use crate::starknet::StarknetApi;
+use crate::starknet_v0_4::StarknetApiV0_4;
+
+/// The spec versions served under `/rpc/<version>`, along with the namespace of the methods they
+/// override and the v0.3 methods they share, if not all of them. Requests to `/` are served by the
+/// v0.3 methods.
+pub const SPEC_VERSIONS: [(&str, &str, Option<&[&str]>); 2] =
+    [("v0.3", "starknet", None), ("v0.4", "starknet_v0_4", Some(&V0_4_SHARED_METHODS))];
+
+/// The v0.3 methods whose input and output are unchanged in v0.4. The fee estimates, events,
+/// blocks and transactions changed shape in v0.4, so the methods returning them aren't served
+/// until they are adapted.
+const V0_4_SHARED_METHODS: [&str; 15] = [
+    "addDeclareTransaction",
+    "addDeployAccountTransaction",
+    "addInvokeTransaction",
+    "blockHashAndNumber",
+    "blockNumber",
+    "call",
+    "chainId",
+    "getBlockTransactionCount",
+    "getClass",
+    "getClassAt",
+    "getClassHashAt",
+    "getNonce",
+    "getStateUpdate",
+    "getStorageAt",
+    "syncing",
+];
 
 pub async fn spawn<S>(
     katana_api: KatanaApi<S>,
     starknet_api: StarknetApi<S>,
+    faucet_api: FaucetApi<S>,
     config: ServerConfig,
 ) -> Result<NodeHandle>
 where
-    S: Sequencer + Send + Sync + 'static,
+    S: Sequencer + Clone + Send + Sync + 'static,
 {
     let mut methods = RpcModule::new(());
+    methods.merge(StarknetApiV0_4::new(starknet_api.clone()).into_rpc())?;
     methods.merge(starknet_api.into_rpc())?;
-    methods.merge(katana_api.into_rpc())?;
+    if !config.disable_dev_api {
+        methods.merge(katana_api.into_rpc())?;
+    }
+    if config.faucet {
+        methods.merge(faucet_api.into_rpc())?;
+    }
     methods.register_method("health", |_, _| Ok(serde_json::json!({ "health": true })))?;
 
+    let allowed_origins = if config.allowed_origins.is_empty() {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            config
+                .allowed_origins
+                .iter()
+                .map(|origin| origin.parse::<HeaderValue>())
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+    };
+
+    let spec_versions =
+        SpecVersionLayer::new(SPEC_VERSIONS, methods.method_names().map(String::from));
+
     let cors = CorsLayer::new()
             // Allow `POST` when accessing the resource
             .allow_methods([Method::POST, Method::GET])
-            // Allow requests from any origin
-            .allow_origin(Any)
-            .allow_headers([hyper::header::CONTENT_TYPE]);
+            .allow_origin(allowed_origins)
+            .allow_headers([CONTENT_TYPE, AUTHORIZATION]);
+    let rate_limit = config.rate_limit.map(|max_requests| {
+        let limiter = RateLimiter::new(max_requests, Duration::from_secs(60));
+        RateLimitLayer::new(limiter, config.trusted_proxies.clone())
+    });
+
     let middleware = tower::ServiceBuilder::new()
         .layer(cors)
+        .option_layer(rate_limit)
+        .option_layer(config.auth.clone().map(AuthLayer::new))
         .layer(ProxyGetRequestLayer::new("/", "health")?)
+        .option_layer(
+            config
+                .faucet
+                .then(|| ProxyPostRequestLayer::new("/faucet", "faucet_dripAndDeployAccount")),
+        )
+        .layer(spec_versions)
         .timeout(Duration::from_secs(2));
 
     let server = ServerBuilder::new()
         .set_logger(RpcLogger)
         .set_host_filtering(AllowHosts::Any)
+        .batch_requests_supported(true)
         .set_middleware(middleware)
         .build(config.addr())
         .await?;
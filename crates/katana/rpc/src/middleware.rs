@@ -0,0 +1,727 @@
+//! HTTP middlewares guarding access to the RPC server.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use hyper::body::HttpBody;
+use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, UPGRADE};
+use hyper::{Body, HeaderMap, Method, Request, Response, StatusCode, Uri};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::{json, Value};
+use subtle::ConstantTimeEq;
+use tower::{Layer, Service};
+
+use crate::config::AuthConfig;
+
+/// Upper bound on the number of clients tracked by the [RateLimiter].
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Same as the default maximum request size of the JSON-RPC server.
+const MAX_REQUEST_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// The namespace of the methods served on `/`.
+const DEFAULT_NAMESPACE: &str = "starknet";
+
+/// Method names starting with `rpc.` are reserved by JSON-RPC, so this one is never registered
+/// and calls to it fail with a "Method not found" error.
+const UNAVAILABLE_METHOD: &str = "rpc.unavailable";
+
+type ResponseFuture<E> = Pin<Box<dyn Future<Output = Result<Response<Body>, E>> + Send>>;
+
+/// Rejects requests that don't carry the credentials required by an [AuthConfig].
+#[derive(Debug, Clone)]
+pub struct AuthLayer {
+    auth: Arc<AuthConfig>,
+}
+
+impl AuthLayer {
+    pub fn new(auth: AuthConfig) -> Self {
+        Self { auth: Arc::new(auth) }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = Auth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Auth { inner, auth: self.auth.clone() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Auth<S> {
+    inner: S,
+    auth: Arc<AuthConfig>,
+}
+
+impl<S> Service<Request<Body>> for Auth<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if is_health_check(&request) || self.auth.authorize(request.headers()) {
+            Box::pin(self.inner.call(request))
+        } else {
+            Box::pin(std::future::ready(Ok(error_response(StatusCode::UNAUTHORIZED))))
+        }
+    }
+}
+
+impl AuthConfig {
+    fn authorize(&self, headers: &HeaderMap) -> bool {
+        let Some(token) = bearer_token(headers) else {
+            return false;
+        };
+
+        match self {
+            AuthConfig::ApiKey(key) => key.as_bytes().ct_eq(token.as_bytes()).into(),
+            AuthConfig::Jwt(secret) => verify_jwt(token, secret.as_bytes()),
+        }
+    }
+}
+
+/// Rejects the requests of clients that exceeded the limit of a [RateLimiter].
+///
+/// The RPC server doesn't expose the address of a connection to its middlewares, so clients are
+/// identified by the forwarding headers of the `trusted_proxies` the node is served behind, see
+/// [client_ip]. The requests that don't identify a client share a single limit.
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+    trusted_proxies: Arc<Vec<IpAddr>>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: RateLimiter, trusted_proxies: Vec<IpAddr>) -> Self {
+        Self { limiter: Arc::new(limiter), trusted_proxies: Arc::new(trusted_proxies) }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit { inner, layer: self.clone() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    layer: RateLimitLayer,
+}
+
+impl<S> Service<Request<Body>> for RateLimit<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let client = client_ip(request.headers(), &self.layer.trusted_proxies)
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        if self.layer.limiter.check(client) {
+            Box::pin(self.inner.call(request))
+        } else {
+            Box::pin(std::future::ready(Ok(error_response(StatusCode::TOO_MANY_REQUESTS))))
+        }
+    }
+}
+
+/// Fixed window rate limiter keyed by client IP.
+///
+/// Once [MAX_TRACKED_CLIENTS] clients are tracked, the client whose window started first is
+/// forgotten to make room for a new one.
+#[derive(Debug)]
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    max_clients: usize,
+    clients: Mutex<Clients>,
+}
+
+#[derive(Debug, Default)]
+struct Clients {
+    windows: HashMap<IpAddr, Window>,
+    /// The tracked clients, ordered by the start of their window.
+    by_start: BTreeSet<(Instant, IpAddr)>,
+}
+
+#[derive(Debug)]
+struct Window {
+    started_at: Instant,
+    requests: u32,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            max_clients: MAX_TRACKED_CLIENTS,
+            clients: Mutex::new(Clients::default()),
+        }
+    }
+
+    /// Records a request from `client` and returns whether it is within the limit.
+    pub fn check(&self, client: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().expect("poisoned lock");
+        let Clients { windows, by_start } = &mut *clients;
+
+        if !windows.contains_key(&client) {
+            if windows.len() >= self.max_clients {
+                if let Some((_, oldest)) = by_start.pop_first() {
+                    windows.remove(&oldest);
+                }
+            }
+
+            windows.insert(client, Window { started_at: now, requests: 0 });
+            by_start.insert((now, client));
+        }
+
+        let window = windows.get_mut(&client).expect("client is tracked");
+        if now.duration_since(window.started_at) >= self.window {
+            by_start.remove(&(window.started_at, client));
+            by_start.insert((now, client));
+            *window = Window { started_at: now, requests: 0 };
+        }
+
+        window.requests = window.requests.saturating_add(1);
+        window.requests <= self.max_requests
+    }
+}
+
+/// Serves the methods of a spec version under `/rpc/<version>`.
+///
+/// Each version is associated with a namespace. Calls to a `starknet_<name>` method are routed to
+/// `<namespace>_<name>` if the version overrides that method, to the default method if the version
+/// shares it, and are rejected otherwise. The methods of a versioned namespace are only reachable
+/// through the path of their version.
+#[derive(Debug, Clone)]
+pub struct SpecVersionLayer {
+    routes: Arc<HashMap<String, Arc<Route>>>,
+    default_route: Arc<Route>,
+    versioned_namespaces: Arc<Vec<String>>,
+    methods: Arc<HashSet<String>>,
+}
+
+/// How the methods of a spec version are served.
+#[derive(Debug)]
+struct Route {
+    namespace: String,
+    /// The default methods shared by the version, or `None` if it shares all of them.
+    shared: Option<HashSet<String>>,
+}
+
+impl SpecVersionLayer {
+    /// Creates the layer from `(version, namespace, shared methods)` triples and the names of all
+    /// the methods registered on the server.
+    pub fn new<'a>(
+        versions: impl IntoIterator<Item = (&'a str, &'a str, Option<&'a [&'a str]>)>,
+        methods: impl IntoIterator<Item = String>,
+    ) -> Self {
+        let routes: HashMap<_, _> = versions
+            .into_iter()
+            .map(|(version, namespace, shared)| {
+                let shared = shared.map(|methods| methods.iter().map(|m| m.to_string()).collect());
+                (version.to_string(), Arc::new(Route { namespace: namespace.to_string(), shared }))
+            })
+            .collect();
+
+        let mut versioned_namespaces: Vec<_> = routes
+            .values()
+            .map(|route| route.namespace.clone())
+            .filter(|namespace| namespace != DEFAULT_NAMESPACE)
+            .collect();
+        versioned_namespaces.sort();
+        versioned_namespaces.dedup();
+
+        Self {
+            routes: Arc::new(routes),
+            default_route: Arc::new(Route {
+                namespace: DEFAULT_NAMESPACE.to_string(),
+                shared: None,
+            }),
+            versioned_namespaces: Arc::new(versioned_namespaces),
+            methods: Arc::new(methods.into_iter().collect()),
+        }
+    }
+}
+
+impl<S> Layer<S> for SpecVersionLayer {
+    type Service = SpecVersion<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SpecVersion { inner, layer: self.clone() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpecVersion<S> {
+    inner: S,
+    layer: SpecVersionLayer,
+}
+
+impl<S> Service<Request<Body>> for SpecVersion<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let path = request.uri().path();
+        let route = match path.strip_prefix("/rpc/") {
+            Some(version) => match self.layer.routes.get(version.trim_end_matches('/')) {
+                Some(route) => route.clone(),
+                None => {
+                    return Box::pin(std::future::ready(Ok(error_response(StatusCode::NOT_FOUND))));
+                }
+            },
+            // WebSocket upgrades are served as is.
+            None if path == "/" && request.method() == Method::POST => {
+                self.layer.default_route.clone()
+            }
+            None => return Box::pin(self.inner.call(request)),
+        };
+
+        let layer = self.layer.clone();
+        // The service that was polled ready must be the one called.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+
+            let body = match read_body(body).await {
+                Ok(body) => body,
+                Err(status) => return Ok(error_response(status)),
+            };
+            let body = route_methods(&body, &route, &layer.versioned_namespaces, &layer.methods)
+                .unwrap_or(body);
+
+            parts.uri = Uri::from_static("/");
+            parts.headers.insert(CONTENT_LENGTH, body.len().into());
+
+            inner.call(Request::from_parts(parts, Body::from(body))).await
+        })
+    }
+}
+
+/// Proxies `POST /path` requests to calls of a JSON-RPC method, whose single parameter is the
+/// body of the request.
+///
+/// Responds with the result of the call, or with its error and a `400 Bad Request` status.
+#[derive(Debug, Clone)]
+pub struct ProxyPostRequestLayer {
+    path: Arc<str>,
+    method: Arc<str>,
+}
+
+impl ProxyPostRequestLayer {
+    pub fn new(path: &str, method: &str) -> Self {
+        Self { path: Arc::from(path), method: Arc::from(method) }
+    }
+}
+
+impl<S> Layer<S> for ProxyPostRequestLayer {
+    type Service = ProxyPostRequest<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ProxyPostRequest { inner, path: self.path.clone(), method: self.method.clone() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProxyPostRequest<S> {
+    inner: S,
+    path: Arc<str>,
+    method: Arc<str>,
+}
+
+impl<S> Service<Request<Body>> for ProxyPostRequest<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if request.method() != Method::POST || request.uri().path() != &*self.path {
+            return Box::pin(self.inner.call(request));
+        }
+
+        let method = self.method.clone();
+        // The service that was polled ready must be the one called.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+
+            let params = match read_body(body).await.and_then(|body| {
+                serde_json::from_slice::<Value>(&body).map_err(|_| StatusCode::BAD_REQUEST)
+            }) {
+                Ok(params) => params,
+                Err(status) => return Ok(error_response(status)),
+            };
+
+            let call = json!({ "jsonrpc": "2.0", "id": 0, "method": &*method, "params": [params] });
+            let body = call.to_string();
+
+            parts.uri = Uri::from_static("/");
+            parts.headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            parts.headers.insert(CONTENT_LENGTH, body.len().into());
+
+            let response = inner.call(Request::from_parts(parts, Body::from(body))).await?;
+            let (parts, body) = response.into_parts();
+
+            let Ok(body) = hyper::body::to_bytes(body).await else {
+                return Ok(error_response(StatusCode::INTERNAL_SERVER_ERROR));
+            };
+
+            // Responses that aren't from the JSON-RPC server, e.g. a rejected authentication,
+            // are forwarded as is.
+            let Ok(mut output) = serde_json::from_slice::<Value>(&body) else {
+                return Ok(Response::from_parts(parts, Body::from(body)));
+            };
+
+            let (status, output) = match output.get_mut("result") {
+                Some(result) => (StatusCode::OK, result.take()),
+                None => (
+                    StatusCode::BAD_REQUEST,
+                    output.get_mut("error").map(Value::take).unwrap_or_default(),
+                ),
+            };
+
+            Ok(Response::builder()
+                .status(status)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(output.to_string()))
+                .expect("valid response"))
+        })
+    }
+}
+
+async fn read_body(mut body: Body) -> Result<Vec<u8>, StatusCode> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk.map_err(|_| StatusCode::BAD_REQUEST)?);
+        if bytes.len() > MAX_REQUEST_BODY_SIZE {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Renames the `starknet_*` methods called in a request, or in each request of a batch, to the
+/// ones of the route's namespace, and the methods the route doesn't serve to [UNAVAILABLE_METHOD].
+/// Returns `None` if the body isn't valid JSON, in which case it is left for the server to reject.
+fn route_methods(
+    body: &[u8],
+    route: &Route,
+    versioned_namespaces: &[String],
+    methods: &HashSet<String>,
+) -> Option<Vec<u8>> {
+    let mut request: Value = serde_json::from_slice(body).ok()?;
+
+    let calls = match &mut request {
+        Value::Array(calls) => calls.iter_mut().collect(),
+        call => vec![call],
+    };
+
+    for call in calls {
+        let Some(method) = call.get_mut("method") else {
+            continue;
+        };
+        let Some(name) = method.as_str() else {
+            continue;
+        };
+
+        // Versioned methods are only called through the `starknet_` name on their own path.
+        if versioned_namespaces.iter().any(|ns| name.starts_with(&format!("{ns}_"))) {
+            *method = Value::String(UNAVAILABLE_METHOD.to_string());
+            continue;
+        }
+
+        let Some(name) = name.strip_prefix("starknet_") else {
+            continue;
+        };
+
+        let versioned = format!("{}_{name}", route.namespace);
+        if methods.contains(&versioned) {
+            *method = Value::String(versioned);
+        } else if route.shared.as_ref().is_some_and(|shared| !shared.contains(name)) {
+            *method = Value::String(UNAVAILABLE_METHOD.to_string());
+        }
+    }
+
+    serde_json::to_vec(&request).ok()
+}
+
+fn error_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(status.canonical_reason().unwrap_or_default()))
+        .expect("valid response")
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get(AUTHORIZATION)?.to_str().ok()?.strip_prefix("Bearer ").map(str::trim)
+}
+
+/// The IP of the client of a request forwarded by one of the `trusted_proxies`: the last address
+/// of `X-Forwarded-For` that isn't a trusted proxy, or the `X-Real-IP` address. The headers are
+/// only read when the node is served behind trusted proxies, as clients can set them otherwise.
+fn client_ip(headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    if trusted_proxies.is_empty() {
+        return None;
+    }
+
+    let forwarded_for = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|ip| ip.trim().parse::<IpAddr>().ok())
+        .collect::<Vec<_>>();
+
+    forwarded_for
+        .into_iter()
+        .rev()
+        .find(|ip| !trusted_proxies.contains(ip))
+        .or_else(|| headers.get("x-real-ip")?.to_str().ok()?.trim().parse().ok())
+}
+
+/// The `GET /` health check stays open to anyone, unlike WebSocket upgrades on the same path.
+fn is_health_check(request: &Request<Body>) -> bool {
+    request.method() == Method::GET
+        && request.uri().path() == "/"
+        && !request.headers().contains_key(UPGRADE)
+}
+
+/// Verifies the signature of a HS256 JSON Web Token and its `exp` claim, if any.
+fn verify_jwt(token: &str, secret: &[u8]) -> bool {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.required_spec_claims.clear();
+    validation.leeway = 0;
+
+    jsonwebtoken::decode::<Value>(token, &DecodingKey::from_secret(secret), &validation).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use jsonwebtoken::{EncodingKey, Header};
+
+    use super::*;
+
+    fn sign_jwt(claims: Value, secret: &[u8]) -> String {
+        sign_jwt_with(Header::default(), claims, secret)
+    }
+
+    fn sign_jwt_with(header: Header, claims: Value, secret: &[u8]) -> String {
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn api_key_auth() {
+        let auth = AuthConfig::ApiKey("secret".into());
+        assert!(auth.authorize(&bearer("secret")));
+        assert!(!auth.authorize(&bearer("secreT")));
+        assert!(!auth.authorize(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn jwt_auth() {
+        let auth = AuthConfig::Jwt("secret".into());
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        assert!(auth.authorize(&bearer(&sign_jwt(json!({ "sub": "player" }), b"secret"))));
+        assert!(auth.authorize(&bearer(&sign_jwt(json!({ "exp": now + 60 }), b"secret"))));
+        assert!(!auth.authorize(&bearer(&sign_jwt(json!({ "exp": now - 60 }), b"secret"))));
+        assert!(!auth.authorize(&bearer(&sign_jwt(json!({}), b"other secret"))));
+        assert!(!auth.authorize(&bearer("not.a.jwt")));
+
+        let hs384 = Header::new(Algorithm::HS384);
+        assert!(!auth.authorize(&bearer(&sign_jwt_with(hs384, json!({}), b"secret"))));
+
+        let unsigned = format!(
+            "{}.{}.",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"none","typ":"JWT"}"#),
+            URL_SAFE_NO_PAD.encode("{}")
+        );
+        assert!(!auth.authorize(&bearer(&unsigned)));
+    }
+
+    #[test]
+    fn health_check_is_open() {
+        let health = Request::get("/").body(Body::empty()).unwrap();
+        assert!(is_health_check(&health));
+
+        let upgrade = Request::get("/").header(UPGRADE, "websocket").body(Body::empty()).unwrap();
+        assert!(!is_health_check(&upgrade));
+
+        let call = Request::post("/").body(Body::empty()).unwrap();
+        assert!(!is_health_check(&call));
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn rate_limit_per_client() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+
+        assert!(limiter.check(ip("1.1.1.1")));
+        assert!(limiter.check(ip("1.1.1.1")));
+        assert!(!limiter.check(ip("1.1.1.1")));
+        assert!(limiter.check(ip("2.2.2.2")));
+    }
+
+    #[test]
+    fn rate_limit_window_resets() {
+        let limiter = RateLimiter::new(1, Duration::ZERO);
+
+        assert!(limiter.check(ip("1.1.1.1")));
+        assert!(limiter.check(ip("1.1.1.1")));
+    }
+
+    #[test]
+    fn rate_limit_evicts_oldest_client() {
+        let limiter =
+            RateLimiter { max_clients: 2, ..RateLimiter::new(1, Duration::from_secs(60)) };
+
+        assert!(limiter.check(ip("1.1.1.1")));
+        assert!(limiter.check(ip("2.2.2.2")));
+        assert!(limiter.check(ip("3.3.3.3")));
+
+        let clients = limiter.clients.lock().unwrap();
+        assert_eq!(clients.windows.len(), 2);
+        assert_eq!(clients.by_start.len(), 2);
+        assert!(!clients.windows.contains_key(&ip("1.1.1.1")));
+        drop(clients);
+
+        assert!(!limiter.check(ip("3.3.3.3")));
+    }
+
+    #[test]
+    fn route_versioned_methods() {
+        let layer = SpecVersionLayer::new(
+            [("v0.3", "starknet", None), ("v0.4", "starknet_v0_4", Some(&["blockNumber"][..]))],
+            ["starknet_v0_4_getTransactionReceipt".to_string()],
+        );
+        let v0_4 = &layer.routes["v0.4"];
+        let route = |body: Value, route: &Route| -> Value {
+            let routed = route_methods(
+                body.to_string().as_bytes(),
+                route,
+                &layer.versioned_namespaces,
+                &layer.methods,
+            )
+            .unwrap();
+            serde_json::from_slice(&routed).unwrap()
+        };
+
+        let body = json!([
+            { "jsonrpc": "2.0", "id": 0, "method": "starknet_getTransactionReceipt" },
+            { "jsonrpc": "2.0", "id": 1, "method": "starknet_blockNumber" },
+            { "jsonrpc": "2.0", "id": 2, "method": "katana_generateBlock" },
+            { "jsonrpc": "2.0", "id": 3, "method": "starknet_pendingTransactions" },
+            { "jsonrpc": "2.0", "id": 4, "method": "starknet_v0_4_getTransactionReceipt" }
+        ]);
+        let routed = route(body.clone(), v0_4);
+
+        assert_eq!(routed[0]["method"], "starknet_v0_4_getTransactionReceipt");
+        assert_eq!(routed[1]["method"], "starknet_blockNumber");
+        assert_eq!(routed[2]["method"], "katana_generateBlock");
+        assert_eq!(routed[3]["method"], UNAVAILABLE_METHOD);
+        assert_eq!(routed[4]["method"], UNAVAILABLE_METHOD);
+
+        for default in [&layer.routes["v0.3"], &layer.default_route] {
+            let routed = route(body.clone(), default);
+
+            assert_eq!(routed[0]["method"], "starknet_getTransactionReceipt");
+            assert_eq!(routed[3]["method"], "starknet_pendingTransactions");
+            assert_eq!(routed[4]["method"], UNAVAILABLE_METHOD);
+        }
+
+        let body = json!({ "jsonrpc": "2.0", "id": 0, "method": "starknet_getTransactionReceipt" });
+        assert_eq!(route(body, v0_4)["method"], "starknet_v0_4_getTransactionReceipt");
+        assert!(
+            route_methods(b"not json", v0_4, &layer.versioned_namespaces, &layer.methods).is_none()
+        );
+    }
+
+    #[test]
+    fn client_ip_from_trusted_proxy_headers() {
+        let proxy = ip("10.0.0.1");
+        let mut headers = HeaderMap::new();
+        assert_eq!(client_ip(&headers, &[proxy]), None);
+
+        headers.insert("x-real-ip", "2.2.2.2".parse().unwrap());
+        assert_eq!(client_ip(&headers, &[proxy]), Some(ip("2.2.2.2")));
+
+        // The left-most addresses are set by the client, only the one appended by the proxy counts.
+        headers.insert("x-forwarded-for", "1.1.1.1, 3.3.3.3, 10.0.0.2".parse().unwrap());
+        assert_eq!(client_ip(&headers, &[proxy, ip("10.0.0.2")]), Some(ip("3.3.3.3")));
+    }
+
+    #[test]
+    fn client_ip_ignores_headers_without_trusted_proxies() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-real-ip", "2.2.2.2".parse().unwrap());
+        headers.insert("x-forwarded-for", "1.1.1.1".parse().unwrap());
+
+        assert_eq!(client_ip(&headers, &[]), None);
+    }
+}
@@ -1,53 +1,55 @@
 use std::str::FromStr;
 use std::sync::Arc;
 
+use blockifier::execution::contract_class::ContractClass as InnerContractClass;
 use blockifier::state::errors::StateError;
 use blockifier::transaction::account_transaction::AccountTransaction;
-use blockifier::transaction::transactions::DeclareTransaction;
+use blockifier::transaction::transactions::{DeclareTransaction, L1HandlerTransaction};
 use jsonrpsee::core::{async_trait, Error};
-use katana_core::backend::contract::StarknetContract;
+use katana_core::backend::contract::{DeclaredClass, StarknetContract};
 use katana_core::backend::transaction::ExternalFunctionCall;
 use katana_core::constants::SEQUENCER_ADDRESS;
 use katana_core::sequencer::Sequencer;
 use katana_core::sequencer_error::SequencerError;
-use katana_core::util::starkfelt_to_u128;
+use katana_core::utils::contract::{
+    compile_sierra_class, compiled_class_hash, legacy_inner_to_rpc_class,
+    legacy_rpc_to_inner_class, legacy_rpc_to_legacy_class,
+};
+use katana_core::utils::transaction::{
+    broadcasted_deploy_account_rpc_to_inner, broadcasted_invoke_v1_rpc_to_inner,
+    compute_declare_v1_transaction_hash, compute_declare_v2_transaction_hash,
+    convert_inner_to_rpc_tx, declare_v1_rpc_to_inner, declare_v2_rpc_to_inner,
+};
 use starknet::core::types::{
     BlockHashAndNumber, BlockId, BlockStatus, BlockTag, BlockWithTxHashes, BlockWithTxs,
     BroadcastedDeclareTransaction, BroadcastedDeployAccountTransaction,
     BroadcastedInvokeTransaction, BroadcastedTransaction, ContractClass, DeclareTransactionReceipt,
-    DeclareTransactionResult, DeployAccountTransactionReceipt, DeployAccountTransactionResult,
-    DeployTransactionReceipt, EmittedEvent, Event, EventFilterWithPage, EventsPage, FeeEstimate,
-    FieldElement, FunctionCall, InvokeTransactionReceipt, InvokeTransactionResult,
+    DeclareTransactionResult, DeclareTransactionV1, DeclareTransactionV2,
+    DeployAccountTransactionReceipt, DeployAccountTransactionResult, DeployTransactionReceipt,
+    EmittedEvent, Event, EventFilterWithPage, EventsPage, FeeEstimate, FieldElement,
+    FlattenedSierraClass, FunctionCall, InvokeTransactionReceipt, InvokeTransactionResult,
     MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs, MaybePendingTransactionReceipt,
     MsgToL1, PendingBlockWithTxHashes, PendingBlockWithTxs, PendingDeclareTransactionReceipt,
     PendingDeployAccountTransactionReceipt, PendingInvokeTransactionReceipt,
-    PendingTransactionReceipt, StateUpdate, Transaction, TransactionReceipt, TransactionStatus,
-};
-use starknet::core::utils::get_contract_address;
-use starknet_api::core::{
-    ClassHash, CompiledClassHash, ContractAddress, EntryPointSelector, Nonce, PatriciaKey,
+    PendingTransactionReceipt, StateUpdate, SyncStatusType, Transaction, TransactionReceipt,
+    TransactionStatus,
 };
+use starknet_api::core::{ClassHash, ContractAddress, EntryPointSelector, PatriciaKey};
 use starknet_api::hash::{StarkFelt, StarkHash};
+use starknet_api::patricia_key;
 use starknet_api::state::StorageKey;
 use starknet_api::transaction::{
-    Calldata, ContractAddressSalt, DeclareTransactionV0V1, DeclareTransactionV2,
-    DeployAccountTransaction, Fee, InvokeTransaction, InvokeTransactionV1,
-    Transaction as InnerTransaction, TransactionHash, TransactionOutput, TransactionSignature,
-    TransactionVersion,
-};
-use starknet_api::{patricia_key, stark_felt};
-use utils::transaction::{
-    compute_declare_v1_transaction_hash, compute_declare_v2_transaction_hash,
-    compute_invoke_v1_transaction_hash, convert_inner_to_rpc_tx,
+    Calldata, Fee, InvokeTransaction, L1HandlerTransaction as InnerL1HandlerTransaction,
+    Transaction as InnerTransaction, TransactionHash, TransactionOutput,
 };
 
-use crate::api::starknet::{Felt, StarknetApiError, StarknetApiServer};
-use crate::utils;
-use crate::utils::contract::{
-    legacy_inner_to_rpc_class, legacy_rpc_to_inner_class, rpc_to_inner_class,
+use crate::api::starknet::{
+    ClassHashMismatchData, Felt, MsgFromL1, StarknetApiError, StarknetApiServer,
+    TransactionExecutionErrorData, TransactionReceiptWithResources, TransactionResources,
+    TransactionStatusResult,
 };
-use crate::utils::transaction::compute_deploy_account_v1_transaction_hash;
 
+#[derive(Clone)]
 pub struct StarknetApi<S> {
     sequencer: S,
 }
@@ -59,16 +61,41 @@ where
     pub fn new(sequencer: S) -> Self {
         Self { sequencer }
     }
+
+    /// The status of a transaction, served by `starknet_getTransactionStatus` from v0.4 on.
+    pub(crate) async fn transaction_status(
+        &self,
+        transaction_hash: FieldElement,
+    ) -> Result<TransactionStatusResult, Error> {
+        let hash = TransactionHash(StarkFelt::from(transaction_hash));
+
+        let starknet = self.sequencer.starknet().await;
+        let transaction = starknet
+            .transactions
+            .by_hash(&hash)
+            .ok_or(Error::from(StarknetApiError::TxnHashNotFound))?;
+
+        Ok(TransactionStatusResult::from(transaction))
+    }
 }
 #[async_trait]
 impl<S> StarknetApiServer for StarknetApi<S>
 where
     S: Sequencer + Send + Sync + 'static,
 {
+    async fn spec_version(&self) -> Result<String, Error> {
+        Ok("0.3.0".to_string())
+    }
+
     async fn chain_id(&self) -> Result<String, Error> {
         Ok(self.sequencer.chain_id().await.as_hex())
     }
 
+    async fn syncing(&self) -> Result<SyncStatusType, Error> {
+        // Katana is its own sequencer, so there is never anything to sync.
+        Ok(SyncStatusType::NotSyncing)
+    }
+
     async fn nonce(
         &self,
         block_id: BlockId,
@@ -247,7 +274,7 @@ where
     async fn transaction_receipt(
         &self,
         transaction_hash: FieldElement,
-    ) -> Result<MaybePendingTransactionReceipt, Error> {
+    ) -> Result<TransactionReceiptWithResources, Error> {
         let hash = TransactionHash(StarkFelt::from(transaction_hash));
 
         let tx = self
@@ -533,7 +560,18 @@ where
             _ => return Err(Error::from(StarknetApiError::UnsupportedTransactionVersion)),
         };
 
-        Ok(receipt)
+        let resources = self
+            .sequencer
+            .transaction_resources(&hash)
+            .await
+            .map_err(|_| Error::from(StarknetApiError::InternalServerError))?
+            .map(TransactionResources::from);
+
+        Ok(TransactionReceiptWithResources {
+            receipt,
+            execution_resources: resources.as_ref().map(|r| r.execution_resources.clone()),
+            fee_breakdown: resources.map(|r| r.fee),
+        })
     }
 
     async fn class_hash_at(
@@ -707,50 +745,17 @@ where
         let chain_id = FieldElement::from_hex_be(&self.sequencer.chain_id().await.as_hex())
             .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
 
-        let BroadcastedDeployAccountTransaction {
-            class_hash,
-            constructor_calldata,
-            contract_address_salt,
-            max_fee,
-            nonce,
-            signature,
-        } = deploy_account_transaction;
-
-        let contract_address = get_contract_address(
-            contract_address_salt,
-            class_hash,
-            &constructor_calldata,
-            FieldElement::ZERO,
-        );
-
-        let transaction_hash = compute_deploy_account_v1_transaction_hash(
-            contract_address,
-            &constructor_calldata,
-            class_hash,
-            contract_address_salt,
-            max_fee,
-            chain_id,
-            nonce,
-        );
-
-        let transaction = DeployAccountTransaction {
-            signature: TransactionSignature(signature.into_iter().map(|s| s.into()).collect()),
-            contract_address_salt: ContractAddressSalt(StarkFelt::from(contract_address_salt)),
-            constructor_calldata: Calldata(Arc::new(
-                constructor_calldata.into_iter().map(|d| d.into()).collect(),
-            )),
-            class_hash: ClassHash(class_hash.into()),
-            contract_address: ContractAddress(patricia_key!(contract_address)),
-            max_fee: Fee(starkfelt_to_u128(max_fee.into())
-                .map_err(|_| Error::from(StarknetApiError::InternalServerError))?),
-            nonce: Nonce(nonce.into()),
-            transaction_hash: TransactionHash(transaction_hash.into()),
-            version: TransactionVersion(stark_felt!(1_u32)),
-        };
+        let transaction =
+            broadcasted_deploy_account_rpc_to_inner(deploy_account_transaction, chain_id)
+                .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
 
-        self.sequencer.add_deploy_account_transaction(transaction).await;
+        let (transaction_hash, contract_address) =
+            self.sequencer.add_deploy_account_transaction(transaction).await;
 
-        Ok(DeployAccountTransactionResult { transaction_hash, contract_address })
+        Ok(DeployAccountTransactionResult {
+            transaction_hash: transaction_hash.0.into(),
+            contract_address: (*contract_address.0.key()).into(),
+        })
     }
 
     async fn estimate_fee(
@@ -761,171 +766,56 @@ where
         let chain_id = FieldElement::from_hex_be(&self.sequencer.chain_id().await.as_hex())
             .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
 
-        let mut res = Vec::new();
-
-        for r in request {
-            let transaction = match r {
-                BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V1(tx)) => {
-                    let (class_hash, contract) = legacy_rpc_to_inner_class(&tx.contract_class)?;
-
-                    let transaction_hash = compute_declare_v1_transaction_hash(
-                        tx.sender_address,
-                        class_hash,
-                        tx.max_fee,
-                        chain_id,
-                        tx.nonce,
-                    );
-
-                    let transaction = DeclareTransactionV0V1 {
-                        nonce: Nonce(tx.nonce.into()),
-                        class_hash: ClassHash(class_hash.into()),
-                        transaction_hash: TransactionHash(transaction_hash.into()),
-                        sender_address: ContractAddress(patricia_key!(tx.sender_address)),
-                        max_fee: Fee(starkfelt_to_u128(tx.max_fee.into())
-                            .map_err(|_| Error::from(StarknetApiError::InternalServerError))?),
-                        signature: TransactionSignature(
-                            tx.signature.into_iter().map(|e| e.into()).collect(),
-                        ),
-                    };
-
-                    AccountTransaction::Declare(
-                        DeclareTransaction::new(
-                            starknet_api::transaction::DeclareTransaction::V1(transaction),
-                            contract,
-                        )
-                        .map_err(|_| Error::from(StarknetApiError::InternalServerError))?,
-                    )
-                }
-                BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V2(tx)) => {
-                    let (class_hash, contract_class) = rpc_to_inner_class(&tx.contract_class)
-                        .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
-
-                    let transaction_hash = compute_declare_v2_transaction_hash(
-                        tx.sender_address,
-                        class_hash,
-                        tx.max_fee,
-                        chain_id,
-                        tx.nonce,
-                        tx.compiled_class_hash,
-                    );
-
-                    let transaction = DeclareTransactionV2 {
-                        nonce: Nonce(tx.nonce.into()),
-                        class_hash: ClassHash(class_hash.into()),
-                        transaction_hash: TransactionHash(transaction_hash.into()),
-                        sender_address: ContractAddress(patricia_key!(tx.sender_address)),
-                        compiled_class_hash: CompiledClassHash(tx.compiled_class_hash.into()),
-                        max_fee: Fee(starkfelt_to_u128(tx.max_fee.into())
-                            .map_err(|_| Error::from(StarknetApiError::InternalServerError))?),
-                        signature: TransactionSignature(
-                            tx.signature.into_iter().map(|e| e.into()).collect(),
-                        ),
-                    };
-
-                    AccountTransaction::Declare(
-                        DeclareTransaction::new(
-                            starknet_api::transaction::DeclareTransaction::V2(transaction),
-                            contract_class,
-                        )
-                        .map_err(|_| Error::from(StarknetApiError::InternalServerError))?,
-                    )
-                }
-
-                BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(transaction)) => {
-                    let transaction_hash = compute_invoke_v1_transaction_hash(
-                        transaction.sender_address,
-                        &transaction.calldata,
-                        transaction.max_fee,
-                        chain_id,
-                        transaction.nonce,
-                    );
-
-                    let transaction = InvokeTransactionV1 {
-                        transaction_hash: TransactionHash(StarkFelt::from(transaction_hash)),
-                        sender_address: ContractAddress(patricia_key!(transaction.sender_address)),
-                        nonce: Nonce(StarkFelt::from(transaction.nonce)),
-                        calldata: Calldata(Arc::new(
-                            transaction.calldata.into_iter().map(StarkFelt::from).collect(),
-                        )),
-                        max_fee: Fee(starkfelt_to_u128(StarkFelt::from(transaction.max_fee))
-                            .map_err(|_| Error::from(StarknetApiError::InternalServerError))?),
-                        signature: TransactionSignature(
-                            transaction.signature.into_iter().map(StarkFelt::from).collect(),
-                        ),
-                    };
+        let transactions = request
+            .into_iter()
+            .map(|tx| broadcasted_to_account_transaction(tx, chain_id).map(|(tx, _)| tx))
+            .collect::<Result<Vec<_>, _>>()?;
 
-                    AccountTransaction::Invoke(InvokeTransaction::V1(transaction))
-                }
+        self.sequencer.estimate_fee(transactions, block_id).await.map_err(|e| match e {
+            SequencerError::BlockNotFound(_) | SequencerError::StateNotFound(_) => {
+                Error::from(StarknetApiError::BlockNotFound)
+            }
+            SequencerError::FeeEstimation { index, error } => {
+                Error::from(TransactionExecutionErrorData {
+                    transaction_index: index,
+                    execution_error: error.to_string(),
+                })
+            }
+            _ => Error::from(StarknetApiError::InternalServerError),
+        })
+    }
 
-                BroadcastedTransaction::DeployAccount(BroadcastedDeployAccountTransaction {
-                    max_fee,
-                    signature,
-                    nonce,
-                    contract_address_salt,
-                    constructor_calldata,
-                    class_hash,
-                }) => {
-                    let contract_address = get_contract_address(
-                        contract_address_salt,
-                        class_hash,
-                        &constructor_calldata,
-                        FieldElement::ZERO,
-                    );
-
-                    let transaction_hash = compute_deploy_account_v1_transaction_hash(
-                        contract_address,
-                        &constructor_calldata,
-                        class_hash,
-                        contract_address_salt,
-                        max_fee,
-                        chain_id,
-                        nonce,
-                    );
-
-                    let transaction = DeployAccountTransaction {
-                        signature: TransactionSignature(
-                            signature.into_iter().map(|s| s.into()).collect(),
-                        ),
-                        contract_address_salt: ContractAddressSalt(StarkFelt::from(
-                            contract_address_salt,
-                        )),
-                        constructor_calldata: Calldata(Arc::new(
-                            constructor_calldata.into_iter().map(|d| d.into()).collect(),
-                        )),
-                        class_hash: ClassHash(class_hash.into()),
-                        contract_address: ContractAddress(patricia_key!(contract_address)),
-                        max_fee: Fee(starkfelt_to_u128(max_fee.into())
-                            .map_err(|_| Error::from(StarknetApiError::InternalServerError))?),
-                        nonce: Nonce(nonce.into()),
-                        transaction_hash: TransactionHash(transaction_hash.into()),
-                        version: TransactionVersion(stark_felt!(1_u32)),
-                    };
-
-                    AccountTransaction::DeployAccount(transaction)
-                }
+    async fn estimate_message_fee(
+        &self,
+        message: MsgFromL1,
+        block_id: BlockId,
+    ) -> Result<FeeEstimate, Error> {
+        // The sender of the message is the first argument of the L1 handler.
+        let calldata = std::iter::once(message.from_address)
+            .chain(message.payload)
+            .map(StarkFelt::from)
+            .collect::<Vec<_>>();
 
-                _ => return Err(Error::from(StarknetApiError::UnsupportedTransactionVersion)),
-            };
-
-            let fee_estimate =
-                self.sequencer.estimate_fee(transaction, block_id).await.map_err(|e| match e {
-                    SequencerError::BlockNotFound(_) => {
-                        Error::from(StarknetApiError::BlockNotFound)
-                    }
-                    SequencerError::TransactionExecution(_) => {
-                        Error::from(StarknetApiError::ContractError)
-                    }
-                    _ => Error::from(StarknetApiError::InternalServerError),
-                })?;
-
-            res.push(FeeEstimate {
-                gas_price: fee_estimate.gas_price,
-                overall_fee: fee_estimate.overall_fee,
-                gas_consumed: fee_estimate.gas_consumed,
-            });
-        }
+        let transaction = L1HandlerTransaction {
+            tx: InnerL1HandlerTransaction {
+                contract_address: ContractAddress(patricia_key!(message.to_address)),
+                entry_point_selector: EntryPointSelector(StarkFelt::from(
+                    message.entry_point_selector,
+                )),
+                calldata: Calldata(Arc::new(calldata)),
+                ..Default::default()
+            },
+            // The fee paid on L1 is only known once the message is sent, so it must not limit
+            // the estimation.
+            paid_fee_on_l1: Fee(u128::MAX),
+        };
 
-        Ok(res)
+        self.sequencer.estimate_message_fee(transaction, block_id).await.map_err(|e| match e {
+            SequencerError::BlockNotFound(_) => Error::from(StarknetApiError::BlockNotFound),
+            SequencerError::ContractNotFound(_) => Error::from(StarknetApiError::ContractNotFound),
+            SequencerError::TransactionExecution(_) => Error::from(StarknetApiError::ContractError),
+            _ => Error::from(StarknetApiError::InternalServerError),
+        })
     }
 
     async fn add_declare_transaction(
@@ -934,81 +824,10 @@ where
     ) -> Result<DeclareTransactionResult, Error> {
         let chain_id = FieldElement::from_hex_be(&self.sequencer.chain_id().await.as_hex())
             .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
-        let (transaction_hash, class_hash, transaction, sierra_class) = match declare_transaction {
-            BroadcastedDeclareTransaction::V1(tx) => {
-                let (class_hash, contract) = legacy_rpc_to_inner_class(&tx.contract_class)?;
-
-                let transaction_hash = compute_declare_v1_transaction_hash(
-                    tx.sender_address,
-                    class_hash,
-                    tx.max_fee,
-                    chain_id,
-                    tx.nonce,
-                );
-
-                let transaction = DeclareTransactionV0V1 {
-                    transaction_hash: TransactionHash(transaction_hash.into()),
-                    class_hash: ClassHash(class_hash.into()),
-                    sender_address: ContractAddress(patricia_key!(tx.sender_address)),
-                    nonce: Nonce(tx.nonce.into()),
-                    max_fee: Fee(starkfelt_to_u128(tx.max_fee.into())
-                        .map_err(|_| Error::from(StarknetApiError::InternalServerError))?),
-                    signature: TransactionSignature(
-                        tx.signature.into_iter().map(|e| e.into()).collect(),
-                    ),
-                };
-
-                (
-                    transaction_hash,
-                    class_hash,
-                    DeclareTransaction::new(
-                        starknet_api::transaction::DeclareTransaction::V1(transaction),
-                        contract,
-                    )
-                    .map_err(|_| Error::from(StarknetApiError::InternalServerError))?,
-                    None,
-                )
-            }
-            BroadcastedDeclareTransaction::V2(tx) => {
-                let (class_hash, contract_class) = rpc_to_inner_class(&tx.contract_class)
-                    .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
-
-                let transaction_hash = compute_declare_v2_transaction_hash(
-                    tx.sender_address,
-                    class_hash,
-                    tx.max_fee,
-                    chain_id,
-                    tx.nonce,
-                    tx.compiled_class_hash,
-                );
-
-                let transaction = DeclareTransactionV2 {
-                    nonce: Nonce(tx.nonce.into()),
-                    class_hash: ClassHash(class_hash.into()),
-                    transaction_hash: TransactionHash(transaction_hash.into()),
-                    sender_address: ContractAddress(patricia_key!(tx.sender_address)),
-                    compiled_class_hash: CompiledClassHash(tx.compiled_class_hash.into()),
-                    max_fee: Fee(starkfelt_to_u128(tx.max_fee.into())
-                        .map_err(|_| Error::from(StarknetApiError::InternalServerError))?),
-                    signature: TransactionSignature(
-                        tx.signature.into_iter().map(|e| e.into()).collect(),
-                    ),
-                };
-
-                (
-                    transaction_hash,
-                    class_hash,
-                    DeclareTransaction::new(
-                        starknet_api::transaction::DeclareTransaction::V2(transaction),
-                        contract_class,
-                    )
-                    .map_err(|_| Error::from(StarknetApiError::InternalServerError))?,
-                    Some(tx.contract_class.as_ref().clone()),
-                )
-            }
-        };
+        let (transaction_hash, class_hash, transaction, class) =
+            broadcasted_declare_to_inner(declare_transaction, chain_id)?;
 
-        self.sequencer.add_declare_transaction(transaction, sierra_class).await;
+        self.sequencer.add_declare_transaction(transaction, class).await.map_err(declare_error)?;
 
         Ok(DeclareTransactionResult { transaction_hash, class_hash })
     }
@@ -1022,27 +841,9 @@ where
                 let chain_id = FieldElement::from_hex_be(&self.sequencer.chain_id().await.as_hex())
                     .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
 
-                let transaction_hash = compute_invoke_v1_transaction_hash(
-                    transaction.sender_address,
-                    &transaction.calldata,
-                    transaction.max_fee,
-                    chain_id,
-                    transaction.nonce,
-                );
-
-                let transaction = InvokeTransactionV1 {
-                    transaction_hash: TransactionHash(StarkFelt::from(transaction_hash)),
-                    sender_address: ContractAddress(patricia_key!(transaction.sender_address)),
-                    nonce: Nonce(StarkFelt::from(transaction.nonce)),
-                    calldata: Calldata(Arc::new(
-                        transaction.calldata.into_iter().map(StarkFelt::from).collect(),
-                    )),
-                    max_fee: Fee(starkfelt_to_u128(StarkFelt::from(transaction.max_fee))
-                        .map_err(|_| Error::from(StarknetApiError::InternalServerError))?),
-                    signature: TransactionSignature(
-                        transaction.signature.into_iter().map(StarkFelt::from).collect(),
-                    ),
-                };
+                let transaction = broadcasted_invoke_v1_rpc_to_inner(transaction, chain_id)
+                    .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
+                let transaction_hash = transaction.transaction_hash.0.into();
 
                 self.sequencer.add_invoke_transaction(InvokeTransaction::V1(transaction)).await;
 
@@ -1053,3 +854,148 @@ where
         }
     }
 }
+
+/// Converts a broadcasted declare transaction into an executable one, along with the hashes of
+/// the transaction and of the class, and the definition of the class it declares. The compiled
+/// class hash of a Sierra class is checked against the one of its compilation.
+fn broadcasted_declare_to_inner(
+    declare_transaction: BroadcastedDeclareTransaction,
+    chain_id: FieldElement,
+) -> Result<(FieldElement, FieldElement, DeclareTransaction, DeclaredClass), Error> {
+    match declare_transaction {
+        BroadcastedDeclareTransaction::V1(tx) => {
+            let (class_hash, contract) = legacy_rpc_to_inner_class(&tx.contract_class)?;
+            let legacy_class = legacy_rpc_to_legacy_class(&tx.contract_class)?;
+
+            let transaction_hash = compute_declare_v1_transaction_hash(
+                tx.sender_address,
+                class_hash,
+                tx.max_fee,
+                chain_id,
+                tx.nonce,
+            );
+
+            let transaction = declare_v1_rpc_to_inner(DeclareTransactionV1 {
+                transaction_hash,
+                max_fee: tx.max_fee,
+                signature: tx.signature,
+                nonce: tx.nonce,
+                class_hash,
+                sender_address: tx.sender_address,
+            })
+            .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
+
+            Ok((
+                transaction_hash,
+                class_hash,
+                DeclareTransaction::new(
+                    starknet_api::transaction::DeclareTransaction::V1(transaction),
+                    contract,
+                )
+                .map_err(|_| Error::from(StarknetApiError::InternalServerError))?,
+                DeclaredClass::Legacy(legacy_class),
+            ))
+        }
+        BroadcastedDeclareTransaction::V2(tx) => {
+            let class_hash = tx.contract_class.class_hash();
+            let casm_contract = compile_sierra_class(&tx.contract_class)
+                .map_err(|_| Error::from(StarknetApiError::CompilationFailed))?;
+
+            let compiled_class_hash = compiled_class_hash(&casm_contract)
+                .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
+            if compiled_class_hash != tx.compiled_class_hash {
+                return Err(Error::from(StarknetApiError::CompiledClassHashMismatch));
+            }
+
+            let contract_class = InnerContractClass::V1(
+                casm_contract
+                    .try_into()
+                    .map_err(|_| Error::from(StarknetApiError::InternalServerError))?,
+            );
+
+            let transaction_hash = compute_declare_v2_transaction_hash(
+                tx.sender_address,
+                class_hash,
+                tx.max_fee,
+                chain_id,
+                tx.nonce,
+                tx.compiled_class_hash,
+            );
+
+            let transaction = declare_v2_rpc_to_inner(DeclareTransactionV2 {
+                transaction_hash,
+                max_fee: tx.max_fee,
+                signature: tx.signature,
+                nonce: tx.nonce,
+                class_hash,
+                compiled_class_hash: tx.compiled_class_hash,
+                sender_address: tx.sender_address,
+            })
+            .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
+
+            Ok((
+                transaction_hash,
+                class_hash,
+                DeclareTransaction::new(
+                    starknet_api::transaction::DeclareTransaction::V2(transaction),
+                    contract_class,
+                )
+                .map_err(|_| Error::from(StarknetApiError::InternalServerError))?,
+                DeclaredClass::Sierra(tx.contract_class.as_ref().clone()),
+            ))
+        }
+    }
+}
+
+/// Maps the errors of a declare transaction to the ones of the spec.
+pub(crate) fn declare_error(err: SequencerError) -> Error {
+    match err {
+        SequencerError::ClassAlreadyDeclared(_) => {
+            Error::from(StarknetApiError::ClassAlreadyDeclared)
+        }
+        SequencerError::ClassHashMismatch { declared, actual } => {
+            Error::from(ClassHashMismatchData {
+                declared_class_hash: declared.0.into(),
+                actual_class_hash: actual.0.into(),
+            })
+        }
+        SequencerError::InvalidContractClass(_) => {
+            Error::from(StarknetApiError::InvalidContractClass)
+        }
+        _ => Error::from(StarknetApiError::InternalServerError),
+    }
+}
+
+/// Converts a broadcasted transaction into an executable one, along with the Sierra class it
+/// declares. A declared class is checked as by `starknet_addDeclareTransaction`, except for its
+/// duplicates, which depend on the state it is executed on.
+pub(crate) fn broadcasted_to_account_transaction(
+    transaction: BroadcastedTransaction,
+    chain_id: FieldElement,
+) -> Result<(AccountTransaction, Option<FlattenedSierraClass>), Error> {
+    let mut sierra_class = None;
+
+    let transaction = match transaction {
+        BroadcastedTransaction::Declare(tx) => {
+            let (_, class_hash, transaction, class) = broadcasted_declare_to_inner(tx, chain_id)?;
+            sierra_class = class.verify(ClassHash(class_hash.into())).map_err(declare_error)?;
+            AccountTransaction::Declare(transaction)
+        }
+
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(transaction)) => {
+            AccountTransaction::Invoke(InvokeTransaction::V1(
+                broadcasted_invoke_v1_rpc_to_inner(transaction, chain_id)
+                    .map_err(|_| Error::from(StarknetApiError::InternalServerError))?,
+            ))
+        }
+
+        BroadcastedTransaction::DeployAccount(transaction) => AccountTransaction::DeployAccount(
+            broadcasted_deploy_account_rpc_to_inner(transaction, chain_id)
+                .map_err(|_| Error::from(StarknetApiError::InternalServerError))?,
+        ),
+
+        _ => return Err(Error::from(StarknetApiError::UnsupportedTransactionVersion)),
+    };
+
+    Ok((transaction, sierra_class))
+}
@@ -0,0 +1,60 @@
+use jsonrpsee::core::{async_trait, Error};
+use katana_core::sequencer::Sequencer;
+use serde_json::Value;
+use starknet::core::types::FieldElement;
+
+use crate::api::starknet::{StarknetApiError, StarknetApiServer, TransactionStatusResult};
+use crate::api::starknet_v0_4::StarknetApiV0_4Server;
+use crate::starknet::StarknetApi;
+
+/// Adapts the output of [StarknetApi] to the v0.4 spec.
+pub struct StarknetApiV0_4<S> {
+    inner: StarknetApi<S>,
+}
+
+impl<S> StarknetApiV0_4<S>
+where
+    S: Sequencer + Send + Sync + 'static,
+{
+    pub fn new(inner: StarknetApi<S>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl<S> StarknetApiV0_4Server for StarknetApiV0_4<S>
+where
+    S: Sequencer + Send + Sync + 'static,
+{
+    async fn spec_version(&self) -> Result<String, Error> {
+        Ok("0.4.0".to_string())
+    }
+
+    async fn transaction_receipt(&self, transaction_hash: FieldElement) -> Result<Value, Error> {
+        let status = self.inner.transaction_status(transaction_hash).await?;
+        // Transactions rejected by validation aren't included in a block and have no receipt.
+        if status.execution_status.is_none() {
+            return Err(StarknetApiError::TxnHashNotFound.into());
+        }
+
+        let receipt = StarknetApiServer::transaction_receipt(&self.inner, transaction_hash).await?;
+        let mut receipt = serde_json::to_value(receipt)
+            .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
+        let status = serde_json::to_value(status)
+            .map_err(|_| Error::from(StarknetApiError::InternalServerError))?;
+
+        // Replaces the v0.3 `status` with the v0.4 `execution_status` and `finality_status`.
+        if let (Some(fields), Value::Object(status)) = (receipt.as_object_mut(), status) {
+            fields.remove("status");
+            fields.extend(status);
+        }
+        Ok(receipt)
+    }
+
+    async fn transaction_status(
+        &self,
+        transaction_hash: FieldElement,
+    ) -> Result<TransactionStatusResult, Error> {
+        self.inner.transaction_status(transaction_hash).await
+    }
+}
@@ -1,2 +0,0 @@
-pub mod contract;
-pub mod transaction;
@@ -0,0 +1,116 @@
+use dojo_test_utils::sequencer::{get_default_test_starknet_config, TestSequencer};
+use katana_core::constants::{DEFAULT_ACCOUNT_CONTRACT_CLASS_HASH, FEE_TOKEN_ADDRESS};
+use katana_core::sequencer::SequencerConfig;
+use katana_core::utils::transaction::compute_deploy_account_v1_transaction_hash;
+use serde_json::{json, Value};
+use starknet::accounts::ConnectedAccount;
+use starknet::core::types::{BlockId, BlockTag, FieldElement};
+use starknet::core::utils::{get_contract_address, get_storage_var_address};
+use starknet::providers::Provider;
+use starknet::signers::SigningKey;
+
+/// A v1 deploy account transaction of the default account contract, and the hash and address it
+/// deploys to.
+async fn deploy_account_transaction(
+    sequencer: &TestSequencer,
+    salt: FieldElement,
+) -> (Value, FieldElement, FieldElement) {
+    let provider = sequencer.account().provider().clone();
+
+    let signer = SigningKey::from_secret_scalar(FieldElement::from(0x1337_u32));
+    let class_hash = FieldElement::from(*DEFAULT_ACCOUNT_CONTRACT_CLASS_HASH);
+    let calldata = vec![signer.verifying_key().scalar()];
+    let max_fee = FieldElement::from_hex_be("0x2386f26fc10000").unwrap();
+
+    let address = get_contract_address(salt, class_hash, &calldata, FieldElement::ZERO);
+    let transaction_hash = compute_deploy_account_v1_transaction_hash(
+        address,
+        &calldata,
+        class_hash,
+        salt,
+        max_fee,
+        provider.chain_id().await.unwrap(),
+        FieldElement::ZERO,
+    );
+    let signature = signer.sign(&transaction_hash).unwrap();
+
+    let transaction = json!({
+        "type": "DEPLOY_ACCOUNT",
+        "version": "0x1",
+        "class_hash": format!("{class_hash:#x}"),
+        "contract_address_salt": format!("{salt:#x}"),
+        "constructor_calldata": calldata.iter().map(|c| format!("{c:#x}")).collect::<Vec<_>>(),
+        "max_fee": format!("{max_fee:#x}"),
+        "nonce": "0x0",
+        "signature": [format!("{:#x}", signature.r), format!("{:#x}", signature.s)]
+    });
+
+    (transaction, transaction_hash, address)
+}
+
+#[tokio::test]
+async fn test_faucet_drip_and_deploy_account() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+    let provider = sequencer.account().provider().clone();
+
+    let (transaction, transaction_hash, address) =
+        deploy_account_transaction(&sequencer, FieldElement::from(42_u32)).await;
+
+    let res = reqwest::Client::new()
+        .post(sequencer.url().join("/faucet").unwrap())
+        .header("Content-Type", "application/json")
+        .body(transaction.to_string())
+        .send()
+        .await
+        .unwrap();
+
+    assert!(res.status().is_success());
+
+    let res: Value = serde_json::from_str(&res.text().await.unwrap()).unwrap();
+    assert_eq!(res["transaction_hash"], format!("{transaction_hash:#x}"));
+    assert_eq!(res["contract_address"], format!("{address:#x}"));
+
+    assert_eq!(
+        provider.get_class_hash_at(BlockId::Tag(BlockTag::Latest), address).await.unwrap(),
+        FieldElement::from(*DEFAULT_ACCOUNT_CONTRACT_CLASS_HASH)
+    );
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
+#[tokio::test]
+async fn test_faucet_rejected_deploy_mints_nothing() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+    let provider = sequencer.account().provider().clone();
+
+    let (mut transaction, _, address) =
+        deploy_account_transaction(&sequencer, FieldElement::from(43_u32)).await;
+    transaction["signature"] = json!(["0x1", "0x2"]);
+
+    let res = reqwest::Client::new()
+        .post(sequencer.url().join("/faucet").unwrap())
+        .header("Content-Type", "application/json")
+        .body(transaction.to_string())
+        .send()
+        .await
+        .unwrap();
+
+    assert!(!res.status().is_success());
+
+    let res: Value = serde_json::from_str(&res.text().await.unwrap()).unwrap();
+    assert_eq!(res["code"], 3);
+
+    let balance = provider
+        .get_storage_at(
+            (*FEE_TOKEN_ADDRESS).into(),
+            get_storage_var_address("ERC20_balances", &[address]).unwrap(),
+            BlockId::Tag(BlockTag::Pending),
+        )
+        .await
+        .unwrap();
+    assert_eq!(balance, FieldElement::ZERO);
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
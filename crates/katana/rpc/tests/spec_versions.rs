@@ -0,0 +1,221 @@
+use dojo_test_utils::sequencer::{get_default_test_starknet_config, TestSequencer};
+use katana_core::constants::FEE_TOKEN_ADDRESS;
+use katana_core::sequencer::SequencerConfig;
+use serde_json::{json, Value};
+use starknet::accounts::{Account, Call};
+use starknet::core::types::FieldElement;
+use starknet::core::utils::get_selector_from_name;
+
+async fn post(sequencer: &TestSequencer, path: &str, body: Value) -> Value {
+    let url = sequencer.url().join(path).unwrap();
+    let res = reqwest::Client::new()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap();
+    serde_json::from_str(&res.text().await.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_batch_request() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+
+    let res = post(
+        &sequencer,
+        "/",
+        json!([
+            { "jsonrpc": "2.0", "id": 0, "method": "starknet_blockNumber", "params": [] },
+            { "jsonrpc": "2.0", "id": 1, "method": "starknet_chainId", "params": [] }
+        ]),
+    )
+    .await;
+
+    let responses = res.as_array().expect("batch response must be an array");
+    assert_eq!(responses.len(), 2);
+    assert!(responses.iter().all(|r| r.get("result").is_some()));
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
+#[tokio::test]
+async fn test_versioned_receipts() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+
+    let res = sequencer
+        .account()
+        .execute(vec![Call {
+            to: (*FEE_TOKEN_ADDRESS).into(),
+            selector: get_selector_from_name("transfer").unwrap(),
+            calldata: vec![FieldElement::ONE, FieldElement::from(100_u32), FieldElement::ZERO],
+        }])
+        .send()
+        .await
+        .unwrap();
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "starknet_getTransactionReceipt",
+        "params": [format!("{:#x}", res.transaction_hash)]
+    });
+
+    for path in ["/", "/rpc/v0.3"] {
+        let receipt = post(&sequencer, path, request.clone()).await;
+        assert_eq!(receipt["result"]["status"], "ACCEPTED_ON_L2", "{path}");
+        assert!(receipt["result"].get("execution_status").is_none(), "{path}");
+    }
+
+    let receipt = post(&sequencer, "/rpc/v0.4", request.clone()).await;
+    assert!(receipt["result"].get("status").is_none());
+    assert_eq!(receipt["result"]["execution_status"], "SUCCEEDED");
+    assert_eq!(receipt["result"]["finality_status"], "ACCEPTED_ON_L2");
+
+    // Methods not overridden by a version are served by the default ones.
+    let res = post(
+        &sequencer,
+        "/rpc/v0.4",
+        json!({ "jsonrpc": "2.0", "id": 0, "method": "starknet_blockNumber", "params": [] }),
+    )
+    .await;
+    assert!(res.get("result").is_some());
+
+    // Methods removed from a version, and versioned methods called outside of their path, aren't
+    // found.
+    for method in [
+        "starknet_pendingTransactions",
+        "starknet_estimateFee",
+        "starknet_getEvents",
+        "starknet_getBlockWithTxs",
+        "starknet_getTransactionByHash",
+    ] {
+        let res = post(
+            &sequencer,
+            "/rpc/v0.4",
+            json!({ "jsonrpc": "2.0", "id": 0, "method": method, "params": [] }),
+        )
+        .await;
+        assert_eq!(res["error"]["code"], -32601, "{method}");
+    }
+
+    for path in ["/", "/rpc/v0.3", "/rpc/v0.4"] {
+        let res = post(
+            &sequencer,
+            path,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "starknet_v0_4_getTransactionReceipt",
+                "params": request["params"]
+            }),
+        )
+        .await;
+        assert_eq!(res["error"]["code"], -32601, "{path}");
+    }
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
+#[tokio::test]
+async fn test_spec_version_and_syncing() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+
+    let request =
+        json!({ "jsonrpc": "2.0", "id": 0, "method": "starknet_specVersion", "params": [] });
+    for (path, version) in [("/", "0.3.0"), ("/rpc/v0.3", "0.3.0"), ("/rpc/v0.4", "0.4.0")] {
+        let res = post(&sequencer, path, request.clone()).await;
+        assert_eq!(res["result"], version, "{path}");
+    }
+
+    let res = post(
+        &sequencer,
+        "/",
+        json!({ "jsonrpc": "2.0", "id": 0, "method": "starknet_syncing", "params": [] }),
+    )
+    .await;
+    assert_eq!(res["result"], false);
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
+#[tokio::test]
+async fn test_transaction_status() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+
+    let res = sequencer
+        .account()
+        .execute(vec![Call {
+            to: (*FEE_TOKEN_ADDRESS).into(),
+            selector: get_selector_from_name("transfer").unwrap(),
+            calldata: vec![FieldElement::ONE, FieldElement::from(100_u32), FieldElement::ZERO],
+        }])
+        .send()
+        .await
+        .unwrap();
+
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "starknet_getTransactionStatus",
+        "params": [format!("{:#x}", res.transaction_hash)]
+    });
+
+    let status = post(&sequencer, "/rpc/v0.4", request.clone()).await;
+    assert_eq!(status["result"]["finality_status"], "ACCEPTED_ON_L2");
+    assert_eq!(status["result"]["execution_status"], "SUCCEEDED");
+
+    // The method was introduced by v0.4.
+    for path in ["/", "/rpc/v0.3"] {
+        let res = post(&sequencer, path, request.clone()).await;
+        assert_eq!(res["error"]["code"], -32601, "{path}");
+    }
+
+    let missing = post(
+        &sequencer,
+        "/rpc/v0.4",
+        json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "starknet_getTransactionStatus",
+            "params": ["0x1"]
+        }),
+    )
+    .await;
+    assert_eq!(missing["error"]["code"], 25);
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
+#[tokio::test]
+async fn test_estimate_message_fee_of_unknown_contract() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+
+    let res = post(
+        &sequencer,
+        "/",
+        json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "starknet_estimateMessageFee",
+            "params": [
+                {
+                    "from_address": "0x1",
+                    "to_address": "0xdead",
+                    "entry_point_selector": "0x1",
+                    "payload": ["0x2a"]
+                },
+                "latest"
+            ]
+        }),
+    )
+    .await;
+    assert_eq!(res["error"]["code"], 20);
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
@@ -3,10 +3,22 @@ use std::path::PathBuf;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
+use assert_matches::assert_matches;
+use blockifier::execution::contract_class::{
+    ContractClass as BlockifierContractClass, ContractClassV0,
+};
+use blockifier::transaction::account_transaction::AccountTransaction;
+use blockifier::transaction::transaction_execution::Transaction;
+use blockifier::transaction::transactions::DeclareTransaction;
 use cairo_lang_starknet::casm_contract_class::CasmContractClass;
 use cairo_lang_starknet::contract_class::ContractClass;
-use dojo_test_utils::sequencer::{get_default_test_starknet_config, TestSequencer};
-use katana_core::sequencer::SequencerConfig;
+use dojo_test_utils::sequencer::{get_default_test_starknet_config, StarknetConfig, TestSequencer};
+use katana_core::backend::contract::DeclaredClass;
+use katana_core::backend::ReorgTransaction;
+use katana_core::constants::{FEE_TOKEN_ADDRESS, UDC_ADDRESS};
+use katana_core::sequencer::{Sequencer, SequencerConfig};
+use katana_core::sequencer_error::SequencerError;
+use serde_json::{json, Value};
 use starknet::accounts::{Account, Call, ConnectedAccount};
 use starknet::core::types::contract::legacy::LegacyContractClass;
 use starknet::core::types::contract::{CompiledClass, SierraClass};
@@ -16,6 +28,11 @@ use starknet::core::types::{
 };
 use starknet::core::utils::{get_contract_address, get_selector_from_name};
 use starknet::providers::Provider;
+use starknet_api::block::BlockNumber;
+use starknet_api::core::{ClassHash, ContractAddress, PatriciaKey};
+use starknet_api::hash::{StarkFelt, StarkHash};
+use starknet_api::patricia_key;
+use starknet_api::transaction::{DeclareTransactionV0V1, Fee, TransactionHash};
 
 #[tokio::test]
 async fn test_send_declare_and_deploy_contract() {
@@ -87,6 +104,131 @@ async fn test_send_declare_and_deploy_contract() {
     sequencer.stop().expect("failed to stop sequencer");
 }
 
+#[tokio::test]
+async fn test_declare_validation() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+    let account = sequencer.account();
+
+    let path: PathBuf = PathBuf::from("tests/test_data/cairo1_contract.json");
+    let (contract, compiled_class_hash) = prepare_contract_declaration_params(&path).unwrap();
+    let contract = Arc::new(contract);
+
+    let err = account
+        .declare(contract.clone(), compiled_class_hash + FieldElement::ONE)
+        .send()
+        .await
+        .unwrap_err();
+    assert!(format!("{err:?}").contains("CompiledClassHashMismatch"), "{err:?}");
+
+    account.declare(contract.clone(), compiled_class_hash).send().await.unwrap();
+
+    let err = account.declare(contract, compiled_class_hash).send().await.unwrap_err();
+    assert!(format!("{err:?}").contains("ClassAlreadyDeclared"), "{err:?}");
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
+#[tokio::test]
+async fn test_declared_class_hash_checks() {
+    let starknet_config = StarknetConfig {
+        auto_mine: false,
+        parallel_execution: true,
+        ..get_default_test_starknet_config()
+    };
+    let sequencer = TestSequencer::start(SequencerConfig::default(), starknet_config).await;
+    let sender = sequencer.raw_account().account_address;
+
+    let json = fs::read_to_string("tests/test_data/cairo0_contract.json").unwrap();
+    let legacy_class: LegacyContractClass = serde_json::from_str(&json).unwrap();
+    let class_hash = legacy_class.class_hash().unwrap();
+
+    let declare = |class_hash: FieldElement, transaction_hash: u8| {
+        let transaction = DeclareTransactionV0V1 {
+            class_hash: ClassHash(class_hash.into()),
+            sender_address: ContractAddress(patricia_key!(sender)),
+            transaction_hash: TransactionHash(StarkFelt::from(transaction_hash)),
+            max_fee: Fee(10_u128.pow(18)),
+            ..Default::default()
+        };
+        let class: ContractClassV0 = serde_json::from_str(&json).unwrap();
+        DeclareTransaction::new(
+            starknet_api::transaction::DeclareTransaction::V1(transaction),
+            BlockifierContractClass::V0(class),
+        )
+        .unwrap()
+    };
+    let definition = || DeclaredClass::Legacy(serde_json::from_str(&json).unwrap());
+
+    let err = sequencer
+        .sequencer
+        .add_declare_transaction(declare(class_hash + FieldElement::ONE, 1), definition())
+        .await
+        .unwrap_err();
+    assert_matches!(err, SequencerError::ClassHashMismatch { .. });
+
+    sequencer
+        .sequencer
+        .add_declare_transaction(declare(class_hash, 2), definition())
+        .await
+        .unwrap();
+
+    // The first declaration is queued, and not executed yet.
+    let err = sequencer
+        .sequencer
+        .add_declare_transaction(declare(class_hash, 3), definition())
+        .await
+        .unwrap_err();
+    assert_matches!(err, SequencerError::ClassAlreadyDeclared(_));
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
+#[tokio::test]
+async fn test_reorg_replacement_declares() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+    let account = sequencer.account();
+    let sender = sequencer.raw_account().account_address;
+
+    let json = fs::read_to_string("tests/test_data/cairo0_contract.json").unwrap();
+    let legacy_class: LegacyContractClass = serde_json::from_str(&json).unwrap();
+    let class_hash = legacy_class.class_hash().unwrap();
+    account.declare_legacy(Arc::new(legacy_class)).send().await.unwrap();
+
+    let declare = |transaction_hash: u8| {
+        let transaction = DeclareTransactionV0V1 {
+            class_hash: ClassHash(class_hash.into()),
+            sender_address: ContractAddress(patricia_key!(sender)),
+            transaction_hash: TransactionHash(StarkFelt::from(transaction_hash)),
+            max_fee: Fee(10_u128.pow(18)),
+            ..Default::default()
+        };
+        let class: ContractClassV0 = serde_json::from_str(&json).unwrap();
+        let transaction = DeclareTransaction::new(
+            starknet_api::transaction::DeclareTransaction::V1(transaction),
+            BlockifierContractClass::V0(class),
+        )
+        .unwrap();
+        ReorgTransaction {
+            transaction: Transaction::AccountTransaction(AccountTransaction::Declare(transaction)),
+            sierra_class: None,
+        }
+    };
+
+    // The class is declared by the dropped transaction mined again, or twice by the replacements.
+    let err = sequencer.sequencer.reorg(1, true, vec![declare(1)]).await.unwrap_err();
+    assert_matches!(err, SequencerError::ClassAlreadyDeclared(_));
+    let err = sequencer.sequencer.reorg(1, false, vec![declare(1), declare(2)]).await.unwrap_err();
+    assert_matches!(err, SequencerError::ClassAlreadyDeclared(_));
+    assert_eq!(sequencer.sequencer.block_number().await, BlockNumber(1));
+
+    // The class isn't declared anymore once its block is dropped.
+    sequencer.sequencer.reorg(1, false, vec![declare(1)]).await.unwrap();
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
 #[tokio::test]
 async fn test_send_declare_and_deploy_legcay_contract() {
     let sequencer =
@@ -160,6 +302,181 @@ async fn test_send_declare_and_deploy_legcay_contract() {
     sequencer.stop().expect("failed to stop sequencer");
 }
 
+#[tokio::test]
+async fn test_transaction_resources() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+    let account = sequencer.account();
+
+    let res = account
+        .execute(vec![Call {
+            to: (*FEE_TOKEN_ADDRESS).into(),
+            selector: get_selector_from_name("transfer").unwrap(),
+            calldata: vec![FieldElement::ONE, FieldElement::from(100_u32), FieldElement::ZERO],
+        }])
+        .send()
+        .await
+        .unwrap();
+
+    let request = |method: &str| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": method,
+            "params": [format!("{:#x}", res.transaction_hash)]
+        })
+    };
+
+    let resources = post(&sequencer, request("katana_transactionResources")).await;
+    let resources = &resources["result"];
+    let execution_resources = &resources["execution_resources"];
+    let fee = &resources["fee"];
+
+    assert!(execution_resources["steps"].as_u64().unwrap() > 0);
+    assert!(execution_resources["builtin_instance_counter"]["range_check_builtin"].is_u64());
+    let felt = |value: &Value| FieldElement::from_hex_be(value.as_str().unwrap()).unwrap();
+    assert_eq!(
+        felt(&fee["l1_gas_fee"]),
+        FieldElement::from(execution_resources["l1_gas_usage"].as_u64().unwrap())
+            * felt(&fee["gas_price"])
+    );
+    assert!(felt(&fee["actual_fee"]) <= felt(&fee["l1_gas_fee"]) + felt(&fee["computation_fee"]));
+
+    // The receipt carries the same resources.
+    let receipt = post(&sequencer, request("starknet_getTransactionReceipt")).await;
+    assert_eq!(&receipt["result"]["execution_resources"], execution_resources);
+    assert_eq!(&receipt["result"]["fee_breakdown"], fee);
+
+    let unknown = post(
+        &sequencer,
+        json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "katana_transactionResources",
+            "params": ["0x1"]
+        }),
+    )
+    .await;
+    assert_eq!(unknown["error"]["code"], 25);
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
+#[tokio::test]
+async fn test_transaction_profile() {
+    let sequencer =
+        TestSequencer::start(SequencerConfig::default(), get_default_test_starknet_config()).await;
+    let account = sequencer.account();
+
+    let res = account
+        .execute(vec![Call {
+            to: (*FEE_TOKEN_ADDRESS).into(),
+            selector: get_selector_from_name("transfer").unwrap(),
+            calldata: vec![FieldElement::ONE, FieldElement::from(100_u32), FieldElement::ZERO],
+        }])
+        .send()
+        .await
+        .unwrap();
+
+    let profile = sequencer
+        .sequencer
+        .transaction_profile(&TransactionHash(StarkFelt::from(res.transaction_hash)))
+        .await
+        .expect("executed transaction must have a profile");
+
+    assert!(profile.total_steps() > 0);
+
+    let fee_token = format!("{:#x}", FieldElement::from(*FEE_TOKEN_ADDRESS));
+    let account_address = format!("{:#x}", sequencer.raw_account().account_address);
+    let transfer = profile
+        .samples
+        .iter()
+        .find(|s| s.stack[0] == "execute" && s.stack.len() == 3)
+        .expect("the transfer must be called by the account");
+
+    assert_eq!(transfer.stack[1], format!("{account_address}::__execute__"));
+    assert_eq!(transfer.stack[2], format!("{fee_token}::transfer"));
+    assert!(profile.to_folded().lines().any(|line| line.starts_with("validate;")));
+
+    let missing = sequencer
+        .sequencer
+        .transaction_profile(&TransactionHash(StarkFelt::from(FieldElement::ONE)))
+        .await;
+    assert!(missing.is_none());
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
+#[tokio::test]
+async fn test_sierra_transaction_profile() {
+    let starknet_config = StarknetConfig {
+        profile_debug_info_dir: Some(PathBuf::from("tests/test_data")),
+        ..get_default_test_starknet_config()
+    };
+    let sequencer = TestSequencer::start(SequencerConfig::default(), starknet_config).await;
+    let account = sequencer.account();
+
+    let path: PathBuf = PathBuf::from("tests/test_data/cairo1_contract.json");
+    let (contract, compiled_class_hash) = prepare_contract_declaration_params(&path).unwrap();
+    let res = account.declare(Arc::new(contract), compiled_class_hash).send().await.unwrap();
+
+    let constructor_calldata = vec![FieldElement::from(1_u32), FieldElement::from(2_u32)];
+    let contract_address = get_contract_address(
+        FieldElement::ZERO,
+        res.class_hash,
+        &constructor_calldata,
+        FieldElement::ZERO,
+    );
+
+    account
+        .execute(vec![Call {
+            to: (*UDC_ADDRESS).into(),
+            selector: get_selector_from_name("deployContract").unwrap(),
+            calldata: [
+                vec![res.class_hash, FieldElement::ZERO, FieldElement::ZERO, FieldElement::TWO],
+                constructor_calldata,
+            ]
+            .concat(),
+        }])
+        .send()
+        .await
+        .unwrap();
+
+    let res = account
+        .execute(vec![Call {
+            to: contract_address,
+            selector: get_selector_from_name("test_storage_read_write").unwrap(),
+            calldata: vec![FieldElement::ONE, FieldElement::from(42_u32)],
+        }])
+        .send()
+        .await
+        .unwrap();
+
+    let profile = sequencer
+        .sequencer
+        .transaction_profile(&TransactionHash(StarkFelt::from(res.transaction_hash)))
+        .await
+        .expect("executed transaction must have a profile");
+
+    // The steps of the Sierra call are all spent in the Cairo functions it runs.
+    let call = format!("{contract_address:#x}::test_storage_read_write");
+    assert!(profile.samples.iter().any(|s| s.stack.len() > 3 && s.stack[2] == call));
+    assert!(profile.samples.iter().all(|s| s.stack.last() != Some(&call)));
+
+    sequencer.stop().expect("failed to stop sequencer");
+}
+
+async fn post(sequencer: &TestSequencer, body: Value) -> Value {
+    let res = reqwest::Client::new()
+        .post(sequencer.url())
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap();
+    serde_json::from_str(&res.text().await.unwrap()).unwrap()
+}
+
 fn prepare_contract_declaration_params(
     artifact_path: &PathBuf,
 ) -> Result<(FlattenedSierraClass, FieldElement)> {
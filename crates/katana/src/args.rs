@@ -1,12 +1,20 @@
+use std::net::IpAddr;
 use std::path::PathBuf;
 
-use clap::{Args, Parser};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use katana_core::backend::config::{Environment, StarknetConfig};
+use katana_core::block_context::FeeTokenAddresses;
 use katana_core::constants::{
-    DEFAULT_GAS_PRICE, DEFAULT_INVOKE_MAX_STEPS, DEFAULT_VALIDATE_MAX_STEPS,
+    DEFAULT_GAS_PRICE, DEFAULT_INVOKE_MAX_STEPS, DEFAULT_PARALLEL_BATCH_SIZE,
+    DEFAULT_VALIDATE_MAX_STEPS,
 };
 use katana_core::sequencer::SequencerConfig;
-use katana_rpc::config::ServerConfig;
+use katana_rpc::config::{AuthConfig, ServerConfig};
+use starknet::core::types::FieldElement;
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::{StarkFelt, StarkHash};
+use starknet_api::patricia_key;
+use url::Url;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -16,6 +24,17 @@ pub struct KatanaArgs {
     #[arg(help = "Don't print anything on startup.")]
     pub silent: bool,
 
+    #[arg(long)]
+    #[arg(value_name = "FORMAT")]
+    #[arg(value_enum)]
+    #[arg(default_value_t = LogFormat::Full)]
+    #[arg(help = "The format of the logs.")]
+    #[arg(long_help = "The format of the logs. `json` writes a JSON object per line, with the \
+                       timestamp, level, target, message and fields of the log, such as the \
+                       transaction hash or the block number, and doesn't print the startup \
+                       banner.")]
+    pub log_format: LogFormat,
+
     #[arg(long)]
     #[arg(conflicts_with = "block_time")]
     #[arg(help = "Disable auto and interval mining, and mine on demand instead.")]
@@ -26,6 +45,14 @@ pub struct KatanaArgs {
     #[arg(help = "Block time in seconds for interval mining.")]
     pub block_time: Option<u64>,
 
+    #[arg(long)]
+    #[arg(value_name = "PATH")]
+    #[arg(help = "Rebuild the chain from a file written by `katana export` on startup.")]
+    #[arg(long_help = "Rebuild the chain from a file written by `katana export` on startup. \
+                       The transactions are re-executed on a genesis generated from the \
+                       Starknet options, which must match the ones of the exporting node.")]
+    pub import: Option<PathBuf>,
+
     #[command(flatten)]
     #[command(next_help_heading = "Server options")]
     pub server: ServerOptions,
@@ -33,6 +60,83 @@ pub struct KatanaArgs {
     #[command(flatten)]
     #[command(next_help_heading = "Starknet options")]
     pub starknet: StarknetOptions,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Full,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    #[command(about = "Re-execute the blocks of a remote chain and report divergences.")]
+    Replay(ReplayArgs),
+
+    #[command(about = "Export the blocks, transactions, receipts and classes of a chain to a \
+                       JSON Lines file.")]
+    Export(ExportArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    #[arg(long)]
+    #[arg(value_name = "URL")]
+    #[arg(help = "The JSON-RPC endpoint of the chain to replay.")]
+    pub rpc: Url,
+
+    #[arg(long)]
+    #[arg(value_name = "KEY")]
+    #[arg(conflicts_with = "jwt")]
+    #[arg(help = "The API key required by the node.")]
+    pub api_key: Option<String>,
+
+    #[arg(long)]
+    #[arg(value_name = "TOKEN")]
+    #[arg(help = "A JWT signed with the secret required by the node.")]
+    pub jwt: Option<String>,
+
+    #[arg(long)]
+    #[arg(value_name = "BLOCK")]
+    #[arg(help = "The first block to replay, on the remote state of the block preceding it.")]
+    pub from: u64,
+
+    #[arg(long)]
+    #[arg(value_name = "BLOCK")]
+    #[arg(help = "The last block to replay.")]
+    pub to: u64,
+
+    #[command(flatten)]
+    #[command(next_help_heading = "Starknet options")]
+    pub starknet: StarknetOptions,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[arg(long)]
+    #[arg(value_name = "URL")]
+    #[arg(default_value = "http://localhost:5050")]
+    #[arg(help = "The JSON-RPC endpoint of the chain to export.")]
+    pub rpc: Url,
+
+    #[arg(long)]
+    #[arg(value_name = "KEY")]
+    #[arg(conflicts_with = "jwt")]
+    #[arg(help = "The API key required by the node.")]
+    pub api_key: Option<String>,
+
+    #[arg(long)]
+    #[arg(value_name = "TOKEN")]
+    #[arg(help = "A JWT signed with the secret required by the node.")]
+    pub jwt: Option<String>,
+
+    #[arg(long)]
+    #[arg(value_name = "PATH")]
+    #[arg(help = "The file to write the chain to.")]
+    pub out: PathBuf,
 }
 
 #[derive(Debug, Args, Clone)]
@@ -45,6 +149,52 @@ pub struct ServerOptions {
     #[arg(long)]
     #[arg(help = "The IP address the server will listen on.")]
     pub host: Option<String>,
+
+    #[arg(long)]
+    #[arg(value_name = "ORIGINS")]
+    #[arg(value_delimiter = ',')]
+    #[arg(help = "Comma separated list of origins allowed to make cross-origin requests.")]
+    #[arg(long_help = "Comma separated list of origins allowed to make cross-origin requests. \
+                       Any origin is allowed if not specified.")]
+    pub allowed_origins: Vec<String>,
+
+    #[arg(long)]
+    #[arg(value_name = "KEY")]
+    #[arg(conflicts_with = "jwt_secret")]
+    #[arg(help = "Require clients to send this key as a bearer token.")]
+    pub api_key: Option<String>,
+
+    #[arg(long)]
+    #[arg(value_name = "SECRET")]
+    #[arg(help = "Require clients to send a HS256 JWT signed with this secret as a bearer token.")]
+    pub jwt_secret: Option<String>,
+
+    #[arg(long)]
+    #[arg(value_name = "REQUESTS")]
+    #[arg(help = "Maximum number of requests per minute per client IP.")]
+    #[arg(long_help = "Maximum number of requests per minute per client IP. Clients are \
+                       identified by the X-Forwarded-For or X-Real-IP header of the trusted \
+                       proxies the node is served behind. Without trusted proxies, all the \
+                       clients share the limit.")]
+    pub rate_limit: Option<u32>,
+
+    #[arg(long = "rpc-trusted-proxy")]
+    #[arg(value_name = "IP")]
+    #[arg(value_delimiter = ',')]
+    #[arg(requires = "rate_limit")]
+    #[arg(help = "The reverse proxies the node is only reachable through, whose forwarding \
+                  headers identify the clients.")]
+    pub trusted_proxies: Vec<IpAddr>,
+
+    #[arg(long)]
+    #[arg(help = "Don't expose the katana_* dev methods.")]
+    pub disable_dev_api: bool,
+
+    #[arg(long)]
+    #[arg(conflicts_with = "disable_dev_api")]
+    #[arg(help = "Expose the faucet_* methods and the POST /faucet route, which fund and deploy \
+                  accounts for anyone.")]
+    pub faucet: bool,
 }
 
 #[derive(Debug, Args, Clone)]
@@ -71,6 +221,51 @@ pub struct StarknetOptions {
     #[arg(help = "Allow transaction max fee to be zero.")]
     pub allow_zero_max_fee: bool,
 
+    #[arg(long = "profile")]
+    #[arg(value_name = "DIR")]
+    #[arg(help = "Write the Cairo VM profile of every executed transaction to this directory.")]
+    #[arg(long_help = "Trace the Cairo VM execution of every executed transaction and write the \
+                       steps spent in each function to <DIR>/<transaction hash>.folded, in the \
+                       folded stacks format read by flamegraph tools such as \
+                       inferno-flamegraph and speedscope.")]
+    pub profile_dir: Option<PathBuf>,
+
+    #[arg(long = "profile-debug-info")]
+    #[arg(value_name = "DIR")]
+    #[arg(help = "Directory of Sierra contract class artifacts to name the functions of \
+                  profiles from.")]
+    #[arg(long_help = "Directory of Sierra contract class artifacts, such as the target/dev \
+                       directory of a Dojo project, to read the debug info of declared classes \
+                       from. Artifacts are matched by class hash. Without debug info, the \
+                       Cairo functions of profiles are named by their Sierra id.")]
+    pub profile_debug_info_dir: Option<PathBuf>,
+
+    #[arg(long)]
+    #[arg(help = "Execute the transactions of a block in parallel.")]
+    #[arg(long_help = "Execute the transactions of a block in parallel, re-executing serially \
+                       the ones which conflict with an earlier transaction of the block. Only \
+                       applies with interval or on-demand mining, where transactions are queued \
+                       and executed in batches.")]
+    pub parallel_execution: bool,
+
+    #[arg(long)]
+    #[arg(requires = "parallel_execution")]
+    #[arg(value_name = "SIZE")]
+    #[arg(default_value_t = DEFAULT_PARALLEL_BATCH_SIZE)]
+    #[arg(help = "The number of queued transactions which triggers their parallel execution.")]
+    #[arg(long_help = "The number of queued transactions which triggers their parallel \
+                       execution. Smaller batches are executed periodically, and when the block \
+                       is mined.")]
+    pub parallel_batch_size: usize,
+
+    #[arg(long)]
+    #[arg(value_name = "FILE")]
+    #[arg(help = "Append a JSON record of every executed transaction to this file.")]
+    #[arg(long_help = "Append a JSON record of every executed transaction to this file, one \
+                       per line, with its hash, type, sender, calldata, status, revert reason, \
+                       events, fee and resources.")]
+    pub tx_log: Option<PathBuf>,
+
     #[command(flatten)]
     #[command(next_help_heading = "Environment options")]
     pub environment: EnvironmentOptions,
@@ -94,6 +289,24 @@ pub struct EnvironmentOptions {
     #[arg(long)]
     #[arg(help = "The maximum number of steps available for the account execution logic.")]
     pub invoke_max_steps: Option<u32>,
+
+    #[arg(long)]
+    #[arg(value_name = "VERSIONS")]
+    #[arg(value_delimiter = ',')]
+    #[arg(help = "Comma separated list of transaction versions whose fees are charged in STRK.")]
+    #[arg(long_help = "Comma separated list of transaction versions whose fees are charged in \
+                       STRK. The fees of the other versions are charged in ETH.")]
+    pub strk_fee_versions: Vec<u8>,
+
+    #[arg(long)]
+    #[arg(value_name = "ADDRESS")]
+    #[arg(help = "The address to deploy the ETH fee token at.")]
+    pub eth_fee_token_address: Option<FieldElement>,
+
+    #[arg(long)]
+    #[arg(value_name = "ADDRESS")]
+    #[arg(help = "The address to deploy the STRK fee token at.")]
+    pub strk_fee_token_address: Option<FieldElement>,
 }
 
 impl KatanaArgs {
@@ -105,34 +318,70 @@ impl KatanaArgs {
         ServerConfig {
             port: self.server.port,
             host: self.server.host.clone().unwrap_or("0.0.0.0".into()),
+            allowed_origins: self.server.allowed_origins.clone(),
+            auth: match (&self.server.api_key, &self.server.jwt_secret) {
+                (Some(key), _) => Some(AuthConfig::ApiKey(key.clone())),
+                (_, Some(secret)) => Some(AuthConfig::Jwt(secret.clone())),
+                _ => None,
+            },
+            rate_limit: self.server.rate_limit,
+            trusted_proxies: self.server.trusted_proxies.clone(),
+            disable_dev_api: self.server.disable_dev_api,
+            faucet: self.server.faucet,
         }
     }
 
     pub fn starknet_config(&self) -> StarknetConfig {
         StarknetConfig {
-            total_accounts: self.starknet.total_accounts,
-            seed: parse_seed(&self.starknet.seed),
-            account_path: self.starknet.account_path.clone(),
-            allow_zero_max_fee: self.starknet.allow_zero_max_fee,
             auto_mine: self.block_time.is_none() && !self.no_mining,
+            ..self.starknet.starknet_config()
+        }
+    }
+}
+
+impl StarknetOptions {
+    pub fn starknet_config(&self) -> StarknetConfig {
+        StarknetConfig {
+            total_accounts: self.total_accounts,
+            seed: parse_seed(&self.seed),
+            account_path: self.account_path.clone(),
+            allow_zero_max_fee: self.allow_zero_max_fee,
+            profile_dir: self.profile_dir.clone(),
+            profile_debug_info_dir: self.profile_debug_info_dir.clone(),
+            parallel_execution: self.parallel_execution,
+            parallel_batch_size: self.parallel_batch_size,
+            tx_log: self.tx_log.clone(),
+            auto_mine: true,
             env: Environment {
-                chain_id: self.starknet.environment.chain_id.clone(),
-                gas_price: self.starknet.environment.gas_price.unwrap_or(DEFAULT_GAS_PRICE),
+                chain_id: self.environment.chain_id.clone(),
+                gas_price: self.environment.gas_price.unwrap_or(DEFAULT_GAS_PRICE),
                 invoke_max_steps: self
-                    .starknet
                     .environment
                     .invoke_max_steps
                     .unwrap_or(DEFAULT_INVOKE_MAX_STEPS),
                 validate_max_steps: self
-                    .starknet
                     .environment
                     .validate_max_steps
                     .unwrap_or(DEFAULT_VALIDATE_MAX_STEPS),
+                strk_fee_versions: self.environment.strk_fee_versions.clone(),
+                fee_token_addresses: self.environment.fee_token_addresses(),
             },
         }
     }
 }
 
+impl EnvironmentOptions {
+    fn fee_token_addresses(&self) -> FeeTokenAddresses {
+        let defaults = FeeTokenAddresses::default();
+        let address = |felt: FieldElement| ContractAddress(patricia_key!(StarkFelt::from(felt)));
+
+        FeeTokenAddresses {
+            eth: self.eth_fee_token_address.map_or(defaults.eth, address),
+            strk: self.strk_fee_token_address.map_or(defaults.strk, address),
+        }
+    }
+}
+
 fn parse_seed(seed: &str) -> [u8; 32] {
     let seed = seed.as_bytes();
 
@@ -147,6 +396,8 @@ fn parse_seed(seed: &str) -> [u8; 32] {
 
 #[cfg(test)]
 mod test {
+    use katana_core::block_context::FeeToken;
+
     use super::*;
 
     #[test]
@@ -180,4 +431,76 @@ mod test {
         assert_eq!(block_context.validate_max_n_steps, 100);
         assert_eq!(block_context.invoke_tx_max_n_steps, 200);
     }
+
+    #[test]
+    fn fee_token_from_args() {
+        let config = KatanaArgs::parse_from(["katana"]).starknet_config();
+        assert_eq!(config.fee_token(1), FeeToken::Eth);
+
+        let config =
+            KatanaArgs::parse_from(["katana", "--strk-fee-versions", "1,2"]).starknet_config();
+        assert_eq!(config.fee_token(0), FeeToken::Eth);
+        assert_eq!(config.fee_token(1), FeeToken::Strk);
+        assert_eq!(config.fee_token(2), FeeToken::Strk);
+        assert_eq!(config.fee_token_addresses(), FeeTokenAddresses::default());
+
+        let config =
+            KatanaArgs::parse_from(["katana", "--strk-fee-token-address", "0x5"]).starknet_config();
+        let addresses = config.fee_token_addresses();
+        assert_eq!(addresses.eth, FeeTokenAddresses::default().eth);
+        assert_eq!(addresses.strk, ContractAddress(patricia_key!(5_u8)));
+        assert_eq!(config.block_context().fee_token_address, addresses.eth);
+    }
+
+    #[test]
+    fn server_config_from_args() {
+        let config = KatanaArgs::parse_from(["katana"]).server_config();
+        assert!(config.allowed_origins.is_empty());
+        assert!(config.auth.is_none());
+        assert!(config.rate_limit.is_none());
+        assert!(!config.disable_dev_api);
+        assert!(!config.faucet);
+
+        let config = KatanaArgs::parse_from([
+            "katana",
+            "--allowed-origins",
+            "https://a.dev,https://b.dev",
+            "--jwt-secret",
+            "secret",
+            "--rate-limit",
+            "60",
+            "--rpc-trusted-proxy",
+            "10.0.0.1,10.0.0.2",
+            "--disable-dev-api",
+        ])
+        .server_config();
+
+        assert_eq!(config.allowed_origins, vec!["https://a.dev", "https://b.dev"]);
+        assert!(matches!(config.auth, Some(AuthConfig::Jwt(secret)) if secret == "secret"));
+        assert_eq!(config.rate_limit, Some(60));
+        assert_eq!(
+            config.trusted_proxies,
+            vec!["10.0.0.1".parse::<IpAddr>().unwrap(), "10.0.0.2".parse().unwrap()]
+        );
+        assert!(config.disable_dev_api);
+
+        let args = KatanaArgs::try_parse_from(["katana", "--api-key", "a", "--jwt-secret", "b"]);
+        assert!(args.is_err(), "api key and jwt secret are mutually exclusive");
+
+        assert!(KatanaArgs::parse_from(["katana", "--faucet"]).server_config().faucet);
+        let args = KatanaArgs::try_parse_from(["katana", "--faucet", "--disable-dev-api"]);
+        assert!(args.is_err(), "the faucet is a dev api");
+    }
+
+    #[test]
+    fn log_options_from_args() {
+        let args = KatanaArgs::parse_from(["katana"]);
+        assert_eq!(args.log_format, LogFormat::Full);
+        assert!(args.starknet_config().tx_log.is_none());
+
+        let args =
+            KatanaArgs::parse_from(["katana", "--log-format", "json", "--tx-log", "txs.jsonl"]);
+        assert_eq!(args.log_format, LogFormat::Json);
+        assert_eq!(args.starknet_config().tx_log, Some(PathBuf::from("txs.jsonl")));
+    }
 }
@@ -0,0 +1,79 @@
+//! The HTTP client used to read a remote chain, and the raw JSON-RPC calls to the methods which
+//! aren't part of the Starknet provider.
+
+use anyhow::{bail, Context, Result};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use starknet::core::types::FieldElement;
+use url::Url;
+
+/// The JSON-RPC error code of calls to unknown methods.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Returns a client which sends `auth_token` as a bearer token on every request, for nodes which
+/// require an API key or a JWT.
+pub(crate) fn http_client(auth_token: Option<&str>) -> Result<reqwest::Client> {
+    let mut headers = HeaderMap::new();
+    if let Some(token) = auth_token {
+        let mut value = HeaderValue::from_str(&format!("Bearer {token}"))
+            .context("the auth token isn't a valid header value")?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+
+    Ok(reqwest::Client::builder().default_headers(headers).build()?)
+}
+
+/// Calls `method` on the node served at `rpc` and returns its result, or `None` if the node
+/// doesn't expose the method.
+pub(crate) async fn call<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    rpc: &Url,
+    method: &str,
+    params: Value,
+) -> Result<Option<T>> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": method,
+        "params": params
+    });
+    let response = client
+        .post(rpc.clone())
+        .header("Content-Type", "application/json")
+        .body(request.to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+    let mut response: Value = serde_json::from_str(&response.text().await?)?;
+
+    match response.get("error") {
+        Some(error) if error["code"] == METHOD_NOT_FOUND => Ok(None),
+        Some(error) => bail!("{method} failed: {error}"),
+        None => Ok(Some(serde_json::from_value(response["result"].take())?)),
+    }
+}
+
+/// A mint as returned by `katana_blockMints`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct BlockMint {
+    pub address: FieldElement,
+    pub amount: FieldElement,
+    pub transaction_index: usize,
+}
+
+/// Reads the balances minted in a block, in order. Nodes which don't expose `katana_blockMints`
+/// are assumed to have none.
+pub(crate) async fn block_mints(
+    client: &reqwest::Client,
+    rpc: &Url,
+    block_number: u64,
+) -> Result<Vec<BlockMint>> {
+    let params = json!([{ "block_number": block_number }]);
+    let mints = call(client, rpc, "katana_blockMints", params)
+        .await
+        .with_context(|| format!("failed to read the mints of block {block_number}"))?;
+    Ok(mints.unwrap_or_default())
+}
@@ -0,0 +1,318 @@
+//! Exports the history of a chain to a portable JSON Lines file, and rebuilds a chain by
+//! re-executing an exported history.
+//!
+//! Every line of an export is a [ChainRecord]. The first line is a [ChainRecord::Header], and
+//! each block is written as a [ChainRecord::Block] followed by a [ChainRecord::Transaction] for
+//! each of its transactions, in execution order. The class declared by a declare transaction is
+//! written as a [ChainRecord::Class] right before the transaction, and the balances minted with
+//! `katana_mint` or the faucet are written as [ChainRecord::Mint]s where they were minted.
+//!
+//! The genesis block is not exported, so a chain can only be imported by a node started with the
+//! same genesis configuration as the exporting node.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, bail, Context, Result};
+use blockifier::transaction::transaction_execution::Transaction as BlockifierTransaction;
+use katana_core::backend::StarknetWrapper;
+use katana_core::utils::starkfelt_to_u128;
+use katana_core::utils::transaction::rpc_to_blockifier_transaction;
+use serde::{Deserialize, Serialize};
+use starknet::core::types::{
+    BlockId, ContractClass, FieldElement, FlattenedSierraClass, MaybePendingBlockWithTxs,
+    MaybePendingTransactionReceipt, Transaction, TransactionReceipt, TransactionStatus,
+};
+use starknet::core::utils::{cairo_short_string_to_felt, parse_cairo_short_string};
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::{JsonRpcClient, Provider};
+use starknet_api::core::{ClassHash, ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkHash;
+use starknet_api::patricia_key;
+use starknet_api::transaction::TransactionHash;
+use url::Url;
+
+use crate::client::{block_mints, http_client};
+use crate::replay::declared_class_hash;
+
+/// A line of an exported chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+pub enum ChainRecord {
+    /// The chain the records were exported from.
+    Header { chain_id: FieldElement, latest_block_number: u64 },
+    /// A block, whose transactions are the records that follow it.
+    Block {
+        block_number: u64,
+        block_hash: FieldElement,
+        parent_hash: FieldElement,
+        timestamp: u64,
+        sequencer_address: FieldElement,
+        transactions: Vec<FieldElement>,
+    },
+    /// A class declared by the transaction that follows it.
+    Class { class_hash: FieldElement, class: ContractClass },
+    /// A transaction along with its receipt.
+    Transaction { transaction: Transaction, receipt: TransactionReceipt },
+    /// A balance credited to an address in every fee token outside of any transaction.
+    Mint { address: FieldElement, amount: FieldElement },
+}
+
+/// Writes the history of the chain served at `rpc` to `writer`, one [ChainRecord] per line, and
+/// returns the number of exported blocks. `auth_token` is sent as a bearer token to nodes which
+/// require an API key or a JWT.
+///
+/// Mints are read with `katana_blockMints`, and are not exported if the node doesn't expose the
+/// `katana_*` methods.
+pub async fn export_chain<W: Write>(
+    rpc: &Url,
+    auth_token: Option<&str>,
+    mut writer: W,
+) -> Result<u64> {
+    let client = http_client(auth_token)?;
+    let provider = JsonRpcClient::new(HttpTransport::new_with_client(rpc.clone(), client.clone()));
+
+    let chain_id = provider.chain_id().await?;
+    let latest_block_number = provider.block_number().await?;
+
+    write_record(&mut writer, &ChainRecord::Header { chain_id, latest_block_number })?;
+
+    for block_number in 1..=latest_block_number {
+        let block = match provider.get_block_with_txs(BlockId::Number(block_number)).await? {
+            MaybePendingBlockWithTxs::Block(block) => block,
+            MaybePendingBlockWithTxs::PendingBlock(_) => {
+                bail!("block {block_number} is still pending")
+            }
+        };
+
+        write_record(
+            &mut writer,
+            &ChainRecord::Block {
+                block_number,
+                block_hash: block.block_hash,
+                parent_hash: block.parent_hash,
+                timestamp: block.timestamp,
+                sequencer_address: block.sequencer_address,
+                transactions: block.transactions.iter().map(|tx| *tx.transaction_hash()).collect(),
+            },
+        )?;
+
+        let mut mints = block_mints(&client, rpc, block_number).await?.into_iter().peekable();
+
+        for (index, transaction) in block.transactions.into_iter().enumerate() {
+            while let Some(mint) = mints.next_if(|mint| mint.transaction_index <= index) {
+                write_record(
+                    &mut writer,
+                    &ChainRecord::Mint { address: mint.address, amount: mint.amount },
+                )?;
+            }
+
+            if let Some(class_hash) = declared_class_hash(&transaction) {
+                let class = provider.get_class(BlockId::Number(block_number), class_hash).await?;
+                write_record(&mut writer, &ChainRecord::Class { class_hash, class })?;
+            }
+
+            let transaction_hash = *transaction.transaction_hash();
+            let receipt = match provider.get_transaction_receipt(transaction_hash).await? {
+                MaybePendingTransactionReceipt::Receipt(receipt) => receipt,
+                MaybePendingTransactionReceipt::PendingReceipt(_) => bail!(
+                    "transaction {transaction_hash:#x} of block {block_number} is still pending"
+                ),
+            };
+
+            write_record(&mut writer, &ChainRecord::Transaction { transaction, receipt })?;
+        }
+
+        for mint in mints {
+            write_record(
+                &mut writer,
+                &ChainRecord::Mint { address: mint.address, amount: mint.amount },
+            )?;
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(latest_block_number)
+}
+
+/// Rebuilds a chain by re-executing the records read from `reader` on `starknet`, and returns the
+/// number of imported blocks.
+///
+/// `starknet` must not have any block yet. Its genesis block is generated from its own
+/// configuration, which must match the one of the exporting node. The import fails if a
+/// transaction is not accepted on re-execution.
+pub fn import_chain<R: BufRead>(starknet: &mut StarknetWrapper, reader: R) -> Result<u64> {
+    if starknet.blocks.total_blocks() > 0 {
+        bail!("cannot import a chain on top of existing blocks");
+    }
+
+    let auto_mine = starknet.config.auto_mine;
+    starknet.config.auto_mine = false;
+    let result = import_records(starknet, reader);
+    starknet.config.auto_mine = auto_mine;
+
+    result
+}
+
+fn import_records<R: BufRead>(starknet: &mut StarknetWrapper, reader: R) -> Result<u64> {
+    let mut lines = reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map(|line| !line.trim().is_empty()).unwrap_or(true));
+
+    let (_, header) = lines.next().ok_or(anyhow!("the chain export is empty"))?;
+    let ChainRecord::Header { chain_id, .. } = serde_json::from_str(&header?)? else {
+        bail!("the chain export must start with a header record");
+    };
+
+    let local_chain_id = cairo_short_string_to_felt(&starknet.config.env.chain_id)?;
+    if chain_id != local_chain_id {
+        bail!(
+            "the chain was exported from chain {}, but the node runs on chain {}",
+            parse_cairo_short_string(&chain_id).unwrap_or_else(|_| format!("{chain_id:#x}")),
+            starknet.config.env.chain_id
+        );
+    }
+
+    starknet.generate_genesis_block();
+
+    let mut block = PendingBlock::default();
+    let mut classes = HashMap::new();
+
+    for (index, line) in lines {
+        let record: ChainRecord = serde_json::from_str(&line?)
+            .with_context(|| format!("invalid record on line {}", index + 1))?;
+
+        match record {
+            ChainRecord::Header { .. } => bail!("unexpected header record on line {}", index + 1),
+
+            ChainRecord::Block { block_number: number, timestamp, .. } => {
+                if block.number.is_some() {
+                    close_block(starknet, &mut block)?;
+                }
+
+                starknet.set_next_block_timestamp(timestamp)?;
+                starknet.generate_pending_block();
+
+                if starknet.block_context.block_number.0 != number {
+                    bail!(
+                        "expected block {}, found block {number} on line {}",
+                        starknet.block_context.block_number.0,
+                        index + 1
+                    );
+                }
+
+                block.number = Some(number);
+            }
+
+            ChainRecord::Class { class_hash, class } => {
+                classes.insert(class_hash, class);
+            }
+
+            ChainRecord::Transaction { transaction, .. } => {
+                let number = block
+                    .number
+                    .ok_or(anyhow!("transaction before any block on line {}", index + 1))?;
+                let transaction_hash = *transaction.transaction_hash();
+
+                let class = declared_class_hash(&transaction).and_then(|hash| {
+                    classes.remove(&hash).map(|class| {
+                        if let ContractClass::Sierra(sierra_class) = &class {
+                            block.sierra_classes.push((hash, sierra_class.clone()));
+                        }
+                        class
+                    })
+                });
+
+                let transaction = rpc_to_blockifier_transaction(transaction, class)
+                    .with_context(|| format!("invalid transaction in block {number}"))?;
+                block.entries.push(BlockEntry::Transaction(transaction_hash, transaction));
+            }
+
+            ChainRecord::Mint { address, amount } => {
+                if block.number.is_none() {
+                    bail!("mint before any block on line {}", index + 1);
+                }
+                block.entries.push(BlockEntry::Mint(address, amount));
+            }
+        }
+    }
+
+    match block.number {
+        Some(number) => {
+            close_block(starknet, &mut block)?;
+            Ok(number)
+        }
+        None => Ok(0),
+    }
+}
+
+/// The records of the block being imported.
+#[derive(Default)]
+struct PendingBlock {
+    number: Option<u64>,
+    entries: Vec<BlockEntry>,
+    sierra_classes: Vec<(FieldElement, FlattenedSierraClass)>,
+}
+
+/// A transaction or a mint of the block being imported, in execution order.
+enum BlockEntry {
+    Transaction(FieldElement, BlockifierTransaction),
+    Mint(FieldElement, FieldElement),
+}
+
+/// Executes the transactions and mints of the pending block, mines it and stores the Sierra
+/// classes it declares.
+fn close_block(starknet: &mut StarknetWrapper, block: &mut PendingBlock) -> Result<()> {
+    let mut transaction_hashes = Vec::new();
+    let mut transactions = Vec::new();
+
+    for entry in block.entries.drain(..) {
+        match entry {
+            BlockEntry::Transaction(hash, transaction) => {
+                transaction_hashes.push(hash);
+                transactions.push(transaction);
+            }
+            BlockEntry::Mint(address, amount) => {
+                // The transactions before the mint are executed first, as they were on the
+                // exporting node.
+                starknet.handle_transactions(std::mem::take(&mut transactions));
+                let amount = starkfelt_to_u128(amount.into())?;
+                starknet.mint(ContractAddress(patricia_key!(address)), amount)?;
+            }
+        }
+    }
+    starknet.handle_transactions(transactions);
+
+    for transaction_hash in transaction_hashes {
+        let status = starknet
+            .transactions
+            .by_hash(&TransactionHash(transaction_hash.into()))
+            .map(|tx| tx.status);
+        if status != Some(TransactionStatus::Pending) {
+            bail!(
+                "transaction {transaction_hash:#x} of block {} was not accepted on re-execution",
+                block.number.unwrap_or_default()
+            );
+        }
+    }
+
+    starknet.generate_latest_block();
+
+    for (class_hash, sierra_class) in block.sierra_classes.drain(..) {
+        starknet
+            .state
+            .classes
+            .entry(ClassHash(class_hash.into()))
+            .and_modify(|r| r.sierra_class = Some(sierra_class));
+    }
+
+    Ok(())
+}
+
+fn write_record<W: Write>(writer: &mut W, record: &ChainRecord) -> Result<()> {
+    serde_json::to_writer(&mut *writer, record)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
@@ -0,0 +1,6 @@
+mod client;
+pub mod export;
+mod node;
+pub mod replay;
+
+pub use node::{KatanaNode, NodeBuilder};
@@ -1,56 +1,127 @@
+use std::fs::File;
+use std::io::BufWriter;
 use std::process::exit;
-use std::sync::Arc;
 
 use clap::Parser;
-use env_logger::Env;
-use katana_core::sequencer::KatanaSequencer;
-use katana_rpc::{spawn, KatanaApi, NodeHandle, StarknetApi};
-use log::error;
+use katana::export::export_chain;
+use katana::replay::replay;
+use katana::NodeBuilder;
+use tracing::{error, info};
+use tracing_subscriber::EnvFilter;
 use yansi::Paint;
 
 mod args;
 
-use args::KatanaArgs;
+use args::{Commands, ExportArgs, KatanaArgs, LogFormat, ReplayArgs};
+
+const DEFAULT_LOG_FILTER: &str =
+    "info,katana_rpc=debug,katana_core=trace,blockifier=off,jsonrpsee_server=off,hyper=off";
 
 #[tokio::main]
 async fn main() {
-    env_logger::Builder::from_env(Env::default().default_filter_or(
-        "info,katana_rpc=debug,katana_core=trace,blockifier=off,jsonrpsee_server=off,hyper=off",
-    ))
-    .init();
+    let mut config = KatanaArgs::parse();
+
+    init_logger(config.log_format);
 
-    let config = KatanaArgs::parse();
+    match config.command.take() {
+        Some(Commands::Replay(args)) => return run_replay(args).await,
+        Some(Commands::Export(args)) => return run_export(args).await,
+        None => {}
+    }
 
-    let server_config = config.server_config();
-    let sequencer_config = config.sequencer_config();
-    let starknet_config = config.starknet_config();
+    let mut builder = NodeBuilder::new()
+        .server_config(config.server_config())
+        .sequencer_config(config.sequencer_config())
+        .starknet_config(config.starknet_config());
 
-    let sequencer = Arc::new(KatanaSequencer::new(sequencer_config, starknet_config));
-    let starknet_api = StarknetApi::new(sequencer.clone());
-    let katana_api = KatanaApi::new(sequencer.clone());
+    if let Some(path) = config.import.clone() {
+        builder = builder.import(path);
+    }
 
-    match spawn(katana_api, starknet_api, server_config).await {
-        Ok(NodeHandle { addr, handle, .. }) => {
-            if !config.silent {
-                let accounts = sequencer.starknet.read().await.predeployed_accounts.display();
+    let node = builder.start().await;
+
+    match node {
+        Ok(node) => {
+            if config.log_format == LogFormat::Json {
+                info!(address = %node.addr(), "JSON-RPC server started");
+            } else if !config.silent {
+                let accounts =
+                    node.sequencer().starknet.read().await.predeployed_accounts.display();
 
                 print_intro(
                     accounts,
                     config.starknet.seed,
-                    format!("🚀 JSON-RPC server started: {}", Paint::red(format!("http://{addr}"))),
+                    format!(
+                        "🚀 JSON-RPC server started: {}",
+                        Paint::red(format!("http://{}", node.addr()))
+                    ),
                 );
             }
 
-            sequencer.start().await;
-            handle.stopped().await;
+            node.stopped().await;
         }
         Err(err) => {
-            error! {"{}", err};
+            error!("{err:#}");
             exit(1);
         }
     };
 }
 
+/// Initializes the logger, filtered by `RUST_LOG` if it is set. In the JSON format, the fields
+/// of the events, such as the transaction hash or the block number, are written as separate keys
+/// next to the message.
+fn init_logger(format: LogFormat) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match format {
+        LogFormat::Full => builder.init(),
+        LogFormat::Json => builder.json().flatten_event(true).init(),
+    }
+}
+
+async fn run_replay(args: ReplayArgs) {
+    let auth_token = args.api_key.as_deref().or(args.jwt.as_deref());
+    let config = args.starknet.starknet_config();
+
+    match replay(&args.rpc, auth_token, config, args.from, args.to).await {
+        Ok(divergences) if divergences.is_empty() => {
+            info!("Replayed blocks {} to {} without divergence", args.from, args.to);
+        }
+        Ok(divergences) => {
+            for divergence in &divergences {
+                error!("{divergence}");
+            }
+            error!("Found {} divergence(s)", divergences.len());
+            exit(1);
+        }
+        Err(err) => {
+            error!("{err}");
+            exit(1);
+        }
+    }
+}
+
+async fn run_export(args: ExportArgs) {
+    let file = match File::create(&args.out) {
+        Ok(file) => file,
+        Err(err) => {
+            error!("Failed to create {}: {err}", args.out.display());
+            exit(1);
+        }
+    };
+
+    let auth_token = args.api_key.as_deref().or(args.jwt.as_deref());
+    match export_chain(&args.rpc, auth_token, BufWriter::new(file)).await {
+        Ok(blocks) => info!("Exported {blocks} block(s) to {}", args.out.display()),
+        Err(err) => {
+            error!("{err}");
+            exit(1);
+        }
+    }
+}
+
 fn print_intro(accounts: String, seed: String, address: String) {
     println!(
         "{}",
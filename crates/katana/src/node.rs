@@ -0,0 +1,149 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use katana_core::backend::block::StarknetBlock;
+use katana_core::backend::config::StarknetConfig;
+use katana_core::sequencer::{KatanaSequencer, SequencerConfig};
+use katana_rpc::config::ServerConfig;
+use katana_rpc::{spawn, FaucetApi, KatanaApi, NodeHandle, StarknetApi};
+use tokio::sync::broadcast;
+
+use crate::export::import_chain;
+
+/// Builder for starting a Katana node in-process.
+///
+/// By default, the node uses the default sequencer and Starknet configurations, and its RPC
+/// server listens on an OS-assigned port on `localhost`.
+pub struct NodeBuilder {
+    sequencer_config: SequencerConfig,
+    starknet_config: StarknetConfig,
+    server_config: ServerConfig,
+    import_path: Option<PathBuf>,
+}
+
+impl NodeBuilder {
+    pub fn new() -> Self {
+        Self {
+            sequencer_config: SequencerConfig::default(),
+            starknet_config: StarknetConfig::default(),
+            server_config: ServerConfig { port: 0, host: "localhost".into(), ..Default::default() },
+            import_path: None,
+        }
+    }
+
+    pub fn sequencer_config(mut self, config: SequencerConfig) -> Self {
+        self.sequencer_config = config;
+        self
+    }
+
+    pub fn starknet_config(mut self, config: StarknetConfig) -> Self {
+        self.starknet_config = config;
+        self
+    }
+
+    pub fn server_config(mut self, config: ServerConfig) -> Self {
+        self.server_config = config;
+        self
+    }
+
+    /// Rebuilds the chain from a file written by `katana export` before starting the node.
+    pub fn import(mut self, path: PathBuf) -> Self {
+        self.import_path = Some(path);
+        self
+    }
+
+    /// Spawns the RPC server and starts the sequencer.
+    pub async fn start(self) -> Result<KatanaNode> {
+        let sequencer =
+            Arc::new(KatanaSequencer::new(self.sequencer_config, self.starknet_config)?);
+
+        if let Some(path) = self.import_path {
+            let file = File::open(&path)
+                .with_context(|| format!("failed to open chain export {}", path.display()))?;
+            import_chain(&mut *sequencer.starknet.write().await, BufReader::new(file))?;
+        }
+
+        let starknet_api = StarknetApi::new(sequencer.clone());
+        let katana_api = KatanaApi::new(sequencer.clone());
+        let faucet_api = FaucetApi::new(sequencer.clone());
+        let handle = spawn(katana_api, starknet_api, faucet_api, self.server_config).await?;
+
+        sequencer.start().await;
+
+        Ok(KatanaNode { sequencer, handle })
+    }
+}
+
+impl Default for NodeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A running Katana node.
+pub struct KatanaNode {
+    sequencer: Arc<KatanaSequencer>,
+    handle: NodeHandle,
+}
+
+impl KatanaNode {
+    /// The address the RPC server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.handle.addr
+    }
+
+    /// Direct access to the sequencer, bypassing the RPC server.
+    pub fn sequencer(&self) -> Arc<KatanaSequencer> {
+        self.sequencer.clone()
+    }
+
+    /// Returns a receiver which yields every new block mined by the node.
+    pub async fn subscribe_blocks(&self) -> broadcast::Receiver<StarknetBlock> {
+        self.sequencer.subscribe_blocks().await
+    }
+
+    /// Stops producing blocks and executing queued transactions, and signals the RPC server to
+    /// shut down.
+    pub fn stop(&self) -> Result<()> {
+        self.sequencer.stop();
+        Ok(self.handle.handle.stop()?)
+    }
+
+    /// Waits until the RPC server has stopped.
+    pub async fn stopped(self) {
+        self.handle.handle.stopped().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use katana_core::sequencer::Sequencer;
+    use starknet_api::block::BlockNumber;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn start_and_subscribe_to_blocks() {
+        let node = NodeBuilder::new().start().await.unwrap();
+        let mut blocks = node.subscribe_blocks().await;
+
+        assert_eq!(node.sequencer().block_number().await, BlockNumber(0));
+
+        {
+            let sequencer = node.sequencer();
+            let mut starknet = sequencer.mut_starknet().await;
+            starknet.generate_latest_block();
+            starknet.generate_pending_block();
+        }
+
+        let block = blocks.recv().await.unwrap();
+        assert_eq!(block.block_number(), BlockNumber(1));
+
+        node.stop().unwrap();
+        node.stopped().await;
+    }
+}
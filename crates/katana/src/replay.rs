@@ -0,0 +1,467 @@
+//! Re-executes the blocks of a remote chain locally and reports where the local execution
+//! diverges from the remote one.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+
+use anyhow::{bail, Context, Result};
+use blockifier::block_context::BlockContext;
+use blockifier::execution::contract_class::ContractClass as InnerContractClass;
+use blockifier::state::cached_state::CachedState;
+use blockifier::state::errors::StateError;
+use blockifier::state::state_api::{StateReader, StateResult};
+use blockifier::transaction::errors::TransactionExecutionError;
+use blockifier::transaction::objects::TransactionExecutionInfo;
+use blockifier::transaction::transaction_execution::Transaction as BlockifierTransaction;
+use blockifier::transaction::transactions::ExecutableTransaction;
+use katana_core::backend::add_balance;
+use katana_core::backend::config::StarknetConfig;
+use katana_core::backend::transaction::execution_events;
+use katana_core::block_context::FeeTokenAddresses;
+use katana_core::utils::contract::{
+    compile_sierra_class, compiled_class_hash, legacy_rpc_to_inner_class,
+};
+use katana_core::utils::transaction::{account_transaction_version, rpc_to_blockifier_transaction};
+use katana_core::utils::{convert_state_diff_to_rpc_state_diff, starkfelt_to_u128};
+use serde_json::json;
+use starknet::core::types::{
+    BlockId, ContractClass, DeclareTransaction, Event, FieldElement, MaybePendingBlockWithTxs,
+    MaybePendingTransactionReceipt, PendingTransactionReceipt, StarknetError, StateDiff,
+    Transaction, TransactionReceipt,
+};
+use starknet::core::utils::parse_cairo_short_string;
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::{JsonRpcClient, Provider, ProviderError};
+use starknet_api::block::{BlockNumber, BlockTimestamp};
+use starknet_api::core::{
+    ChainId, ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey,
+};
+use starknet_api::hash::{StarkFelt, StarkHash};
+use starknet_api::state::StorageKey;
+use starknet_api::{patricia_key, stark_felt};
+use tokio::runtime::Handle;
+use url::Url;
+
+use crate::client::{block_mints, call, http_client, BlockMint};
+
+/// A difference between the remote execution of a transaction or block and its local
+/// re-execution.
+#[derive(Debug)]
+pub enum Divergence {
+    /// The transaction is part of the remote block but failed locally.
+    Rejected { block_number: u64, transaction_hash: FieldElement, error: String },
+    /// The transaction was charged a different fee.
+    ActualFee {
+        block_number: u64,
+        transaction_hash: FieldElement,
+        remote: FieldElement,
+        local: FieldElement,
+    },
+    /// The transaction emitted different events.
+    Events {
+        block_number: u64,
+        transaction_hash: FieldElement,
+        remote: Vec<Event>,
+        local: Vec<Event>,
+    },
+    /// The block resulted in a different state diff.
+    StateDiff { block_number: u64, remote: StateDiff, local: StateDiff },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Rejected { block_number, transaction_hash, error } => write!(
+                f,
+                "Block {block_number} | Transaction {transaction_hash:#x} failed locally: {error}"
+            ),
+            Divergence::ActualFee { block_number, transaction_hash, remote, local } => write!(
+                f,
+                "Block {block_number} | Transaction {transaction_hash:#x} actual fee differs | \
+                 Remote: {remote} | Local: {local}"
+            ),
+            Divergence::Events { block_number, transaction_hash, remote, local } => write!(
+                f,
+                "Block {block_number} | Transaction {transaction_hash:#x} events differ | Remote: \
+                 {remote:?} | Local: {local:?}"
+            ),
+            Divergence::StateDiff { block_number, remote, local } => write!(
+                f,
+                "Block {block_number} | State diff differs | Remote: {remote:?} | Local: {local:?}"
+            ),
+        }
+    }
+}
+
+/// Re-executes the blocks `from..=to` of the chain served at `rpc` and returns every divergence
+/// between the remote and the local execution. `auth_token` is sent as a bearer token to nodes
+/// which require an API key or a JWT.
+///
+/// Each block is executed on the remote state of the block preceding it, with the sequencer
+/// address and the timestamp of the remote block. The gas price of the block is read from the
+/// fee breakdown of Katana receipts, and is the one of `starknet_config` on other nodes, whose
+/// configuration also sets the fee tokens and the execution limits.
+///
+/// The state is read while the transactions are executed, which blocks the current thread, so
+/// the replay must run on a multi-threaded runtime.
+pub async fn replay(
+    rpc: &Url,
+    auth_token: Option<&str>,
+    starknet_config: StarknetConfig,
+    from: u64,
+    to: u64,
+) -> Result<Vec<Divergence>> {
+    if from == 0 {
+        bail!("the genesis block cannot be replayed");
+    }
+    if from > to {
+        bail!("invalid block range: {from} > {to}");
+    }
+
+    let client = http_client(auth_token)?;
+    let provider = JsonRpcClient::new(HttpTransport::new_with_client(rpc.clone(), client.clone()));
+
+    let chain_id = parse_cairo_short_string(&provider.chain_id().await?)?;
+    let fee_token_addresses = starknet_config.fee_token_addresses();
+
+    // Classes never change once declared, so they are only fetched and compiled once.
+    let mut classes = HashMap::new();
+    let mut divergences = Vec::new();
+
+    for block_number in from..=to {
+        let block = match provider.get_block_with_txs(BlockId::Number(block_number)).await? {
+            MaybePendingBlockWithTxs::Block(block) => block,
+            MaybePendingBlockWithTxs::PendingBlock(_) => {
+                bail!("block {block_number} is still pending")
+            }
+        };
+
+        let mut block_context = starknet_config.block_context();
+        block_context.chain_id = ChainId(chain_id.clone());
+        block_context.block_number = BlockNumber(block_number);
+        block_context.block_timestamp = BlockTimestamp(block.timestamp);
+        block_context.sequencer_address = ContractAddress(patricia_key!(block.sequencer_address));
+        if let Some(transaction) = block.transactions.first() {
+            if let Some(gas_price) =
+                receipt_gas_price(&client, rpc, *transaction.transaction_hash()).await?
+            {
+                block_context.gas_price = gas_price;
+            }
+        }
+
+        let mut state = CachedState::new(RemoteState {
+            provider: &provider,
+            block_id: BlockId::Number(block_number - 1),
+            classes: &mut classes,
+        });
+
+        let mut mints = block_mints(&client, rpc, block_number).await?.into_iter().peekable();
+
+        for (index, transaction) in block.transactions.into_iter().enumerate() {
+            while let Some(mint) = mints.next_if(|mint| mint.transaction_index <= index) {
+                apply_mint(&mut state, &fee_token_addresses, mint)?;
+            }
+
+            let transaction_hash = *transaction.transaction_hash();
+            let contract_class = match declared_class_hash(&transaction) {
+                Some(class_hash) => {
+                    Some(provider.get_class(BlockId::Number(block_number), class_hash).await?)
+                }
+                None => None,
+            };
+            let transaction = rpc_to_blockifier_transaction(transaction, contract_class)
+                .with_context(|| format!("invalid transaction in block {block_number}"))?;
+
+            let mut transaction_context = block_context.clone();
+            if let BlockifierTransaction::AccountTransaction(tx) = &transaction {
+                let fee_token = starknet_config.fee_token(account_transaction_version(tx));
+                transaction_context.fee_token_address = fee_token_addresses.get(fee_token);
+            }
+
+            let execution_info = match tokio::task::block_in_place(|| {
+                execute_transaction(transaction, &mut state, &transaction_context)
+            }) {
+                Ok(info) if info.revert_error.is_none() => info,
+                Ok(info) => {
+                    let error = info.revert_error.unwrap_or_default();
+                    divergences.push(Divergence::Rejected {
+                        block_number,
+                        transaction_hash,
+                        error,
+                    });
+                    continue;
+                }
+                Err(err) => {
+                    let error = format!("{err:?}");
+                    divergences.push(Divergence::Rejected {
+                        block_number,
+                        transaction_hash,
+                        error,
+                    });
+                    continue;
+                }
+            };
+
+            let remote = provider.get_transaction_receipt(transaction_hash).await?;
+            let (remote_events, remote_fee) = receipt_events_and_fee(remote);
+
+            let local_fee = FieldElement::from(stark_felt!(execution_info.actual_fee.0));
+            if local_fee != remote_fee {
+                divergences.push(Divergence::ActualFee {
+                    block_number,
+                    transaction_hash,
+                    remote: remote_fee,
+                    local: local_fee,
+                });
+            }
+
+            let local_events = execution_events(&execution_info)
+                .into_iter()
+                .map(|e| Event {
+                    from_address: (*e.from_address.0.key()).into(),
+                    keys: e.content.keys.into_iter().map(|k| k.0.into()).collect(),
+                    data: e.content.data.0.into_iter().map(|d| d.into()).collect(),
+                })
+                .collect::<Vec<_>>();
+
+            if !same_events(&local_events, &remote_events) {
+                divergences.push(Divergence::Events {
+                    block_number,
+                    transaction_hash,
+                    remote: remote_events,
+                    local: local_events,
+                });
+            }
+        }
+
+        for mint in mints {
+            apply_mint(&mut state, &fee_token_addresses, mint)?;
+        }
+
+        let remote = provider.get_state_update(BlockId::Number(block_number)).await?.state_diff;
+        let local = convert_state_diff_to_rpc_state_diff(state.to_state_diff());
+
+        if NormalizedStateDiff::from(&remote) != NormalizedStateDiff::from(&local) {
+            divergences.push(Divergence::StateDiff { block_number, remote, local });
+        }
+    }
+
+    Ok(divergences)
+}
+
+/// Returns the hash of the class declared by `transaction`, if it is a declare transaction.
+pub(crate) fn declared_class_hash(transaction: &Transaction) -> Option<FieldElement> {
+    match transaction {
+        Transaction::Declare(DeclareTransaction::V1(tx)) => Some(tx.class_hash),
+        Transaction::Declare(DeclareTransaction::V2(tx)) => Some(tx.class_hash),
+        _ => None,
+    }
+}
+
+fn execute_transaction<S: StateReader>(
+    transaction: BlockifierTransaction,
+    state: &mut CachedState<S>,
+    block_context: &BlockContext,
+) -> Result<TransactionExecutionInfo, TransactionExecutionError> {
+    match transaction {
+        BlockifierTransaction::AccountTransaction(tx) => tx.execute(state, block_context),
+        BlockifierTransaction::L1HandlerTransaction(tx) => tx.execute(state, block_context),
+    }
+}
+
+fn apply_mint(
+    state: &mut CachedState<RemoteState<'_>>,
+    fee_token_addresses: &FeeTokenAddresses,
+    mint: BlockMint,
+) -> Result<()> {
+    let address = ContractAddress(patricia_key!(mint.address));
+    let amount = starkfelt_to_u128(mint.amount.into())?;
+    tokio::task::block_in_place(|| add_balance(state, fee_token_addresses, address, amount))?;
+    Ok(())
+}
+
+/// Reads the gas price of the block of a transaction from the fee breakdown of its receipt,
+/// which is only returned by Katana.
+async fn receipt_gas_price(
+    client: &reqwest::Client,
+    rpc: &Url,
+    transaction_hash: FieldElement,
+) -> Result<Option<u128>> {
+    let receipt: Option<serde_json::Value> =
+        call(client, rpc, "starknet_getTransactionReceipt", json!([transaction_hash])).await?;
+
+    let Some(gas_price) = receipt.as_ref().and_then(|r| r.get("fee_breakdown")).map(|f| &f["gas_price"])
+    else {
+        return Ok(None);
+    };
+
+    let gas_price: FieldElement = serde_json::from_value(gas_price.clone())?;
+    Ok(Some(starkfelt_to_u128(gas_price.into())?))
+}
+
+/// The state of a remote chain at a block, read through its JSON-RPC API.
+struct RemoteState<'a> {
+    provider: &'a JsonRpcClient<HttpTransport>,
+    block_id: BlockId,
+    /// The classes read so far, along with their compiled class hash.
+    classes: &'a mut HashMap<ClassHash, (InnerContractClass, CompiledClassHash)>,
+}
+
+impl RemoteState<'_> {
+    /// Returns the class `class_hash` along with its compiled class hash, which is the class hash
+    /// itself for legacy classes.
+    fn class(
+        &mut self,
+        class_hash: ClassHash,
+    ) -> StateResult<(InnerContractClass, CompiledClassHash)> {
+        if let Some(class) = self.classes.get(&class_hash) {
+            return Ok(class.clone());
+        }
+
+        let class = match block_on(
+            self.provider.get_class(self.block_id, FieldElement::from(class_hash.0)),
+        ) {
+            Ok(class) => class,
+            Err(ProviderError::StarknetError(StarknetError::ClassHashNotFound)) => {
+                return Err(StateError::UndeclaredClassHash(class_hash));
+            }
+            Err(err) => return Err(read_error(err)),
+        };
+
+        let class = match class {
+            ContractClass::Legacy(class) => {
+                let (_, class) = legacy_rpc_to_inner_class(&class).map_err(read_error)?;
+                (class, CompiledClassHash(class_hash.0))
+            }
+            ContractClass::Sierra(class) => {
+                let casm_contract = compile_sierra_class(&class).map_err(read_error)?;
+                let compiled_hash = compiled_class_hash(&casm_contract).map_err(read_error)?;
+                let class = InnerContractClass::V1(casm_contract.try_into().map_err(read_error)?);
+                (class, CompiledClassHash(compiled_hash.into()))
+            }
+        };
+
+        self.classes.insert(class_hash, class.clone());
+        Ok(class)
+    }
+}
+
+impl StateReader for RemoteState<'_> {
+    fn get_storage_at(
+        &mut self,
+        contract_address: ContractAddress,
+        key: StorageKey,
+    ) -> StateResult<StarkFelt> {
+        let address = FieldElement::from(*contract_address.0.key());
+        let key = FieldElement::from(*key.0.key());
+        match block_on(self.provider.get_storage_at(address, key, self.block_id)) {
+            Ok(value) => Ok(value.into()),
+            Err(ProviderError::StarknetError(StarknetError::ContractNotFound)) => {
+                Ok(StarkFelt::default())
+            }
+            Err(err) => Err(read_error(err)),
+        }
+    }
+
+    fn get_nonce_at(&mut self, contract_address: ContractAddress) -> StateResult<Nonce> {
+        let address = FieldElement::from(*contract_address.0.key());
+        match block_on(self.provider.get_nonce(self.block_id, address)) {
+            Ok(nonce) => Ok(Nonce(nonce.into())),
+            Err(ProviderError::StarknetError(StarknetError::ContractNotFound)) => {
+                Ok(Nonce::default())
+            }
+            Err(err) => Err(read_error(err)),
+        }
+    }
+
+    fn get_compiled_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> StateResult<InnerContractClass> {
+        Ok(self.class(*class_hash)?.0)
+    }
+
+    fn get_class_hash_at(&mut self, contract_address: ContractAddress) -> StateResult<ClassHash> {
+        let address = FieldElement::from(*contract_address.0.key());
+        match block_on(self.provider.get_class_hash_at(self.block_id, address)) {
+            Ok(class_hash) => Ok(ClassHash(class_hash.into())),
+            Err(ProviderError::StarknetError(StarknetError::ContractNotFound)) => {
+                Ok(ClassHash::default())
+            }
+            Err(err) => Err(read_error(err)),
+        }
+    }
+
+    fn get_compiled_class_hash(&mut self, class_hash: ClassHash) -> StateResult<CompiledClassHash> {
+        Ok(self.class(class_hash)?.1)
+    }
+}
+
+/// Waits on `future` from the synchronous state reader, which must be called inside
+/// [tokio::task::block_in_place].
+fn block_on<F: Future>(future: F) -> F::Output {
+    Handle::current().block_on(future)
+}
+
+fn read_error(err: impl fmt::Display) -> StateError {
+    StateError::StateReadError(err.to_string())
+}
+
+fn receipt_events_and_fee(receipt: MaybePendingTransactionReceipt) -> (Vec<Event>, FieldElement) {
+    match receipt {
+        MaybePendingTransactionReceipt::Receipt(receipt) => match receipt {
+            TransactionReceipt::Invoke(r) => (r.events, r.actual_fee),
+            TransactionReceipt::L1Handler(r) => (r.events, r.actual_fee),
+            TransactionReceipt::Declare(r) => (r.events, r.actual_fee),
+            TransactionReceipt::Deploy(r) => (r.events, r.actual_fee),
+            TransactionReceipt::DeployAccount(r) => (r.events, r.actual_fee),
+        },
+        MaybePendingTransactionReceipt::PendingReceipt(receipt) => match receipt {
+            PendingTransactionReceipt::Invoke(r) => (r.events, r.actual_fee),
+            PendingTransactionReceipt::L1Handler(r) => (r.events, r.actual_fee),
+            PendingTransactionReceipt::Declare(r) => (r.events, r.actual_fee),
+            PendingTransactionReceipt::Deploy(r) => (r.events, r.actual_fee),
+            PendingTransactionReceipt::DeployAccount(r) => (r.events, r.actual_fee),
+        },
+    }
+}
+
+fn same_events(a: &[Event], b: &[Event]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| a.from_address == b.from_address && a.keys == b.keys && a.data == b.data)
+}
+
+/// An order-independent view of a [StateDiff].
+#[derive(Debug, PartialEq, Eq)]
+struct NormalizedStateDiff {
+    storage: BTreeMap<(FieldElement, FieldElement), FieldElement>,
+    declared_classes: BTreeMap<FieldElement, FieldElement>,
+    deployed_contracts: BTreeMap<FieldElement, FieldElement>,
+    nonces: BTreeMap<FieldElement, FieldElement>,
+}
+
+impl From<&StateDiff> for NormalizedStateDiff {
+    fn from(diff: &StateDiff) -> Self {
+        Self {
+            storage: diff
+                .storage_diffs
+                .iter()
+                .flat_map(|d| d.storage_entries.iter().map(|e| ((d.address, e.key), e.value)))
+                .collect(),
+            declared_classes: diff
+                .declared_classes
+                .iter()
+                .map(|c| (c.class_hash, c.compiled_class_hash))
+                .collect(),
+            deployed_contracts: diff
+                .deployed_contracts
+                .iter()
+                .map(|c| (c.address, c.class_hash))
+                .collect(),
+            nonces: diff.nonces.iter().map(|n| (n.contract_address, n.nonce)).collect(),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use blockifier::abi::abi_utils::get_storage_var_address;
+use blockifier::state::state_api::StateReader;
+use katana::export::{export_chain, import_chain, ChainRecord};
+use katana::NodeBuilder;
+use katana_core::backend::config::StarknetConfig;
+use katana_core::backend::StarknetWrapper;
+use katana_core::constants::{DEFAULT_ACCOUNT_CONTRACT_CLASS_HASH, FEE_TOKEN_ADDRESS};
+use katana_core::utils::transaction::compute_deploy_account_v1_transaction_hash;
+use katana_rpc::config::{AuthConfig, ServerConfig};
+use serde_json::json;
+use starknet::accounts::{Account, Call, SingleOwnerAccount};
+use starknet::core::types::{FieldElement, TransactionStatus};
+use starknet::core::utils::{
+    cairo_short_string_to_felt, get_contract_address, get_selector_from_name,
+};
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::JsonRpcClient;
+use starknet::signers::{LocalWallet, SigningKey};
+use starknet_api::block::BlockNumber;
+use starknet_api::core::{ContractAddress, PatriciaKey};
+use starknet_api::hash::StarkHash;
+use starknet_api::patricia_key;
+use starknet_api::transaction::TransactionHash;
+use url::Url;
+
+fn test_starknet_config() -> StarknetConfig {
+    StarknetConfig { total_accounts: 2, ..Default::default() }
+}
+
+#[tokio::test]
+async fn test_export_and_import_chain() {
+    let node = NodeBuilder::new().starknet_config(test_starknet_config()).start().await.unwrap();
+    let url = Url::parse(&format!("http://{}", node.addr())).unwrap();
+
+    let (sender, recipient) = {
+        let sequencer = node.sequencer();
+        let starknet = sequencer.starknet.read().await;
+        let accounts = &starknet.predeployed_accounts.accounts;
+        (accounts[0].clone(), accounts[1].clone())
+    };
+
+    let account = SingleOwnerAccount::new(
+        JsonRpcClient::new(HttpTransport::new(url.clone())),
+        LocalWallet::from_signing_key(SigningKey::from_secret_scalar(sender.private_key.into())),
+        (*sender.account_address.0.key()).into(),
+        cairo_short_string_to_felt("KATANA").unwrap(),
+    );
+
+    let mut transaction_hashes = Vec::new();
+    for _ in 0..2 {
+        let result = account
+            .execute(vec![Call {
+                to: (*FEE_TOKEN_ADDRESS).into(),
+                selector: get_selector_from_name("transfer").unwrap(),
+                calldata: vec![
+                    (*recipient.account_address.0.key()).into(),
+                    FieldElement::from(100_u32),
+                    FieldElement::ZERO,
+                ],
+            }])
+            .send()
+            .await
+            .unwrap();
+        transaction_hashes.push(result.transaction_hash);
+    }
+
+    let mut export = Vec::new();
+    let blocks = export_chain(&url, None, &mut export).await.unwrap();
+    assert_eq!(blocks, 2);
+
+    let records = std::str::from_utf8(&export)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<ChainRecord>(line).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(records.len(), 5, "a header, and a block and a transaction per block");
+    assert!(matches!(records[0], ChainRecord::Header { latest_block_number: 2, .. }));
+    assert!(matches!(records[1], ChainRecord::Block { block_number: 1, .. }));
+    assert!(matches!(records[2], ChainRecord::Transaction { .. }));
+
+    let mut starknet = StarknetWrapper::new(test_starknet_config()).unwrap();
+    assert_eq!(import_chain(&mut starknet, export.as_slice()).unwrap(), 2);
+    assert_eq!(starknet.blocks.current_block_number(), BlockNumber(2));
+    assert!(starknet.config.auto_mine, "auto mining must be restored after the import");
+
+    for hash in transaction_hashes {
+        let tx = starknet.transactions.by_hash(&TransactionHash(hash.into())).unwrap();
+        assert_eq!(tx.status, TransactionStatus::AcceptedOnL2);
+    }
+
+    let balance_key =
+        get_storage_var_address("ERC20_balances", &[*recipient.account_address.0.key()]).unwrap();
+    let fee_token = starknet.fee_token_addresses.eth;
+    let expected = node
+        .sequencer()
+        .starknet
+        .read()
+        .await
+        .latest_state()
+        .get_storage_at(fee_token, balance_key)
+        .unwrap();
+    assert_eq!(starknet.latest_state().get_storage_at(fee_token, balance_key).unwrap(), expected);
+
+    // The chain can only be imported on a fresh node.
+    assert!(import_chain(&mut starknet, export.as_slice()).is_err());
+
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+async fn test_export_and_import_faucet_mints() {
+    let node = NodeBuilder::new()
+        .starknet_config(test_starknet_config())
+        .server_config(ServerConfig {
+            port: 0,
+            host: "localhost".into(),
+            faucet: true,
+            auth: Some(AuthConfig::ApiKey("key".into())),
+            ..Default::default()
+        })
+        .start()
+        .await
+        .unwrap();
+    let url = Url::parse(&format!("http://{}", node.addr())).unwrap();
+
+    let signer = SigningKey::from_secret_scalar(FieldElement::from(0x1337_u32));
+    let class_hash = FieldElement::from(*DEFAULT_ACCOUNT_CONTRACT_CLASS_HASH);
+    let salt = FieldElement::from(42_u32);
+    let calldata = vec![signer.verifying_key().scalar()];
+    let max_fee = FieldElement::from_hex_be("0x2386f26fc10000").unwrap();
+
+    let address = get_contract_address(salt, class_hash, &calldata, FieldElement::ZERO);
+    let transaction_hash = compute_deploy_account_v1_transaction_hash(
+        address,
+        &calldata,
+        class_hash,
+        salt,
+        max_fee,
+        cairo_short_string_to_felt("KATANA").unwrap(),
+        FieldElement::ZERO,
+    );
+    let signature = signer.sign(&transaction_hash).unwrap();
+
+    let res = reqwest::Client::new()
+        .post(url.join("/faucet").unwrap())
+        .header("Content-Type", "application/json")
+        .header("Authorization", "Bearer key")
+        .body(
+            json!({
+                "type": "DEPLOY_ACCOUNT",
+                "version": "0x1",
+                "class_hash": format!("{class_hash:#x}"),
+                "contract_address_salt": format!("{salt:#x}"),
+                "constructor_calldata": calldata.iter().map(|c| format!("{c:#x}")).collect::<Vec<_>>(),
+                "max_fee": format!("{max_fee:#x}"),
+                "nonce": "0x0",
+                "signature": [format!("{:#x}", signature.r), format!("{:#x}", signature.s)]
+            })
+            .to_string(),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    assert!(export_chain(&url, None, &mut Vec::new()).await.is_err());
+
+    let mut export = Vec::new();
+    let blocks = export_chain(&url, Some("key"), &mut export).await.unwrap();
+    assert_eq!(blocks, 2, "the mint and the deployment are mined in their own block");
+
+    let records = std::str::from_utf8(&export)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<ChainRecord>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert!(matches!(records[1], ChainRecord::Block { block_number: 1, .. }));
+    assert!(matches!(records[2], ChainRecord::Mint { address: a, .. } if a == address));
+    assert!(matches!(records[3], ChainRecord::Block { block_number: 2, .. }));
+    assert!(matches!(records[4], ChainRecord::Transaction { .. }));
+
+    let mut starknet = StarknetWrapper::new(test_starknet_config()).unwrap();
+    assert_eq!(import_chain(&mut starknet, export.as_slice()).unwrap(), 2);
+
+    let tx = starknet.transactions.by_hash(&TransactionHash(transaction_hash.into())).unwrap();
+    assert_eq!(tx.status, TransactionStatus::AcceptedOnL2);
+
+    let address = ContractAddress(patricia_key!(address));
+    assert_eq!(starknet.latest_state().get_class_hash_at(address).unwrap().0, class_hash.into());
+
+    let balance_key = get_storage_var_address("ERC20_balances", &[*address.0.key()]).unwrap();
+    let mut expected = node.sequencer().starknet.read().await.latest_state();
+    for fee_token in starknet.fee_token_addresses.all() {
+        assert_eq!(
+            starknet.latest_state().get_storage_at(fee_token, balance_key).unwrap(),
+            expected.get_storage_at(fee_token, balance_key).unwrap()
+        );
+    }
+
+    node.stop().unwrap();
+}
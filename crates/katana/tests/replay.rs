@@ -0,0 +1,71 @@
+use katana::replay::replay;
+use katana::NodeBuilder;
+use katana_core::backend::config::StarknetConfig;
+use katana_core::constants::FEE_TOKEN_ADDRESS;
+use starknet::accounts::{Account, Call, SingleOwnerAccount};
+use starknet::core::types::FieldElement;
+use starknet::core::utils::{cairo_short_string_to_felt, get_selector_from_name};
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::JsonRpcClient;
+use starknet::signers::{LocalWallet, SigningKey};
+use url::Url;
+
+fn test_starknet_config() -> StarknetConfig {
+    StarknetConfig { total_accounts: 2, ..Default::default() }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_replay_local_chain() {
+    let mut config = test_starknet_config();
+    config.env.gas_price = 10;
+    let remote = NodeBuilder::new().starknet_config(config).start().await.unwrap();
+    let url = Url::parse(&format!("http://{}", remote.addr())).unwrap();
+
+    let (sender, recipient) = {
+        let sequencer = remote.sequencer();
+        let starknet = sequencer.starknet.read().await;
+        let accounts = &starknet.predeployed_accounts.accounts;
+        (accounts[0].clone(), accounts[1].clone())
+    };
+
+    let account = SingleOwnerAccount::new(
+        JsonRpcClient::new(HttpTransport::new(url.clone())),
+        LocalWallet::from_signing_key(SigningKey::from_secret_scalar(sender.private_key.into())),
+        (*sender.account_address.0.key()).into(),
+        cairo_short_string_to_felt("KATANA").unwrap(),
+    );
+
+    for _ in 0..2 {
+        account
+            .execute(vec![Call {
+                to: (*FEE_TOKEN_ADDRESS).into(),
+                selector: get_selector_from_name("transfer").unwrap(),
+                calldata: vec![
+                    (*recipient.account_address.0.key()).into(),
+                    FieldElement::from(100_u32),
+                    FieldElement::ZERO,
+                ],
+            }])
+            .send()
+            .await
+            .unwrap();
+    }
+
+    // The gas price of the remote blocks is used instead of the configured one.
+    let divergences = replay(&url, None, test_starknet_config(), 1, 2).await.unwrap();
+    assert!(divergences.is_empty(), "unexpected divergences: {divergences:?}");
+
+    // The second block is executed on the remote state left by the first one.
+    let divergences = replay(&url, None, test_starknet_config(), 2, 2).await.unwrap();
+    assert!(divergences.is_empty(), "unexpected divergences: {divergences:?}");
+
+    // Charging the fees in another token must diverge.
+    let mut config = test_starknet_config();
+    config.env.strk_fee_versions = vec![1];
+    let divergences = replay(&url, None, config, 1, 2).await.unwrap();
+    assert!(!divergences.is_empty(), "replay with another fee token must diverge");
+
+    assert!(replay(&url, None, test_starknet_config(), 0, 2).await.is_err());
+
+    remote.stop().unwrap();
+}
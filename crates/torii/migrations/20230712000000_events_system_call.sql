@@ -0,0 +1,19 @@
+-- Events emitted outside of a system call (eg when registering a component) have no system call.
+CREATE TABLE events_new (
+    id TEXT NOT NULL PRIMARY KEY,
+    system_call_id INTEGER,
+    keys TEXT NOT NULL,
+    data TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (system_call_id) REFERENCES system_calls(id)
+);
+
+INSERT INTO events_new (id, system_call_id, keys, data, created_at)
+SELECT id, system_call_id, keys, data, created_at FROM events;
+
+DROP TABLE events;
+ALTER TABLE events_new RENAME TO events;
+
+CREATE INDEX idx_events_keys ON events (keys);
+CREATE INDEX idx_events_created_at ON events (created_at);
+CREATE INDEX idx_system_calls_transaction_hash ON system_calls (transaction_hash);
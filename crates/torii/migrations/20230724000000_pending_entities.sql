@@ -0,0 +1,10 @@
+-- The entities as changed by the transactions of the pending block, on top of the indexed ones,
+-- until the block is closed. An entity whose components were all deleted has no component names.
+CREATE TABLE pending_entities (
+    id TEXT NOT NULL PRIMARY KEY,
+    partition TEXT NOT NULL,
+    keys TEXT,
+    component_names TEXT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,4 @@
+-- Events emitted outside of a system call (eg when registering a component) have no system call.
+ALTER TABLE events ALTER COLUMN system_call_id DROP NOT NULL;
+
+CREATE INDEX idx_system_calls_transaction_hash ON system_calls (transaction_hash);
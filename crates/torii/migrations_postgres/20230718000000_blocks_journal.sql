@@ -0,0 +1,19 @@
+-- The processed blocks, to detect reorgs from their parent hashes.
+CREATE TABLE blocks (
+    number BIGINT NOT NULL PRIMARY KEY,
+    hash TEXT NOT NULL,
+    parent_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- The statements reverting the writes made while processing a block, applied in reverse order on
+-- a reorg.
+CREATE TABLE journal (
+    id BIGSERIAL PRIMARY KEY,
+    block_number BIGINT NOT NULL,
+    statement TEXT NOT NULL,
+    arguments TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_journal_block_number ON journal (block_number);
@@ -0,0 +1,4 @@
+-- The entity component changed by a journaled write, notified again when the write is rolled back.
+ALTER TABLE journal ADD COLUMN entity_id TEXT;
+ALTER TABLE journal ADD COLUMN keys TEXT;
+ALTER TABLE journal ADD COLUMN component TEXT;
@@ -2,7 +2,6 @@ use camino::Utf8PathBuf;
 use clap::Parser;
 use dojo_world::manifest::Manifest;
 use graphql::server::start_graphql;
-use sqlx::sqlite::SqlitePoolOptions;
 use starknet::core::types::FieldElement;
 use starknet::providers::jsonrpc::HttpTransport;
 use starknet::providers::JsonRpcClient;
@@ -12,13 +11,17 @@ use tracing::error;
 use tracing_subscriber::fmt;
 use url::Url;
 
-use crate::engine::Processors;
+use crate::db::{Backend, DbPoolOptions};
+use crate::engine::{EngineConfig, Processors};
 use crate::indexer::Indexer;
 use crate::processors::register_component::RegisterComponentProcessor;
 use crate::processors::register_system::RegisterSystemProcessor;
+use crate::processors::store_del_record::StoreDelRecordProcessor;
 use crate::processors::store_set_record::StoreSetRecordProcessor;
+use crate::processors::store_transaction::StoreTransactionProcessor;
 use crate::state::State;
 
+mod db;
 mod engine;
 mod graphql;
 mod indexer;
@@ -39,8 +42,9 @@ struct Args {
     /// The rpc endpoint to use
     #[arg(long, default_value = "http://localhost:5050")]
     rpc: String,
-    /// Database url
-    #[arg(short, long, default_value = "sqlite::memory:")]
+    /// Database url, either a SQLite or a PostgreSQL one
+    #[cfg_attr(feature = "sqlite", arg(short, long, default_value = "sqlite::memory:"))]
+    #[cfg_attr(not(feature = "sqlite"), arg(short, long))]
     database_url: String,
     /// Specify a local manifest to intiailize from
     #[arg(short, long)]
@@ -48,6 +52,15 @@ struct Args {
     /// Specify a block to start indexing from, ignored if stored head exists
     #[arg(short, long)]
     start_block: Option<u64>,
+    /// Number of blocks processed at once
+    #[arg(long, default_value = "10240")]
+    blocks_chunk_size: u64,
+    /// Number of events fetched per request
+    #[arg(long, default_value = "1024")]
+    events_chunk_size: u64,
+    /// Apply the events of the pending block to the pending overlay, queried with `pending: true`
+    #[arg(long)]
+    index_pending: bool,
 }
 
 #[tokio::main]
@@ -72,9 +85,8 @@ async fn main() -> anyhow::Result<()> {
     })?;
 
     let database_url = &args.database_url;
-    #[cfg(feature = "sqlite")]
-    let pool = SqlitePoolOptions::new().max_connections(5).connect(database_url).await?;
-    sqlx::migrate!().run(&pool).await?;
+    let pool = DbPoolOptions::new().max_connections(5).connect(database_url).await?;
+    Backend::of(&pool).migrator().run(&pool).await?;
 
     let provider = JsonRpcClient::new(HttpTransport::new(Url::parse(&args.rpc).unwrap()));
 
@@ -87,16 +99,34 @@ async fn main() -> anyhow::Result<()> {
     let state = Sql::new(pool.clone(), args.world_address).await?;
     state.load_from_manifest(manifest.clone()).await?;
     let processors = Processors {
+        transaction: vec![Box::new(StoreTransactionProcessor {
+            world_address: args.world_address,
+        })],
         event: vec![
             Box::new(RegisterComponentProcessor),
             Box::new(RegisterSystemProcessor),
             Box::new(StoreSetRecordProcessor),
+            Box::new(StoreDelRecordProcessor),
         ],
         ..Processors::default()
     };
 
-    let indexer = Indexer::new(&state, &provider, processors, manifest, args.start_block);
-    let graphql = start_graphql(&pool);
+    let config = EngineConfig {
+        blocks_chunk_size: args.blocks_chunk_size,
+        events_chunk_size: args.events_chunk_size,
+        index_pending: args.index_pending,
+        ..EngineConfig::default()
+    };
+    let indexer = Indexer::new(
+        &state,
+        &provider,
+        processors,
+        manifest,
+        args.world_address,
+        args.start_block,
+        config,
+    );
+    let graphql = start_graphql(&pool, state.entity_updates(), state.component_registrations());
 
     tokio::select! {
         res = indexer.start() => {
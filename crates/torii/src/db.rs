@@ -0,0 +1,71 @@
+//! The database backends, SQLite and PostgreSQL, each enabled by its feature. Torii connects to
+//! the one `--database-url` points to through `sqlx::Any`. Statements are written in the SQL both
+//! backends understand (ie `$1` parameters and `ON CONFLICT` upserts), and the few differences
+//! live here.
+
+use sqlx::any::AnyKind;
+use sqlx::migrate::Migrator;
+
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("At least one of the `sqlite` and `postgres` features must be enabled.");
+
+pub type Db = sqlx::Any;
+pub type DbPool = sqlx::AnyPool;
+pub type DbPoolOptions = sqlx::any::AnyPoolOptions;
+pub type DbRow = sqlx::any::AnyRow;
+
+#[cfg(feature = "sqlite")]
+static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+#[cfg(feature = "postgres")]
+static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("./migrations_postgres");
+
+/// The backend a pool is connected to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+    #[cfg(feature = "postgres")]
+    Postgres,
+}
+
+impl Backend {
+    pub fn of(pool: &DbPool) -> Self {
+        match pool.any_kind() {
+            #[cfg(feature = "sqlite")]
+            AnyKind::Sqlite => Backend::Sqlite,
+            #[cfg(feature = "postgres")]
+            AnyKind::Postgres => Backend::Postgres,
+        }
+    }
+
+    /// Returns the migrations setting up the schema of the backend.
+    pub fn migrator(self) -> &'static Migrator {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite => &SQLITE_MIGRATOR,
+            #[cfg(feature = "postgres")]
+            Backend::Postgres => &POSTGRES_MIGRATOR,
+        }
+    }
+
+    /// Returns the type of the timestamp columns.
+    pub fn datetime_type(self) -> &'static str {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite => "DATETIME",
+            #[cfg(feature = "postgres")]
+            Backend::Postgres => "TIMESTAMPTZ",
+        }
+    }
+
+    /// Returns the type of the integer columns. Integers are 64-bit in SQLite whatever their
+    /// declared type, while PostgreSQL's `INTEGER` is 32-bit.
+    pub fn integer_type(self) -> &'static str {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite => "INTEGER",
+            #[cfg(feature = "postgres")]
+            Backend::Postgres => "BIGINT",
+        }
+    }
+}
@@ -1,9 +1,12 @@
+use std::collections::HashSet;
 use std::error::Error;
 use std::time::Duration;
 
 use starknet::core::types::{
-    BlockId, BlockTag, BlockWithTxs, Event, InvokeTransaction, MaybePendingBlockWithTxs,
-    MaybePendingTransactionReceipt, Transaction, TransactionReceipt,
+    BlockId, BlockStatus, BlockTag, BlockWithTxHashes, BlockWithTxs, EmittedEvent, Event,
+    EventFilter, FieldElement, InvokeTransaction, InvokeTransactionReceipt,
+    MaybePendingBlockWithTxHashes, MaybePendingBlockWithTxs, MaybePendingTransactionReceipt,
+    PendingTransactionReceipt, Transaction, TransactionReceipt, TransactionStatus,
 };
 use starknet::core::utils::get_selector_from_name;
 use starknet::providers::jsonrpc::{JsonRpcClient, JsonRpcTransport};
@@ -11,10 +14,15 @@ use starknet::providers::Provider;
 use tokio::time::sleep;
 use tracing::{error, info, warn};
 
+use crate::processors::store_transaction::calls_world;
 use crate::processors::{BlockProcessor, EventProcessor, TransactionProcessor};
 use crate::state::sql::Executable;
 use crate::state::State;
 
+#[cfg(test)]
+#[path = "engine_test.rs"]
+mod test;
+
 pub struct Processors<S: State, T: JsonRpcTransport + Sync + Send> {
     pub block: Vec<Box<dyn BlockProcessor<S, T>>>,
     pub transaction: Vec<Box<dyn TransactionProcessor<S, T>>>,
@@ -30,18 +38,37 @@ impl<S: State, T: JsonRpcTransport + Sync + Send> Default for Processors<S, T> {
 #[derive(Debug)]
 pub struct EngineConfig {
     pub block_time: Duration,
+    /// The number of blocks whose events are fetched at once, the head is stored after each block.
+    pub blocks_chunk_size: u64,
+    /// The number of events fetched per `starknet_getEvents` request.
+    pub events_chunk_size: u64,
+    /// Whether to apply the events of the pending block to the pending overlay.
+    pub index_pending: bool,
 }
 
 impl Default for EngineConfig {
     fn default() -> Self {
-        Self { block_time: Duration::from_secs(1) }
+        Self {
+            block_time: Duration::from_secs(1),
+            blocks_chunk_size: 10240,
+            events_chunk_size: 1024,
+            index_pending: false,
+        }
     }
 }
 
+/// The pending block applied to the pending overlay.
+#[derive(Default)]
+struct PendingBlock {
+    parent_hash: FieldElement,
+    transactions: HashSet<FieldElement>,
+}
+
 pub struct Engine<'a, S: State + Executable, T: JsonRpcTransport + Sync + Send> {
     storage: &'a S,
     provider: &'a JsonRpcClient<T>,
     processors: Processors<S, T>,
+    world_address: FieldElement,
     start_block: Option<u64>,
     config: EngineConfig,
 }
@@ -51,10 +78,11 @@ impl<'a, S: State + Executable, T: JsonRpcTransport + Sync + Send> Engine<'a, S,
         storage: &'a S,
         provider: &'a JsonRpcClient<T>,
         processors: Processors<S, T>,
+        world_address: FieldElement,
         start_block: Option<u64>,
         config: EngineConfig,
     ) -> Self {
-        Self { storage, provider, processors, start_block, config }
+        Self { storage, provider, processors, world_address, start_block, config }
     }
 
     pub async fn start(&self) -> Result<(), Box<dyn Error>> {
@@ -69,102 +97,372 @@ impl<'a, S: State + Executable, T: JsonRpcTransport + Sync + Send> Engine<'a, S,
             (_, None) => storage_head,
         };
 
+        // The head is the last processed block.
+        if self.storage.block_hash(current_block_number).await?.is_some() {
+            current_block_number += 1;
+        }
+
+        let mut pending_block = PendingBlock::default();
         loop {
             sleep(self.config.block_time).await;
 
-            let latest_block_with_txs =
-                match self.provider.get_block_with_txs(BlockId::Tag(BlockTag::Latest)).await {
-                    Ok(block_with_txs) => block_with_txs,
-                    Err(e) => {
-                        error!("getting  block: {}", e);
-                        continue;
-                    }
-                };
-
-            let latest_block_number = match latest_block_with_txs {
-                MaybePendingBlockWithTxs::Block(latest_block_with_txs) => {
-                    latest_block_with_txs.block_number
+            let latest_block_number = match self
+                .provider
+                .get_block_with_tx_hashes(BlockId::Tag(BlockTag::Latest))
+                .await
+            {
+                Ok(MaybePendingBlockWithTxHashes::Block(block)) => block.block_number,
+                Ok(_) => continue,
+                Err(e) => {
+                    error!("getting block: {}", e);
+                    continue;
                 }
-                _ => continue,
             };
 
-            // Process all blocks from current to latest.
+            if let Some(head) = current_block_number.checked_sub(1) {
+                if let Some(fork_block_number) =
+                    self.revert_reorg(head, latest_block_number).await?
+                {
+                    current_block_number = fork_block_number + 1;
+                }
+            }
+
+            // Process all blocks from current to latest, a range at a time.
             while current_block_number <= latest_block_number {
-                let block_with_txs = match self
-                    .provider
-                    .get_block_with_txs(BlockId::Number(current_block_number))
-                    .await
+                let to_block_number = latest_block_number
+                    .min(current_block_number + self.config.blocks_chunk_size.max(1) - 1);
+
+                if !self.process_range(current_block_number, to_block_number).await? {
+                    warn!("chain changed while processing blocks, retrying");
+                    break;
+                }
+
+                current_block_number = to_block_number + 1;
+            }
+
+            if self.config.index_pending {
+                if let Err(e) =
+                    self.process_pending(latest_block_number + 1, &mut pending_block).await
                 {
-                    Ok(block_with_txs) => block_with_txs,
-                    Err(e) => {
-                        error!("getting block: {}", e);
-                        continue;
-                    }
-                };
+                    error!("processing pending block: {}", e);
+                }
+            }
+        }
+    }
 
-                self.process(block_with_txs).await?;
+    /// Rolls back the indexed blocks which aren't part of the node's chain anymore, if the head
+    /// was reorged or the node's chain got shorter than it, and returns the last block shared by
+    /// both chains.
+    async fn revert_reorg(
+        &self,
+        head: u64,
+        latest_block_number: u64,
+    ) -> Result<Option<u64>, Box<dyn Error>> {
+        let mut fork_block_number = self.fork_block_number(head.min(latest_block_number)).await?;
 
-                self.storage.set_head(current_block_number).await?;
-                self.storage.execute().await?;
-                current_block_number += 1;
+        // The blocks indexed past the node's latest block were dropped from its chain, the ones
+        // up to it are processed again from the last recorded one.
+        if fork_block_number.is_none() && head > latest_block_number {
+            if let Some((last_block_number, _)) = self.storage.previous_block(head + 1).await? {
+                if last_block_number > latest_block_number {
+                    let (shared_block_number, _) =
+                        self.storage.previous_block(latest_block_number + 1).await?.ok_or(
+                            "the indexed chain doesn't share its genesis block with the node",
+                        )?;
+                    fork_block_number = Some(shared_block_number);
+                }
+            }
+        }
+
+        if let Some(fork_block_number) = fork_block_number {
+            warn!("reorg detected at block {}, rolling back to block {}", head, fork_block_number);
+            self.storage.rollback(fork_block_number).await?;
+        }
+
+        Ok(fork_block_number)
+    }
+
+    /// Returns the last block shared by the indexed chain and the node's chain, if the last block
+    /// indexed up to `block_number` isn't part of the node's chain anymore. The blocks are walked
+    /// back through the recorded ones, the blocks before the first of them being unindexed.
+    async fn fork_block_number(
+        &self,
+        mut block_number: u64,
+    ) -> Result<Option<u64>, Box<dyn Error>> {
+        let indexed_hash = match self.storage.block_hash(block_number).await? {
+            Some(hash) => hash,
+            None => match self.storage.previous_block(block_number).await? {
+                Some((previous_block_number, hash)) => {
+                    block_number = previous_block_number;
+                    hash
+                }
+                None => return Ok(None),
+            },
+        };
+        if indexed_hash == self.block(block_number).await?.block_hash {
+            return Ok(None);
+        }
+
+        loop {
+            // Nothing was indexed before this block, so everything from it is rolled back.
+            let Some((previous_block_number, indexed_hash)) =
+                self.storage.previous_block(block_number).await?
+            else {
+                return Ok(Some(block_number.checked_sub(1).ok_or(
+                    "the indexed chain doesn't share its genesis block with the node",
+                )?));
+            };
+
+            if indexed_hash == self.block(previous_block_number).await?.block_hash {
+                return Ok(Some(previous_block_number));
             }
+            block_number = previous_block_number;
+        }
+    }
+
+    async fn block(&self, block_number: u64) -> Result<BlockWithTxHashes, Box<dyn Error>> {
+        let block = self
+            .provider
+            .get_block_with_tx_hashes(BlockId::Number(block_number))
+            .await
+            .map_err(|e| format!("getting block: {}", e))?;
+
+        match block {
+            MaybePendingBlockWithTxHashes::Block(block) => Ok(block),
+            _ => Err(format!("block {} is pending", block_number).into()),
+        }
+    }
+
+    async fn block_with_txs(&self, block_number: u64) -> Result<BlockWithTxs, Box<dyn Error>> {
+        let block = self
+            .provider
+            .get_block_with_txs(BlockId::Number(block_number))
+            .await
+            .map_err(|e| format!("getting block: {}", e))?;
+
+        match block {
+            MaybePendingBlockWithTxs::Block(block) => Ok(block),
+            _ => Err(format!("block {} is pending", block_number).into()),
         }
     }
 
-    async fn process(&self, block: MaybePendingBlockWithTxs) -> Result<(), Box<dyn Error>> {
-        let block: BlockWithTxs = match block {
-            MaybePendingBlockWithTxs::Block(block) => block,
+    /// Fetches the world events between two blocks, following the continuation tokens until every
+    /// page is fetched.
+    async fn fetch_events(
+        &self,
+        from_block_number: u64,
+        to_block_number: u64,
+    ) -> Result<Vec<EmittedEvent>, Box<dyn Error>> {
+        // Every world event is fetched, since the transaction processors store all of them, and
+        // they are routed to the event processors by key.
+        let filter = EventFilter {
+            from_block: Some(BlockId::Number(from_block_number)),
+            to_block: Some(BlockId::Number(to_block_number)),
+            address: Some(self.world_address),
+            keys: None,
+        };
+
+        let mut events = vec![];
+        let mut continuation_token = None;
+        loop {
+            let page = self
+                .provider
+                .get_events(filter.clone(), continuation_token, self.config.events_chunk_size)
+                .await
+                .map_err(|e| format!("getting events: {}", e))?;
+            events.extend(page.events);
+
+            continuation_token = page.continuation_token;
+            if continuation_token.is_none() {
+                return Ok(events);
+            }
+        }
+    }
+
+    /// Processes the blocks of a range, along with their transactions which call the world or
+    /// emitted world events. Returns `false` if the chain changed while processing, in which case
+    /// the range is rolled back.
+    async fn process_range(
+        &self,
+        from_block_number: u64,
+        to_block_number: u64,
+    ) -> Result<bool, Box<dyn Error>> {
+        let events = self.fetch_events(from_block_number, to_block_number).await?;
+        let mut events = group_by(&events, |event| event.block_number).into_iter().peekable();
+
+        let mut parent_hash = None;
+        for block_number in from_block_number..=to_block_number {
+            let block = self.block_with_txs(block_number).await?;
+            let block_events =
+                events.next_if(|events| events[0].block_number == block_number).unwrap_or_default();
+
+            // The events or the previous blocks were fetched from another chain.
+            if block_events.first().is_some_and(|event| event.block_hash != block.block_hash)
+                || parent_hash.is_some_and(|parent_hash| parent_hash != block.parent_hash)
+            {
+                self.storage.rollback(from_block_number.saturating_sub(1)).await?;
+                return Ok(false);
+            }
+
+            // Each block is committed along with the head, so that it's processed once.
+            self.storage.set_block(block.block_number, block.block_hash, block.parent_hash).await?;
+            self.process(&block, block_events).await?;
+            self.storage.set_head(block.block_number).await?;
+            self.storage.execute().await?;
+            parent_hash = Some(block.block_hash);
+        }
+
+        info!("processed blocks: {} to {}", from_block_number, to_block_number);
+
+        Ok(true)
+    }
+
+    /// Applies the events of the pending transactions not applied yet to the pending overlay,
+    /// which is cleared once the block it's built on is closed.
+    async fn process_pending(
+        &self,
+        block_number: u64,
+        pending_block: &mut PendingBlock,
+    ) -> Result<(), Box<dyn Error>> {
+        let block = match self
+            .provider
+            .get_block_with_txs(BlockId::Tag(BlockTag::Pending))
+            .await
+            .map_err(|e| format!("getting pending block: {}", e))?
+        {
+            MaybePendingBlockWithTxs::PendingBlock(block) => block,
             _ => return Ok(()),
         };
 
-        process_block(self.storage, self.provider, &self.processors.block, &block).await?;
+        if block.parent_hash != pending_block.parent_hash {
+            self.storage.clear_pending().await?;
+            *pending_block =
+                PendingBlock { parent_hash: block.parent_hash, transactions: HashSet::new() };
+        }
 
-        for transaction in block.clone().transactions {
-            let invoke_transaction = match &transaction {
-                Transaction::Invoke(invoke_transaction) => invoke_transaction,
-                _ => continue,
-            };
+        // The processors expect a closed block and receipts.
+        let block = BlockWithTxs {
+            status: BlockStatus::Pending,
+            block_hash: FieldElement::ZERO,
+            parent_hash: block.parent_hash,
+            block_number,
+            new_root: FieldElement::ZERO,
+            timestamp: block.timestamp,
+            sequencer_address: block.sequencer_address,
+            transactions: block.transactions,
+        };
 
-            let invoke_transaction = match invoke_transaction {
-                InvokeTransaction::V1(invoke_transaction) => invoke_transaction,
-                _ => continue,
+        for transaction in &block.transactions {
+            let Transaction::Invoke(InvokeTransaction::V1(transaction)) = transaction else {
+                continue;
             };
+            if pending_block.transactions.contains(&transaction.transaction_hash) {
+                continue;
+            }
+
+            let receipt =
+                match self.provider.get_transaction_receipt(transaction.transaction_hash).await {
+                    Ok(MaybePendingTransactionReceipt::PendingReceipt(
+                        PendingTransactionReceipt::Invoke(receipt),
+                    )) => receipt,
+                    _ => continue,
+                };
+
+            let events = receipt.events;
+            let receipt = TransactionReceipt::Invoke(InvokeTransactionReceipt {
+                transaction_hash: receipt.transaction_hash,
+                actual_fee: receipt.actual_fee,
+                status: TransactionStatus::Pending,
+                block_hash: FieldElement::ZERO,
+                block_number,
+                messages_sent: receipt.messages_sent,
+                events: events.clone(),
+            });
+
+            self.storage.set_pending(true).await;
+            let mut result = Ok(());
+            for event in &events {
+                result = process_event(
+                    self.storage,
+                    self.provider,
+                    &self.processors.event,
+                    &block,
+                    &receipt,
+                    event,
+                )
+                .await;
+                if result.is_err() {
+                    break;
+                }
+            }
+            self.storage.set_pending(false).await;
+
+            // The transaction isn't retried, it is indexed again once the block is closed.
+            if let Err(e) = result {
+                warn!("skipping pending transaction {:#x}: {}", transaction.transaction_hash, e);
+            }
+
+            pending_block.transactions.insert(transaction.transaction_hash);
+        }
+
+        Ok(())
+    }
 
+    /// Processes a block and its transactions which call the world or emitted world events.
+    async fn process(
+        &self,
+        block: &BlockWithTxs,
+        events: &[EmittedEvent],
+    ) -> Result<(), Box<dyn Error>> {
+        process_block(self.storage, self.provider, &self.processors.block, block).await?;
+
+        for transaction in &block.transactions {
+            let transaction_hash = *transaction.transaction_hash();
+            let transaction_events =
+                events.iter().filter(|event| event.transaction_hash == transaction_hash);
+            if transaction_events.clone().next().is_none()
+                && !calls_world(transaction, self.world_address)
+            {
+                continue;
+            }
+
+            // The block isn't committed without all of its transactions.
             let receipt = match self
                 .provider
-                .get_transaction_receipt(invoke_transaction.transaction_hash)
+                .get_transaction_receipt(transaction_hash)
                 .await
+                .map_err(|e| format!("getting receipt of {:#x}: {}", transaction_hash, e))?
             {
-                Ok(receipt) => receipt,
-                _ => continue,
-            };
-
-            let receipt = match receipt {
                 MaybePendingTransactionReceipt::Receipt(receipt) => receipt,
-                _ => continue,
+                MaybePendingTransactionReceipt::PendingReceipt(_) => {
+                    return Err(format!("transaction {:#x} is pending", transaction_hash).into());
+                }
             };
 
             process_transaction(
                 self.storage,
                 self.provider,
                 &self.processors.transaction,
-                &block,
-                &receipt.clone(),
+                block,
+                &receipt,
             )
             .await?;
 
-            if let TransactionReceipt::Invoke(invoke_receipt) = receipt.clone() {
-                for event in &invoke_receipt.events {
-                    process_event(
-                        self.storage,
-                        self.provider,
-                        &self.processors.event,
-                        &block,
-                        &receipt,
-                        event,
-                    )
-                    .await?;
-                }
+            for event in transaction_events {
+                let event = Event {
+                    from_address: event.from_address,
+                    keys: event.keys.clone(),
+                    data: event.data.clone(),
+                };
+                process_event(
+                    self.storage,
+                    self.provider,
+                    &self.processors.event,
+                    block,
+                    &receipt,
+                    &event,
+                )
+                .await?;
             }
         }
 
@@ -209,10 +507,25 @@ async fn process_event<S: State, T: starknet::providers::jsonrpc::JsonRpcTranspo
     event: &Event,
 ) -> Result<(), Box<dyn Error>> {
     for processor in processors {
-        if get_selector_from_name(&processor.event_key())? == event.keys[0] {
+        if event.keys.first() == Some(&get_selector_from_name(&processor.event_key())?) {
             processor.process(storage, provider, block, receipt, event).await?;
         }
     }
 
     Ok(())
 }
+
+/// Splits the events into runs of consecutive events with the same key.
+fn group_by<K: PartialEq>(
+    mut events: &[EmittedEvent],
+    key: impl Fn(&EmittedEvent) -> K,
+) -> Vec<&[EmittedEvent]> {
+    let mut groups = vec![];
+    while let Some(first) = events.first() {
+        let len = events.iter().take_while(|event| key(event) == key(first)).count();
+        let (group, rest) = events.split_at(len);
+        groups.push(group);
+        events = rest;
+    }
+    groups
+}
@@ -0,0 +1,423 @@
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use dojo_types::component::Member;
+use dojo_world::manifest::{Component, System};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use starknet::core::types::FieldElement;
+use starknet::core::utils::{cairo_short_string_to_felt, get_selector_from_name};
+use starknet::providers::jsonrpc::{
+    JsonRpcClient, JsonRpcMethod, JsonRpcResponse, JsonRpcTransport,
+};
+
+use super::{Engine, EngineConfig, Processors};
+use crate::db::DbPool;
+use crate::processors::store_transaction::StoreTransactionProcessor;
+use crate::state::sql::{Executable, Sql};
+use crate::state::State;
+use crate::tests::common::test_pool;
+
+const WORLD_ADDRESS: u64 = 0x420;
+
+/// A node serving the blocks, events and receipts requests of the engine, whose chain can be
+/// replaced as on a reorg.
+#[derive(Clone, Default)]
+struct MockTransport {
+    /// The hashes of the blocks of the chain, by number.
+    chain: Arc<Mutex<Vec<FieldElement>>>,
+    /// The block number and transaction hash of the world events of the chain, each with a key
+    /// no processor handles.
+    events: Arc<Mutex<Vec<(u64, u64)>>>,
+    /// The block number, hash and calldata of the transactions of the chain without events.
+    transactions: Arc<Mutex<Vec<(u64, u64, Vec<FieldElement>)>>>,
+}
+
+impl MockTransport {
+    fn set_chain(&self, hashes: &[u64]) {
+        *self.chain.lock().unwrap() = hashes.iter().map(|hash| FieldElement::from(*hash)).collect();
+    }
+
+    fn set_events(&self, events: &[(u64, u64)]) {
+        *self.events.lock().unwrap() = events.to_vec();
+    }
+
+    fn set_transactions(&self, transactions: &[(u64, u64, Vec<FieldElement>)]) {
+        *self.transactions.lock().unwrap() = transactions.to_vec();
+    }
+
+    /// Returns the block number, hash, calldata and number of events of the transactions of the
+    /// chain, the ones with events calling no contract.
+    fn transactions(&self) -> Vec<(u64, u64, Vec<FieldElement>, usize)> {
+        let mut transactions: Vec<(u64, u64, Vec<FieldElement>, usize)> = vec![];
+        for (block_number, transaction_hash) in self.events.lock().unwrap().iter() {
+            match transactions.last_mut() {
+                Some(transaction) if transaction.1 == *transaction_hash => transaction.3 += 1,
+                _ => transactions.push((
+                    *block_number,
+                    *transaction_hash,
+                    vec![FieldElement::ZERO],
+                    1,
+                )),
+            }
+        }
+        for (block_number, transaction_hash, calldata) in self.transactions.lock().unwrap().iter() {
+            transactions.push((*block_number, *transaction_hash, calldata.clone(), 0));
+        }
+        transactions
+    }
+
+    fn events(&self, params: &Value) -> Value {
+        let chain = self.chain.lock().unwrap();
+        let filter = params.get("filter").or_else(|| params.get(0)).expect("missing filter");
+        let from_block = filter["from_block"]["block_number"].as_u64().unwrap();
+        let to_block = filter["to_block"]["block_number"].as_u64().unwrap();
+
+        let events = self
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(block_number, _)| (from_block..=to_block).contains(block_number))
+            .map(|(block_number, transaction_hash)| {
+                json!({
+                    "from_address": format!("{:#x}", WORLD_ADDRESS),
+                    "keys": ["0x1"],
+                    "data": [],
+                    "block_hash": format!("{:#x}", chain[*block_number as usize]),
+                    "block_number": block_number,
+                    "transaction_hash": format!("{:#x}", transaction_hash),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({ "events": events, "continuation_token": null })
+    }
+
+    fn receipt(&self, params: &Value) -> Value {
+        let chain = self.chain.lock().unwrap();
+        let transaction_hash =
+            params.get("transaction_hash").or_else(|| params.get(0)).expect("missing hash");
+        let (block_number, _, _, events) = self
+            .transactions()
+            .into_iter()
+            .find(|(_, hash, ..)| format!("{:#x}", hash) == transaction_hash.as_str().unwrap())
+            .expect("unknown transaction");
+        let event = json!({
+            "from_address": format!("{:#x}", WORLD_ADDRESS),
+            "keys": ["0x1"],
+            "data": [],
+        });
+
+        json!({
+            "type": "INVOKE",
+            "transaction_hash": transaction_hash,
+            "actual_fee": "0x0",
+            "status": "ACCEPTED_ON_L2",
+            "block_hash": format!("{:#x}", chain[block_number as usize]),
+            "block_number": block_number,
+            "messages_sent": [],
+            "events": vec![event; events],
+        })
+    }
+
+    fn block(&self, params: &Value) -> Value {
+        let chain = self.chain.lock().unwrap();
+        let block_id = params.get("block_id").or_else(|| params.get(0)).expect("missing block id");
+        let number = match block_id {
+            Value::String(tag) if tag == "latest" => chain.len() - 1,
+            block_id => block_id["block_number"].as_u64().expect("unsupported block id") as usize,
+        };
+        let parent_hash = number.checked_sub(1).map_or(FieldElement::ZERO, |parent| chain[parent]);
+
+        let transactions = self
+            .transactions()
+            .into_iter()
+            .filter(|(block_number, ..)| *block_number == number as u64)
+            .map(|(_, transaction_hash, calldata, _)| {
+                json!({
+                    "type": "INVOKE",
+                    "version": "0x1",
+                    "transaction_hash": format!("{:#x}", transaction_hash),
+                    "max_fee": "0x0",
+                    "signature": [],
+                    "nonce": "0x0",
+                    "sender_address": "0x1",
+                    "calldata": calldata
+                        .iter()
+                        .map(|felt| format!("{:#x}", felt))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "status": "ACCEPTED_ON_L2",
+            "block_hash": format!("{:#x}", chain[number]),
+            "parent_hash": format!("{:#x}", parent_hash),
+            "block_number": number,
+            "new_root": "0x0",
+            "timestamp": 0,
+            "sequencer_address": "0x0",
+            "transactions": transactions,
+        })
+    }
+}
+
+#[async_trait]
+impl JsonRpcTransport for MockTransport {
+    type Error = Infallible;
+
+    async fn send_request<P, R>(
+        &self,
+        method: JsonRpcMethod,
+        params: P,
+    ) -> Result<JsonRpcResponse<R>, Self::Error>
+    where
+        P: Serialize + Send,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params).unwrap();
+        let result = match serde_json::to_value(method).unwrap().as_str().unwrap() {
+            "starknet_getBlockWithTxHashes" | "starknet_getBlockWithTxs" => self.block(&params),
+            "starknet_getEvents" => self.events(&params),
+            "starknet_getTransactionReceipt" => self.receipt(&params),
+            method => panic!("unexpected request {}", method),
+        };
+
+        Ok(serde_json::from_value(json!({ "id": 1, "jsonrpc": "2.0", "result": result })).unwrap())
+    }
+}
+
+async fn state_fixture(pool: &DbPool) -> Sql {
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+    state
+        .register_component(Component {
+            name: "Position".into(),
+            members: vec![Member { name: "value".into(), ty: "u32".into(), slot: 0, offset: 0 }],
+            class_hash: FieldElement::ONE,
+        })
+        .await
+        .unwrap();
+    state.execute().await.unwrap();
+    state
+}
+
+/// Indexes the blocks of the node's chain, without their events.
+async fn index_chain(state: &Sql, transport: &MockTransport) {
+    let chain = transport.chain.lock().unwrap().clone();
+    for (number, hash) in chain.iter().enumerate() {
+        let parent_hash = number.checked_sub(1).map_or(FieldElement::ZERO, |parent| chain[parent]);
+        state.set_block(number as u64, *hash, parent_hash).await.unwrap();
+    }
+    state.set_head(chain.len() as u64 - 1).await.unwrap();
+    state.execute().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_revert_reorg() {
+    let pool = test_pool().await;
+    let state = state_fixture(&pool).await;
+    let transport = MockTransport::default();
+    let provider = JsonRpcClient::new(transport.clone());
+    let engine = Engine::new(
+        &state,
+        &provider,
+        Processors::default(),
+        FieldElement::ZERO,
+        None,
+        EngineConfig::default(),
+    );
+
+    transport.set_chain(&[0x10, 0x11, 0x12, 0x13]);
+    index_chain(&state, &transport).await;
+    // An entity is set in the last block.
+    state.set_block(3, FieldElement::from(0x13_u64), FieldElement::from(0x12_u64)).await.unwrap();
+    state
+        .set_entity(
+            "Position".into(),
+            FieldElement::ZERO,
+            vec![FieldElement::ONE],
+            vec![FieldElement::ONE],
+        )
+        .await
+        .unwrap();
+    state.execute().await.unwrap();
+
+    assert_eq!(engine.revert_reorg(3, 3).await.unwrap(), None);
+    assert_eq!(state.head().await.unwrap(), 3);
+
+    // The last two blocks are replaced.
+    transport.set_chain(&[0x10, 0x11, 0x22, 0x23]);
+    let mut entity_updates = state.entity_updates().subscribe();
+
+    assert_eq!(engine.revert_reorg(3, 3).await.unwrap(), Some(1));
+    assert_eq!(state.head().await.unwrap(), 1);
+    assert_eq!(state.block_hash(1).await.unwrap(), Some(FieldElement::from(0x11_u64)));
+    assert_eq!(state.block_hash(2).await.unwrap(), None);
+
+    let positions = sqlx::query("SELECT * FROM external_position").fetch_all(&pool).await.unwrap();
+    assert_eq!(positions.len(), 0);
+    let update = entity_updates.try_recv().unwrap();
+    assert_eq!(update.component, "Position");
+    assert!(update.deleted);
+
+    // The rolled back head is part of the node's chain.
+    assert_eq!(engine.revert_reorg(1, 3).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_revert_reorg_shorter_chain() {
+    let pool = test_pool().await;
+    let state = state_fixture(&pool).await;
+    let transport = MockTransport::default();
+    let provider = JsonRpcClient::new(transport.clone());
+    let engine = Engine::new(
+        &state,
+        &provider,
+        Processors::default(),
+        FieldElement::ZERO,
+        None,
+        EngineConfig::default(),
+    );
+
+    transport.set_chain(&[0x10, 0x11, 0x12, 0x13]);
+    index_chain(&state, &transport).await;
+    state.set_block(3, FieldElement::from(0x13_u64), FieldElement::from(0x12_u64)).await.unwrap();
+    state
+        .set_entity(
+            "Position".into(),
+            FieldElement::ZERO,
+            vec![FieldElement::ONE],
+            vec![FieldElement::ONE],
+        )
+        .await
+        .unwrap();
+    state.execute().await.unwrap();
+
+    // The last two blocks are dropped, the remaining ones are unchanged.
+    transport.set_chain(&[0x10, 0x11]);
+    assert_eq!(engine.revert_reorg(3, 1).await.unwrap(), Some(1));
+    assert_eq!(state.head().await.unwrap(), 1);
+    assert_eq!(state.block_hash(2).await.unwrap(), None);
+    let positions = sqlx::query("SELECT * FROM external_position").fetch_all(&pool).await.unwrap();
+    assert_eq!(positions.len(), 0);
+
+    // The remaining blocks are replaced too.
+    transport.set_chain(&[0x10, 0x11, 0x12, 0x13]);
+    index_chain(&state, &transport).await;
+    transport.set_chain(&[0x10, 0x21]);
+    assert_eq!(engine.revert_reorg(3, 1).await.unwrap(), Some(0));
+    assert_eq!(state.head().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_revert_reorg_without_common_block() {
+    let pool = test_pool().await;
+    let state = state_fixture(&pool).await;
+    let transport = MockTransport::default();
+    let provider = JsonRpcClient::new(transport.clone());
+    let engine = Engine::new(
+        &state,
+        &provider,
+        Processors::default(),
+        FieldElement::ZERO,
+        None,
+        EngineConfig::default(),
+    );
+
+    transport.set_chain(&[0x10, 0x11]);
+    index_chain(&state, &transport).await;
+
+    transport.set_chain(&[0x20, 0x21]);
+    assert!(engine.revert_reorg(1, 1).await.is_err());
+    assert_eq!(state.head().await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_process_range() {
+    let pool = test_pool().await;
+    let state = state_fixture(&pool).await;
+    let transport = MockTransport::default();
+    let provider = JsonRpcClient::new(transport.clone());
+    let world_address = FieldElement::from(WORLD_ADDRESS);
+    let processors = Processors {
+        transaction: vec![Box::new(StoreTransactionProcessor { world_address })],
+        ..Processors::default()
+    };
+    let engine =
+        Engine::new(&state, &provider, processors, world_address, None, EngineConfig::default());
+
+    transport.set_chain(&[0x10, 0x11, 0x12, 0x13]);
+    transport.set_events(&[(1, 0xa), (1, 0xb)]);
+    assert!(engine.process_range(0, 3).await.unwrap());
+
+    // The events are fetched whatever their keys.
+    let events: Vec<(String,)> =
+        sqlx::query_as("SELECT id FROM events ORDER BY id").fetch_all(&pool).await.unwrap();
+    assert_eq!(events, vec![("0xa:0".to_string(),), ("0xb:0".to_string(),)]);
+    assert_eq!(state.head().await.unwrap(), 3);
+    assert_eq!(state.block_hash(2).await.unwrap(), Some(FieldElement::from(0x12_u64)));
+
+    // Processing a block again leaves it as it is.
+    assert!(engine.process_range(1, 3).await.unwrap());
+    let events = sqlx::query("SELECT * FROM events").fetch_all(&pool).await.unwrap();
+    assert_eq!(events.len(), 2);
+
+    transport.set_chain(&[0x10, 0x11, 0x12, 0x23]);
+    assert_eq!(engine.fork_block_number(3).await.unwrap(), Some(2));
+    transport.set_chain(&[0x10, 0x21, 0x22, 0x23]);
+    assert_eq!(engine.fork_block_number(3).await.unwrap(), Some(0));
+}
+
+#[tokio::test]
+async fn test_process_system_call_without_events() {
+    let pool = test_pool().await;
+    let state = state_fixture(&pool).await;
+    state
+        .register_system(System {
+            name: "Spawn".into(),
+            inputs: vec![],
+            outputs: vec![],
+            class_hash: FieldElement::ONE,
+            dependencies: vec![],
+        })
+        .await
+        .unwrap();
+    state.execute().await.unwrap();
+
+    let transport = MockTransport::default();
+    let provider = JsonRpcClient::new(transport.clone());
+    let world_address = FieldElement::from(WORLD_ADDRESS);
+    let processors = Processors {
+        transaction: vec![Box::new(StoreTransactionProcessor { world_address })],
+        ..Processors::default()
+    };
+    let engine =
+        Engine::new(&state, &provider, processors, world_address, None, EngineConfig::default());
+
+    // A call to `World::execute`, followed by a transaction calling another contract.
+    let execute = vec![
+        FieldElement::ONE,
+        world_address,
+        get_selector_from_name("execute").unwrap(),
+        FieldElement::TWO,
+        cairo_short_string_to_felt("Spawn").unwrap(),
+        FieldElement::ZERO,
+    ];
+    let mut transfer = execute.clone();
+    transfer[1] = FieldElement::from(0x421_u64);
+
+    transport.set_chain(&[0x10, 0x11]);
+    transport.set_transactions(&[(1, 0xc, execute), (1, 0xd, transfer)]);
+    assert!(engine.process_range(0, 1).await.unwrap());
+
+    let system_calls: Vec<(String, String)> =
+        sqlx::query_as("SELECT system_id, transaction_hash FROM system_calls")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(system_calls, vec![("spawn".to_string(), "0xc".to_string())]);
+}
@@ -1,15 +1,18 @@
+use std::sync::Arc;
+
 use async_graphql::dynamic::{Field, FieldFuture, FieldValue, InputValue, TypeRef};
 use async_graphql::{Name, Value};
 use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
 use serde::Deserialize;
-use sqlx::{FromRow, Pool, Sqlite};
+use sqlx::FromRow;
 
-use super::query::{query_all, query_by_id, ID};
+use super::connection::{order_columns, resolve_connection};
+use super::query::{query_by_id, ID};
 use super::{ObjectTrait, TypeMapping, ValueMapping};
-use crate::graphql::constants::DEFAULT_LIMIT;
+use crate::db::{DbPool, DbRow};
 use crate::graphql::types::ScalarType;
-use crate::graphql::utils::remove_quotes;
+use crate::graphql::utils::{remove_quotes, to_snake_case};
 
 #[derive(FromRow, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -72,7 +75,7 @@ impl ObjectTrait for ComponentObject {
         vec![
             Field::new(self.name(), TypeRef::named_nn(self.type_name()), |ctx| {
                 FieldFuture::new(async move {
-                    let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
+                    let mut conn = ctx.data::<DbPool>()?.acquire().await?;
                     let id = remove_quotes(ctx.args.try_get("id")?.string()?);
                     let component = query_by_id(&mut conn, "components", ID::Str(id)).await?;
                     let result = ComponentObject::value_mapping(component);
@@ -80,27 +83,17 @@ impl ObjectTrait for ComponentObject {
                 })
             })
             .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID))),
-            Field::new("components", TypeRef::named_list(self.type_name()), |ctx| {
-                FieldFuture::new(async move {
-                    let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
-                    let limit = ctx
-                        .args
-                        .try_get("limit")
-                        .and_then(|limit| limit.u64())
-                        .unwrap_or(DEFAULT_LIMIT);
-
-                    let components: Vec<Component> =
-                        query_all(&mut conn, "components", limit).await?;
-                    let result: Vec<FieldValue<'_>> = components
-                        .into_iter()
-                        .map(ComponentObject::value_mapping)
-                        .map(FieldValue::owned_any)
-                        .collect();
-
-                    Ok(Some(FieldValue::list(result)))
-                })
-            })
-            .argument(InputValue::new("limit", TypeRef::named(TypeRef::INT))),
+            resolve_connection(
+                "components",
+                self.type_name(),
+                "components",
+                None,
+                order_columns(self.field_type_mapping(), to_snake_case),
+                None,
+                Arc::new(|row: &DbRow| {
+                    Component::from_row(row).map(ComponentObject::value_mapping)
+                }),
+            ),
         ]
     }
 }
@@ -1,22 +1,41 @@
-use std::collections::HashMap;
+use std::sync::Arc;
 
 use async_graphql::dynamic::{
-    Field, FieldFuture, FieldValue, InputValue, ResolverContext, TypeRef,
+    Field, FieldValue, InputObject, InputValue, ResolverContext, SubscriptionField,
+    SubscriptionFieldFuture, TypeRef, ValueAccessor,
 };
+use async_graphql::futures_util::future::ready;
+use async_graphql::futures_util::StreamExt;
 use async_graphql::{Name, Value};
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use sqlx::pool::PoolConnection;
-use sqlx::sqlite::SqliteRow;
-use sqlx::{FromRow, Pool, QueryBuilder, Row, Sqlite};
+use sqlx::{FromRow, QueryBuilder, Row};
+use tokio::sync::broadcast;
 
+use super::connection::{
+    order_columns, order_input, pending_argument, resolve_connection, FiltersFn, NodeFn,
+};
+use super::query::{Comparator, Filter, QueryValue};
 use super::{ObjectTrait, TypeMapping, ValueMapping};
-use crate::graphql::constants::DEFAULT_LIMIT;
+use crate::db::{Db, DbPool, DbRow};
 use crate::graphql::types::ScalarType;
+use crate::graphql::utils::subscription::{
+    component_matches, entity_update_stream, keys_match, optional_string_list,
+};
+use crate::state::EntityUpdate;
 
 const BOOLEAN_TRUE: i64 = 1;
 
-pub type ComponentFilters = HashMap<String, String>;
+// Suffixes of the comparison inputs of numeric members
+const COMPARATORS: [(&str, Comparator); 6] = [
+    ("GT", Comparator::Gt),
+    ("GTE", Comparator::Gte),
+    ("LT", Comparator::Lt),
+    ("LTE", Comparator::Lte),
+    ("NEQ", Comparator::Neq),
+    ("IN", Comparator::In),
+];
 
 #[derive(FromRow, Deserialize)]
 pub struct ComponentMembers {
@@ -55,114 +74,208 @@ impl ObjectTrait for ComponentStateObject {
     }
 
     fn resolvers(&self) -> Vec<Field> {
-        vec![resolve_many(
+        vec![resolve_many(&self.name, &self.type_name, &self.field_type_mapping)]
+    }
+
+    fn input_objects(&self) -> Vec<InputObject> {
+        vec![order_input(&self.type_name), where_input(&self.type_name, &self.field_type_mapping)]
+    }
+
+    fn subscriptions(&self) -> Option<Vec<SubscriptionField>> {
+        Some(vec![subscribe_updated(
             self.name.to_string(),
             self.type_name.to_string(),
             self.field_type_mapping.clone(),
-        )]
+        )])
     }
 }
 
-fn resolve_many(name: String, type_name: String, field_type_mapping: TypeMapping) -> Field {
-    let ftm_clone = field_type_mapping.clone();
-
-    let field =
-        Field::new(format!("{}Components", &name), TypeRef::named_list(type_name), move |ctx| {
-            // FIX: field_type_mapping and name needs to be passed down to the doubly
-            // nested async closures, thus the cloning. could handle this better
-            let field_type_mapping = field_type_mapping.clone();
-            let name = name.clone();
-
-            FieldFuture::new(async move {
-                // parse optional input query params
-                let (filters, limit) = parse_inputs(&ctx, &field_type_mapping)?;
+fn resolve_many(name: &str, type_name: &str, field_type_mapping: &TypeMapping) -> Field {
+    let where_mapping = field_type_mapping.clone();
+    let filters: FiltersFn =
+        Arc::new(move |ctx: &ResolverContext<'_>| parse_where(ctx, &where_mapping));
+
+    let node_mapping = field_type_mapping.clone();
+    let node: NodeFn = Arc::new(move |row: &DbRow| value_mapping_from_row(row, &node_mapping));
+
+    resolve_connection(
+        &format!("{}Components", name),
+        type_name,
+        &format!("external_{}", name),
+        Some(pending_table_name(name, field_type_mapping)),
+        order_columns(field_type_mapping, |member| format!("external_{}", member)),
+        Some(filters),
+        node,
+    )
+    .argument(InputValue::new("where", TypeRef::named(where_type_name(type_name))))
+}
 
-                let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
-                let state_values =
-                    component_states_query(&mut conn, &name, &filters, limit, &field_type_mapping)
+fn subscribe_updated(
+    name: String,
+    type_name: String,
+    field_type_mapping: TypeMapping,
+) -> SubscriptionField {
+    SubscriptionField::new(format!("{}Updated", &name), TypeRef::named_nn(type_name), move |ctx| {
+        let field_type_mapping = field_type_mapping.clone();
+        let name = name.clone();
+
+        SubscriptionFieldFuture::new(async move {
+            let pool = ctx.data::<DbPool>()?.clone();
+            let receiver = ctx.data::<broadcast::Sender<EntityUpdate>>()?.subscribe();
+            let keys = optional_string_list(&ctx, "keys")?.unwrap_or_default();
+            let pending = pending_argument(&ctx)?;
+
+            // deleted components have no state left to send
+            let filter_name = name.clone();
+            let updates = entity_update_stream(receiver)
+                .filter(move |update| {
+                    ready(
+                        !update.deleted
+                            && (pending || !update.pending)
+                            && component_matches(&update.component, &filter_name)
+                            && keys_match(&update.keys, &keys),
+                    )
+                })
+                .then(move |update| {
+                    let pool = pool.clone();
+                    let name = name.clone();
+                    let field_type_mapping = field_type_mapping.clone();
+
+                    async move {
+                        let mut conn = pool.acquire().await?;
+                        let state = component_state_by_id(
+                            &mut conn,
+                            &name,
+                            &update.entity_id,
+                            &field_type_mapping,
+                            update.pending,
+                        )
                         .await?;
+                        Ok::<_, async_graphql::Error>(FieldValue::owned_any(state))
+                    }
+                });
 
-                let result: Vec<FieldValue<'_>> =
-                    state_values.into_iter().map(FieldValue::owned_any).collect();
+            Ok(updates)
+        })
+    })
+    .argument(InputValue::new("keys", TypeRef::named_list(TypeRef::STRING)))
+    .argument(InputValue::new("pending", TypeRef::named(TypeRef::BOOLEAN)))
+}
 
-                Ok(Some(FieldValue::list(result)))
-            })
-        });
+/// The component table with the pending overlay applied, where the pending components, including
+/// the deleted ones, replace the indexed ones.
+fn pending_table_name(name: &str, field_type_mapping: &TypeMapping) -> String {
+    let columns = ["id".to_string(), "partition".to_string()]
+        .into_iter()
+        .chain(field_type_mapping.keys().map(|member| format!("external_{}", member)))
+        .chain(["created_at".to_string()])
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "(SELECT {columns} FROM pending_external_{name} WHERE deleted = FALSE UNION ALL SELECT \
+         {columns} FROM external_{name} WHERE id NOT IN (SELECT id FROM pending_external_{name})) \
+         AS external_{name}"
+    )
+}
 
-    add_arguments(field, ftm_clone)
+fn where_type_name(type_name: &str) -> String {
+    format!("{}WhereInput", type_name)
 }
 
-fn add_arguments(field: Field, field_type_mapping: TypeMapping) -> Field {
-    field_type_mapping
-        .into_iter()
-        .fold(field, |field, (name, ty)| {
-            field.argument(InputValue::new(name.as_str(), TypeRef::named(ty)))
-        })
-        .argument(InputValue::new("limit", TypeRef::named(TypeRef::INT)))
+// Equality on every member, and comparisons on numeric members (ie xGT, xIN)
+fn where_input(type_name: &str, field_type_mapping: &TypeMapping) -> InputObject {
+    field_type_mapping.iter().fold(
+        InputObject::new(where_type_name(type_name)),
+        |input, (name, ty)| {
+            let input = input.field(InputValue::new(name.as_str(), TypeRef::named(ty)));
+            if !is_numeric(ty) {
+                return input;
+            }
+
+            COMPARATORS.iter().fold(input, |input, (suffix, comparator)| {
+                let type_ref = match comparator {
+                    Comparator::In => TypeRef::named_nn_list(ty),
+                    _ => TypeRef::named(ty),
+                };
+                input.field(InputValue::new(format!("{}{}", name, suffix), type_ref))
+            })
+        },
+    )
 }
 
-fn parse_inputs(
+fn parse_where(
     ctx: &ResolverContext<'_>,
     field_type_mapping: &TypeMapping,
-) -> async_graphql::Result<(ComponentFilters, u64), async_graphql::Error> {
-    let mut inputs: ComponentFilters = ComponentFilters::new();
+) -> async_graphql::Result<Vec<Filter>> {
+    let Some(input) = ctx.args.get("where").filter(|input| !input.is_null()) else {
+        return Ok(Vec::new());
+    };
+    let input = input.object()?;
 
+    let mut filters = Vec::new();
     for (name, ty) in field_type_mapping.iter() {
-        let maybe_input = ctx.args.try_get(name.as_str()).ok();
-        if let Some(input) = maybe_input {
-            let input_str = if ScalarType::from_str(ty)?.is_numeric_type() {
-                input.u64()?.to_string()
-            } else {
-                input.string()?.to_string()
+        let column = format!("external_{}", name);
+        let numeric = is_numeric(ty);
+
+        let comparators = std::iter::once(("", Comparator::Eq))
+            .chain(COMPARATORS.iter().copied().filter(|_| numeric));
+        for (suffix, comparator) in comparators {
+            let Some(value) = input.get(&format!("{}{}", name, suffix)).filter(|v| !v.is_null())
+            else {
+                continue;
+            };
+
+            let value = match comparator {
+                Comparator::In => QueryValue::List(
+                    value
+                        .list()?
+                        .iter()
+                        .map(|value| filter_value(&value, numeric))
+                        .collect::<async_graphql::Result<Vec<_>>>()?,
+                ),
+                _ => filter_value(&value, numeric)?,
             };
 
-            inputs.insert(name.to_string(), input_str);
+            filters.push(Filter { column: column.clone(), comparator, value });
         }
     }
 
-    let limit = ctx.args.try_get("limit").and_then(|limit| limit.u64()).unwrap_or(DEFAULT_LIMIT);
+    Ok(filters)
+}
 
-    Ok((inputs, limit))
+fn filter_value(value: &ValueAccessor<'_>, numeric: bool) -> async_graphql::Result<QueryValue> {
+    if !numeric {
+        return Ok(QueryValue::String(value.string()?.to_string()));
+    }
+
+    // sqlite stores booleans as 0 or 1
+    match value.boolean() {
+        Ok(value) => Ok(QueryValue::Int(value as i64)),
+        Err(_) => Ok(QueryValue::Int(value.i64()?)),
+    }
+}
+
+fn is_numeric(ty: &str) -> bool {
+    ScalarType::from_str(ty).map(|ty| ty.is_numeric_type()).unwrap_or(false)
 }
 
 pub async fn component_state_by_id(
-    conn: &mut PoolConnection<Sqlite>,
+    conn: &mut PoolConnection<Db>,
     name: &str,
     id: &str,
     fields: &TypeMapping,
+    pending: bool,
 ) -> sqlx::Result<ValueMapping> {
-    let table_name = format!("external_{}", name);
-    let mut builder: QueryBuilder<'_, Sqlite> = QueryBuilder::new("SELECT * FROM ");
+    let table_name =
+        if pending { pending_table_name(name, fields) } else { format!("external_{}", name) };
+    let mut builder: QueryBuilder<'_, Db> = QueryBuilder::new("SELECT * FROM ");
     builder.push(table_name).push(" WHERE id = ").push_bind(id);
     let row = builder.build().fetch_one(conn).await?;
     value_mapping_from_row(&row, fields)
 }
 
-pub async fn component_states_query(
-    conn: &mut PoolConnection<Sqlite>,
-    name: &str,
-    filters: &ComponentFilters,
-    limit: u64,
-    fields: &TypeMapping,
-) -> sqlx::Result<Vec<ValueMapping>> {
-    let table_name = format!("external_{}", name);
-    let mut builder: QueryBuilder<'_, Sqlite> = QueryBuilder::new("SELECT * FROM ");
-    builder.push(table_name);
-
-    if !filters.is_empty() {
-        builder.push(" WHERE ");
-        let mut separated = builder.separated(" AND ");
-        for (name, value) in filters.iter() {
-            separated.push(format!("external_{} = '{}'", name, value));
-        }
-    }
-    builder.push(" ORDER BY created_at DESC LIMIT ").push_bind(limit.to_string());
-
-    let compoent_states = builder.build().fetch_all(conn).await?;
-    compoent_states.iter().map(|row| value_mapping_from_row(row, fields)).collect()
-}
-
-fn value_mapping_from_row(row: &SqliteRow, fields: &TypeMapping) -> sqlx::Result<ValueMapping> {
+fn value_mapping_from_row(row: &DbRow, fields: &TypeMapping) -> sqlx::Result<ValueMapping> {
     let mut value_mapping = ValueMapping::new();
 
     for (field_name, field_type) in fields {
@@ -193,7 +306,7 @@ fn value_mapping_from_row(row: &SqliteRow, fields: &TypeMapping) -> sqlx::Result
 }
 
 pub async fn type_mapping_from(
-    conn: &mut PoolConnection<Sqlite>,
+    conn: &mut PoolConnection<Db>,
     component_id: &str,
 ) -> sqlx::Result<TypeMapping> {
     let component_members: Vec<ComponentMembers> = sqlx::query_as(
@@ -203,9 +316,9 @@ pub async fn type_mapping_from(
                     name,
                     type AS ty,
                     slot,
-                    offset,
+                    "offset",
                     created_at
-                FROM component_members WHERE component_id = ?
+                FROM component_members WHERE component_id = $1
             "#,
     )
     .bind(component_id)
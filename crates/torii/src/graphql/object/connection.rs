@@ -0,0 +1,301 @@
+use std::sync::Arc;
+
+use async_graphql::dynamic::{
+    Enum, Field, FieldFuture, FieldValue, InputObject, InputValue, Object, ResolverContext, TypeRef,
+};
+use async_graphql::{Error, Name, Value};
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+
+use super::query::{
+    cursor_from_row, query_page, Cursor, Direction, Filter, Order, Page, PageArguments, QueryValue,
+};
+use super::{value_field, TypeMapping, ValueMapping};
+use crate::db::{DbPool, DbRow};
+use crate::graphql::types::ScalarType;
+use crate::graphql::utils::to_enum_value;
+
+/// Maps the values of an order field enum to the columns they sort on.
+pub type OrderColumns = IndexMap<String, String>;
+/// Builds the filters of a connection from the arguments of its field.
+pub type FiltersFn =
+    Arc<dyn Fn(&ResolverContext<'_>) -> async_graphql::Result<Vec<Filter>> + Send + Sync>;
+/// Builds the node of an edge from its row.
+pub type NodeFn = Arc<dyn Fn(&DbRow) -> sqlx::Result<ValueMapping> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct Edge {
+    pub node: ValueMapping,
+    pub cursor: String,
+}
+
+pub struct Connection {
+    pub edges: Vec<Edge>,
+    pub page_info: ValueMapping,
+    pub total_count: i64,
+}
+
+pub fn connection_type_name(type_name: &str) -> String {
+    format!("{}Connection", type_name)
+}
+
+fn edge_type_name(type_name: &str) -> String {
+    format!("{}Edge", type_name)
+}
+
+fn order_type_name(type_name: &str) -> String {
+    format!("{}Order", type_name)
+}
+
+fn order_field_type_name(type_name: &str) -> String {
+    format!("{}OrderField", type_name)
+}
+
+pub fn order_columns(fields: &TypeMapping, column: impl Fn(&str) -> String) -> OrderColumns {
+    fields.keys().map(|name| (to_enum_value(name), column(name))).collect()
+}
+
+// Connection and edge objects wrapping the object
+pub fn connection_objects(type_name: &str) -> Vec<Object> {
+    let edge = Object::new(edge_type_name(type_name))
+        .field(Field::new("node", TypeRef::named_nn(type_name), |ctx| {
+            FieldFuture::new(async move {
+                let edge = ctx.parent_value.try_downcast_ref::<Edge>()?;
+                Ok(Some(FieldValue::owned_any(edge.node.clone())))
+            })
+        }))
+        .field(Field::new("cursor", TypeRef::named_nn(ScalarType::Cursor.to_string()), |ctx| {
+            FieldFuture::new(async move {
+                let edge = ctx.parent_value.try_downcast_ref::<Edge>()?;
+                Ok(Some(Value::from(edge.cursor.clone())))
+            })
+        }));
+
+    let connection = Object::new(connection_type_name(type_name))
+        .field(Field::new("edges", TypeRef::named_nn_list_nn(edge_type_name(type_name)), |ctx| {
+            FieldFuture::new(async move {
+                let connection = ctx.parent_value.try_downcast_ref::<Connection>()?;
+                let edges = connection.edges.iter().cloned().map(FieldValue::owned_any);
+                Ok(Some(FieldValue::list(edges)))
+            })
+        }))
+        .field(Field::new("pageInfo", TypeRef::named_nn("PageInfo"), |ctx| {
+            FieldFuture::new(async move {
+                let connection = ctx.parent_value.try_downcast_ref::<Connection>()?;
+                Ok(Some(FieldValue::owned_any(connection.page_info.clone())))
+            })
+        }))
+        .field(Field::new("totalCount", TypeRef::named_nn(TypeRef::INT), |ctx| {
+            FieldFuture::new(async move {
+                let connection = ctx.parent_value.try_downcast_ref::<Connection>()?;
+                Ok(Some(Value::from(connection.total_count)))
+            })
+        }));
+
+    vec![connection, edge]
+}
+
+pub fn page_info_object() -> Object {
+    Object::new("PageInfo")
+        .field(value_field("hasPreviousPage", TypeRef::named_nn(TypeRef::BOOLEAN)))
+        .field(value_field("hasNextPage", TypeRef::named_nn(TypeRef::BOOLEAN)))
+        .field(value_field("startCursor", TypeRef::named(ScalarType::Cursor.to_string())))
+        .field(value_field("endCursor", TypeRef::named(ScalarType::Cursor.to_string())))
+}
+
+pub fn order_direction_enum() -> Enum {
+    Enum::new("OrderDirection").item("ASC").item("DESC")
+}
+
+pub fn order_field_enum(type_name: &str, fields: &TypeMapping) -> Enum {
+    fields.keys().fold(Enum::new(order_field_type_name(type_name)), |order_field, name| {
+        order_field.item(to_enum_value(name))
+    })
+}
+
+pub fn order_input(type_name: &str) -> InputObject {
+    InputObject::new(order_type_name(type_name))
+        .field(InputValue::new("direction", TypeRef::named_nn("OrderDirection")))
+        .field(InputValue::new("field", TypeRef::named_nn(order_field_type_name(type_name))))
+}
+
+/// Creates a field resolving a page of the rows of a table, as a connection of the given type.
+/// Given the table with the pending overlay applied, the field takes a `pending` argument to query
+/// it instead.
+pub fn resolve_connection(
+    name: &str,
+    type_name: &str,
+    table_name: &str,
+    pending_table_name: Option<String>,
+    order_columns: OrderColumns,
+    filters: Option<FiltersFn>,
+    node: NodeFn,
+) -> Field {
+    let table_name = table_name.to_string();
+    let has_pending = pending_table_name.is_some();
+
+    let field = Field::new(name, TypeRef::named_nn(connection_type_name(type_name)), move |ctx| {
+        let table_name = table_name.clone();
+        let pending_table_name = pending_table_name.clone();
+        let order_columns = order_columns.clone();
+        let filters = filters.clone();
+        let node = node.clone();
+
+        FieldFuture::new(async move {
+            let table_name = match pending_table_name {
+                Some(pending_table_name) if pending_argument(&ctx)? => pending_table_name,
+                _ => table_name,
+            };
+            let page_arguments = parse_page_arguments(&ctx)?;
+            let order = parse_order(&ctx, &order_columns)?;
+            let filters = match filters {
+                Some(filters) => filters(&ctx)?,
+                None => Vec::new(),
+            };
+
+            let mut conn = ctx.data::<DbPool>()?.acquire().await?;
+            let page =
+                query_page(&mut conn, &table_name, &filters, &order, &page_arguments).await?;
+            let connection = connection_output(page, &order, node.as_ref())?;
+
+            Ok(Some(FieldValue::owned_any(connection)))
+        })
+    });
+
+    let field = field
+        .argument(InputValue::new("first", TypeRef::named(TypeRef::INT)))
+        .argument(InputValue::new("after", TypeRef::named(ScalarType::Cursor.to_string())))
+        .argument(InputValue::new("last", TypeRef::named(TypeRef::INT)))
+        .argument(InputValue::new("before", TypeRef::named(ScalarType::Cursor.to_string())))
+        .argument(InputValue::new("orderBy", TypeRef::named(order_type_name(type_name))));
+
+    if has_pending {
+        field.argument(InputValue::new("pending", TypeRef::named(TypeRef::BOOLEAN)))
+    } else {
+        field
+    }
+}
+
+/// Whether the pending overlay is queried, which it isn't by default.
+pub fn pending_argument(ctx: &ResolverContext<'_>) -> async_graphql::Result<bool> {
+    match ctx.args.get("pending").filter(|pending| !pending.is_null()) {
+        Some(pending) => pending.boolean(),
+        None => Ok(false),
+    }
+}
+
+fn connection_output(
+    page: Page,
+    order: &Order,
+    node: &(dyn Fn(&DbRow) -> sqlx::Result<ValueMapping> + Send + Sync),
+) -> async_graphql::Result<Connection> {
+    let edges = page
+        .rows
+        .iter()
+        .map(|row| {
+            let cursor = cursor_from_row(row, &order.column)?;
+            Ok(Edge { node: node(row)?, cursor: encode_cursor(&cursor) })
+        })
+        .collect::<sqlx::Result<Vec<Edge>>>()?;
+
+    let cursor_value = |edge: Option<&Edge>| match edge {
+        Some(edge) => Value::from(edge.cursor.clone()),
+        None => Value::Null,
+    };
+    let page_info = IndexMap::from([
+        (Name::new("hasPreviousPage"), Value::from(page.has_previous_page)),
+        (Name::new("hasNextPage"), Value::from(page.has_next_page)),
+        (Name::new("startCursor"), cursor_value(edges.first())),
+        (Name::new("endCursor"), cursor_value(edges.last())),
+    ]);
+
+    Ok(Connection { edges, page_info, total_count: page.total_count })
+}
+
+fn parse_page_arguments(ctx: &ResolverContext<'_>) -> async_graphql::Result<PageArguments> {
+    let argument = |name: &str| ctx.args.get(name).filter(|value| !value.is_null());
+    let count = |name: &str| argument(name).map(|count| count.u64()).transpose();
+    let cursor = |name: &str| {
+        argument(name).map(|cursor| cursor.string().and_then(decode_cursor)).transpose()
+    };
+
+    let page = PageArguments {
+        first: count("first")?,
+        after: cursor("after")?,
+        last: count("last")?,
+        before: cursor("before")?,
+    };
+
+    if page.first.is_some() && page.last.is_some() {
+        return Err("`first` and `last` cannot be used together".into());
+    }
+
+    Ok(page)
+}
+
+fn parse_order(
+    ctx: &ResolverContext<'_>,
+    order_columns: &OrderColumns,
+) -> async_graphql::Result<Order> {
+    let Some(order_by) = ctx.args.get("orderBy").filter(|value| !value.is_null()) else {
+        return Ok(Order::default());
+    };
+
+    let order_by = order_by.object()?;
+    let field = order_by.try_get("field")?.enum_name()?;
+    let column = order_columns
+        .get(field)
+        .ok_or_else(|| Error::new(format!("Unknown order field {}", field)))?;
+    let direction = match order_by.try_get("direction")?.enum_name()? {
+        "ASC" => Direction::Asc,
+        _ => Direction::Desc,
+    };
+
+    Ok(Order { column: column.clone(), direction })
+}
+
+// Cursors are opaque to clients, they are the hex encoded json of the order value and the id
+fn encode_cursor(cursor: &Cursor) -> String {
+    let json = serde_json::json!([json_value(&cursor.value), json_value(&cursor.id)]);
+    json.to_string().bytes().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_cursor(cursor: &str) -> async_graphql::Result<Cursor> {
+    let invalid_cursor = || Error::new(format!("Invalid cursor {}", cursor));
+
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| cursor.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(invalid_cursor)?;
+
+    match serde_json::from_slice::<Vec<serde_json::Value>>(&bytes).as_deref() {
+        Ok([value, id]) => Ok(Cursor {
+            value: query_value(value).ok_or_else(invalid_cursor)?,
+            id: query_value(id).ok_or_else(invalid_cursor)?,
+        }),
+        _ => Err(invalid_cursor()),
+    }
+}
+
+fn json_value(value: &QueryValue) -> serde_json::Value {
+    match value {
+        QueryValue::Int(value) => serde_json::Value::from(*value),
+        QueryValue::String(value) => serde_json::Value::from(value.clone()),
+        QueryValue::DateTime(value) => serde_json::json!({ "datetime": value.to_rfc3339() }),
+        QueryValue::List(values) => values.iter().map(json_value).collect(),
+    }
+}
+
+fn query_value(value: &serde_json::Value) -> Option<QueryValue> {
+    match value {
+        serde_json::Value::Number(value) => value.as_i64().map(QueryValue::Int),
+        serde_json::Value::String(value) => Some(QueryValue::String(value.clone())),
+        serde_json::Value::Object(object) => object
+            .get("datetime")
+            .and_then(|value| value.as_str())
+            .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+            .map(|value| QueryValue::DateTime(value.with_timezone(&Utc))),
+        _ => None,
+    }
+}
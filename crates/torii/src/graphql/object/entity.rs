@@ -1,18 +1,30 @@
-use async_graphql::dynamic::{Field, FieldFuture, FieldValue, InputValue, TypeRef};
+use std::sync::Arc;
+
+use async_graphql::dynamic::{
+    Field, FieldFuture, FieldValue, InputValue, ResolverContext, SubscriptionField,
+    SubscriptionFieldFuture, TypeRef,
+};
+use async_graphql::futures_util::future::ready;
+use async_graphql::futures_util::StreamExt;
 use async_graphql::{Name, Value};
 use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
 use serde::Deserialize;
-use sqlx::pool::PoolConnection;
-use sqlx::{FromRow, Pool, QueryBuilder, Result, Sqlite};
+use sqlx::{FromRow, Result};
+use tokio::sync::broadcast;
 
 use super::component_state::{component_state_by_id, type_mapping_from};
-use super::query::{query_by_id, ID};
-use super::{ObjectTrait, TypeMapping, ValueMapping};
-use crate::graphql::constants::DEFAULT_LIMIT;
+use super::connection::{order_columns, pending_argument, resolve_connection, FiltersFn};
+use super::query::{query_by_id, Comparator, Filter, QueryValue, ID};
+use super::{value_field, ObjectTrait, TypeMapping, ValueMapping};
+use crate::db::{DbPool, DbRow};
 use crate::graphql::types::ScalarType;
-use crate::graphql::utils::csv_to_vec;
 use crate::graphql::utils::extract_value::extract;
+use crate::graphql::utils::subscription::{
+    component_matches, entity_update_stream, keys_match, optional_string_list,
+};
+use crate::graphql::utils::{csv_to_vec, to_snake_case};
+use crate::state::EntityUpdate;
 
 #[derive(FromRow, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -23,8 +35,21 @@ pub struct Entity {
     pub component_names: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Whether the entity is read from the pending overlay.
+    #[sqlx(default)]
+    #[serde(default)]
+    pub pending: bool,
 }
 
+/// The entities with the pending overlay applied. The overlay hides the entities whose components
+/// were all deleted.
+const PENDING_ENTITIES: &str = "(SELECT id, partition, keys, component_names, created_at, \
+                                updated_at, TRUE AS pending FROM pending_entities WHERE \
+                                component_names != '' UNION ALL SELECT id, partition, keys, \
+                                component_names, created_at, updated_at, FALSE AS pending FROM \
+                                entities WHERE id NOT IN (SELECT id FROM pending_entities)) AS \
+                                entities";
+
 pub struct EntityObject {
     pub field_type_mapping: TypeMapping,
 }
@@ -55,6 +80,7 @@ impl EntityObject {
                 Name::new("updatedAt"),
                 Value::from(entity.updated_at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
             ),
+            (Name::new("pending"), Value::from(entity.pending)),
         ])
     }
 }
@@ -73,109 +99,163 @@ impl ObjectTrait for EntityObject {
     }
 
     fn nested_fields(&self) -> Option<Vec<Field>> {
-        Some(vec![Field::new("components", TypeRef::named_list("ComponentUnion"), move |ctx| {
-            FieldFuture::new(async move {
-                let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
-                let entity = ctx.parent_value.try_downcast_ref::<ValueMapping>()?;
-
-                let components = csv_to_vec(&extract::<String>(entity, "componentNames")?);
-                let id = extract::<String>(entity, "id")?;
-
-                let mut results: Vec<FieldValue<'_>> = Vec::new();
-                for component_name in components {
-                    let table_name = component_name.to_lowercase();
-                    let field_type_mapping = type_mapping_from(&mut conn, &table_name).await?;
-                    let state =
-                        component_state_by_id(&mut conn, &table_name, &id, &field_type_mapping)
-                            .await?;
-                    results
-                        .push(FieldValue::with_type(FieldValue::owned_any(state), component_name));
-                }
-
-                Ok(Some(FieldValue::list(results)))
-            })
-        })])
+        // Not ordered on, since the indexed entities don't have it
+        let pending = value_field("pending", TypeRef::named_nn(TypeRef::BOOLEAN));
+
+        Some(vec![
+            pending,
+            Field::new("components", TypeRef::named_list("ComponentUnion"), move |ctx| {
+                FieldFuture::new(async move {
+                    let mut conn = ctx.data::<DbPool>()?.acquire().await?;
+                    let entity = ctx.parent_value.try_downcast_ref::<ValueMapping>()?;
+
+                    let components = csv_to_vec(&extract::<String>(entity, "componentNames")?);
+                    let id = extract::<String>(entity, "id")?;
+                    let pending = extract::<bool>(entity, "pending")?;
+
+                    let mut results: Vec<FieldValue<'_>> = Vec::new();
+                    for component_name in components {
+                        let table_name = component_name.to_lowercase();
+                        let field_type_mapping = type_mapping_from(&mut conn, &table_name).await?;
+                        let state = component_state_by_id(
+                            &mut conn,
+                            &table_name,
+                            &id,
+                            &field_type_mapping,
+                            pending,
+                        )
+                        .await?;
+                        results.push(FieldValue::with_type(
+                            FieldValue::owned_any(state),
+                            component_name,
+                        ));
+                    }
+
+                    Ok(Some(FieldValue::list(results)))
+                })
+            }),
+        ])
     }
 
     fn resolvers(&self) -> Vec<Field> {
         vec![
             resolve_one(self.name(), self.type_name()), // one
-            resolve_many("entities", self.type_name()), // many
+            resolve_many("entities", self.type_name(), self.field_type_mapping()), // many
         ]
     }
+
+    fn subscriptions(&self) -> Option<Vec<SubscriptionField>> {
+        Some(vec![subscribe_updated("entityUpdated", self.type_name())])
+    }
 }
 
 fn resolve_one(name: &str, type_name: &str) -> Field {
     Field::new(name, TypeRef::named_nn(type_name), |ctx| {
         FieldFuture::new(async move {
-            let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
+            let mut conn = ctx.data::<DbPool>()?.acquire().await?;
             let id = ctx.args.try_get("id")?.string()?.to_string();
-            let entity = query_by_id(&mut conn, "entities", ID::Str(id)).await?;
+            let table_name = if pending_argument(&ctx)? { PENDING_ENTITIES } else { "entities" };
+            let entity = query_by_id(&mut conn, table_name, ID::Str(id)).await?;
             let result = EntityObject::value_mapping(entity);
             Ok(Some(FieldValue::owned_any(result)))
         })
     })
     .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID)))
+    .argument(InputValue::new("pending", TypeRef::named(TypeRef::BOOLEAN)))
 }
 
-fn resolve_many(name: &str, type_name: &str) -> Field {
-    Field::new(name, TypeRef::named_list(type_name), |ctx| {
-        FieldFuture::new(async move {
-            let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
+fn resolve_many(name: &str, type_name: &str, field_type_mapping: &TypeMapping) -> Field {
+    let filters: FiltersFn = Arc::new(|ctx: &ResolverContext<'_>| {
+        let mut filters = Vec::new();
 
-            let keys_value = ctx.args.try_get("keys")?;
-            let keys = keys_value
-                .list()?
-                .iter()
-                .map(
-                    |val| val.string().unwrap().to_string(), // safe unwrap
-                )
-                .collect();
+        if let Some(keys) = optional_string_list(ctx, "keys")? {
+            filters.push(Filter {
+                column: "keys".to_string(),
+                comparator: Comparator::Like,
+                value: QueryValue::String(format!("{},%", keys.join(","))),
+            });
+        }
 
-            let limit =
-                ctx.args.try_get("limit").and_then(|limit| limit.u64()).unwrap_or(DEFAULT_LIMIT);
+        if let Some(name) = ctx.args.get("componentName").filter(|name| !name.is_null()) {
+            // match the whole name in the csv of component names
+            filters.push(Filter {
+                column: "',' || component_names || ','".to_string(),
+                comparator: Comparator::Like,
+                value: QueryValue::String(format!("%,{},%", name.string()?)),
+            });
+        }
 
-            let component_name = ctx.args.try_get("componentName") // Add the component name argument
-                .and_then(|name| name.string().map(|s| s.to_string())).ok();
+        Ok(filters)
+    });
 
-            let entities = entities_by_sk(&mut conn, keys, component_name, limit).await?;
-            Ok(Some(FieldValue::list(entities.into_iter().map(FieldValue::owned_any))))
-        })
-    })
-    .argument(InputValue::new("keys", TypeRef::named_nn_list_nn(TypeRef::STRING)))
-    .argument(InputValue::new("limit", TypeRef::named(TypeRef::INT)))
+    resolve_connection(
+        name,
+        type_name,
+        "entities",
+        Some(PENDING_ENTITIES.to_string()),
+        order_columns(field_type_mapping, to_snake_case),
+        Some(filters),
+        Arc::new(|row: &DbRow| Entity::from_row(row).map(EntityObject::value_mapping)),
+    )
+    .argument(InputValue::new("keys", TypeRef::named_list(TypeRef::STRING)))
     .argument(InputValue::new("componentName", TypeRef::named(TypeRef::STRING)))
 }
 
-async fn entities_by_sk(
-    conn: &mut PoolConnection<Sqlite>,
-    keys: Vec<String>,
-    component_name: Option<String>, // Add the filter parameter
-    limit: u64,
-) -> Result<Vec<ValueMapping>> {
-    let mut builder: QueryBuilder<'_, Sqlite> = QueryBuilder::new("SELECT * FROM entities");
-    let keys_str = format!("{},%", keys.join(","));
-    builder.push(" WHERE keys LIKE ").push_bind(keys_str);
-
-    if let Some(name) = component_name {
-        builder
-            .push(" AND (")
-            .push("component_names = ")
-            .push_bind(name.clone())
-            .push(" OR ")
-            .push("component_names LIKE ")
-            .push_bind(format!("{},%", name))
-            .push(" OR ")
-            .push("component_names LIKE ")
-            .push_bind(format!("%,{}", name))
-            .push(" OR ")
-            .push("component_names LIKE ")
-            .push_bind(format!("%,{},%", name))
-            .push(")");
-    }
+fn subscribe_updated(name: &str, type_name: &str) -> SubscriptionField {
+    SubscriptionField::new(name, TypeRef::named_nn(type_name), |ctx| {
+        SubscriptionFieldFuture::new(async move {
+            let pool = ctx.data::<DbPool>()?.clone();
+            let receiver = ctx.data::<broadcast::Sender<EntityUpdate>>()?.subscribe();
+            let keys = optional_string_list(&ctx, "keys")?.unwrap_or_default();
+            let component_names = optional_string_list(&ctx, "componentNames")?;
+            let pending = pending_argument(&ctx)?;
+
+            let updates = entity_update_stream(receiver)
+                .filter(move |update| {
+                    let names_match = component_names.as_ref().map_or(true, |names| {
+                        names.iter().any(|name| component_matches(&update.component, name))
+                    });
+                    ready(
+                        (pending || !update.pending)
+                            && names_match
+                            && keys_match(&update.keys, &keys),
+                    )
+                })
+                .then(move |update| {
+                    let pool = pool.clone();
+                    async move {
+                        let entity = updated_entity(&pool, update).await?;
+                        let result = EntityObject::value_mapping(entity);
+                        Ok::<_, async_graphql::Error>(FieldValue::owned_any(result))
+                    }
+                });
 
-    builder.push(" ORDER BY created_at DESC LIMIT ").push(limit);
+            Ok(updates)
+        })
+    })
+    .argument(InputValue::new("keys", TypeRef::named_list(TypeRef::STRING)))
+    .argument(InputValue::new("componentNames", TypeRef::named_list(TypeRef::STRING)))
+    .argument(InputValue::new("pending", TypeRef::named(TypeRef::BOOLEAN)))
+}
 
-    let entities: Vec<Entity> = builder.build_query_as().fetch_all(conn).await?;
-    Ok(entities.into_iter().map(EntityObject::value_mapping).collect())
+async fn updated_entity(pool: &DbPool, update: EntityUpdate) -> Result<Entity> {
+    let table_name = if update.pending { PENDING_ENTITIES } else { "entities" };
+    let entity = sqlx::query_as(&format!("SELECT * FROM {} WHERE id = $1", table_name))
+        .bind(&update.entity_id)
+        .fetch_optional(pool)
+        .await?;
+
+    // The entity row is removed along with its last component
+    Ok(entity.unwrap_or_else(|| {
+        let now = Utc::now();
+        Entity {
+            id: update.entity_id,
+            partition: String::new(),
+            keys: Some(update.keys),
+            component_names: String::new(),
+            created_at: now,
+            updated_at: now,
+            pending: update.pending,
+        }
+    }))
 }
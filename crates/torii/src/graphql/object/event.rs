@@ -1,17 +1,20 @@
+use std::sync::Arc;
+
 use async_graphql::dynamic::{Field, FieldFuture, FieldValue, InputValue, TypeRef};
 use async_graphql::{Name, Value};
 use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
 use serde::Deserialize;
-use sqlx::{FromRow, Pool, Sqlite};
+use sqlx::FromRow;
 
-use super::query::{query_all, query_by_id, ID};
+use super::connection::{order_columns, resolve_connection};
+use super::query::{query_by_id, ID};
 use super::system_call::{SystemCall, SystemCallObject};
-use super::{ObjectTrait, TypeMapping, ValueMapping};
-use crate::graphql::constants::DEFAULT_LIMIT;
+use super::{value_field, ObjectTrait, TypeMapping, ValueMapping};
+use crate::db::{DbPool, DbRow};
 use crate::graphql::types::ScalarType;
 use crate::graphql::utils::extract_value::extract;
-use crate::graphql::utils::remove_quotes;
+use crate::graphql::utils::{remove_quotes, to_snake_case};
 
 #[derive(FromRow, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -20,7 +23,7 @@ pub struct Event {
     pub keys: String,
     pub data: String,
     pub created_at: DateTime<Utc>,
-    pub system_call_id: i64,
+    pub system_call_id: Option<i64>,
 }
 
 pub struct EventObject {
@@ -34,7 +37,6 @@ impl EventObject {
                 (Name::new("id"), TypeRef::ID.to_string()),
                 (Name::new("keys"), TypeRef::STRING.to_string()),
                 (Name::new("data"), TypeRef::STRING.to_string()),
-                (Name::new("systemCallId"), TypeRef::INT.to_string()),
                 (Name::new("createdAt"), ScalarType::DateTime.to_string()),
             ]),
         }
@@ -71,7 +73,7 @@ impl ObjectTrait for EventObject {
         vec![
             Field::new(self.name(), TypeRef::named_nn(self.type_name()), |ctx| {
                 FieldFuture::new(async move {
-                    let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
+                    let mut conn = ctx.data::<DbPool>()?.acquire().await?;
                     let id = remove_quotes(ctx.args.try_get("id")?.string()?);
                     let event = query_by_id(&mut conn, "events", ID::Str(id)).await?;
                     let result = EventObject::value_mapping(event);
@@ -79,40 +81,36 @@ impl ObjectTrait for EventObject {
                 })
             })
             .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID))),
-            Field::new("events", TypeRef::named_list(self.type_name()), |ctx| {
-                FieldFuture::new(async move {
-                    let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
-                    let limit = ctx
-                        .args
-                        .try_get("limit")
-                        .and_then(|limit| limit.u64())
-                        .unwrap_or(DEFAULT_LIMIT);
-
-                    let events: Vec<Event> = query_all(&mut conn, "events", limit).await?;
-                    let result: Vec<FieldValue<'_>> = events
-                        .into_iter()
-                        .map(EventObject::value_mapping)
-                        .map(FieldValue::owned_any)
-                        .collect();
-
-                    Ok(Some(FieldValue::list(result)))
-                })
-            })
-            .argument(InputValue::new("limit", TypeRef::named(TypeRef::INT))),
+            resolve_connection(
+                "events",
+                self.type_name(),
+                "events",
+                None,
+                order_columns(self.field_type_mapping(), to_snake_case),
+                None,
+                Arc::new(|row: &DbRow| Event::from_row(row).map(EventObject::value_mapping)),
+            ),
         ]
     }
 
     fn nested_fields(&self) -> Option<Vec<Field>> {
-        Some(vec![Field::new("systemCall", TypeRef::named_nn("SystemCall"), |ctx| {
-            FieldFuture::new(async move {
-                let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
-                let event_values = ctx.parent_value.try_downcast_ref::<ValueMapping>()?;
-                let syscall_id = extract::<i64>(event_values, "system_call_id")?;
-                let system_call: SystemCall =
-                    query_by_id(&mut conn, "system_calls", ID::I64(syscall_id)).await?;
-                let result = SystemCallObject::value_mapping(system_call);
-                Ok(Some(FieldValue::owned_any(result)))
-            })
-        })])
+        // Events emitted outside of a system call have neither a system call nor its id.
+        Some(vec![
+            value_field("systemCallId", TypeRef::named(TypeRef::INT)),
+            Field::new("systemCall", TypeRef::named("SystemCall"), |ctx| {
+                FieldFuture::new(async move {
+                    let event_values = ctx.parent_value.try_downcast_ref::<ValueMapping>()?;
+                    let syscall_id = match extract::<Option<i64>>(event_values, "systemCallId")? {
+                        Some(syscall_id) => syscall_id,
+                        None => return Ok(None),
+                    };
+                    let mut conn = ctx.data::<DbPool>()?.acquire().await?;
+                    let system_call: SystemCall =
+                        query_by_id(&mut conn, "system_calls", ID::I64(syscall_id)).await?;
+                    let result = SystemCallObject::value_mapping(system_call);
+                    Ok(Some(FieldValue::owned_any(result)))
+                })
+            }),
+        ])
     }
 }
@@ -1,15 +1,20 @@
 pub mod component;
 pub mod component_state;
+pub mod connection;
 pub mod entity;
 pub mod event;
 mod query;
 pub mod system;
 pub mod system_call;
 
-use async_graphql::dynamic::{Field, FieldFuture, Object, TypeRef};
+use async_graphql::dynamic::{
+    Enum, Field, FieldFuture, InputObject, Object, SubscriptionField, TypeRef,
+};
 use async_graphql::{Name, Value};
 use indexmap::IndexMap;
 
+use self::connection::{connection_objects, order_field_enum, order_input};
+
 // Type aliases for GraphQL fields
 pub type TypeMapping = IndexMap<Name, String>;
 pub type ValueMapping = IndexMap<Name, Value>;
@@ -22,6 +27,23 @@ pub trait ObjectTrait {
     fn nested_fields(&self) -> Option<Vec<Field>> {
         None
     }
+    fn subscriptions(&self) -> Option<Vec<SubscriptionField>> {
+        None
+    }
+
+    // Connection and edge objects returned by the connection resolver
+    fn connection_objects(&self) -> Vec<Object> {
+        connection_objects(self.type_name())
+    }
+
+    // Input objects of the connection resolver (ie orderBy)
+    fn input_objects(&self) -> Vec<InputObject> {
+        vec![order_input(self.type_name())]
+    }
+
+    fn enums(&self) -> Vec<Enum> {
+        vec![order_field_enum(self.type_name(), self.field_type_mapping())]
+    }
 
     // Create a new GraphQL object
     fn object(&self) -> Object {
@@ -45,9 +67,14 @@ pub trait ObjectTrait {
 }
 
 fn create_field(name: &str, field_type: &str) -> Field {
+    value_field(name, TypeRef::named_nn(field_type))
+}
+
+// Field resolving its value from the parent value mapping
+pub fn value_field(name: &str, type_ref: TypeRef) -> Field {
     let outer_name = name.to_owned();
 
-    Field::new(name, TypeRef::named_nn(field_type), move |ctx| {
+    Field::new(name, type_ref, move |ctx| {
         let inner_name = outer_name.to_owned();
 
         FieldFuture::new(async move {
@@ -1,21 +1,117 @@
+use chrono::{DateTime, Utc};
 use sqlx::pool::PoolConnection;
-use sqlx::sqlite::SqliteRow;
-use sqlx::{FromRow, QueryBuilder, Result, Sqlite};
+use sqlx::{FromRow, QueryBuilder, Result, Row};
+
+use crate::db::{Db, DbRow};
+use crate::graphql::constants::DEFAULT_LIMIT;
 
 pub enum ID {
     Str(String),
     I64(i64),
 }
 
-pub async fn query_by_id<T>(
-    conn: &mut PoolConnection<Sqlite>,
-    table_name: &str,
-    id: ID,
-) -> Result<T>
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+impl Direction {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Direction::Asc => "ASC",
+            Direction::Desc => "DESC",
+        }
+    }
+
+    fn reverse(&self) -> Direction {
+        match self {
+            Direction::Asc => Direction::Desc,
+            Direction::Desc => Direction::Asc,
+        }
+    }
+}
+
+pub struct Order {
+    pub column: String,
+    pub direction: Direction,
+}
+
+impl Default for Order {
+    fn default() -> Self {
+        Self { column: "created_at".to_string(), direction: Direction::Desc }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparator {
+    Eq,
+    Neq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+    Like,
+}
+
+impl Comparator {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Comparator::Eq => "=",
+            Comparator::Neq => "!=",
+            Comparator::Gt => ">",
+            Comparator::Gte => ">=",
+            Comparator::Lt => "<",
+            Comparator::Lte => "<=",
+            Comparator::In => "IN",
+            Comparator::Like => "LIKE",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryValue {
+    Int(i64),
+    String(String),
+    DateTime(DateTime<Utc>),
+    List(Vec<QueryValue>),
+}
+
+pub struct Filter {
+    pub column: String,
+    pub comparator: Comparator,
+    pub value: QueryValue,
+}
+
+/// The position of a row in an ordered connection, ie the value of the order column and the id
+/// of the row to break ties.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub value: QueryValue,
+    pub id: QueryValue,
+}
+
+#[derive(Default)]
+pub struct PageArguments {
+    pub first: Option<u64>,
+    pub after: Option<Cursor>,
+    pub last: Option<u64>,
+    pub before: Option<Cursor>,
+}
+
+pub struct Page {
+    pub rows: Vec<DbRow>,
+    pub has_previous_page: bool,
+    pub has_next_page: bool,
+    pub total_count: i64,
+}
+
+pub async fn query_by_id<T>(conn: &mut PoolConnection<Db>, table_name: &str, id: ID) -> Result<T>
 where
-    T: Send + Unpin + for<'a> FromRow<'a, SqliteRow>,
+    T: Send + Unpin + for<'a> FromRow<'a, DbRow>,
 {
-    let query = format!("SELECT * FROM {} WHERE id = ?", table_name);
+    let query = format!("SELECT * FROM {} WHERE id = $1", table_name);
     let result = match id {
         ID::Str(id) => sqlx::query_as::<_, T>(&query).bind(id).fetch_one(conn).await?,
         ID::I64(id) => sqlx::query_as::<_, T>(&query).bind(id).fetch_one(conn).await?,
@@ -23,16 +119,125 @@ where
     Ok(result)
 }
 
-pub async fn query_all<T>(
-    conn: &mut PoolConnection<Sqlite>,
+/// Fetches a page of the rows matching the filters, keyed on the order column and the id so that
+/// rows inserted while paginating don't shift the pages.
+pub async fn query_page(
+    conn: &mut PoolConnection<Db>,
     table_name: &str,
-    limit: u64,
-) -> Result<Vec<T>>
-where
-    T: Send + Unpin + for<'a> FromRow<'a, SqliteRow>,
-{
-    let mut builder: QueryBuilder<'_, Sqlite> = QueryBuilder::new("SELECT * FROM ");
-    builder.push(table_name).push(" ORDER BY created_at DESC LIMIT ").push(limit);
-    let results: Vec<T> = builder.build_query_as().fetch_all(conn).await?;
-    Ok(results)
+    filters: &[Filter],
+    order: &Order,
+    page: &PageArguments,
+) -> Result<Page> {
+    let mut builder: QueryBuilder<'_, Db> = QueryBuilder::new("SELECT COUNT(*) FROM ");
+    builder.push(table_name);
+    push_filters(&mut builder, filters);
+    let (total_count,): (i64,) = builder.build_query_as().fetch_one(&mut *conn).await?;
+
+    // paginating backward reads the rows in reverse order, from the `before` cursor
+    let backward = page.last.is_some();
+    let limit = if backward { page.last } else { page.first }.unwrap_or(DEFAULT_LIMIT);
+    let direction = if backward { order.direction.reverse() } else { order.direction };
+
+    let mut builder: QueryBuilder<'_, Db> = QueryBuilder::new("SELECT * FROM ");
+    builder.push(table_name);
+    let mut has_where = push_filters(&mut builder, filters);
+
+    let (after_comparator, before_comparator) = match order.direction {
+        Direction::Asc => (Comparator::Gt, Comparator::Lt),
+        Direction::Desc => (Comparator::Lt, Comparator::Gt),
+    };
+    for (cursor, comparator) in [(&page.after, after_comparator), (&page.before, before_comparator)]
+    {
+        if let Some(cursor) = cursor {
+            builder.push(if has_where { " AND " } else { " WHERE " });
+            builder.push("(").push(&order.column).push(", id) ").push(comparator.as_sql());
+            builder.push(" (");
+            push_value(&mut builder, &cursor.value);
+            builder.push(", ");
+            push_value(&mut builder, &cursor.id);
+            builder.push(")");
+            has_where = true;
+        }
+    }
+
+    builder
+        .push(" ORDER BY ")
+        .push(&order.column)
+        .push(" ")
+        .push(direction.as_sql())
+        .push(", id ")
+        .push(direction.as_sql());
+    // fetch an extra row to know whether there is another page
+    builder.push(" LIMIT ").push(limit + 1);
+
+    let mut rows = builder.build().fetch_all(conn).await?;
+    let has_more = rows.len() as u64 > limit;
+    rows.truncate(limit as usize);
+
+    if backward {
+        rows.reverse();
+        Ok(Page {
+            rows,
+            has_previous_page: has_more,
+            has_next_page: page.before.is_some(),
+            total_count,
+        })
+    } else {
+        Ok(Page {
+            rows,
+            has_previous_page: page.after.is_some(),
+            has_next_page: has_more,
+            total_count,
+        })
+    }
+}
+
+pub fn cursor_from_row(row: &DbRow, column: &str) -> Result<Cursor> {
+    Ok(Cursor { value: value_from_row(row, column)?, id: value_from_row(row, "id")? })
+}
+
+fn value_from_row(row: &DbRow, column: &str) -> Result<QueryValue> {
+    if let Ok(value) = row.try_get::<i64, &str>(column) {
+        return Ok(QueryValue::Int(value));
+    }
+
+    // timestamps are text in sqlite but a native type in postgres
+    match row.try_get::<String, &str>(column) {
+        Ok(value) => Ok(QueryValue::String(value)),
+        Err(_) => row.try_get::<DateTime<Utc>, &str>(column).map(QueryValue::DateTime),
+    }
+}
+
+fn push_filters(builder: &mut QueryBuilder<'_, Db>, filters: &[Filter]) -> bool {
+    for (i, filter) in filters.iter().enumerate() {
+        builder.push(if i == 0 { " WHERE " } else { " AND " });
+        builder.push(&filter.column).push(" ").push(filter.comparator.as_sql()).push(" ");
+        push_value(builder, &filter.value);
+    }
+
+    !filters.is_empty()
+}
+
+fn push_value(builder: &mut QueryBuilder<'_, Db>, value: &QueryValue) {
+    match value {
+        QueryValue::Int(value) => {
+            builder.push_bind(*value);
+        }
+        QueryValue::String(value) => {
+            builder.push_bind(value.clone());
+        }
+        QueryValue::DateTime(value) => {
+            builder.push_bind(*value);
+        }
+        QueryValue::List(values) => {
+            builder.push("(");
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    builder.push(", ");
+                }
+                push_value(builder, value);
+            }
+            builder.push(")");
+        }
+    }
 }
@@ -1,17 +1,20 @@
+use std::sync::Arc;
+
 use async_graphql::dynamic::{Field, FieldFuture, FieldValue, InputValue, TypeRef};
 use async_graphql::{Name, Value};
 use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
 use serde::Deserialize;
-use sqlx::{FromRow, Pool, Sqlite};
+use sqlx::FromRow;
 
-use super::query::{query_all, query_by_id, ID};
+use super::connection::{order_columns, resolve_connection};
+use super::query::{query_by_id, ID};
 use super::system_call::system_calls_by_system_id;
 use super::{ObjectTrait, TypeMapping, ValueMapping};
-use crate::graphql::constants::DEFAULT_LIMIT;
+use crate::db::{DbPool, DbRow};
 use crate::graphql::types::ScalarType;
 use crate::graphql::utils::extract_value::extract;
-use crate::graphql::utils::remove_quotes;
+use crate::graphql::utils::{remove_quotes, to_snake_case};
 
 #[derive(FromRow, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -74,7 +77,7 @@ impl ObjectTrait for SystemObject {
         vec![
             Field::new(self.name(), TypeRef::named_nn(self.type_name()), |ctx| {
                 FieldFuture::new(async move {
-                    let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
+                    let mut conn = ctx.data::<DbPool>()?.acquire().await?;
                     let id = remove_quotes(ctx.args.try_get("id")?.string()?);
                     let system = query_by_id(&mut conn, "systems", ID::Str(id)).await?;
                     let result = SystemObject::value_mapping(system);
@@ -82,33 +85,22 @@ impl ObjectTrait for SystemObject {
                 })
             })
             .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID))),
-            Field::new("systems", TypeRef::named_list(self.type_name()), |ctx| {
-                FieldFuture::new(async move {
-                    let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
-                    let limit = ctx
-                        .args
-                        .try_get("limit")
-                        .and_then(|limit| limit.u64())
-                        .unwrap_or(DEFAULT_LIMIT);
-
-                    let systems: Vec<System> = query_all(&mut conn, "systems", limit).await?;
-                    let result: Vec<FieldValue<'_>> = systems
-                        .into_iter()
-                        .map(SystemObject::value_mapping)
-                        .map(FieldValue::owned_any)
-                        .collect();
-
-                    Ok(Some(FieldValue::list(result)))
-                })
-            })
-            .argument(InputValue::new("limit", TypeRef::named(TypeRef::INT))),
+            resolve_connection(
+                "systems",
+                self.type_name(),
+                "systems",
+                None,
+                order_columns(self.field_type_mapping(), to_snake_case),
+                None,
+                Arc::new(|row: &DbRow| System::from_row(row).map(SystemObject::value_mapping)),
+            ),
         ]
     }
 
     fn nested_fields(&self) -> Option<Vec<Field>> {
         Some(vec![Field::new("systemCalls", TypeRef::named_nn_list_nn("SystemCall"), |ctx| {
             FieldFuture::new(async move {
-                let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
+                let mut conn = ctx.data::<DbPool>()?.acquire().await?;
                 let system_values = ctx.parent_value.try_downcast_ref::<ValueMapping>()?;
 
                 let id = extract::<String>(system_values, "id")?;
@@ -1,17 +1,21 @@
+use std::sync::Arc;
+
 use async_graphql::dynamic::{Field, FieldFuture, FieldValue, InputValue, TypeRef};
 use async_graphql::{Name, Value};
 use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
 use serde::Deserialize;
 use sqlx::pool::PoolConnection;
-use sqlx::{FromRow, Pool, Result, Sqlite};
+use sqlx::{FromRow, Result};
 
-use super::query::{query_all, query_by_id, ID};
+use super::connection::{order_columns, resolve_connection};
+use super::query::{query_by_id, ID};
 use super::system::SystemObject;
 use super::{ObjectTrait, TypeMapping, ValueMapping};
-use crate::graphql::constants::DEFAULT_LIMIT;
+use crate::db::{Db, DbPool, DbRow};
 use crate::graphql::types::ScalarType;
 use crate::graphql::utils::extract_value::extract;
+use crate::graphql::utils::to_snake_case;
 
 #[derive(FromRow, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -72,7 +76,7 @@ impl ObjectTrait for SystemCallObject {
         vec![
             Field::new(self.name(), TypeRef::named_nn(self.type_name()), |ctx| {
                 FieldFuture::new(async move {
-                    let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
+                    let mut conn = ctx.data::<DbPool>()?.acquire().await?;
                     let id = ctx.args.try_get("id")?.i64()?;
                     let system_call = query_by_id(&mut conn, "system_calls", ID::I64(id)).await?;
                     let result = SystemCallObject::value_mapping(system_call);
@@ -80,33 +84,24 @@ impl ObjectTrait for SystemCallObject {
                 })
             })
             .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::INT))),
-            Field::new("systemCalls", TypeRef::named_list(self.type_name()), |ctx| {
-                FieldFuture::new(async move {
-                    let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
-                    let limit = ctx
-                        .args
-                        .try_get("limit")
-                        .and_then(|limit| limit.u64())
-                        .unwrap_or(DEFAULT_LIMIT);
-
-                    let system_calls: Vec<SystemCall> =
-                        query_all(&mut conn, "system_calls", limit).await?;
-                    let result: Vec<FieldValue<'_>> = system_calls
-                        .into_iter()
-                        .map(SystemCallObject::value_mapping)
-                        .map(FieldValue::owned_any)
-                        .collect();
-
-                    Ok(Some(FieldValue::list(result)))
-                })
-            }),
+            resolve_connection(
+                "systemCalls",
+                self.type_name(),
+                "system_calls",
+                None,
+                order_columns(self.field_type_mapping(), to_snake_case),
+                None,
+                Arc::new(|row: &DbRow| {
+                    SystemCall::from_row(row).map(SystemCallObject::value_mapping)
+                }),
+            ),
         ]
     }
 
     fn nested_fields(&self) -> Option<Vec<Field>> {
         Some(vec![Field::new("system", TypeRef::named_nn("System"), |ctx| {
             FieldFuture::new(async move {
-                let mut conn = ctx.data::<Pool<Sqlite>>()?.acquire().await?;
+                let mut conn = ctx.data::<DbPool>()?.acquire().await?;
                 let syscall_values = ctx.parent_value.try_downcast_ref::<ValueMapping>()?;
                 let system_id = extract::<String>(syscall_values, "systemId")?;
                 let system = query_by_id(&mut conn, "systems", ID::Str(system_id)).await?;
@@ -118,7 +113,7 @@ impl ObjectTrait for SystemCallObject {
 }
 
 pub async fn system_calls_by_system_id(
-    conn: &mut PoolConnection<Sqlite>,
+    conn: &mut PoolConnection<Db>,
     id: &str,
 ) -> Result<Vec<ValueMapping>> {
     let system_calls: Vec<SystemCall> =
@@ -1,9 +1,14 @@
+use std::sync::Arc;
+
 use anyhow::Result;
-use async_graphql::dynamic::{Object, Scalar, Schema, Union};
-use sqlx::SqlitePool;
+use async_graphql::dynamic::{Object, Scalar, Schema, Subscription, Union};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{error, info};
 
 use super::object::component::Component;
 use super::object::component_state::{type_mapping_from, ComponentStateObject};
+use super::object::connection::{order_direction_enum, page_info_object};
 use super::object::entity::EntityObject;
 use super::object::event::EventObject;
 use super::object::system::SystemObject;
@@ -11,9 +16,17 @@ use super::object::system_call::SystemCallObject;
 use super::object::ObjectTrait;
 use super::types::ScalarType;
 use super::utils::format_name;
+use crate::db::DbPool;
+use crate::state::EntityUpdate;
+
+/// The schema being served, swapped for a new one whenever the components change.
+pub type SharedSchema = Arc<RwLock<Schema>>;
 
-pub async fn build_schema(pool: &SqlitePool) -> Result<Schema> {
-    let mut schema_builder = Schema::build("Query", None, None);
+pub async fn build_schema(
+    pool: &DbPool,
+    entity_updates: broadcast::Sender<EntityUpdate>,
+) -> Result<Schema> {
+    let mut schema_builder = Schema::build("Query", None, Some("Subscription"));
 
     // static objects + dynamic objects
     let mut objects = static_objects();
@@ -32,6 +45,16 @@ pub async fn build_schema(pool: &SqlitePool) -> Result<Schema> {
         query_root = query_root.field(field);
     }
 
+    // add subscription fields to subscription root
+    let mut subscription_root = Subscription::new("Subscription");
+    for object in &objects {
+        if let Some(subscriptions) = object.subscriptions() {
+            for field in subscriptions {
+                subscription_root = subscription_root.field(field);
+            }
+        }
+    }
+
     // register custom scalars
     for scalar_type in ScalarType::types().iter() {
         schema_builder = schema_builder.register(Scalar::new(scalar_type.to_string()));
@@ -42,10 +65,53 @@ pub async fn build_schema(pool: &SqlitePool) -> Result<Schema> {
         schema_builder = schema_builder.register(object.object());
     }
 
+    // register connection objects and their inputs (ie orderBy)
+    schema_builder = schema_builder.register(page_info_object()).register(order_direction_enum());
+    for object in &objects {
+        for connection_object in object.connection_objects() {
+            schema_builder = schema_builder.register(connection_object);
+        }
+        for input_object in object.input_objects() {
+            schema_builder = schema_builder.register(input_object);
+        }
+        for enum_object in object.enums() {
+            schema_builder = schema_builder.register(enum_object);
+        }
+    }
+
     // component union
     schema_builder = schema_builder.register(component_union);
 
-    schema_builder.register(query_root).data(pool.clone()).finish().map_err(|e| e.into())
+    schema_builder
+        .register(query_root)
+        .register(subscription_root)
+        .data(pool.clone())
+        .data(entity_updates)
+        .finish()
+        .map_err(|e| e.into())
+}
+
+/// Rebuilds the schema every time a component is registered, until the registrations channel
+/// is closed. The new schema replaces the shared one only once it is fully built.
+pub async fn reload_schema(
+    schema: SharedSchema,
+    pool: DbPool,
+    entity_updates: broadcast::Sender<EntityUpdate>,
+    mut component_registrations: broadcast::Receiver<String>,
+) {
+    loop {
+        match component_registrations.recv().await {
+            Ok(name) => info!("rebuilding schema for component: {}", name),
+            // the missed registrations are all picked up by a single rebuild
+            Err(RecvError::Lagged(_)) => info!("rebuilding schema for missed components"),
+            Err(RecvError::Closed) => return,
+        }
+
+        match build_schema(&pool, entity_updates.clone()).await {
+            Ok(new_schema) => *schema.write().await = new_schema,
+            Err(e) => error!("failed to rebuild schema: {:?}", e),
+        }
+    }
 }
 
 // predefined base objects
@@ -58,7 +124,7 @@ fn static_objects() -> Vec<Box<dyn ObjectTrait>> {
     ]
 }
 
-async fn dynamic_objects(pool: &SqlitePool) -> Result<(Vec<Box<dyn ObjectTrait>>, Union)> {
+async fn dynamic_objects(pool: &DbPool) -> Result<(Vec<Box<dyn ObjectTrait>>, Union)> {
     let mut conn = pool.acquire().await?;
     let mut objects: Vec<Box<dyn ObjectTrait>> = Vec::new();
 
@@ -1,22 +1,58 @@
-use async_graphql::http::GraphiQLSource;
-use async_graphql_poem::GraphQL;
+use std::sync::Arc;
+
+use async_graphql::http::{GraphiQLSource, ALL_WEBSOCKET_PROTOCOLS};
+use async_graphql_poem::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
 use poem::listener::TcpListener;
 use poem::middleware::Cors;
-use poem::web::Html;
+use poem::web::websocket::WebSocket;
+use poem::web::{Data, Html};
 use poem::{get, handler, EndpointExt, IntoResponse, Route, Server};
-use sqlx::{Pool, Sqlite};
+use tokio::sync::{broadcast, RwLock};
 
-use super::schema::build_schema;
+use super::schema::{build_schema, reload_schema, SharedSchema};
+use crate::db::DbPool;
+use crate::state::EntityUpdate;
 
 #[handler]
 async fn graphiql() -> impl IntoResponse {
-    Html(GraphiQLSource::build().endpoint("/").finish())
+    Html(GraphiQLSource::build().endpoint("/").subscription_endpoint("/ws").finish())
+}
+
+#[handler]
+async fn graphql(schema: Data<&SharedSchema>, req: GraphQLRequest) -> GraphQLResponse {
+    let schema = schema.read().await.clone();
+    schema.execute(req.0).await.into()
+}
+
+#[handler]
+async fn graphql_ws(
+    schema: Data<&SharedSchema>,
+    protocol: GraphQLProtocol,
+    websocket: WebSocket,
+) -> impl IntoResponse {
+    // a subscription keeps the schema it was started with
+    let schema = schema.read().await.clone();
+    websocket
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |stream| GraphQLWebSocket::new(stream, schema, protocol).serve())
 }
 
-pub async fn start_graphql(pool: &Pool<Sqlite>) -> anyhow::Result<()> {
-    let schema = build_schema(pool).await?;
+pub async fn start_graphql(
+    pool: &DbPool,
+    entity_updates: broadcast::Sender<EntityUpdate>,
+    component_registrations: broadcast::Sender<String>,
+) -> anyhow::Result<()> {
+    // subscribe before building so no registration is missed in between
+    let registrations = component_registrations.subscribe();
+    let schema: SharedSchema =
+        Arc::new(RwLock::new(build_schema(pool, entity_updates.clone()).await?));
+    tokio::spawn(reload_schema(schema.clone(), pool.clone(), entity_updates, registrations));
 
-    let app = Route::new().at("/", get(graphiql).post(GraphQL::new(schema))).with(Cors::new());
+    let app = Route::new()
+        .at("/", get(graphiql).post(graphql))
+        .at("/ws", get(graphql_ws))
+        .data(schema)
+        .with(Cors::new());
     Server::new(TcpListener::bind("0.0.0.0:8080")).run(app).await?;
 
     Ok(())
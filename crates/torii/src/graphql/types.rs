@@ -1,6 +1,8 @@
 use std::collections::HashSet;
 use std::fmt;
 
+use crate::db::Backend;
+
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub enum ScalarType {
     U8,
@@ -106,9 +108,9 @@ impl ScalarType {
         }
     }
 
-    pub fn as_sql_type(&self) -> &'static str {
+    pub fn as_sql_type(&self, backend: Backend) -> &'static str {
         if self.is_numeric_type() {
-            "INTEGER"
+            backend.integer_type()
         } else {
             "TEXT"
         }
@@ -9,12 +9,27 @@ pub trait ExtractValue: Sized {
     fn extract(value_accessor: ValueAccessor<'_>) -> Result<Self>;
 }
 
+impl ExtractValue for bool {
+    fn extract(value_accessor: ValueAccessor<'_>) -> Result<Self> {
+        value_accessor.boolean()
+    }
+}
+
 impl ExtractValue for i64 {
     fn extract(value_accessor: ValueAccessor<'_>) -> Result<Self> {
         value_accessor.i64()
     }
 }
 
+impl ExtractValue for Option<i64> {
+    fn extract(value_accessor: ValueAccessor<'_>) -> Result<Self> {
+        if value_accessor.is_null() {
+            return Ok(None);
+        }
+        value_accessor.i64().map(Some)
+    }
+}
+
 impl ExtractValue for String {
     fn extract(value_accessor: ValueAccessor<'_>) -> Result<Self> {
         let str = value_accessor.string()?;
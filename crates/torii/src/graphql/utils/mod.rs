@@ -1,4 +1,5 @@
 pub mod extract_value;
+pub mod subscription;
 pub mod value_accessor;
 
 pub fn remove_quotes(s: &str) -> String {
@@ -14,3 +15,18 @@ pub fn format_name(input: &str) -> (String, String) {
 pub fn csv_to_vec(csv: &str) -> Vec<String> {
     csv.split(',').map(|s| s.trim().to_string()).collect()
 }
+
+pub fn to_snake_case(input: &str) -> String {
+    input.chars().fold(String::new(), |mut snake, c| {
+        if c.is_uppercase() && !snake.is_empty() {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+        snake
+    })
+}
+
+// GraphQL enum value of a field, ie createdAt => CREATED_AT
+pub fn to_enum_value(name: &str) -> String {
+    to_snake_case(name).to_uppercase()
+}
@@ -0,0 +1,47 @@
+use async_graphql::dynamic::ResolverContext;
+use async_graphql::futures_util::stream::{self, Stream};
+use async_graphql::Result;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+use crate::state::EntityUpdate;
+
+/// Turns a receiver of entity updates into a stream, skipping the updates a lagging subscriber
+/// has missed.
+pub fn entity_update_stream(receiver: Receiver<EntityUpdate>) -> impl Stream<Item = EntityUpdate> {
+    stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(update) => return Some((update, receiver)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+/// Whether the keys of an entity, stored as `0x1,0x2,`, start with the given keys. No keys
+/// matches every entity.
+pub fn keys_match(entity_keys: &str, keys: &[String]) -> bool {
+    keys.is_empty() || entity_keys.starts_with(&format!("{},", keys.join(",")))
+}
+
+/// Whether an updated component is the one of the given name. Names are compared ignoring case,
+/// like the `componentName` filter of the entities query.
+pub fn component_matches(component: &str, name: &str) -> bool {
+    component.eq_ignore_ascii_case(name)
+}
+
+pub fn optional_string_list(ctx: &ResolverContext<'_>, name: &str) -> Result<Option<Vec<String>>> {
+    match ctx.args.get(name).filter(|value| !value.is_null()) {
+        Some(value) => {
+            let list = value.list()?;
+            let strings = list
+                .iter()
+                .map(|value| value.string().map(|s| s.to_string()))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Some(strings))
+        }
+        None => Ok(None),
+    }
+}
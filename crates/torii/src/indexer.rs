@@ -1,6 +1,7 @@
 use std::error::Error;
 
 use dojo_world::manifest::Manifest;
+use starknet::core::types::FieldElement;
 use starknet::providers::jsonrpc::{JsonRpcClient, JsonRpcTransport};
 use tracing::info;
 
@@ -22,10 +23,11 @@ impl<'a, S: State + Executable, T: JsonRpcTransport + Sync + Send> Indexer<'a, S
         provider: &'a JsonRpcClient<T>,
         processors: Processors<S, T>,
         manifest: Manifest,
+        world_address: FieldElement,
         start_block: Option<u64>,
+        config: EngineConfig,
     ) -> Self {
-        let engine =
-            Engine::new(storage, provider, processors, start_block, EngineConfig::default());
+        let engine = Engine::new(storage, provider, processors, world_address, start_block, config);
         Self { storage, provider, engine, manifest }
     }
 
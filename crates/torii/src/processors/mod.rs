@@ -1,13 +1,16 @@
-use anyhow::{Error, Result};
+use anyhow::{anyhow, Error, Result};
 use async_trait::async_trait;
-use starknet::core::types::{BlockWithTxs, Event, TransactionReceipt};
+use starknet::core::types::{BlockWithTxs, Event, FieldElement, TransactionReceipt};
+use starknet::core::utils::parse_cairo_short_string;
 use starknet::providers::jsonrpc::{JsonRpcClient, JsonRpcTransport};
 
 use crate::state::State;
 
 pub mod register_component;
 pub mod register_system;
+pub mod store_del_record;
 pub mod store_set_record;
+pub mod store_transaction;
 
 #[async_trait]
 pub trait EventProcessor<S: State, T: JsonRpcTransport> {
@@ -44,3 +47,29 @@ pub trait TransactionProcessor<S: State, T: JsonRpcTransport> {
         transaction_receipt: &TransactionReceipt,
     ) -> Result<(), Error>;
 }
+
+/// Returns the array of felts whose length is at `len_index` in the event data.
+pub(crate) fn values_at(
+    data: &[FieldElement],
+    len_index: usize,
+) -> Result<Vec<FieldElement>, Error> {
+    let len =
+        data.get(len_index).ok_or_else(|| anyhow!("Missing array length at {}", len_index))?;
+    let len: usize = u32::try_from(*len)?.try_into()?;
+    let start = len_index + 1;
+    let values = start.checked_add(len).and_then(|end| data.get(start..end)).ok_or_else(|| {
+        anyhow!("Array of {} values at {} exceeds the event data", len, len_index)
+    })?;
+    Ok(values.to_vec())
+}
+
+/// Returns the name of the component whose table id is at `index` in the event data, or `None`
+/// for a partitioned table.
+///
+/// The table id of the default partition is the component name. The table id of any other
+/// partition is the hash of the component name and the partition, from which neither can be
+/// recovered, so only the default partition is indexed.
+pub(crate) fn component_name(data: &[FieldElement], index: usize) -> Result<Option<String>, Error> {
+    let table_id = data.get(index).ok_or_else(|| anyhow!("Missing table id at {}", index))?;
+    Ok(parse_cairo_short_string(table_id).ok())
+}
@@ -0,0 +1,44 @@
+use anyhow::{Error, Ok, Result};
+use async_trait::async_trait;
+use starknet::core::types::{BlockWithTxs, Event, TransactionReceipt};
+use starknet::providers::jsonrpc::{JsonRpcClient, JsonRpcTransport};
+use starknet_crypto::FieldElement;
+use tracing::{info, warn};
+
+use super::{component_name, values_at, EventProcessor};
+use crate::state::State;
+
+#[derive(Default)]
+pub struct StoreDelRecordProcessor;
+
+const COMPONENT_INDEX: usize = 0;
+const NUM_KEYS_INDEX: usize = 1;
+
+#[async_trait]
+impl<S: State + Sync, T: JsonRpcTransport> EventProcessor<S, T> for StoreDelRecordProcessor {
+    fn event_key(&self) -> String {
+        "StoreDelRecord".to_string()
+    }
+
+    async fn process(
+        &self,
+        storage: &S,
+        _provider: &JsonRpcClient<T>,
+        _block: &BlockWithTxs,
+        _transaction_receipt: &TransactionReceipt,
+        event: &Event,
+    ) -> Result<(), Error> {
+        let Some(name) = component_name(&event.data, COMPONENT_INDEX)? else {
+            warn!("skipping store delete record of a partitioned table");
+            return Ok(());
+        };
+        info!("store delete record: {}", name);
+
+        let keys = values_at(&event.data, NUM_KEYS_INDEX)?;
+        // Only the default partition is indexed, see `component_name`.
+        let partition = FieldElement::ZERO;
+
+        storage.delete_entity(name, partition, keys).await?;
+        Ok(())
+    }
+}
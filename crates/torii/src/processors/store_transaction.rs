@@ -0,0 +1,192 @@
+use anyhow::{anyhow, Error, Ok, Result};
+use async_trait::async_trait;
+use starknet::core::types::{
+    BlockWithTxs, FieldElement, InvokeTransaction, Transaction, TransactionReceipt,
+};
+use starknet::core::utils::{get_selector_from_name, parse_cairo_short_string};
+use starknet::providers::jsonrpc::{JsonRpcClient, JsonRpcTransport};
+use tracing::{info, warn};
+
+use super::TransactionProcessor;
+use crate::state::State;
+
+#[cfg(test)]
+#[path = "store_transaction_test.rs"]
+mod test;
+
+/// Stores the `World::execute` calls of a transaction as system calls, and the events emitted by
+/// the world as events of the transaction.
+pub struct StoreTransactionProcessor {
+    pub world_address: FieldElement,
+}
+
+/// A call made by an account.
+struct Call {
+    to: FieldElement,
+    selector: FieldElement,
+    calldata: Vec<FieldElement>,
+}
+
+#[async_trait]
+impl<S: State + Sync, T: JsonRpcTransport> TransactionProcessor<S, T>
+    for StoreTransactionProcessor
+{
+    fn get_transaction_hash(&self) -> String {
+        // Every transaction is processed.
+        String::new()
+    }
+
+    async fn process(
+        &self,
+        storage: &S,
+        _provider: &JsonRpcClient<T>,
+        block: &BlockWithTxs,
+        transaction_receipt: &TransactionReceipt,
+    ) -> Result<(), Error> {
+        let receipt = match transaction_receipt {
+            TransactionReceipt::Invoke(receipt) => receipt,
+            _ => return Ok(()),
+        };
+
+        let calldata = block.transactions.iter().find_map(|transaction| match transaction {
+            Transaction::Invoke(InvokeTransaction::V1(transaction))
+                if transaction.transaction_hash == receipt.transaction_hash =>
+            {
+                Some(&transaction.calldata)
+            }
+            _ => None,
+        });
+
+        let mut system_calls = 0;
+        if let Some(calldata) = calldata {
+            let calls = parse_calls(calldata).unwrap_or_else(|e| {
+                warn!(
+                    "skipping undecodable calldata of transaction {:#x}: {}",
+                    receipt.transaction_hash, e
+                );
+                vec![]
+            });
+
+            let execute_selector = get_selector_from_name("execute")?;
+            for call in calls {
+                if call.to != self.world_address || call.selector != execute_selector {
+                    continue;
+                }
+
+                // `execute` takes the system name followed by the system calldata as a span.
+                let Some(system) =
+                    call.calldata.first().and_then(|system| parse_cairo_short_string(system).ok())
+                else {
+                    warn!(
+                        "skipping system call without a valid system name in transaction {:#x}",
+                        receipt.transaction_hash
+                    );
+                    continue;
+                };
+                let system_calldata = call.calldata.get(2..).unwrap_or_default().to_vec();
+                info!("system call: {}", system);
+
+                storage
+                    .store_system_call(system, receipt.transaction_hash, system_calldata)
+                    .await?;
+                system_calls += 1;
+            }
+        }
+
+        // The receipt doesn't tell which call emitted an event, so events are only linked to a
+        // system call when it's the only one of the transaction.
+        let system_call_index = (system_calls == 1).then_some(0);
+        for (index, event) in receipt.events.iter().enumerate() {
+            if event.from_address != self.world_address {
+                continue;
+            }
+
+            storage
+                .store_event(
+                    format!("{:#x}:{}", receipt.transaction_hash, index),
+                    event.keys.clone(),
+                    event.data.clone(),
+                    receipt.transaction_hash,
+                    system_call_index,
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns whether the transaction is an invoke calling the world, whether it emits world events
+/// or not.
+pub(crate) fn calls_world(transaction: &Transaction, world_address: FieldElement) -> bool {
+    match transaction {
+        Transaction::Invoke(InvokeTransaction::V1(transaction)) => {
+            parse_calls(&transaction.calldata)
+                .is_ok_and(|calls| calls.iter().any(|call| call.to == world_address))
+        }
+        _ => false,
+    }
+}
+
+/// Decodes the calldata of an account's `__execute__`, in either of the layouts of the
+/// accounts:
+/// - Cairo 0: the call array (`to`, `selector`, `data_offset` and `data_len` of each call),
+///   followed by the length and the calldata of all the calls.
+/// - Cairo 1: an `Array<Call>`, the `to`, `selector` and calldata span of each call.
+///
+/// The layout which accounts for the whole calldata is used.
+fn parse_calls(calldata: &[FieldElement]) -> Result<Vec<Call>> {
+    parse_legacy_calls(calldata)
+        .or_else(|| parse_array_calls(calldata))
+        .ok_or_else(|| anyhow!("Invalid account calldata"))
+}
+
+fn parse_legacy_calls(calldata: &[FieldElement]) -> Option<Vec<Call>> {
+    let calls_len = usize_at(calldata, 0)?;
+    let data_len_index = calls_len.checked_mul(4)?.checked_add(1)?;
+    let data_start = data_len_index + 1;
+    if data_start.checked_add(usize_at(calldata, data_len_index)?)? != calldata.len() {
+        return None;
+    }
+
+    (0..calls_len)
+        .map(|i| {
+            let call_start = 1 + i * 4;
+            let data_offset = data_start.checked_add(usize_at(calldata, call_start + 2)?)?;
+            let data_len = usize_at(calldata, call_start + 3)?;
+            let data = calldata.get(data_offset..data_offset.checked_add(data_len)?)?;
+
+            Some(Call {
+                to: calldata[call_start],
+                selector: calldata[call_start + 1],
+                calldata: data.to_vec(),
+            })
+        })
+        .collect()
+}
+
+fn parse_array_calls(calldata: &[FieldElement]) -> Option<Vec<Call>> {
+    let calls_len = usize_at(calldata, 0)?;
+
+    let mut calls = vec![];
+    let mut offset = 1;
+    for _ in 0..calls_len {
+        let data_len = usize_at(calldata, offset + 2)?;
+        let data_start = offset + 3;
+        let data = calldata.get(data_start..data_start.checked_add(data_len)?)?;
+
+        calls.push(Call {
+            to: calldata[offset],
+            selector: calldata[offset + 1],
+            calldata: data.to_vec(),
+        });
+        offset = data_start + data_len;
+    }
+
+    (offset == calldata.len()).then_some(calls)
+}
+
+fn usize_at(calldata: &[FieldElement], index: usize) -> Option<usize> {
+    let value = u32::try_from(*calldata.get(index)?).ok()?;
+    Some(value as usize)
+}
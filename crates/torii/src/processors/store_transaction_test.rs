@@ -0,0 +1,58 @@
+use starknet::core::types::FieldElement;
+
+use super::parse_calls;
+
+fn felts(values: &[u64]) -> Vec<FieldElement> {
+    values.iter().map(|value| FieldElement::from(*value)).collect()
+}
+
+#[test]
+fn test_parse_legacy_calls() {
+    // Two calls, with the calldata of both after the call array.
+    let calldata = felts(&[2, 0x10, 0x11, 0, 2, 0x20, 0x21, 2, 1, 3, 0xa, 0xb, 0xc]);
+
+    let calls = parse_calls(&calldata).unwrap();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].to, FieldElement::from(0x10_u64));
+    assert_eq!(calls[0].selector, FieldElement::from(0x11_u64));
+    assert_eq!(calls[0].calldata, felts(&[0xa, 0xb]));
+    assert_eq!(calls[1].to, FieldElement::from(0x20_u64));
+    assert_eq!(calls[1].selector, FieldElement::from(0x21_u64));
+    assert_eq!(calls[1].calldata, felts(&[0xc]));
+}
+
+#[test]
+fn test_parse_array_calls() {
+    // Two calls, each followed by its calldata.
+    let calldata = felts(&[2, 0x10, 0x11, 2, 0xa, 0xb, 0x20, 0x21, 1, 0xc]);
+
+    let calls = parse_calls(&calldata).unwrap();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].to, FieldElement::from(0x10_u64));
+    assert_eq!(calls[0].selector, FieldElement::from(0x11_u64));
+    assert_eq!(calls[0].calldata, felts(&[0xa, 0xb]));
+    assert_eq!(calls[1].to, FieldElement::from(0x20_u64));
+    assert_eq!(calls[1].selector, FieldElement::from(0x21_u64));
+    assert_eq!(calls[1].calldata, felts(&[0xc]));
+}
+
+#[test]
+fn test_parse_calls_without_calldata() {
+    let calls = parse_calls(&felts(&[1, 0x10, 0x11, 0, 0, 0])).unwrap();
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].calldata.is_empty());
+
+    let calls = parse_calls(&felts(&[1, 0x10, 0x11, 0])).unwrap();
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].calldata.is_empty());
+}
+
+#[test]
+fn test_parse_invalid_calls() {
+    // Truncated calldata, in both layouts.
+    assert!(parse_calls(&felts(&[2, 0x10, 0x11, 0, 2, 0x20, 0x21, 2, 1, 3, 0xa])).is_err());
+    assert!(parse_calls(&felts(&[2, 0x10, 0x11, 2, 0xa, 0xb, 0x20, 0x21, 1])).is_err());
+    // Trailing data which no call accounts for.
+    assert!(parse_calls(&felts(&[1, 0x10, 0x11, 1, 0xa, 0xb])).is_err());
+    assert!(parse_calls(&[]).is_err());
+}
@@ -22,15 +22,62 @@ pub struct World {
     executor_class_hash: SQLFieldElement,
 }
 
+/// A change to the components of an entity, published once it is committed.
+#[derive(Clone, Debug)]
+pub struct EntityUpdate {
+    pub entity_id: String,
+    /// The keys of the entity, formatted as they are stored in the `entities` table.
+    pub keys: String,
+    /// The name of the component which was set or deleted.
+    pub component: String,
+    pub deleted: bool,
+    /// Whether the update was made to the pending overlay.
+    pub pending: bool,
+}
+
 #[async_trait]
 pub trait State {
     async fn load_from_manifest(&self, manifest: Manifest) -> Result<()>;
     async fn head(&self) -> Result<u64>;
     async fn set_head(&self, head: u64) -> Result<()>;
+    /// Records the block about to be processed, against which the following writes are
+    /// journaled.
+    async fn set_block(
+        &self,
+        number: u64,
+        hash: FieldElement,
+        parent_hash: FieldElement,
+    ) -> Result<()>;
+    async fn block_hash(&self, number: u64) -> Result<Option<FieldElement>>;
+    /// Returns the number and hash of the last block recorded before `number`.
+    async fn previous_block(&self, number: u64) -> Result<Option<(u64, FieldElement)>>;
+    /// Reverts the writes journaled for the blocks after `number`, which becomes the head.
+    async fn rollback(&self, number: u64) -> Result<()>;
+    /// Routes the entity writes to the pending overlay until it's turned off. Only entities are
+    /// overlaid, the rest is indexed once the block is closed.
+    async fn set_pending(&self, pending: bool);
+    /// Clears the pending overlay, once the block it was built on is closed.
+    async fn clear_pending(&self) -> Result<()>;
     async fn world(&self) -> Result<World>;
     async fn set_world(&self, world: World) -> Result<()>;
     async fn register_component(&self, component: Component) -> Result<()>;
     async fn register_system(&self, system: System) -> Result<()>;
+    async fn store_system_call(
+        &self,
+        system: String,
+        transaction_hash: FieldElement,
+        calldata: Vec<FieldElement>,
+    ) -> Result<()>;
+    /// Stores an event emitted by the world, linked to the system call of its transaction at
+    /// `system_call_index`, in the order they were stored, if the call emitting it is known.
+    async fn store_event(
+        &self,
+        event_id: String,
+        keys: Vec<FieldElement>,
+        data: Vec<FieldElement>,
+        transaction_hash: FieldElement,
+        system_call_index: Option<usize>,
+    ) -> Result<()>;
     async fn set_entity(
         &self,
         component: String,
@@ -42,7 +89,7 @@ pub trait State {
         &self,
         component: String,
         partition: FieldElement,
-        key: FieldElement,
+        keys: Vec<FieldElement>,
     ) -> Result<()>;
     async fn entity(
         &self,
@@ -1,100 +1,467 @@
-use super::{State, World};
+use std::collections::HashSet;
+
+use super::{EntityUpdate, State, World};
+use crate::db::{Backend, Db, DbPool, DbRow};
 use crate::graphql::types::ScalarType;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use dojo_world::manifest::{Component, Manifest, System};
 use sqlx::pool::PoolConnection;
-use sqlx::sqlite::SqliteRow;
-use sqlx::{Executor, Pool, Row, Sqlite};
+use sqlx::{Executor, Row, Transaction};
 use starknet::core::types::FieldElement;
 use starknet_crypto::poseidon_hash_many;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 
 #[cfg(test)]
 #[path = "sql_test.rs"]
 mod test;
 
+/// The number of entity updates buffered for slow subscribers.
+const ENTITY_UPDATES_CAPACITY: usize = 1024;
+/// The number of component registrations buffered for slow subscribers.
+const COMPONENT_REGISTRATIONS_CAPACITY: usize = 64;
+/// The number of blocks behind the last processed one whose writes are journaled, reorgs deeper
+/// than this can't be rolled back.
+const JOURNAL_DEPTH: u64 = 64;
+
+/// A value bound to a parameter of a query.
+#[derive(Clone, Debug)]
+pub enum Argument {
+    Null,
+    Int(i64),
+    Bool(bool),
+    String(String),
+    FieldElement(FieldElement),
+}
+
+impl Argument {
+    /// Encodes the argument to be stored in the journal.
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Argument::Null => serde_json::Value::Null,
+            Argument::Int(value) => serde_json::Value::from(*value),
+            Argument::Bool(value) => serde_json::Value::from(*value),
+            Argument::String(value) => serde_json::Value::from(value.clone()),
+            Argument::FieldElement(value) => serde_json::Value::from(format!("{:#x}", value)),
+        }
+    }
+
+    fn from_json(value: &serde_json::Value) -> Result<Self> {
+        match value {
+            serde_json::Value::Null => Ok(Argument::Null),
+            serde_json::Value::Number(number) => number
+                .as_i64()
+                .map(Argument::Int)
+                .ok_or_else(|| anyhow!("Invalid journaled argument {}", number)),
+            serde_json::Value::Bool(value) => Ok(Argument::Bool(*value)),
+            serde_json::Value::String(value) => Ok(Argument::String(value.clone())),
+            _ => Err(anyhow!("Invalid journaled argument {}", value)),
+        }
+    }
+}
+
+/// A statement and the arguments bound to its parameters, in order.
+#[derive(Clone, Debug)]
+pub struct Query {
+    statement: String,
+    arguments: Vec<Argument>,
+}
+
+impl Query {
+    pub fn new(statement: impl Into<String>, arguments: Vec<Argument>) -> Self {
+        Self { statement: statement.into(), arguments }
+    }
+}
+
 #[async_trait]
 pub trait Executable {
     async fn execute(&self) -> Result<()>;
-    async fn queue(&self, queries: Vec<String>);
+    async fn queue(&self, queries: Vec<Query>);
 }
 
 pub struct Sql {
     world_address: FieldElement,
-    pool: Pool<Sqlite>,
-    query_queue: Mutex<Vec<String>>,
+    pool: DbPool,
+    query_queue: Mutex<Vec<Query>>,
+    /// The block being processed, against which the writes are journaled.
+    journal_block: Mutex<Option<i64>>,
+    /// Whether the entity writes go to the pending overlay.
+    pending: Mutex<bool>,
+    entity_updates: broadcast::Sender<EntityUpdate>,
+    /// The components registered since the last commit, published once it is done.
+    registered_components: Mutex<Vec<String>>,
+    component_registrations: broadcast::Sender<String>,
 }
 
 impl Sql {
-    pub async fn new(pool: Pool<Sqlite>, world_address: FieldElement) -> Result<Self> {
+    pub async fn new(pool: DbPool, world_address: FieldElement) -> Result<Self> {
         let queries = vec![
-            format!(
-                "INSERT OR IGNORE INTO indexers (id, head) VALUES ('{:#x}', '{}')",
-                world_address, 0
+            Query::new(
+                "INSERT INTO indexers (id, head) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                vec![Argument::FieldElement(world_address), Argument::Int(0)],
             ),
-            format!(
-                "INSERT OR IGNORE INTO worlds (id, world_address) VALUES ('{:#x}', '{:#x}')",
-                world_address, world_address
+            Query::new(
+                "INSERT INTO worlds (id, world_address) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                vec![Argument::FieldElement(world_address), Argument::FieldElement(world_address)],
             ),
         ];
 
         let mut tx = pool.begin().await?;
 
         for query in queries {
-            tx.execute(sqlx::query(&query)).await?;
+            execute_query(&mut tx, query).await?;
+        }
+
+        // The components registered before the pending overlay existed don't have its tables.
+        let components: Vec<(String,)> =
+            sqlx::query_as("SELECT id FROM components").fetch_all(&mut tx).await?;
+        for (component_id,) in components {
+            let members: Vec<(String, String)> = sqlx::query_as(
+                "SELECT name, type FROM component_members WHERE component_id = $1 ORDER BY slot",
+            )
+            .bind(&component_id)
+            .fetch_all(&mut tx)
+            .await?;
+
+            let component_id = validate_identifier(&component_id)?;
+            for query in component_tables(Backend::of(&pool), component_id, &members)? {
+                execute_query(&mut tx, query).await?;
+            }
         }
 
         tx.commit().await?;
 
-        Ok(Self { pool, world_address, query_queue: Mutex::new(vec![]) })
+        let (entity_updates, _) = broadcast::channel(ENTITY_UPDATES_CAPACITY);
+        let (component_registrations, _) = broadcast::channel(COMPONENT_REGISTRATIONS_CAPACITY);
+
+        Ok(Self {
+            pool,
+            world_address,
+            query_queue: Mutex::new(vec![]),
+            journal_block: Mutex::new(None),
+            pending: Mutex::new(false),
+            entity_updates,
+            registered_components: Mutex::new(vec![]),
+            component_registrations,
+        })
+    }
+
+    /// Returns the sender of the entity updates, from which any number of subscribers can be
+    /// created.
+    pub fn entity_updates(&self) -> broadcast::Sender<EntityUpdate> {
+        self.entity_updates.clone()
+    }
+
+    /// Returns the sender of the names of newly registered components, published once their
+    /// tables are committed, and of the components dropped by a rollback.
+    pub fn component_registrations(&self) -> broadcast::Sender<String> {
+        self.component_registrations.clone()
+    }
+
+    /// Queues `queries` along with the journal entries of the `undo` queries reverting them, which
+    /// are applied in reverse order on rollback, and of the entity component they change, which is
+    /// notified again on rollback. Writes made outside of a block aren't journaled.
+    async fn queue_journaled(
+        &self,
+        mut queries: Vec<Query>,
+        undo: Vec<Query>,
+        update: Option<&EntityUpdate>,
+    ) {
+        if let Some(block_number) = *self.journal_block.lock().await {
+            let entity = match update {
+                Some(update) => [&update.entity_id, &update.keys, &update.component]
+                    .map(|value| Argument::String(value.clone())),
+                None => [Argument::Null, Argument::Null, Argument::Null],
+            };
+
+            for query in undo {
+                let arguments = query.arguments.iter().map(Argument::to_json).collect();
+                queries.push(Query::new(
+                    "INSERT INTO journal (block_number, statement, arguments, entity_id, keys, \
+                     component) VALUES ($1, $2, $3, $4, $5, $6)",
+                    [
+                        Argument::Int(block_number),
+                        Argument::String(query.statement),
+                        Argument::String(serde_json::Value::Array(arguments).to_string()),
+                    ]
+                    .into_iter()
+                    .chain(entity.clone())
+                    .collect(),
+                ));
+            }
+        }
+
+        self.queue(queries).await;
+    }
+
+    /// Returns the query restoring the component of an entity to its current values, or removing
+    /// it if the entity doesn't have the component.
+    async fn restore_component(
+        &self,
+        table: &str,
+        entity_id: &str,
+        member_results: &[DbRow],
+    ) -> Result<Query> {
+        let row = sqlx::query(&format!("SELECT * FROM external_{} WHERE id = $1", table))
+            .bind(entity_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let Some(row) = row else {
+            return Ok(Query::new(
+                format!("DELETE FROM external_{} WHERE id = $1", table),
+                vec![Argument::String(entity_id.to_string())],
+            ));
+        };
+
+        let mut names = vec![];
+        let mut arguments = vec![
+            Argument::String(entity_id.to_string()),
+            Argument::String(row.try_get::<String, &str>("partition")?),
+        ];
+        for member in member_results {
+            let name = format!("external_{}", member.try_get::<String, &str>("name")?);
+            let ty = member.try_get::<String, &str>("type")?;
+            let value = if ScalarType::from_str(ty)?.is_numeric_type() {
+                row.try_get::<Option<i64>, &str>(name.as_str())?.map(Argument::Int)
+            } else {
+                row.try_get::<Option<String>, &str>(name.as_str())?.map(Argument::String)
+            };
+            arguments.push(value.unwrap_or(Argument::Null));
+            names.push(name);
+        }
+
+        Ok(upsert_component(&format!("external_{}", table), &names, arguments))
+    }
+
+    /// Commits the components registered since the last commit, whose members and tables are
+    /// read by the entity writes.
+    async fn commit_registrations(&self) -> Result<()> {
+        if self.registered_components.lock().await.is_empty() {
+            return Ok(());
+        }
+
+        // tx commit required
+        self.execute().await
+    }
+
+    async fn component_ids(&self) -> Result<HashSet<String>> {
+        let components: Vec<(String,)> =
+            sqlx::query_as("SELECT id FROM components").fetch_all(&self.pool).await?;
+        Ok(components.into_iter().map(|(id,)| id).collect())
+    }
+
+    async fn component_members(&self, table: &str) -> Result<Vec<DbRow>> {
+        let member_results =
+            sqlx::query("SELECT * FROM component_members WHERE component_id = $1 ORDER BY slot")
+                .bind(table)
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(member_results)
+    }
+
+    /// Returns the entity as overlaid by the pending writes, or as indexed if it has none.
+    async fn pending_entity(&self, entity_id: &str) -> Result<Option<DbRow>> {
+        for table in ["pending_entities", "entities"] {
+            let entity = sqlx::query(&format!("SELECT * FROM {} WHERE id = $1", table))
+                .bind(entity_id)
+                .fetch_optional(&self.pool)
+                .await?;
+            if entity.is_some() {
+                return Ok(entity);
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn set_pending_entity(
+        &self,
+        table: &str,
+        component: String,
+        partition: FieldElement,
+        keys: Vec<FieldElement>,
+        values: Vec<FieldElement>,
+    ) -> Result<()> {
+        let entity_id = format!("{:#x}", poseidon_hash_many(&keys));
+        let entity = self.pending_entity(&entity_id).await?;
+        let component_names = component_names(entity.as_ref(), &component)?;
+
+        let member_results = self.component_members(table).await?;
+        let (mut names, mut arguments) = format_values(&member_results, values)?;
+        let keys_str = format_keys(&keys);
+
+        let insert_entities = upsert_entity(
+            "pending_entities",
+            vec![
+                Argument::String(entity_id.clone()),
+                Argument::FieldElement(partition),
+                Argument::String(keys_str.clone()),
+                Argument::String(component_names),
+            ],
+        );
+
+        names.push("deleted".to_string());
+        arguments.push(Argument::Bool(false));
+        arguments
+            .splice(0..0, [Argument::String(entity_id.clone()), Argument::FieldElement(partition)]);
+        let insert_components =
+            upsert_component(&format!("pending_external_{}", table), &names, arguments);
+
+        self.queue(vec![insert_entities, insert_components]).await;
+        self.execute().await?;
+
+        self.publish_entity_update(EntityUpdate {
+            entity_id,
+            keys: keys_str,
+            component,
+            deleted: false,
+            pending: true,
+        });
+        Ok(())
+    }
+
+    async fn delete_pending_entity(
+        &self,
+        table: &str,
+        component: String,
+        partition: FieldElement,
+        keys: Vec<FieldElement>,
+    ) -> Result<()> {
+        let entity_id = format!("{:#x}", poseidon_hash_many(&keys));
+        let Some(entity) = self.pending_entity(&entity_id).await? else {
+            return Ok(());
+        };
+
+        let component_names = entity
+            .try_get::<Option<String>, &str>("component_names")?
+            .unwrap_or_default()
+            .split(',')
+            .filter(|name| !name.is_empty() && *name != component)
+            .collect::<Vec<_>>()
+            .join(",");
+        let keys_str = format_keys(&keys);
+
+        // The entity is kept without component names, hiding the indexed one.
+        let queries = vec![
+            upsert_entity(
+                "pending_entities",
+                vec![
+                    Argument::String(entity_id.clone()),
+                    Argument::FieldElement(partition),
+                    Argument::String(keys_str.clone()),
+                    Argument::String(component_names),
+                ],
+            ),
+            Query::new(
+                format!(
+                    "INSERT INTO pending_external_{} (id, partition, deleted) VALUES ($1, $2, $3) \
+                     ON CONFLICT(id) DO UPDATE SET deleted = excluded.deleted",
+                    table
+                ),
+                vec![
+                    Argument::String(entity_id.clone()),
+                    Argument::FieldElement(partition),
+                    Argument::Bool(true),
+                ],
+            ),
+        ];
+
+        self.queue(queries).await;
+        self.execute().await?;
+
+        self.publish_entity_update(EntityUpdate {
+            entity_id,
+            keys: keys_str,
+            component,
+            deleted: true,
+            pending: true,
+        });
+        Ok(())
+    }
+
+    fn publish_entity_update(&self, update: EntityUpdate) {
+        // It's fine if there are no active subscribers.
+        let _ = self.entity_updates.send(update);
     }
 }
 
 #[async_trait]
 impl Executable for Sql {
-    async fn queue(&self, queries: Vec<String>) {
+    async fn queue(&self, queries: Vec<Query>) {
         let mut query_queue = self.query_queue.lock().await;
         query_queue.extend(queries);
     }
 
     async fn execute(&self) -> Result<()> {
         let queries;
+        let registered_components;
         {
             let mut query_queue = self.query_queue.lock().await;
             queries = query_queue.clone();
             query_queue.clear();
+            registered_components = std::mem::take(&mut *self.registered_components.lock().await);
         }
 
         let mut tx = self.pool.begin().await?;
 
         for query in queries {
-            tx.execute(sqlx::query(&query)).await?;
+            execute_query(&mut tx, query).await?;
         }
 
         tx.commit().await?;
 
+        for component in registered_components {
+            // It's fine if there are no active subscribers.
+            let _ = self.component_registrations.send(component);
+        }
+
         Ok(())
     }
 }
 
+async fn execute_query(tx: &mut Transaction<'_, Db>, query: Query) -> Result<()> {
+    let Query { statement, arguments } = query;
+
+    let mut query = sqlx::query(&statement);
+    for argument in arguments {
+        query = match argument {
+            Argument::Null => query.bind(None::<String>),
+            Argument::Int(value) => query.bind(value),
+            Argument::Bool(value) => query.bind(value),
+            Argument::String(value) => query.bind(value),
+            Argument::FieldElement(value) => query.bind(format!("{:#x}", value)),
+        };
+    }
+
+    tx.execute(query).await?;
+    Ok(())
+}
+
 #[async_trait]
 impl State for Sql {
     async fn load_from_manifest(&self, manifest: Manifest) -> Result<()> {
-        let mut updates = vec![
-            format!("world_address = '{:#x}'", self.world_address),
-            format!("world_class_hash = '{:#x}'", manifest.world.class_hash),
-            format!("executor_class_hash = '{:#x}'", manifest.executor.class_hash),
+        let mut columns = vec!["world_address", "world_class_hash", "executor_class_hash"];
+        let mut arguments = vec![
+            Argument::FieldElement(self.world_address),
+            Argument::FieldElement(manifest.world.class_hash),
+            Argument::FieldElement(manifest.executor.class_hash),
         ];
 
         if let Some(executor_address) = manifest.executor.address {
-            updates.push(format!("executor_address = '{:#x}'", executor_address));
+            columns.push("executor_address");
+            arguments.push(Argument::FieldElement(executor_address));
         }
 
-        self.queue(vec![format!(
-            "UPDATE worlds SET {} WHERE id = '{:#x}'",
-            updates.join(","),
-            self.world_address
+        let updates = columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| format!("{} = ${}", column, i + 1))
+            .collect::<Vec<_>>();
+        arguments.push(Argument::FieldElement(self.world_address));
+        self.queue(vec![Query::new(
+            format!("UPDATE worlds SET {} WHERE id = ${}", updates.join(","), arguments.len()),
+            arguments,
         )])
         .await;
 
@@ -110,96 +477,399 @@ impl State for Sql {
     }
 
     async fn head(&self) -> Result<u64> {
-        let mut conn: PoolConnection<Sqlite> = self.pool.acquire().await?;
-        let indexer: (i64,) = sqlx::query_as(&format!(
-            "SELECT head FROM indexers WHERE id = '{:#x}'",
-            self.world_address
-        ))
-        .fetch_one(&mut conn)
-        .await?;
+        let mut conn: PoolConnection<Db> = self.pool.acquire().await?;
+        let indexer: (i64,) = sqlx::query_as("SELECT head FROM indexers WHERE id = $1")
+            .bind(format!("{:#x}", self.world_address))
+            .fetch_one(&mut conn)
+            .await?;
         Ok(indexer.0.try_into().expect("doesnt fit in u64"))
     }
 
     async fn set_head(&self, head: u64) -> Result<()> {
-        self.queue(vec![format!(
-            "UPDATE indexers SET head = {head} WHERE id = '{:#x}'",
-            self.world_address
+        let head = i64::try_from(head)?;
+        self.queue(vec![Query::new(
+            "UPDATE indexers SET head = $1 WHERE id = $2",
+            vec![Argument::Int(head), Argument::FieldElement(self.world_address)],
         )])
         .await;
         Ok(())
     }
 
+    async fn set_block(
+        &self,
+        number: u64,
+        hash: FieldElement,
+        parent_hash: FieldElement,
+    ) -> Result<()> {
+        let number = i64::try_from(number)?;
+        *self.journal_block.lock().await = Some(number);
+
+        // The blocks deep enough not to be reorged anymore don't need to be reverted, the last
+        // of them is kept to tell whether a reorg is deeper than the journal.
+        let depth = i64::try_from(JOURNAL_DEPTH)?;
+        self.queue(vec![
+            Query::new(
+                "DELETE FROM journal WHERE block_number <= $1",
+                vec![Argument::Int(number - depth)],
+            ),
+            Query::new("DELETE FROM blocks WHERE number < $1", vec![Argument::Int(number - depth)]),
+        ])
+        .await;
+
+        self.queue(vec![Query::new(
+            "INSERT INTO blocks (number, hash, parent_hash) VALUES ($1, $2, $3) ON \
+             CONFLICT(number) DO UPDATE SET hash = excluded.hash, parent_hash = \
+             excluded.parent_hash",
+            vec![
+                Argument::Int(number),
+                Argument::FieldElement(hash),
+                Argument::FieldElement(parent_hash),
+            ],
+        )])
+        .await;
+        Ok(())
+    }
+
+    async fn block_hash(&self, number: u64) -> Result<Option<FieldElement>> {
+        let block: Option<(String,)> = sqlx::query_as("SELECT hash FROM blocks WHERE number = $1")
+            .bind(i64::try_from(number)?)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match block {
+            Some((hash,)) => Ok(Some(FieldElement::from_hex_be(&hash)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn previous_block(&self, number: u64) -> Result<Option<(u64, FieldElement)>> {
+        let block: Option<(i64, String)> = sqlx::query_as(
+            "SELECT number, hash FROM blocks WHERE number < $1 ORDER BY number DESC LIMIT 1",
+        )
+        .bind(i64::try_from(number)?)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match block {
+            Some((number, hash)) => {
+                Ok(Some((number.try_into()?, FieldElement::from_hex_be(&hash)?)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn rollback(&self, number: u64) -> Result<()> {
+        let head = self.head().await?;
+        if head.saturating_sub(number) > JOURNAL_DEPTH {
+            return Err(anyhow!(
+                "Can't roll back {} blocks, only the last {} are journaled",
+                head - number,
+                JOURNAL_DEPTH
+            ));
+        }
+
+        let number = i64::try_from(number)?;
+        let journal: Vec<(String, String)> = sqlx::query_as(
+            "SELECT statement, arguments FROM journal WHERE block_number > $1 ORDER BY id DESC",
+        )
+        .bind(number)
+        .fetch_all(&self.pool)
+        .await?;
+        let entities: Vec<(String, String, String)> = sqlx::query_as(
+            "SELECT DISTINCT entity_id, keys, component FROM journal WHERE block_number > $1 AND \
+             entity_id IS NOT NULL",
+        )
+        .bind(number)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let components_before = self.component_ids().await?;
+
+        let mut queries = vec![];
+        for (statement, arguments) in journal {
+            let arguments = serde_json::from_str::<Vec<serde_json::Value>>(&arguments)?
+                .iter()
+                .map(Argument::from_json)
+                .collect::<Result<Vec<_>>>()?;
+            queries.push(Query::new(statement, arguments));
+        }
+
+        queries.extend([
+            Query::new("DELETE FROM journal WHERE block_number > $1", vec![Argument::Int(number)]),
+            Query::new("DELETE FROM blocks WHERE number > $1", vec![Argument::Int(number)]),
+            Query::new(
+                "UPDATE indexers SET head = $1 WHERE id = $2",
+                vec![Argument::Int(number), Argument::FieldElement(self.world_address)],
+            ),
+        ]);
+
+        *self.journal_block.lock().await = None;
+        self.queue(queries).await;
+        self.execute().await?;
+
+        // The components registered in the rolled back blocks are dropped, the schema is rebuilt
+        // without them.
+        let components = self.component_ids().await?;
+        for component in components_before.difference(&components) {
+            let _ = self.component_registrations.send(component.clone());
+        }
+
+        // The restored components are notified as set, or deleted if they didn't exist before.
+        for (entity_id, keys, component) in entities {
+            let table = validate_identifier(&component)?.to_lowercase();
+            let restored = components.contains(&table)
+                && sqlx::query(&format!("SELECT id FROM external_{} WHERE id = $1", table))
+                    .bind(&entity_id)
+                    .fetch_optional(&self.pool)
+                    .await?
+                    .is_some();
+
+            self.publish_entity_update(EntityUpdate {
+                entity_id,
+                keys,
+                component,
+                deleted: !restored,
+                pending: false,
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn set_pending(&self, pending: bool) {
+        *self.pending.lock().await = pending;
+    }
+
+    async fn clear_pending(&self) -> Result<()> {
+        let components: Vec<(String,)> =
+            sqlx::query_as("SELECT id FROM components").fetch_all(&self.pool).await?;
+
+        let mut queries = vec![Query::new("DELETE FROM pending_entities", vec![])];
+        for (component,) in components {
+            queries.push(Query::new(
+                format!("DELETE FROM pending_external_{}", validate_identifier(&component)?),
+                vec![],
+            ));
+        }
+
+        self.queue(queries).await;
+        self.execute().await
+    }
+
     async fn world(&self) -> Result<World> {
-        let mut conn: PoolConnection<Sqlite> = self.pool.acquire().await?;
-        let meta: World =
-            sqlx::query_as(&format!("SELECT * FROM worlds WHERE id = '{:#x}'", self.world_address))
-                .fetch_one(&mut conn)
-                .await?;
+        let mut conn: PoolConnection<Db> = self.pool.acquire().await?;
+        let meta: World = sqlx::query_as("SELECT * FROM worlds WHERE id = $1")
+            .bind(format!("{:#x}", self.world_address))
+            .fetch_one(&mut conn)
+            .await?;
 
         Ok(meta)
     }
 
     async fn set_world(&self, world: World) -> Result<()> {
-        self.queue(vec![format!(
-            "UPDATE worlds SET world_address='{:#x}', world_class_hash='{:#x}', \
-             executor_address='{:#x}', executor_class_hash='{:#x}' WHERE id = '{:#x}'",
-            world.world_address,
-            world.world_class_hash,
-            world.executor_address,
-            world.executor_class_hash,
-            world.world_address,
+        self.queue(vec![Query::new(
+            "UPDATE worlds SET world_address = $1, world_class_hash = $2, executor_address = $3, \
+             executor_class_hash = $4 WHERE id = $5",
+            vec![
+                Argument::FieldElement(world.world_address.0),
+                Argument::FieldElement(world.world_class_hash.0),
+                Argument::FieldElement(world.executor_address.0),
+                Argument::FieldElement(world.executor_class_hash.0),
+                Argument::FieldElement(world.world_address.0),
+            ],
         )])
         .await;
         Ok(())
     }
 
     async fn register_component(&self, component: Component) -> Result<()> {
-        let component_id = component.name.to_lowercase();
-        let mut queries = vec![format!(
-            "INSERT INTO components (id, name, class_hash) VALUES ('{}', '{}', '{:#x}') ON \
-             CONFLICT(id) DO UPDATE SET class_hash='{:#x}'",
-            component_id, component.name, component.class_hash, component.class_hash
+        // Components are registered even from the pending block, since its entities need them.
+        // Registering them again once the block is closed leaves them as they are.
+        let component_id = validate_identifier(&component.name)?.to_lowercase();
+        let mut queries = vec![Query::new(
+            "INSERT INTO components (id, name, class_hash) VALUES ($1, $2, $3) ON CONFLICT(id) DO \
+             UPDATE SET class_hash = excluded.class_hash",
+            vec![
+                Argument::String(component_id.clone()),
+                Argument::String(component.name.clone()),
+                Argument::FieldElement(component.class_hash),
+            ],
         )];
 
-        let mut component_table_query = format!(
-            "CREATE TABLE IF NOT EXISTS external_{} (id TEXT NOT NULL PRIMARY KEY, partition TEXT \
-             NOT NULL, ",
-            component.name.to_lowercase()
-        );
+        let members = component
+            .members
+            .iter()
+            .map(|member| (member.name.clone(), member.ty.clone()))
+            .collect::<Vec<_>>();
+        queries.extend(component_tables(Backend::of(&self.pool), &component_id, &members)?);
+
+        for member in component.members {
+            queries.push(Query::new(
+                "INSERT INTO component_members (component_id, name, type, slot, \"offset\") \
+                 VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+                vec![
+                    Argument::String(component_id.clone()),
+                    Argument::String(member.name),
+                    Argument::String(member.ty),
+                    Argument::Int(member.slot.try_into()?),
+                    Argument::Int(member.offset.into()),
+                ],
+            ));
+        }
+
+        // A new component is dropped on rollback, and a registered one gets its class hash back.
+        // The components registered from the pending block aren't journaled, they are kept like
+        // the ones registered before the indexed blocks.
+        if *self.pending.lock().await {
+            self.queue(queries).await;
+        } else {
+            let class_hash: Option<(String,)> =
+                sqlx::query_as("SELECT class_hash FROM components WHERE id = $1")
+                    .bind(&component_id)
+                    .fetch_optional(&self.pool)
+                    .await?;
+            let undo = match class_hash {
+                Some((class_hash,)) => vec![Query::new(
+                    "UPDATE components SET class_hash = $1 WHERE id = $2",
+                    vec![Argument::String(class_hash), Argument::String(component_id.clone())],
+                )],
+                None => vec![
+                    Query::new(
+                        "DELETE FROM components WHERE id = $1",
+                        vec![Argument::String(component_id.clone())],
+                    ),
+                    Query::new(
+                        "DELETE FROM component_members WHERE component_id = $1",
+                        vec![Argument::String(component_id.clone())],
+                    ),
+                    Query::new(format!("DROP TABLE IF EXISTS external_{}", component_id), vec![]),
+                    Query::new(
+                        format!("DROP TABLE IF EXISTS pending_external_{}", component_id),
+                        vec![],
+                    ),
+                ],
+            };
+            self.queue_journaled(queries, undo, None).await;
+        }
 
-        for member in component.clone().members {
-            let sql_type = ScalarType::from_str(member.ty).map(|t| t.as_sql_type())?;
-            component_table_query.push_str(&format!("external_{} {}, ", member.name, sql_type));
+        self.registered_components.lock().await.push(component.name);
+        Ok(())
+    }
+
+    async fn register_system(&self, system: System) -> Result<()> {
+        if *self.pending.lock().await {
+            // Only entities are overlaid, this is indexed once the block is closed.
+            return Ok(());
         }
 
-        component_table_query.push_str(
-            "created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        FOREIGN KEY (id) REFERENCES entities(id));",
+        let system_id = system.name.to_lowercase();
+        let query = Query::new(
+            "INSERT INTO systems (id, name, class_hash) VALUES ($1, $2, $3) ON CONFLICT(id) DO \
+             UPDATE SET class_hash = excluded.class_hash",
+            vec![
+                Argument::String(system_id.clone()),
+                Argument::String(system.name),
+                Argument::FieldElement(system.class_hash),
+            ],
         );
-        queries.push(component_table_query);
 
-        for member in component.members {
-            queries.push(format!(
-                "INSERT OR IGNORE INTO component_members (component_id, name, type, slot, offset) \
-                 VALUES ('{}', '{}', '{}', '{}', '{}')",
-                component_id, member.name, member.ty, member.slot, member.offset,
-            ));
+        // A new system is removed on rollback, and a registered one gets its class hash back.
+        let class_hash: Option<(String,)> =
+            sqlx::query_as("SELECT class_hash FROM systems WHERE id = $1")
+                .bind(&system_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        let undo = match class_hash {
+            Some((class_hash,)) => Query::new(
+                "UPDATE systems SET class_hash = $1 WHERE id = $2",
+                vec![Argument::String(class_hash), Argument::String(system_id)],
+            ),
+            None => {
+                Query::new("DELETE FROM systems WHERE id = $1", vec![Argument::String(system_id)])
+            }
+        };
+        self.queue_journaled(vec![query], vec![undo], None).await;
+        Ok(())
+    }
+
+    async fn store_system_call(
+        &self,
+        system: String,
+        transaction_hash: FieldElement,
+        calldata: Vec<FieldElement>,
+    ) -> Result<()> {
+        if *self.pending.lock().await {
+            // Only entities are overlaid, this is indexed once the block is closed.
+            return Ok(());
         }
 
-        self.queue(queries).await;
+        let query = Query::new(
+            "INSERT INTO system_calls (data, transaction_hash, system_id) VALUES ($1, $2, $3)",
+            vec![
+                Argument::String(format_felts(&calldata)),
+                Argument::FieldElement(transaction_hash),
+                Argument::String(system.to_lowercase()),
+            ],
+        );
+        let undo = Query::new(
+            "DELETE FROM system_calls WHERE transaction_hash = $1",
+            vec![Argument::FieldElement(transaction_hash)],
+        );
+        self.queue_journaled(vec![query], vec![undo], None).await;
         Ok(())
     }
 
-    async fn register_system(&self, system: System) -> Result<()> {
-        let query = format!(
-            "INSERT INTO systems (id, name, class_hash) VALUES ('{}', '{}', '{:#x}') ON \
-             CONFLICT(id) DO UPDATE SET class_hash='{:#x}'",
-            system.name.to_lowercase(),
-            system.name,
-            system.class_hash,
-            system.class_hash
+    async fn store_event(
+        &self,
+        event_id: String,
+        keys: Vec<FieldElement>,
+        data: Vec<FieldElement>,
+        transaction_hash: FieldElement,
+        system_call_index: Option<usize>,
+    ) -> Result<()> {
+        if *self.pending.lock().await {
+            // Only entities are overlaid, this is indexed once the block is closed.
+            return Ok(());
+        }
+
+        // The event is already stored if its block is processed again, and the insert leaves it
+        // as it is, so it isn't journaled again and is only deleted with its first block.
+        let stored = sqlx::query("SELECT id FROM events WHERE id = $1")
+            .bind(&event_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        if stored.is_some() {
+            return Ok(());
+        }
+
+        let undo = Query::new(
+            "DELETE FROM events WHERE id = $1",
+            vec![Argument::String(event_id.clone())],
         );
-        self.queue(vec![query]).await;
+        let mut arguments = vec![
+            Argument::String(event_id),
+            Argument::String(format_felts(&keys)),
+            Argument::String(format_felts(&data)),
+        ];
+        let query = match system_call_index {
+            Some(index) => {
+                arguments.extend([
+                    Argument::FieldElement(transaction_hash),
+                    Argument::Int(index.try_into()?),
+                ]);
+                Query::new(
+                    "INSERT INTO events (id, keys, data, system_call_id) VALUES ($1, $2, $3, \
+                     (SELECT id FROM system_calls WHERE transaction_hash = $4 ORDER BY id LIMIT 1 \
+                     OFFSET $5)) ON CONFLICT DO NOTHING",
+                    arguments,
+                )
+            }
+            None => Query::new(
+                "INSERT INTO events (id, keys, data) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                arguments,
+            ),
+        };
+        self.queue_journaled(vec![query], vec![undo], None).await;
         Ok(())
     }
 
@@ -210,42 +880,52 @@ impl State for Sql {
         keys: Vec<FieldElement>,
         values: Vec<FieldElement>,
     ) -> Result<()> {
+        let table = validate_identifier(&component)?.to_lowercase();
+        self.commit_registrations().await?;
+        if *self.pending.lock().await {
+            return self.set_pending_entity(&table, component, partition, keys, values).await;
+        }
+
         let entity_id = format!("{:#x}", poseidon_hash_many(&keys));
-        let entity_result = sqlx::query("SELECT * FROM entities WHERE id = ?")
+        let entity_result = sqlx::query("SELECT * FROM entities WHERE id = $1")
             .bind(&entity_id)
             .fetch_optional(&self.pool)
             .await?;
-        let component_names = component_names(entity_result, &component)?;
+        let component_names = component_names(entity_result.as_ref(), &component)?;
 
-        let member_results =
-            sqlx::query("SELECT * FROM component_members WHERE component_id = ? ORDER BY slot")
-                .bind(component.to_lowercase())
-                .fetch_all(&self.pool)
-                .await?;
+        let member_results = self.component_members(&table).await?;
 
-        let (names_str, values_str) = format_values(member_results, values)?;
+        let (names, mut arguments) = format_values(&member_results, values)?;
         // TODO: map keys to individual columns
-        let keys_str = keys.iter().map(|k| format!("{:#x},", k)).collect::<Vec<String>>().join("");
-
-        let insert_entities = format!(
-            "INSERT INTO entities (id, partition, keys, component_names) VALUES ('{}', '{:#x}', \
-             '{}', '{}') ON CONFLICT(id) DO UPDATE SET
-             component_names=excluded.component_names, 
-             updated_at=CURRENT_TIMESTAMP",
-            entity_id, partition, keys_str, component_names
-        );
-        let insert_components = format!(
-            "INSERT OR REPLACE INTO external_{} (id, partition, {}) VALUES ('{}', '{:#x}', {})",
-            component.to_lowercase(),
-            names_str,
-            entity_id,
-            partition,
-            values_str
+        let keys_str = format_keys(&keys);
+
+        let insert_entities = upsert_entity(
+            "entities",
+            vec![
+                Argument::String(entity_id.clone()),
+                Argument::FieldElement(partition),
+                Argument::String(keys_str.clone()),
+                Argument::String(component_names),
+            ],
         );
 
+        arguments
+            .splice(0..0, [Argument::String(entity_id.clone()), Argument::FieldElement(partition)]);
+        let insert_components = upsert_component(&format!("external_{}", table), &names, arguments);
+
+        let undo = vec![
+            restore_entity(&entity_id, entity_result.as_ref())?,
+            self.restore_component(&table, &entity_id, &member_results).await?,
+        ];
+
+        let update =
+            EntityUpdate { entity_id, keys: keys_str, component, deleted: false, pending: false };
+
         // tx commit required
-        self.queue(vec![insert_entities, insert_components]).await;
+        self.queue_journaled(vec![insert_entities, insert_components], undo, Some(&update)).await;
         self.execute().await?;
+
+        self.publish_entity_update(update);
         Ok(())
     }
 
@@ -253,10 +933,69 @@ impl State for Sql {
         &self,
         component: String,
         partition: FieldElement,
-        key: FieldElement,
+        keys: Vec<FieldElement>,
     ) -> Result<()> {
-        let query = format!("DELETE FROM {component} WHERE id = {key} AND partition = {partition}");
-        self.queue(vec![query]).await;
+        let table = validate_identifier(&component)?.to_lowercase();
+        self.commit_registrations().await?;
+        if *self.pending.lock().await {
+            return self.delete_pending_entity(&table, component, partition, keys).await;
+        }
+
+        let entity_id = format!("{:#x}", poseidon_hash_many(&keys));
+        let entity_result = sqlx::query("SELECT * FROM entities WHERE id = $1")
+            .bind(&entity_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let Some(entity) = entity_result else {
+            return Ok(());
+        };
+
+        let member_results = self.component_members(&table).await?;
+        let undo = vec![
+            self.restore_component(&table, &entity_id, &member_results).await?,
+            restore_entity(&entity_id, Some(&entity))?,
+        ];
+
+        let component_names = entity
+            .try_get::<String, &str>("component_names")?
+            .split(',')
+            .filter(|name| !name.is_empty() && *name != component)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut queries = vec![Query::new(
+            format!("DELETE FROM external_{} WHERE id = $1 AND partition = $2", table),
+            vec![Argument::String(entity_id.clone()), Argument::FieldElement(partition)],
+        )];
+
+        // The entity is removed along with its last component.
+        if component_names.is_empty() {
+            queries.push(Query::new(
+                "DELETE FROM entities WHERE id = $1",
+                vec![Argument::String(entity_id.clone())],
+            ));
+        } else {
+            queries.push(Query::new(
+                "UPDATE entities SET component_names = $1, updated_at = CURRENT_TIMESTAMP WHERE id \
+                 = $2",
+                vec![Argument::String(component_names), Argument::String(entity_id.clone())],
+            ));
+        }
+
+        let update = EntityUpdate {
+            entity_id,
+            keys: format_keys(&keys),
+            component,
+            deleted: true,
+            pending: false,
+        };
+
+        // tx commit required
+        self.queue_journaled(queries, undo, Some(&update)).await;
+        self.execute().await?;
+
+        self.publish_entity_update(update);
         Ok(())
     }
 
@@ -266,10 +1005,16 @@ impl State for Sql {
         partition: FieldElement,
         key: FieldElement,
     ) -> Result<Vec<FieldElement>> {
-        let query =
-            format!("SELECT * FROM {component} WHERE id = {key} AND partition = {partition}");
-        let mut conn: PoolConnection<Sqlite> = self.pool.acquire().await?;
-        let row: (i32, String, String) = sqlx::query_as(&query).fetch_one(&mut conn).await?;
+        let query = format!(
+            "SELECT * FROM {} WHERE id = $1 AND partition = $2",
+            validate_identifier(&component)?
+        );
+        let mut conn: PoolConnection<Db> = self.pool.acquire().await?;
+        let row: (i32, String, String) = sqlx::query_as(&query)
+            .bind(format!("{:#x}", key))
+            .bind(format!("{:#x}", partition))
+            .fetch_one(&mut conn)
+            .await?;
         Ok(serde_json::from_str(&row.2).unwrap())
     }
 
@@ -278,19 +1023,126 @@ impl State for Sql {
         component: String,
         partition: FieldElement,
     ) -> Result<Vec<Vec<FieldElement>>> {
-        let query = format!("SELECT * FROM {component} WHERE partition = {partition}");
-        let mut conn: PoolConnection<Sqlite> = self.pool.acquire().await?;
-        let mut rows =
-            sqlx::query_as::<_, (i32, String, String)>(&query).fetch_all(&mut conn).await?;
+        let query =
+            format!("SELECT * FROM {} WHERE partition = $1", validate_identifier(&component)?);
+        let mut conn: PoolConnection<Db> = self.pool.acquire().await?;
+        let mut rows = sqlx::query_as::<_, (i32, String, String)>(&query)
+            .bind(format!("{:#x}", partition))
+            .fetch_all(&mut conn)
+            .await?;
         Ok(rows.drain(..).map(|row| serde_json::from_str(&row.2).unwrap()).collect())
     }
 }
 
-fn component_names(entity_result: Option<SqliteRow>, new_component: &str) -> Result<String> {
+fn format_keys(keys: &[FieldElement]) -> String {
+    keys.iter().map(|k| format!("{:#x},", k)).collect::<Vec<String>>().join("")
+}
+
+fn upsert_entity(table: &str, arguments: Vec<Argument>) -> Query {
+    Query::new(
+        format!(
+            "INSERT INTO {} (id, partition, keys, component_names) VALUES ($1, $2, $3, $4) ON \
+             CONFLICT(id) DO UPDATE SET component_names = excluded.component_names, updated_at = \
+             CURRENT_TIMESTAMP",
+            table
+        ),
+        arguments,
+    )
+}
+
+/// Returns the query restoring an entity to its current row, or removing it if it doesn't exist.
+fn restore_entity(entity_id: &str, entity: Option<&DbRow>) -> Result<Query> {
+    let Some(entity) = entity else {
+        return Ok(Query::new(
+            "DELETE FROM entities WHERE id = $1",
+            vec![Argument::String(entity_id.to_string())],
+        ));
+    };
+
+    let optional = |value: Option<String>| value.map_or(Argument::Null, Argument::String);
+    Ok(upsert_entity(
+        "entities",
+        vec![
+            Argument::String(entity_id.to_string()),
+            Argument::String(entity.try_get::<String, &str>("partition")?),
+            optional(entity.try_get::<Option<String>, &str>("keys")?),
+            optional(entity.try_get::<Option<String>, &str>("component_names")?),
+        ],
+    ))
+}
+
+/// Returns the statements creating the table of a component and its pending overlay, if they
+/// don't exist, from the names and types of its members.
+fn component_tables(
+    backend: Backend,
+    component_id: &str,
+    members: &[(String, String)],
+) -> Result<[Query; 2]> {
+    let mut component_table_query = format!(
+        "CREATE TABLE IF NOT EXISTS external_{} (id TEXT NOT NULL PRIMARY KEY, partition TEXT NOT \
+         NULL, ",
+        component_id
+    );
+
+    for (name, ty) in members {
+        let name = validate_identifier(name)?;
+        let sql_type = ScalarType::from_str(ty).map(|t| t.as_sql_type(backend))?;
+        component_table_query.push_str(&format!("external_{} {}, ", name, sql_type));
+    }
+
+    let mut pending_table_query =
+        component_table_query.replacen("external_", "pending_external_", 1);
+    component_table_query.push_str(&format!(
+        "created_at {} NOT NULL DEFAULT CURRENT_TIMESTAMP, FOREIGN KEY (id) REFERENCES \
+         entities(id));",
+        backend.datetime_type()
+    ));
+
+    // The overlay isn't bound to the indexed entities, and deleted components are kept as
+    // tombstones hiding the indexed ones.
+    pending_table_query.push_str(&format!(
+        "deleted BOOLEAN NOT NULL DEFAULT FALSE, created_at {} NOT NULL DEFAULT \
+         CURRENT_TIMESTAMP);",
+        backend.datetime_type()
+    ));
+
+    Ok([Query::new(component_table_query, vec![]), Query::new(pending_table_query, vec![])])
+}
+
+/// Inserts or replaces the component of an entity in a component table, `arguments` being its id,
+/// its partition and the values of the `names` columns.
+fn upsert_component(table: &str, names: &[String], arguments: Vec<Argument>) -> Query {
+    let placeholders =
+        (1..=arguments.len()).map(|i| format!("${}", i)).collect::<Vec<_>>().join(", ");
+    let updates = std::iter::once("partition")
+        .chain(names.iter().map(String::as_str))
+        .map(|column| format!("{column} = excluded.{column}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Query::new(
+        format!(
+            "INSERT INTO {} (id, partition, {}) VALUES ({}) ON CONFLICT(id) DO UPDATE SET {}",
+            table,
+            names.join(", "),
+            placeholders,
+            updates
+        ),
+        arguments,
+    )
+}
+
+fn format_felts(felts: &[FieldElement]) -> String {
+    felts.iter().map(|felt| format!("{:#x}", felt)).collect::<Vec<String>>().join(",")
+}
+
+fn component_names(entity_result: Option<&DbRow>, new_component: &str) -> Result<String> {
     let component_names = match entity_result {
         Some(entity) => {
             let existing = entity.try_get::<String, &str>("component_names")?;
-            if existing.contains(new_component) {
+            if existing.is_empty() {
+                new_component.to_string()
+            } else if existing.contains(new_component) {
                 existing
             } else {
                 format!("{},{}", existing, new_component)
@@ -302,15 +1154,29 @@ fn component_names(entity_result: Option<SqliteRow>, new_component: &str) -> Res
     Ok(component_names)
 }
 
+/// Validates a name interpolated in a statement as part of a table or column name, since those
+/// can't be bound as parameters.
+fn validate_identifier(name: &str) -> Result<&str> {
+    let mut chars = name.chars();
+    let valid = chars.next().map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if valid {
+        Ok(name)
+    } else {
+        Err(anyhow!("Invalid identifier {:?}", name))
+    }
+}
+
 fn format_values(
-    member_results: Vec<SqliteRow>,
+    member_results: &[DbRow],
     values: Vec<FieldElement>,
-) -> Result<(String, String)> {
+) -> Result<(Vec<String>, Vec<Argument>)> {
     let names: Result<Vec<String>> = member_results
         .iter()
         .map(|row| {
             let name = row.try_get::<String, &str>("name")?;
-            Ok(format!("external_{}", name))
+            Ok(format!("external_{}", validate_identifier(&name)?))
         })
         .collect();
 
@@ -318,17 +1184,22 @@ fn format_values(
         member_results.iter().map(|row| Ok(row.try_get::<String, &str>("type")?)).collect();
 
     // format according to type
-    let values: Result<Vec<String>> = values
+    let values: Result<Vec<Argument>> = values
         .iter()
         .zip(types?.iter())
         .map(|(value, ty)| {
             if ScalarType::from_str(ty)?.is_numeric_type() {
-                Ok(format!("'{}'", value))
+                // numeric columns are 64-bit signed integers in both backends
+                let value = u64::try_from(*value)
+                    .ok()
+                    .and_then(|value| i64::try_from(value).ok())
+                    .ok_or_else(|| anyhow!("Value {:#x} out of range for type {}", value, ty))?;
+                Ok(Argument::Int(value))
             } else {
-                Ok(format!("'{:#x}'", value))
+                Ok(Argument::String(format!("{:#x}", value)))
             }
         })
         .collect();
 
-    Ok((names?.join(", "), values?.join(", ")))
+    Ok((names?, values?))
 }
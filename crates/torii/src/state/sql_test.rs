@@ -1,14 +1,18 @@
 use camino::Utf8PathBuf;
 use dojo_types::component::Member;
 use dojo_world::manifest::{Component, System};
-use sqlx::sqlite::SqlitePool;
 use starknet::core::types::FieldElement;
+use tokio::sync::broadcast::Receiver;
 
+use super::JOURNAL_DEPTH;
+use crate::db::DbPool;
 use crate::state::sql::{Executable, Sql};
-use crate::state::State;
+use crate::state::{EntityUpdate, State};
+use crate::tests::common::test_pool;
 
-#[sqlx::test(migrations = "./migrations")]
-async fn test_load_from_manifest(pool: SqlitePool) {
+#[tokio::test]
+async fn test_load_from_manifest() {
+    let pool = test_pool().await;
     let manifest = dojo_world::manifest::Manifest::load_from_path(
         Utf8PathBuf::from_path_buf("../../examples/ecs/target/dev/manifest.json".into()).unwrap(),
     )
@@ -110,3 +114,544 @@ async fn test_load_from_manifest(pool: SqlitePool) {
         .unwrap();
     state.execute().await.unwrap();
 }
+
+#[tokio::test]
+async fn test_delete_entity() {
+    let pool = test_pool().await;
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+
+    for name in ["Position", "Moves"] {
+        state
+            .register_component(Component {
+                name: name.into(),
+                members: vec![Member {
+                    name: "value".into(),
+                    ty: "u32".into(),
+                    slot: 0,
+                    offset: 0,
+                }],
+                class_hash: FieldElement::ONE,
+            })
+            .await
+            .unwrap();
+    }
+    state.execute().await.unwrap();
+
+    let keys = vec![FieldElement::ONE];
+    for name in ["Position", "Moves"] {
+        state
+            .set_entity(name.to_string(), FieldElement::ZERO, keys.clone(), vec![FieldElement::TWO])
+            .await
+            .unwrap();
+    }
+
+    state.delete_entity("Position".to_string(), FieldElement::ZERO, keys.clone()).await.unwrap();
+
+    let positions = sqlx::query("SELECT * FROM external_position").fetch_all(&pool).await.unwrap();
+    assert_eq!(positions.len(), 0);
+
+    let (component_names,): (String,) =
+        sqlx::query_as("SELECT component_names FROM entities").fetch_one(&pool).await.unwrap();
+    assert_eq!(component_names, "Moves");
+
+    // The entity is dropped along with its last component.
+    state.delete_entity("Moves".to_string(), FieldElement::ZERO, keys.clone()).await.unwrap();
+
+    let entities = sqlx::query("SELECT * FROM entities").fetch_all(&pool).await.unwrap();
+    assert_eq!(entities.len(), 0);
+
+    // Deleting a component of an unknown entity is a no-op.
+    state.delete_entity("Moves".to_string(), FieldElement::ZERO, keys).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_store_system_call_and_events() {
+    let pool = test_pool().await;
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+
+    state
+        .register_system(System {
+            name: "Spawn".into(),
+            inputs: vec![],
+            outputs: vec![],
+            class_hash: FieldElement::ONE,
+            dependencies: vec![],
+        })
+        .await
+        .unwrap();
+
+    let transaction_hash = FieldElement::TWO;
+    for data in [FieldElement::ONE, FieldElement::TWO] {
+        state.store_system_call("Spawn".into(), transaction_hash, vec![data]).await.unwrap();
+    }
+    state
+        .store_event(
+            "0x2:0".into(),
+            vec![FieldElement::ONE],
+            vec![FieldElement::TWO, FieldElement::THREE],
+            transaction_hash,
+            Some(1),
+        )
+        .await
+        .unwrap();
+    // An event of a multicall whose call isn't known.
+    state
+        .store_event("0x2:1".into(), vec![FieldElement::TWO], vec![], transaction_hash, None)
+        .await
+        .unwrap();
+    // An event of a transaction without system call, ie a component registration.
+    state
+        .store_event("0x3:0".into(), vec![FieldElement::ONE], vec![], FieldElement::THREE, None)
+        .await
+        .unwrap();
+    state.execute().await.unwrap();
+
+    let system_calls: Vec<(i64, String, String)> =
+        sqlx::query_as("SELECT id, system_id, data FROM system_calls ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(system_calls.len(), 2);
+    assert_eq!((system_calls[1].1.as_str(), system_calls[1].2.as_str()), ("spawn", "0x2"));
+
+    let events: Vec<(String, String, String, Option<i64>)> =
+        sqlx::query_as("SELECT id, keys, data, system_call_id FROM events ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        events,
+        vec![
+            ("0x2:0".into(), "0x1".into(), "0x2,0x3".into(), Some(system_calls[1].0)),
+            ("0x2:1".into(), "0x2".into(), "".into(), None),
+            ("0x3:0".into(), "0x1".into(), "".into(), None),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_rollback() {
+    let pool = test_pool().await;
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+
+    for name in ["Position", "Moves"] {
+        state
+            .register_component(Component {
+                name: name.into(),
+                members: vec![Member {
+                    name: "value".into(),
+                    ty: "u32".into(),
+                    slot: 0,
+                    offset: 0,
+                }],
+                class_hash: FieldElement::ONE,
+            })
+            .await
+            .unwrap();
+    }
+    state
+        .register_system(System {
+            name: "Spawn".into(),
+            inputs: vec![],
+            outputs: vec![],
+            class_hash: FieldElement::ONE,
+            dependencies: vec![],
+        })
+        .await
+        .unwrap();
+    state.execute().await.unwrap();
+
+    let keys = vec![FieldElement::ONE];
+    state.set_block(1, FieldElement::ONE, FieldElement::ZERO).await.unwrap();
+    state
+        .set_entity("Position".into(), FieldElement::ZERO, keys.clone(), vec![FieldElement::ONE])
+        .await
+        .unwrap();
+    state
+        .set_entity("Moves".into(), FieldElement::ZERO, keys.clone(), vec![FieldElement::ONE])
+        .await
+        .unwrap();
+    state.set_head(1).await.unwrap();
+    state.execute().await.unwrap();
+
+    state.set_block(2, FieldElement::TWO, FieldElement::ONE).await.unwrap();
+    state
+        .set_entity("Position".into(), FieldElement::ZERO, keys.clone(), vec![FieldElement::TWO])
+        .await
+        .unwrap();
+    state.delete_entity("Moves".into(), FieldElement::ZERO, keys.clone()).await.unwrap();
+    state.store_system_call("Spawn".into(), FieldElement::TWO, vec![]).await.unwrap();
+    state.store_event("0x2:0".into(), vec![], vec![], FieldElement::TWO, Some(0)).await.unwrap();
+    state.set_head(2).await.unwrap();
+    state.execute().await.unwrap();
+
+    assert_eq!(state.block_hash(2).await.unwrap(), Some(FieldElement::TWO));
+    assert_eq!(position(&pool).await, Some(2));
+    assert_eq!(component_names(&pool).await.as_deref(), Some("Position"));
+
+    let mut entity_updates = state.entity_updates().subscribe();
+    state.rollback(1).await.unwrap();
+
+    assert_eq!(state.head().await.unwrap(), 1);
+    assert_eq!(state.block_hash(1).await.unwrap(), Some(FieldElement::ONE));
+    assert_eq!(state.block_hash(2).await.unwrap(), None);
+    assert_eq!(position(&pool).await, Some(1));
+    assert_eq!(component_names(&pool).await.as_deref(), Some("Position,Moves"));
+    let moves = sqlx::query("SELECT * FROM external_moves").fetch_all(&pool).await.unwrap();
+    assert_eq!(moves.len(), 1);
+    let events = sqlx::query("SELECT * FROM events").fetch_all(&pool).await.unwrap();
+    assert_eq!(events.len(), 0);
+    let system_calls = sqlx::query("SELECT * FROM system_calls").fetch_all(&pool).await.unwrap();
+    assert_eq!(system_calls.len(), 0);
+    assert_eq!(
+        restored_components(&mut entity_updates),
+        vec![("Moves".into(), false), ("Position".into(), false)]
+    );
+
+    state.rollback(0).await.unwrap();
+
+    assert_eq!(position(&pool).await, None);
+    assert_eq!(component_names(&pool).await, None);
+    assert_eq!(
+        restored_components(&mut entity_updates),
+        vec![("Moves".into(), true), ("Position".into(), true)]
+    );
+}
+
+#[tokio::test]
+async fn test_rollback_depth() {
+    let pool = test_pool().await;
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+
+    state
+        .register_system(System {
+            name: "Spawn".into(),
+            inputs: vec![],
+            outputs: vec![],
+            class_hash: FieldElement::ONE,
+            dependencies: vec![],
+        })
+        .await
+        .unwrap();
+    state.execute().await.unwrap();
+
+    let head = JOURNAL_DEPTH + 2;
+    for number in 1..=head {
+        let parent_hash = FieldElement::from(number - 1);
+        state.set_block(number, FieldElement::from(number), parent_hash).await.unwrap();
+        state.store_system_call("Spawn".into(), FieldElement::from(number), vec![]).await.unwrap();
+        state.set_head(number).await.unwrap();
+        state.execute().await.unwrap();
+    }
+
+    // Only the blocks which can still be reorged are journaled.
+    let (oldest,): (i64,) =
+        sqlx::query_as("SELECT MIN(block_number) FROM journal").fetch_one(&pool).await.unwrap();
+    assert_eq!(oldest, 3);
+    assert_eq!(state.block_hash(1).await.unwrap(), None);
+    assert_eq!(state.block_hash(2).await.unwrap(), Some(FieldElement::TWO));
+
+    assert!(state.rollback(1).await.is_err());
+    assert_eq!(state.head().await.unwrap(), head);
+
+    state.rollback(2).await.unwrap();
+    assert_eq!(state.head().await.unwrap(), 2);
+    let journal = sqlx::query("SELECT * FROM journal").fetch_all(&pool).await.unwrap();
+    assert_eq!(journal.len(), 0);
+}
+
+#[tokio::test]
+async fn test_rollback_registrations() {
+    let pool = test_pool().await;
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+    let spawn = |class_hash| System {
+        name: "Spawn".into(),
+        inputs: vec![],
+        outputs: vec![],
+        class_hash,
+        dependencies: vec![],
+    };
+
+    state.set_block(1, FieldElement::ONE, FieldElement::ZERO).await.unwrap();
+    state
+        .register_component(Component {
+            name: "Position".into(),
+            members: vec![Member { name: "value".into(), ty: "u32".into(), slot: 0, offset: 0 }],
+            class_hash: FieldElement::ONE,
+        })
+        .await
+        .unwrap();
+    state.register_system(spawn(FieldElement::ONE)).await.unwrap();
+    state
+        .set_entity(
+            "Position".into(),
+            FieldElement::ZERO,
+            vec![FieldElement::ONE],
+            vec![FieldElement::ONE],
+        )
+        .await
+        .unwrap();
+    state.store_event("0x1:0".into(), vec![], vec![], FieldElement::ONE, None).await.unwrap();
+    state.set_head(1).await.unwrap();
+    state.execute().await.unwrap();
+
+    // The system is upgraded, and the event of the first block is stored again.
+    state.set_block(2, FieldElement::TWO, FieldElement::ONE).await.unwrap();
+    state.register_system(spawn(FieldElement::TWO)).await.unwrap();
+    state.store_event("0x1:0".into(), vec![], vec![], FieldElement::ONE, None).await.unwrap();
+    state.set_head(2).await.unwrap();
+    state.execute().await.unwrap();
+
+    state.rollback(1).await.unwrap();
+
+    let systems: Vec<(String,)> =
+        sqlx::query_as("SELECT class_hash FROM systems").fetch_all(&pool).await.unwrap();
+    assert_eq!(systems, vec![("0x1".to_string(),)]);
+    let events = sqlx::query("SELECT * FROM events").fetch_all(&pool).await.unwrap();
+    assert_eq!(events.len(), 1);
+
+    let mut registrations = state.component_registrations().subscribe();
+    let mut entity_updates = state.entity_updates().subscribe();
+    state.rollback(0).await.unwrap();
+
+    let systems = sqlx::query("SELECT * FROM systems").fetch_all(&pool).await.unwrap();
+    assert_eq!(systems.len(), 0);
+    let components = sqlx::query("SELECT * FROM components").fetch_all(&pool).await.unwrap();
+    assert_eq!(components.len(), 0);
+    let members = sqlx::query("SELECT * FROM component_members").fetch_all(&pool).await.unwrap();
+    assert_eq!(members.len(), 0);
+    assert!(sqlx::query("SELECT * FROM external_position").fetch_all(&pool).await.is_err());
+    assert_eq!(registrations.try_recv().unwrap(), "position");
+    assert_eq!(restored_components(&mut entity_updates), vec![("Position".into(), true)]);
+}
+
+/// Returns the components notified by a rollback and whether they were deleted, by name.
+fn restored_components(entity_updates: &mut Receiver<EntityUpdate>) -> Vec<(String, bool)> {
+    let mut components = vec![];
+    while let Ok(update) = entity_updates.try_recv() {
+        components.push((update.component, update.deleted));
+    }
+    components.sort();
+    components
+}
+
+async fn position(pool: &DbPool) -> Option<i64> {
+    sqlx::query_as::<_, (i64,)>("SELECT external_value FROM external_position")
+        .fetch_optional(pool)
+        .await
+        .unwrap()
+        .map(|(value,)| value)
+}
+
+async fn component_names(pool: &DbPool) -> Option<String> {
+    sqlx::query_as::<_, (String,)>("SELECT component_names FROM entities")
+        .fetch_optional(pool)
+        .await
+        .unwrap()
+        .map(|(names,)| names)
+}
+
+#[tokio::test]
+async fn test_pending_overlay() {
+    let pool = test_pool().await;
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+
+    for name in ["Position", "Moves"] {
+        state
+            .register_component(Component {
+                name: name.into(),
+                members: vec![Member {
+                    name: "value".into(),
+                    ty: "u32".into(),
+                    slot: 0,
+                    offset: 0,
+                }],
+                class_hash: FieldElement::ONE,
+            })
+            .await
+            .unwrap();
+    }
+    state.execute().await.unwrap();
+
+    let keys = vec![FieldElement::ONE];
+    for name in ["Position", "Moves"] {
+        state
+            .set_entity(name.into(), FieldElement::ZERO, keys.clone(), vec![FieldElement::ONE])
+            .await
+            .unwrap();
+    }
+
+    state.set_pending(true).await;
+    state
+        .set_entity("Position".into(), FieldElement::ZERO, keys.clone(), vec![FieldElement::TWO])
+        .await
+        .unwrap();
+    state.delete_entity("Moves".into(), FieldElement::ZERO, keys.clone()).await.unwrap();
+    state.set_pending(false).await;
+
+    // The closed block state is left untouched.
+    assert_eq!(position(&pool).await, Some(1));
+    assert_eq!(component_names(&pool).await.as_deref(), Some("Position,Moves"));
+
+    let (names,): (String,) = sqlx::query_as("SELECT component_names FROM pending_entities")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(names, "Position");
+    let (value,): (i64,) = sqlx::query_as("SELECT external_value FROM pending_external_position")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(value, 2);
+    let (deleted,): (bool,) = sqlx::query_as("SELECT deleted FROM pending_external_moves")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(deleted);
+
+    state.clear_pending().await.unwrap();
+
+    for table in ["pending_entities", "pending_external_position", "pending_external_moves"] {
+        let rows = sqlx::query(&format!("SELECT * FROM {table}")).fetch_all(&pool).await.unwrap();
+        assert_eq!(rows.len(), 0);
+    }
+}
+
+#[tokio::test]
+async fn test_pending_component_registration() {
+    let pool = test_pool().await;
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+    let mut registrations = state.component_registrations().subscribe();
+
+    // A component registered and set by the same pending transaction.
+    state.set_pending(true).await;
+    state
+        .register_component(Component {
+            name: "Position".into(),
+            members: vec![Member { name: "value".into(), ty: "u32".into(), slot: 0, offset: 0 }],
+            class_hash: FieldElement::ONE,
+        })
+        .await
+        .unwrap();
+    state
+        .set_entity(
+            "Position".into(),
+            FieldElement::ZERO,
+            vec![FieldElement::ONE],
+            vec![FieldElement::TWO],
+        )
+        .await
+        .unwrap();
+    state.set_pending(false).await;
+
+    assert_eq!(registrations.try_recv().unwrap(), "Position");
+    let (value,): (i64,) = sqlx::query_as("SELECT external_value FROM pending_external_position")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(value, 2);
+    assert_eq!(position(&pool).await, None);
+}
+
+#[tokio::test]
+async fn test_pending_tables_of_registered_components() {
+    let pool = test_pool().await;
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+    state
+        .register_component(Component {
+            name: "Position".into(),
+            members: vec![Member { name: "value".into(), ty: "u32".into(), slot: 0, offset: 0 }],
+            class_hash: FieldElement::ONE,
+        })
+        .await
+        .unwrap();
+    state.execute().await.unwrap();
+
+    // As registered before the pending overlay existed.
+    sqlx::query("DROP TABLE pending_external_position").execute(&pool).await.unwrap();
+
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+    state.set_pending(true).await;
+    state
+        .set_entity(
+            "Position".into(),
+            FieldElement::ZERO,
+            vec![FieldElement::ONE],
+            vec![FieldElement::TWO],
+        )
+        .await
+        .unwrap();
+
+    let rows =
+        sqlx::query("SELECT * FROM pending_external_position").fetch_all(&pool).await.unwrap();
+    assert_eq!(rows.len(), 1);
+}
+
+#[tokio::test]
+async fn test_hostile_identifiers() {
+    let pool = test_pool().await;
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+
+    let hostile_component = |name: &str, member: &str| Component {
+        name: name.into(),
+        members: vec![Member { name: member.into(), ty: "u32".into(), slot: 0, offset: 0 }],
+        class_hash: FieldElement::ONE,
+    };
+
+    for (name, member) in [
+        ("Position (id TEXT); DROP TABLE entities; --", "value"),
+        ("Position", "value INTEGER); DROP TABLE entities; --"),
+        ("", "value"),
+        ("0Position", "value"),
+    ] {
+        assert!(state.register_component(hostile_component(name, member)).await.is_err());
+    }
+
+    let result = state
+        .set_entity(
+            "Position; DROP TABLE entities; --".to_string(),
+            FieldElement::ZERO,
+            vec![FieldElement::ONE],
+            vec![FieldElement::TWO],
+        )
+        .await;
+    assert!(result.is_err());
+
+    let result = state
+        .delete_entity(
+            "Position; DROP TABLE entities; --".to_string(),
+            FieldElement::ZERO,
+            vec![FieldElement::ONE],
+        )
+        .await;
+    assert!(result.is_err());
+
+    // No statement was injected and the rejected components left nothing behind.
+    let entities = sqlx::query("SELECT * FROM entities").fetch_all(&pool).await.unwrap();
+    assert_eq!(entities.len(), 0);
+    let components = sqlx::query("SELECT * FROM components").fetch_all(&pool).await.unwrap();
+    assert_eq!(components.len(), 0);
+}
+
+#[tokio::test]
+async fn test_hostile_values() {
+    let pool = test_pool().await;
+    let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+
+    // Names are bound as parameters, so quotes are stored as they are.
+    state
+        .register_system(System {
+            name: "Test'); DROP TABLE systems; --".into(),
+            inputs: vec![],
+            outputs: vec![],
+            class_hash: FieldElement::ONE,
+            dependencies: vec![],
+        })
+        .await
+        .unwrap();
+    state.execute().await.unwrap();
+
+    let (name,): (String,) =
+        sqlx::query_as("SELECT name FROM systems").fetch_one(&pool).await.unwrap();
+    assert_eq!(name, "Test'); DROP TABLE systems; --");
+}
@@ -1,22 +1,73 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::{env, fs, thread};
+
 use camino::Utf8PathBuf;
 use serde_json::Value;
-use sqlx::SqlitePool;
+use sqlx::{AnyConnection, Connection, Executor};
 use starknet::core::types::FieldElement;
+use tokio::sync::broadcast;
+use url::Url;
 
+use crate::db::{Backend, DbPool, DbPoolOptions};
 use crate::graphql::schema::build_schema;
 use crate::state::sql::{Executable, Sql};
 use crate::state::State;
 
+/// Returns a pool on an empty database with the migrations applied. The database is a PostgreSQL
+/// one created on the server of `DATABASE_URL` when it points to one, and a SQLite one otherwise.
+pub async fn test_pool() -> DbPool {
+    // Tests running at the same time run on threads named after them, so they get distinct
+    // databases, which are recreated when a test is run again.
+    let name = {
+        let mut hasher = DefaultHasher::new();
+        thread::current().name().hash(&mut hasher);
+        format!("torii_test_{:016x}", hasher.finish())
+    };
+
+    let url = match env::var("DATABASE_URL") {
+        Ok(url) if cfg!(feature = "postgres") && url.starts_with("postgres") => {
+            let mut conn = AnyConnection::connect(&url).await.unwrap();
+            conn.execute(format!("DROP DATABASE IF EXISTS {name} WITH (FORCE)").as_str())
+                .await
+                .unwrap();
+            conn.execute(format!("CREATE DATABASE {name}").as_str()).await.unwrap();
+
+            let mut url = Url::parse(&url).unwrap();
+            url.set_path(&name);
+            url.to_string()
+        }
+        _ => {
+            let path = env::temp_dir().join(format!("{name}.db"));
+            let _ = fs::remove_file(&path);
+            format!("sqlite://{}?mode=rwc", path.display())
+        }
+    };
+
+    let pool = DbPoolOptions::new().connect(&url).await.unwrap();
+    Backend::of(&pool).migrator().run(&pool).await.unwrap();
+    pool
+}
+
+/// Runs the statements of the fixtures in `src/tests/fixtures`.
+pub async fn load_fixtures(pool: &DbPool, names: &[&str]) {
+    for name in names {
+        let statements = fs::read_to_string(format!("src/tests/fixtures/{name}.sql")).unwrap();
+        pool.execute(statements.as_str()).await.unwrap();
+    }
+}
+
 #[allow(dead_code)]
-pub async fn run_graphql_query(pool: &SqlitePool, query: &str) -> Value {
-    let schema = build_schema(pool).await.unwrap();
+pub async fn run_graphql_query(pool: &DbPool, query: &str) -> Value {
+    let (entity_updates, _) = broadcast::channel(1);
+    let schema = build_schema(pool, entity_updates).await.unwrap();
     let res = schema.execute(query).await;
 
     assert!(res.errors.is_empty(), "GraphQL query returned errors: {:?}", res.errors);
     serde_json::to_value(res.data).expect("Failed to serialize GraphQL response")
 }
 
-pub async fn entity_fixtures(pool: &SqlitePool) {
+pub async fn entity_fixtures(pool: &DbPool) {
     let manifest = dojo_world::manifest::Manifest::load_from_path(
         Utf8PathBuf::from_path_buf("../../examples/ecs/target/dev/manifest.json".into()).unwrap(),
     )
@@ -1,10 +1,15 @@
 #[cfg(test)]
 mod tests {
+    use camino::Utf8PathBuf;
     use chrono::{DateTime, Utc};
     use serde::Deserialize;
-    use sqlx::{FromRow, SqlitePool};
+    use serde_json::Value;
+    use sqlx::FromRow;
+    use starknet::core::types::FieldElement;
 
-    use crate::tests::common::{entity_fixtures, run_graphql_query};
+    use crate::state::sql::Sql;
+    use crate::state::State;
+    use crate::tests::common::{entity_fixtures, run_graphql_query, test_pool};
 
     #[derive(Deserialize)]
     struct Moves {
@@ -28,53 +33,148 @@ mod tests {
         pub created_at: DateTime<Utc>,
     }
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_component_no_filter(pool: SqlitePool) {
+    #[tokio::test]
+    async fn test_component_no_filter() {
+        let pool = test_pool().await;
         entity_fixtures(&pool).await;
 
         let query = r#"
                 {
                     movesComponents {
-                        __typename
-                        remaining
+                        totalCount
+                        edges {
+                            node {
+                                __typename
+                                remaining
+                            }
+                        }
                     }
                     positionComponents {
-                        __typename
-                        x
-                        y
+                        totalCount
+                        edges {
+                            node {
+                                __typename
+                                x
+                                y
+                            }
+                        }
                     }
                 }
             "#;
 
         let value = run_graphql_query(&pool, query).await;
 
-        let moves_list = value.get("movesComponents").ok_or("no moves found").unwrap();
-        let moves_list: Vec<Moves> = serde_json::from_value(moves_list.clone()).unwrap();
-        let position_list = value.get("positionComponents").ok_or("no position found").unwrap();
-        let position_list: Vec<Position> = serde_json::from_value(position_list.clone()).unwrap();
+        let moves_list: Vec<Moves> = nodes(&value["movesComponents"]);
+        let position_list: Vec<Position> = nodes(&value["positionComponents"]);
 
+        assert_eq!(value["movesComponents"]["totalCount"], 2);
         assert_eq!(moves_list[0].remaining, 10);
+        assert_eq!(value["positionComponents"]["totalCount"], 2);
         assert_eq!(position_list[0].x, 42);
         assert_eq!(position_list[0].y, 69);
     }
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_component_filter(pool: SqlitePool) {
+    #[tokio::test]
+    async fn test_component_filter() {
+        let pool = test_pool().await;
         entity_fixtures(&pool).await;
 
         let query = r#"
                 {
-                    positionComponents (x: 42) {
-                        __typename
-                        x
-                        y
+                    positionComponents (where: { x: 42 }) {
+                        edges {
+                            node {
+                                __typename
+                                x
+                                y
+                            }
+                        }
                     }
                 }
             "#;
         let value = run_graphql_query(&pool, query).await;
 
-        let positions = value.get("positionComponents").ok_or("no positions found").unwrap();
-        let positions: Vec<Position> = serde_json::from_value(positions.clone()).unwrap();
+        let positions: Vec<Position> = nodes(&value["positionComponents"]);
         assert_eq!(positions[0].y, 69);
     }
+
+    #[tokio::test]
+    async fn test_component_order_and_comparisons() {
+        let pool = test_pool().await;
+        let manifest = dojo_world::manifest::Manifest::load_from_path(
+            Utf8PathBuf::from_path_buf("../../examples/ecs/target/dev/manifest.json".into())
+                .unwrap(),
+        )
+        .unwrap();
+
+        let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+        state.load_from_manifest(manifest).await.unwrap();
+
+        for x in 1..=5u64 {
+            state
+                .set_entity(
+                    "Position".to_string(),
+                    FieldElement::ZERO,
+                    vec![FieldElement::from(x)],
+                    vec![FieldElement::from(x), FieldElement::from(10 - x)],
+                )
+                .await
+                .unwrap();
+        }
+
+        let query = r#"
+                {
+                    positionComponents (
+                        where: { xGT: 1, yGTE: 6 },
+                        orderBy: { field: X, direction: DESC }
+                    ) {
+                        totalCount
+                        edges {
+                            node {
+                                __typename
+                                x
+                                y
+                            }
+                        }
+                    }
+                }
+            "#;
+        let value = run_graphql_query(&pool, query).await;
+
+        let positions: Vec<Position> = nodes(&value["positionComponents"]);
+        assert_eq!(value["positionComponents"]["totalCount"], 3);
+        assert_eq!(positions.iter().map(|p| p.x).collect::<Vec<_>>(), vec![4, 3, 2]);
+
+        let query = r#"
+                {
+                    positionComponents (
+                        where: { xIN: [1, 5], yNEQ: 9 },
+                        orderBy: { field: Y, direction: ASC }
+                    ) {
+                        edges {
+                            node {
+                                __typename
+                                x
+                                y
+                            }
+                        }
+                    }
+                }
+            "#;
+        let value = run_graphql_query(&pool, query).await;
+
+        let positions: Vec<Position> = nodes(&value["positionComponents"]);
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].x, 5);
+        assert_eq!(positions[0].y, 5);
+    }
+
+    fn nodes<T: serde::de::DeserializeOwned>(connection: &Value) -> Vec<T> {
+        connection["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| serde_json::from_value(edge["node"].clone()).unwrap())
+            .collect()
+    }
 }
@@ -1,10 +1,11 @@
 #[cfg(test)]
 mod tests {
     use serde::Deserialize;
-    use sqlx::SqlitePool;
     use starknet_crypto::{poseidon_hash_many, FieldElement};
 
-    use crate::tests::common::{entity_fixtures, run_graphql_query};
+    use crate::state::sql::Sql;
+    use crate::state::State;
+    use crate::tests::common::{entity_fixtures, run_graphql_query, test_pool};
 
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -26,8 +27,9 @@ mod tests {
         y: u32,
     }
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_entity(pool: SqlitePool) {
+    #[tokio::test]
+    async fn test_entity() {
+        let pool = test_pool().await;
         entity_fixtures(&pool).await;
         let entity_id = poseidon_hash_many(&[FieldElement::ONE]);
         let query = format!(
@@ -47,8 +49,9 @@ mod tests {
         assert_eq!(entity.component_names, "Moves".to_string());
     }
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_entity_components(pool: SqlitePool) {
+    #[tokio::test]
+    async fn test_entity_components() {
+        let pool = test_pool().await;
         entity_fixtures(&pool).await;
 
         let entity_id = poseidon_hash_many(&[FieldElement::THREE]);
@@ -85,44 +88,174 @@ mod tests {
         assert_eq!(component_position.y, 69);
     }
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_entities_without_component_filters(pool: SqlitePool) {
+    #[tokio::test]
+    async fn test_entities_without_component_filters() {
+        let pool = test_pool().await;
         entity_fixtures(&pool).await;
 
         let query = "
         {
-            entities (keys: [\"%%\"]) {
-                keys
-                componentNames
+            entities (keys: [\"%%\"], orderBy: { field: KEYS, direction: ASC }) {
+                totalCount
+                edges {
+                    node {
+                        keys
+                        componentNames
+                    }
+                }
             }
         }
         ";
         let value = run_graphql_query(&pool, query).await;
 
         let entities = value.get("entities").ok_or("entities not found").unwrap();
-        let entities: Vec<Entity> = serde_json::from_value(entities.clone()).unwrap();
+        assert_eq!(entities["totalCount"], 3);
+        let entities = entity_nodes(entities);
         assert_eq!(entities[0].keys.clone().unwrap(), "0x1,");
         assert_eq!(entities[1].keys.clone().unwrap(), "0x2,");
         assert_eq!(entities[2].keys.clone().unwrap(), "0x3,");
     }
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_entities_with_component_filters(pool: SqlitePool) {
+    #[tokio::test]
+    async fn test_entities_with_component_filters() {
+        let pool = test_pool().await;
         entity_fixtures(&pool).await;
 
         let query = "
         {
-            entities (keys: [\"%%\"], componentName:\"Moves\") {
-                keys
-                componentNames
+            entities (componentName: \"Moves\", orderBy: { field: KEYS, direction: ASC }) {
+                totalCount
+                edges {
+                    node {
+                        keys
+                        componentNames
+                    }
+                }
             }
         }
         ";
         let value = run_graphql_query(&pool, query).await;
 
         let entities = value.get("entities").ok_or("entities not found").unwrap();
-        let entities: Vec<Entity> = serde_json::from_value(entities.clone()).unwrap();
+        assert_eq!(entities["totalCount"], 2);
+        let entities = entity_nodes(entities);
         assert_eq!(entities[0].keys.clone().unwrap(), "0x1,");
         assert_eq!(entities[1].keys.clone().unwrap(), "0x3,");
     }
+
+    #[tokio::test]
+    async fn test_entities_pagination() {
+        let pool = test_pool().await;
+        entity_fixtures(&pool).await;
+
+        let page = |arguments: &str| {
+            format!(
+                "{{ entities ({}, orderBy: {{ field: KEYS, direction: ASC }}) {{ edges {{ \
+                 cursor node {{ keys componentNames }} }} pageInfo {{ hasPreviousPage \
+                 hasNextPage startCursor endCursor }} }} }}",
+                arguments
+            )
+        };
+
+        let value = run_graphql_query(&pool, &page("first: 2")).await;
+        let entities = &value["entities"];
+        let nodes = entity_nodes(entities);
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].keys.clone().unwrap(), "0x1,");
+        assert_eq!(nodes[1].keys.clone().unwrap(), "0x2,");
+        assert_eq!(entities["pageInfo"]["hasNextPage"], true);
+        assert_eq!(entities["pageInfo"]["hasPreviousPage"], false);
+
+        let end_cursor = entities["pageInfo"]["endCursor"].as_str().unwrap();
+        let value =
+            run_graphql_query(&pool, &page(&format!("first: 2, after: \"{}\"", end_cursor))).await;
+        let entities = &value["entities"];
+        let nodes = entity_nodes(entities);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].keys.clone().unwrap(), "0x3,");
+        assert_eq!(entities["pageInfo"]["hasNextPage"], false);
+        assert_eq!(entities["pageInfo"]["hasPreviousPage"], true);
+
+        let start_cursor = entities["pageInfo"]["startCursor"].as_str().unwrap();
+        let value =
+            run_graphql_query(&pool, &page(&format!("last: 1, before: \"{}\"", start_cursor)))
+                .await;
+        let entities = &value["entities"];
+        let nodes = entity_nodes(entities);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].keys.clone().unwrap(), "0x2,");
+        assert_eq!(entities["pageInfo"]["hasNextPage"], true);
+        assert_eq!(entities["pageInfo"]["hasPreviousPage"], true);
+    }
+
+    #[tokio::test]
+    async fn test_entities_hostile_filters() {
+        let pool = test_pool().await;
+        entity_fixtures(&pool).await;
+
+        // filters are bound as parameters, so they only ever match literally
+        let query = r#"
+        {
+            entities (keys: ["' OR 1=1 --"], componentName: "Moves' OR '1'='1") {
+                totalCount
+            }
+        }
+        "#;
+        let value = run_graphql_query(&pool, query).await;
+        assert_eq!(value["entities"]["totalCount"], 0);
+
+        let entities = sqlx::query("SELECT * FROM entities").fetch_all(&pool).await.unwrap();
+        assert_eq!(entities.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_entities_pending() {
+        let pool = test_pool().await;
+        entity_fixtures(&pool).await;
+
+        let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+        state.set_pending(true).await;
+        state
+            .set_entity(
+                "Moves".to_string(),
+                FieldElement::ZERO,
+                vec![FieldElement::ONE],
+                vec![FieldElement::from(5u8)],
+            )
+            .await
+            .unwrap();
+        state
+            .delete_entity("Moves".to_string(), FieldElement::ZERO, vec![FieldElement::THREE])
+            .await
+            .unwrap();
+        state.set_pending(false).await;
+
+        let entity_id = poseidon_hash_many(&[FieldElement::ONE]);
+        let query = |pending: bool| {
+            format!(
+                "{{ entity (id: \"{:#x}\", pending: {}) {{ pending components {{ ... on Moves {{ \
+                 remaining }} }} }} movesComponents (pending: {}) {{ totalCount }} }}",
+                entity_id, pending, pending
+            )
+        };
+
+        let value = run_graphql_query(&pool, &query(true)).await;
+        assert_eq!(value["entity"]["pending"], true);
+        assert_eq!(value["entity"]["components"][0]["remaining"], 5);
+        assert_eq!(value["movesComponents"]["totalCount"], 1);
+
+        let value = run_graphql_query(&pool, &query(false)).await;
+        assert_eq!(value["entity"]["pending"], false);
+        assert_eq!(value["entity"]["components"][0]["remaining"], 10);
+        assert_eq!(value["movesComponents"]["totalCount"], 2);
+    }
+
+    fn entity_nodes(connection: &serde_json::Value) -> Vec<Entity> {
+        connection["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| serde_json::from_value(edge["node"].clone()).unwrap())
+            .collect()
+    }
 }
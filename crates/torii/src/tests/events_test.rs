@@ -1,17 +1,21 @@
 #[cfg(test)]
 mod tests {
     use serde::Deserialize;
-    use sqlx::SqlitePool;
+    use starknet::core::types::FieldElement;
 
-    use crate::tests::common::run_graphql_query;
+    use crate::state::sql::{Executable, Sql};
+    use crate::state::State;
+    use crate::tests::common::{load_fixtures, run_graphql_query, test_pool};
 
     #[derive(Deserialize)]
     pub struct Event {
         pub id: String,
     }
 
-    #[sqlx::test(migrations = "./migrations", fixtures("systems", "system_calls", "events"))]
-    async fn test_event(pool: SqlitePool) {
+    #[tokio::test]
+    async fn test_event() {
+        let pool = test_pool().await;
+        load_fixtures(&pool, &["systems", "system_calls", "events"]).await;
         let _ = pool.acquire().await;
 
         let query = "{ event(id: \"event_1\") { id } }";
@@ -22,8 +26,35 @@ mod tests {
         assert_eq!(event.id, "event_1".to_string());
     }
 
-    // #[sqlx::test(migrations = "./migrations", fixtures("systems", "system_calls", "events"))]
-    // async fn test_event_by_keys(pool: SqlitePool) {
+    #[tokio::test]
+    async fn test_event_system_call() {
+        let pool = test_pool().await;
+        load_fixtures(&pool, &["systems", "system_calls"]).await;
+        let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+        state.store_system_call("system_1".into(), FieldElement::ONE, vec![]).await.unwrap();
+        state
+            .store_event("0x1:0".into(), vec![], vec![], FieldElement::ONE, Some(0))
+            .await
+            .unwrap();
+        state.store_event("0x2:0".into(), vec![], vec![], FieldElement::TWO, None).await.unwrap();
+        state.execute().await.unwrap();
+
+        let query = "{ event(id: \"0x1:0\") { id systemCall { systemId transactionHash } } }";
+        let value = run_graphql_query(&pool, query).await;
+        let system_call = &value["event"]["systemCall"];
+        assert_eq!(system_call["systemId"], "system_1");
+        assert_eq!(system_call["transactionHash"], "0x1");
+
+        let query = "{ event(id: \"0x2:0\") { id systemCallId systemCall { id } } }";
+        let value = run_graphql_query(&pool, query).await;
+        assert!(value["event"]["systemCallId"].is_null());
+        assert!(value["event"]["systemCall"].is_null());
+    }
+
+    // #[tokio::test]
+    // async fn test_event_by_keys() {
+    //     let pool = test_pool().await;
+    //     load_fixtures(&pool, &["systems", "system_calls", "events"]).await;
     //     let _ = pool.acquire().await;
 
     //     let query = "{ events(keys: [\"key_1\", \"key_2\", \"key_3\"]) { edges { node { id keys \
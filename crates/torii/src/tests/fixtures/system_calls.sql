@@ -1,6 +1,6 @@
-INSERT INTO system_calls (id, system_id, transaction_hash, data)
-VALUES (1, 'system_1', '0x0', "0x1,0x2,0x3");
-INSERT INTO system_calls (id, system_id, transaction_hash, data)
-VALUES (2, 'system_2', '0x0', "0x1,0x2,0x3");
-INSERT INTO system_calls (id, system_id, transaction_hash, data)
-VALUES (3, 'system_3', '0x0', "0x1,0x2,0x3");
\ No newline at end of file
+INSERT INTO system_calls (system_id, transaction_hash, data)
+VALUES ('system_1', '0x0', '0x1,0x2,0x3');
+INSERT INTO system_calls (system_id, transaction_hash, data)
+VALUES ('system_2', '0x0', '0x1,0x2,0x3');
+INSERT INTO system_calls (system_id, transaction_hash, data)
+VALUES ('system_3', '0x0', '0x1,0x2,0x3');
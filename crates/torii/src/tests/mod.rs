@@ -1,4 +1,6 @@
-mod common;
+pub mod common;
 mod components_test;
 mod entities_test;
 mod events_test;
+mod schema_test;
+mod subscription_test;
@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use dojo_types::component::Member;
+    use dojo_world::manifest::Component;
+    use starknet_crypto::FieldElement;
+    use tokio::sync::RwLock;
+
+    use crate::graphql::schema::{build_schema, reload_schema, SharedSchema};
+    use crate::state::sql::{Executable, Sql};
+    use crate::state::State;
+    use crate::tests::common::test_pool;
+
+    #[tokio::test]
+    async fn test_reload_schema() {
+        let pool = test_pool().await;
+        let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+        let registrations = state.component_registrations().subscribe();
+        let mut committed = state.component_registrations().subscribe();
+
+        let schema: SharedSchema =
+            Arc::new(RwLock::new(build_schema(&pool, state.entity_updates()).await.unwrap()));
+        tokio::spawn(reload_schema(
+            schema.clone(),
+            pool.clone(),
+            state.entity_updates(),
+            registrations,
+        ));
+
+        let query = "{ testComponents { totalCount } }";
+        let res = schema.read().await.clone().execute(query).await;
+        assert!(!res.errors.is_empty(), "unregistered component should not be queryable");
+
+        state
+            .register_component(Component {
+                name: "Test".into(),
+                members: vec![Member { name: "test".into(), ty: "u32".into(), slot: 0, offset: 1 }],
+                class_hash: FieldElement::TWO,
+            })
+            .await
+            .unwrap();
+
+        // the registration is only published once the block is committed
+        assert!(committed.try_recv().is_err());
+        state.execute().await.unwrap();
+        assert_eq!(committed.try_recv().unwrap(), "Test");
+
+        // wait for the schema to be swapped
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let res = schema.read().await.clone().execute(query).await;
+                if res.errors.is_empty() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("schema was not reloaded");
+    }
+}
@@ -0,0 +1,130 @@
+#[cfg(test)]
+mod tests {
+    use async_graphql::futures_util::{FutureExt, Stream, StreamExt};
+    use camino::Utf8PathBuf;
+    use serde_json::Value;
+    use starknet_crypto::{poseidon_hash_many, FieldElement};
+    use tokio::sync::broadcast;
+
+    use crate::db::DbPool;
+    use crate::graphql::schema::build_schema;
+    use crate::state::sql::Sql;
+    use crate::state::{EntityUpdate, State};
+    use crate::tests::common::test_pool;
+
+    async fn state_fixture(pool: &DbPool) -> Sql {
+        let manifest = dojo_world::manifest::Manifest::load_from_path(
+            Utf8PathBuf::from_path_buf("../../examples/ecs/target/dev/manifest.json".into())
+                .unwrap(),
+        )
+        .unwrap();
+
+        let state = Sql::new(pool.clone(), FieldElement::ZERO).await.unwrap();
+        state.load_from_manifest(manifest).await.unwrap();
+        state
+    }
+
+    /// Polls the subscription until it listens to the entity updates, so that no update published
+    /// afterwards is missed.
+    async fn wait_for_subscription<S: Stream + Unpin>(
+        stream: &mut S,
+        entity_updates: &broadcast::Sender<EntityUpdate>,
+    ) {
+        let receivers = entity_updates.receiver_count();
+        while entity_updates.receiver_count() == receivers {
+            assert!(stream.next().now_or_never().is_none(), "no update was published yet");
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entity_updated() {
+        let pool = test_pool().await;
+        let state = state_fixture(&pool).await;
+        let schema = build_schema(&pool, state.entity_updates()).await.unwrap();
+
+        let mut stream = Box::pin(schema.execute_stream(
+            r#"subscription { entityUpdated(keys: ["0x1"], componentNames: ["moves"]) { id componentNames } }"#,
+        ));
+        wait_for_subscription(&mut stream, &state.entity_updates()).await;
+
+        let partition = FieldElement::ZERO;
+        let moves_values = vec![FieldElement::from_hex_be("0xa").unwrap()];
+
+        // updates of other entities are filtered out
+        state
+            .set_entity(
+                "Moves".to_string(),
+                partition,
+                vec![FieldElement::TWO],
+                moves_values.clone(),
+            )
+            .await
+            .unwrap();
+        state
+            .set_entity("Moves".to_string(), partition, vec![FieldElement::ONE], moves_values)
+            .await
+            .unwrap();
+
+        let response = stream.next().await.unwrap();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let data = serde_json::to_value(response.data).unwrap();
+        let entity = &data["entityUpdated"];
+        assert_eq!(
+            entity["id"],
+            Value::from(format!("{:#x}", poseidon_hash_many(&[FieldElement::ONE])))
+        );
+        assert_eq!(entity["componentNames"], Value::from("Moves"));
+
+        state.delete_entity("Moves".to_string(), partition, vec![FieldElement::ONE]).await.unwrap();
+
+        let response = stream.next().await.unwrap();
+        let data = serde_json::to_value(response.data).unwrap();
+        assert_eq!(data["entityUpdated"]["componentNames"], Value::from(""));
+    }
+
+    #[tokio::test]
+    async fn test_component_updated() {
+        let pool = test_pool().await;
+        let state = state_fixture(&pool).await;
+        let schema = build_schema(&pool, state.entity_updates()).await.unwrap();
+
+        let mut stream =
+            Box::pin(schema.execute_stream("subscription { positionUpdated { x y } }"));
+        wait_for_subscription(&mut stream, &state.entity_updates()).await;
+
+        let partition = FieldElement::ZERO;
+        let key = vec![FieldElement::THREE];
+
+        // other components are filtered out
+        state
+            .set_entity(
+                "Moves".to_string(),
+                partition,
+                key.clone(),
+                vec![FieldElement::from_hex_be("0xa").unwrap()],
+            )
+            .await
+            .unwrap();
+        state
+            .set_entity(
+                "Position".to_string(),
+                partition,
+                key,
+                vec![
+                    FieldElement::from_hex_be("0x2a").unwrap(),
+                    FieldElement::from_hex_be("0x45").unwrap(),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let response = stream.next().await.unwrap();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let data = serde_json::to_value(response.data).unwrap();
+        assert_eq!(data["positionUpdated"]["x"], Value::from(42));
+        assert_eq!(data["positionUpdated"]["y"], Value::from(69));
+    }
+}